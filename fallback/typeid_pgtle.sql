@@ -0,0 +1,115 @@
+-- Generated from src/fallback.rs, don't edit by hand
+SELECT pgtle.install_extension(
+    'typeid',
+    '0.2.0',
+    'SQL-only fallback of the typeid extension',
+$_pgtle_$
+CREATE FUNCTION typeid_is_valid(input text) RETURNS boolean
+IMMUTABLE STRICT PARALLEL SAFE
+LANGUAGE sql
+AS $$
+    SELECT input ~ '^([a-z]([a-z_]{0,61}[a-z])?_)?[0-7][0123456789abcdefghjkmnpqrstvwxyz]{25}$'
+$$;
+
+CREATE DOMAIN typeid AS text COLLATE "C" CHECK (typeid_is_valid(VALUE));
+
+CREATE FUNCTION typeid_base32_encode(uuid uuid) RETURNS text
+IMMUTABLE STRICT PARALLEL SAFE
+LANGUAGE plpgsql
+AS $$
+DECLARE
+    hex text := replace(uuid::text, '-', '');
+    n numeric := 0;
+    encoded text := '';
+BEGIN
+    FOR i IN 1..32 LOOP
+        n := n * 16 + strpos('0123456789abcdef', substr(hex, i, 1)) - 1;
+    END LOOP;
+    FOR i IN 1..26 LOOP
+        encoded := substr('0123456789abcdefghjkmnpqrstvwxyz', (n % 32)::integer + 1, 1) || encoded;
+        n := div(n, 32);
+    END LOOP;
+    RETURN encoded;
+END
+$$;
+
+CREATE FUNCTION typeid_base32_decode(suffix text) RETURNS uuid
+IMMUTABLE STRICT PARALLEL SAFE
+LANGUAGE plpgsql
+AS $$
+DECLARE
+    n numeric := 0;
+    hex text := '';
+BEGIN
+    FOR i IN 1..26 LOOP
+        n := n * 32 + strpos('0123456789abcdefghjkmnpqrstvwxyz', substr(suffix, i, 1)) - 1;
+    END LOOP;
+    FOR i IN 1..32 LOOP
+        hex := substr('0123456789abcdef', (n % 16)::integer + 1, 1) || hex;
+        n := div(n, 16);
+    END LOOP;
+    RETURN hex::uuid;
+END
+$$;
+
+CREATE FUNCTION typeid_uuid_generate_v7() RETURNS uuid
+LANGUAGE plpgsql
+AS $$
+DECLARE
+    -- Random bits with the variant already set
+    bytes bytea := uuid_send(gen_random_uuid());
+    millis bigint := floor(extract(epoch FROM clock_timestamp()) * 1000);
+    -- The 12 bits after the timestamp count up within a millisecond, starting from a random value
+    -- below half their range
+    counter integer := (get_byte(bytes, 6) & 7) << 8 | get_byte(bytes, 7);
+    -- Timestamp and counter of the last id of the session
+    last text[] := string_to_array(nullif(current_setting('typeid_fallback.clock', true), ''), ':');
+BEGIN
+    IF last IS NOT NULL AND millis <= last[1]::bigint THEN
+        millis := last[1]::bigint;
+        counter := last[2]::integer + 1;
+        IF counter > 4095 THEN
+            millis := millis + 1;
+            counter := 0;
+        END IF;
+    END IF;
+    PERFORM set_config('typeid_fallback.clock', millis || ':' || counter, false);
+
+    bytes := overlay(bytes PLACING substring(int8send(millis) FROM 3) FROM 1);
+    bytes := set_byte(bytes, 6, 112 | counter >> 8);
+    bytes := set_byte(bytes, 7, counter & 255);
+    RETURN encode(bytes, 'hex')::uuid;
+END
+$$;
+
+CREATE FUNCTION uuid_to_typeid(prefix text, uuid uuid) RETURNS typeid
+IMMUTABLE STRICT PARALLEL SAFE
+LANGUAGE sql
+AS $$
+    SELECT (CASE WHEN prefix = '' THEN '' ELSE prefix || '_' END || typeid_base32_encode(uuid))::typeid
+$$;
+
+CREATE FUNCTION typeid_to_uuid(typeid typeid) RETURNS uuid
+IMMUTABLE STRICT PARALLEL SAFE
+LANGUAGE sql
+AS $$
+    SELECT typeid_base32_decode(right(typeid, 26))
+$$;
+
+CREATE FUNCTION typeid_generate(prefix text) RETURNS typeid
+LANGUAGE sql
+AS $$
+    SELECT uuid_to_typeid(prefix, typeid_uuid_generate_v7())
+$$;
+
+CREATE FUNCTION typeid_has_prefix(id typeid, prefix text) RETURNS boolean
+IMMUTABLE PARALLEL SAFE
+LANGUAGE sql
+AS $$
+    SELECT CASE WHEN prefix ~ '^([a-z]([a-z_]{0,61}[a-z])?)?$' THEN
+        id >= uuid_to_typeid(prefix, '00000000-0000-0000-0000-000000000000')
+        AND id <= uuid_to_typeid(prefix, 'ffffffff-ffff-ffff-ffff-ffffffffffff')
+    ELSE false END
+$$;
+$_pgtle_$
+);
@@ -0,0 +1,42 @@
+/*
+ * Datum level interface of the typeid extension for other C extensions.
+ *
+ * Build a typeid datum without going through text I/O:
+ *
+ *     TypeIdParts parts = {.version = TYPEID_PARTS_VERSION};
+ *     memcpy(parts.uuid, uuid->data, 16);
+ *     parts.prefix_len = strlen("user");
+ *     memcpy(parts.prefix, "user", parts.prefix_len);
+ *
+ *     Oid construct = to_regprocedure("typeid_construct(internal)");
+ *     Datum typeid = OidFunctionCall1(construct, PointerGetDatum(&parts));
+ *
+ * and take one apart again:
+ *
+ *     Oid deconstruct = to_regprocedure("typeid_deconstruct(typeid,internal)");
+ *     OidFunctionCall2(deconstruct, typeid, PointerGetDatum(&parts));
+ *
+ * Look the function OIDs up once and cache them, e.g. with fmgr_info().
+ * The layout below is stable for a given TYPEID_PARTS_VERSION.
+ */
+#ifndef TYPEID_H
+#define TYPEID_H
+
+#include <stdint.h>
+
+#define TYPEID_PARTS_VERSION 1
+#define TYPEID_PREFIX_MAX_LEN 63
+
+typedef struct TypeIdParts
+{
+	/* Has to be set to TYPEID_PARTS_VERSION by the caller */
+	uint32_t	version;
+	/* The UUID in network byte order, like the uuid type */
+	uint8_t		uuid[16];
+	/* Number of bytes used in prefix, zero for TypeIDs without a prefix */
+	uint32_t	prefix_len;
+	/* Not NUL terminated */
+	char		prefix[TYPEID_PREFIX_MAX_LEN + 1];
+} TypeIdParts;
+
+#endif							/* TYPEID_H */
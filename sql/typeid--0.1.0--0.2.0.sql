@@ -0,0 +1,1384 @@
+-- Upgrade from 0.1.0 to 0.2.0, keep in sync with the objects added by the Rust sources.
+
+\echo Use "ALTER EXTENSION typeid UPDATE TO '0.2.0'" to load this file. \quit
+
+-- Selectivity estimators and the missing links of <>
+ALTER OPERATOR = (typeid, typeid) SET (RESTRICT = eqsel, JOIN = eqjoinsel);
+ALTER OPERATOR <> (typeid, typeid) SET (RESTRICT = neqsel, JOIN = neqjoinsel);
+ALTER OPERATOR < (typeid, typeid) SET (RESTRICT = scalarltsel, JOIN = scalarltjoinsel);
+ALTER OPERATOR <= (typeid, typeid) SET (RESTRICT = scalarlesel, JOIN = scalarlejoinsel);
+ALTER OPERATOR >= (typeid, typeid) SET (RESTRICT = scalargesel, JOIN = scalargejoinsel);
+ALTER OPERATOR > (typeid, typeid) SET (RESTRICT = scalargtsel, JOIN = scalargtjoinsel);
+-- ALTER OPERATOR can't set COMMUTATOR and NEGATOR before Postgres 17
+UPDATE pg_catalog.pg_operator
+SET oprcom = oid, oprnegate = '=(typeid, typeid)'::regoperator
+WHERE oid = '<>(typeid, typeid)'::regoperator;
+
+CREATE FUNCTION typeid_prefix_like(typeid typeid, pattern text) RETURNS bool
+    IMMUTABLE STRICT PARALLEL SAFE
+    COST 5
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_prefix_like_wrapper';
+
+CREATE FUNCTION typeid_eq_any(typeid typeid, ids typeid[]) RETURNS bool
+    IMMUTABLE STRICT PARALLEL SAFE
+    COST 10
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_eq_any_wrapper';
+
+-- Planner support functions only exist on Postgres 12+
+DO $$
+BEGIN
+    IF current_setting('server_version_num')::int >= 120000 THEN
+        CREATE FUNCTION typeid_prefix_like_support(arg internal) RETURNS internal
+            IMMUTABLE PARALLEL SAFE
+            LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_prefix_like_support_wrapper';
+        CREATE FUNCTION typeid_eq_any_support(arg internal) RETURNS internal
+            IMMUTABLE PARALLEL SAFE
+            LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_eq_any_support_wrapper';
+
+        ALTER FUNCTION typeid_prefix_like(typeid, text) SUPPORT typeid_prefix_like_support;
+        ALTER FUNCTION typeid_eq_any(typeid, typeid[]) SUPPORT typeid_eq_any_support;
+    END IF;
+END
+$$;
+
+CREATE FUNCTION type_id_session_gap_state(this internal, id typeid, gap interval) RETURNS internal
+    LANGUAGE c AS 'MODULE_PATHNAME', 'type_id_session_gap_state_wrapper';
+CREATE FUNCTION type_id_session_gap_finalize(this internal) RETURNS bigint
+    LANGUAGE c AS 'MODULE_PATHNAME', 'type_id_session_gap_finalize_wrapper';
+CREATE AGGREGATE typeid_session_gap(id typeid, gap interval) (
+    SFUNC = type_id_session_gap_state,
+    STYPE = internal,
+    FINALFUNC = type_id_session_gap_finalize
+);
+
+CREATE FUNCTION type_id_strictly_increasing_state(this internal, id typeid) RETURNS internal
+    LANGUAGE c AS 'MODULE_PATHNAME', 'type_id_strictly_increasing_state_wrapper';
+CREATE FUNCTION type_id_strictly_increasing_finalize(this internal) RETURNS typeid
+    LANGUAGE c AS 'MODULE_PATHNAME', 'type_id_strictly_increasing_finalize_wrapper';
+CREATE AGGREGATE typeid_strictly_increasing(id typeid) (
+    SFUNC = type_id_strictly_increasing_state,
+    STYPE = internal,
+    FINALFUNC = type_id_strictly_increasing_finalize
+);
+
+CREATE FUNCTION type_id_prefix_stats_state(this internal, id typeid) RETURNS internal
+    LANGUAGE c AS 'MODULE_PATHNAME', 'type_id_prefix_stats_state_wrapper';
+CREATE FUNCTION type_id_prefix_stats_finalize(this internal) RETURNS jsonb
+    LANGUAGE c AS 'MODULE_PATHNAME', 'type_id_prefix_stats_finalize_wrapper';
+CREATE AGGREGATE typeid_prefix_stats(id typeid) (
+    SFUNC = type_id_prefix_stats_state,
+    STYPE = internal,
+    FINALFUNC = type_id_prefix_stats_finalize
+);
+
+CREATE FUNCTION typeid_series_between(a typeid, b typeid, step interval) RETURNS SETOF typeid
+    STABLE STRICT PARALLEL SAFE
+    COST 10
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_series_between_wrapper';
+
+CREATE FUNCTION typeid_clamp(typeid typeid, lower typeid, upper typeid) RETURNS typeid
+    IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_clamp_wrapper';
+
+CREATE FUNCTION typeid_extension_health() RETURNS jsonb
+    STRICT
+    COST 1000
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_extension_health_wrapper';
+
+CREATE FUNCTION typeid_version() RETURNS TABLE (library_version text, schema_version text)
+    STRICT
+    COST 100
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_version_wrapper';
+
+CREATE FUNCTION typeid_settings() RETURNS TABLE (name text, value text, source text, description text)
+    STRICT
+    COST 100
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_settings_wrapper';
+
+CREATE FUNCTION typeid_validate_batch() RETURNS bigint
+    STRICT
+    COST 10000
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_validate_batch_wrapper';
+
+CREATE TABLE typeid_validation_results (
+    id bigserial PRIMARY KEY,
+    found_at timestamptz NOT NULL DEFAULT now(),
+    target text NOT NULL,
+    value typeid NOT NULL,
+    kind text NOT NULL,
+    detail text,
+    UNIQUE (target, value, kind)
+);
+
+CREATE TABLE typeid_validation_progress (
+    target text PRIMARY KEY,
+    last_value typeid,
+    updated_at timestamptz NOT NULL DEFAULT now()
+);
+
+SELECT pg_catalog.pg_extension_config_dump('typeid_validation_results', '');
+SELECT pg_catalog.pg_extension_config_dump('typeid_validation_progress', '');
+SELECT pg_catalog.pg_extension_config_dump('typeid_validation_results_id_seq', '');
+
+CREATE FUNCTION typeid_register_prefix(prefix text, description text DEFAULT NULL) RETURNS boolean
+    COST 100
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_register_prefix_wrapper';
+CREATE FUNCTION typeid_rename_prefix(prefix text, new_prefix text) RETURNS void
+    STRICT
+    COST 100
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_rename_prefix_wrapper';
+CREATE FUNCTION typeid_retire_prefix(prefix text) RETURNS void
+    STRICT
+    COST 100
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_retire_prefix_wrapper';
+CREATE FUNCTION typeid_registry_history(prefix text DEFAULT NULL) RETURNS TABLE (
+    id bigint,
+    prefix text,
+    action text,
+    previous_prefix text,
+    changed_at timestamptz,
+    changed_by text,
+    acting_role text
+)
+    COST 100
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_registry_history_wrapper';
+
+CREATE FUNCTION typeid_is_valid_prefix(prefix text) RETURNS boolean
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_is_valid_prefix_wrapper';
+
+CREATE TABLE typeid_prefix_registry (
+    prefix text PRIMARY KEY CHECK (typeid_is_valid_prefix(prefix)),
+    description text,
+    registered_at timestamptz NOT NULL DEFAULT now(),
+    retired_at timestamptz
+);
+
+CREATE TABLE typeid_registry_history (
+    id bigserial PRIMARY KEY,
+    prefix text NOT NULL,
+    action text NOT NULL,
+    previous_prefix text,
+    changed_at timestamptz NOT NULL DEFAULT clock_timestamp(),
+    changed_by text NOT NULL DEFAULT session_user,
+    acting_role text NOT NULL DEFAULT current_user
+);
+
+CREATE FUNCTION typeid_registry_audit() RETURNS trigger
+LANGUAGE plpgsql
+SET search_path FROM CURRENT
+AS $$
+BEGIN
+    -- Restores load the registry with COPY and the history after it, which already holds
+    -- these changes
+    IF pg_catalog.current_query() ~* '^\s*COPY\s' THEN
+        RETURN NULL;
+    END IF;
+    IF TG_OP = 'INSERT' THEN
+        INSERT INTO typeid_registry_history (prefix, action) VALUES (NEW.prefix, 'register');
+    ELSIF TG_OP = 'DELETE' THEN
+        INSERT INTO typeid_registry_history (prefix, action) VALUES (OLD.prefix, 'delete');
+    ELSIF NEW.prefix <> OLD.prefix THEN
+        INSERT INTO typeid_registry_history (prefix, action, previous_prefix)
+        VALUES (NEW.prefix, 'rename', OLD.prefix);
+    ELSIF NEW.retired_at IS NOT NULL AND OLD.retired_at IS NULL THEN
+        INSERT INTO typeid_registry_history (prefix, action) VALUES (NEW.prefix, 'retire');
+    ELSE
+        INSERT INTO typeid_registry_history (prefix, action) VALUES (NEW.prefix, 'update');
+    END IF;
+    RETURN NULL;
+END
+$$;
+
+CREATE TRIGGER typeid_registry_audit
+AFTER INSERT OR UPDATE OR DELETE ON typeid_prefix_registry
+FOR EACH ROW EXECUTE FUNCTION typeid_registry_audit();
+
+CREATE FUNCTION typeid_registry_history_append_only() RETURNS trigger
+LANGUAGE plpgsql
+AS $$
+BEGIN
+    RAISE EXCEPTION 'typeid_registry_history is append-only';
+END
+$$;
+
+CREATE TRIGGER typeid_registry_history_append_only
+BEFORE UPDATE OR DELETE ON typeid_registry_history
+FOR EACH ROW EXECUTE FUNCTION typeid_registry_history_append_only();
+
+CREATE TRIGGER typeid_registry_history_no_truncate
+BEFORE TRUNCATE ON typeid_registry_history
+FOR EACH STATEMENT EXECUTE FUNCTION typeid_registry_history_append_only();
+
+SELECT pg_catalog.pg_extension_config_dump('typeid_prefix_registry', '');
+SELECT pg_catalog.pg_extension_config_dump('typeid_registry_history', '');
+SELECT pg_catalog.pg_extension_config_dump('typeid_registry_history_id_seq', '');
+
+CREATE FUNCTION typeid_construct(parts internal) RETURNS typeid
+    IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_construct_wrapper';
+CREATE FUNCTION typeid_deconstruct(typeid typeid, parts internal) RETURNS void
+    IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_deconstruct_wrapper';
+
+CREATE FUNCTION typeid_sync_prefix_enum(enum_name text DEFAULT 'typeid_prefix') RETURNS integer
+    STRICT COST 100
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_sync_prefix_enum_wrapper';
+CREATE FUNCTION typeid_prefix_enum(typeid typeid, enum_type_of anyelement) RETURNS anyelement
+    STABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_prefix_enum_wrapper';
+CREATE FUNCTION typeid_from_enum(prefix anyelement, uuid uuid) RETURNS typeid
+    STRICT STABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_from_enum_wrapper';
+
+CREATE FUNCTION typeid_create_monthly_partitions(
+    parent regclass, prefix text, start_month date, end_month date, execute boolean DEFAULT false
+) RETURNS SETOF text
+    STRICT COST 100
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_create_monthly_partitions_wrapper';
+
+CREATE FUNCTION typeid_diff(
+    a regclass, a_column name, b regclass, b_column name,
+    prefix text DEFAULT NULL, start timestamptz DEFAULT NULL, "end" timestamptz DEFAULT NULL
+) RETURNS TABLE (id typeid, only_in text)
+    COST 10000
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_diff_wrapper';
+
+CREATE FUNCTION typeid_time(internal) RETURNS tsm_handler
+    STRICT
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_time_wrapper';
+
+CREATE FUNCTION typeid_estimate_rows(relation regclass, "column" name, range tstzrange) RETURNS bigint
+    STRICT STABLE PARALLEL SAFE COST 100
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_estimate_rows_wrapper';
+
+CREATE FUNCTION typeid_timestamp_at(typeid typeid, timezone text) RETURNS timestamp
+    STRICT STABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_timestamp_at_wrapper';
+
+CREATE FUNCTION typeid_to_jsonb_parts(typeid typeid) RETURNS jsonb
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_to_jsonb_parts_wrapper';
+CREATE FUNCTION typeid_from_jsonb(parts jsonb) RETURNS typeid
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_from_jsonb_wrapper';
+
+CREATE FUNCTION typeid_generate_map(prefixes jsonb) RETURNS jsonb
+    STRICT COST 10
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_generate_map_wrapper';
+CREATE FUNCTION typeid_coalesce_generate(typeid typeid, prefix text) RETURNS typeid
+    COST 10
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_coalesce_generate_wrapper';
+CREATE FUNCTION typeid_generate_history(
+    prefix text, start timestamptz, "end" timestamptz, count bigint, distribution text DEFAULT 'uniform'
+) RETURNS SETOF typeid
+    STRICT COST 1000
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_generate_history_wrapper';
+CREATE FUNCTION typeid_generate_batch(prefix text, count bigint) RETURNS SETOF typeid
+    STRICT COST 1000
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_generate_batch_wrapper';
+
+CREATE TYPE typeid_struct AS (prefix text, uuid uuid, ts_millis bigint);
+CREATE FUNCTION typeid_to_struct(typeid typeid) RETURNS typeid_struct
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_to_struct_wrapper';
+CREATE FUNCTION typeid_to_structs(ids typeid[]) RETURNS typeid_struct[]
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_to_structs_wrapper';
+
+CREATE FUNCTION typeid_has_prefix(id typeid, prefix text) RETURNS boolean
+IMMUTABLE PARALLEL SAFE
+LANGUAGE sql
+AS $$
+    SELECT CASE WHEN @extschema@.typeid_is_valid_prefix(prefix) THEN
+        id OPERATOR(@extschema@.>=)
+            @extschema@.uuid_to_typeid(prefix, '00000000-0000-0000-0000-000000000000')
+        AND id OPERATOR(@extschema@.<=)
+            @extschema@.uuid_to_typeid(prefix, 'ffffffff-ffff-ffff-ffff-ffffffffffff')
+    ELSE false END
+$$;
+
+CREATE TABLE typeid_tokens (
+    scope text NOT NULL,
+    id typeid NOT NULL,
+    token typeid NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (scope, id),
+    UNIQUE (scope, token)
+);
+
+SELECT pg_catalog.pg_extension_config_dump('typeid_tokens', '');
+
+CREATE FUNCTION typeid_tokenize(id typeid, scope text) RETURNS typeid
+    STRICT COST 100
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_tokenize_wrapper';
+CREATE FUNCTION typeid_detokenize(token typeid, scope text) RETURNS typeid
+    STRICT STABLE COST 100
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_detokenize_wrapper';
+
+CREATE FUNCTION typeid_is_valid(input text) RETURNS boolean
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_is_valid_wrapper';
+
+CREATE FUNCTION typeid_send(typeid typeid) RETURNS bytea
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_send_wrapper';
+CREATE FUNCTION typeid_recv(buf internal, _typioparam oid, typmod integer) RETURNS typeid
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_recv_wrapper';
+
+DO $$
+BEGIN
+    IF current_setting('server_version_num')::int >= 130000 THEN
+        EXECUTE 'ALTER TYPE typeid SET (SEND = typeid_send, RECEIVE = typeid_recv)';
+    ELSE
+        UPDATE pg_catalog.pg_type
+        SET typsend = 'typeid_send(typeid)'::regprocedure,
+            typreceive = 'typeid_recv(internal, oid, integer)'::regprocedure
+        WHERE oid = 'typeid'::regtype;
+    END IF;
+END
+$$;
+
+CREATE FUNCTION typeid_generate_for_tenant(prefix text, tenant bigint) RETURNS typeid
+    STRICT COST 10
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_generate_for_tenant_wrapper';
+CREATE FUNCTION typeid_tenant_of(id typeid) RETURNS bigint
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_tenant_of_wrapper';
+CREATE FUNCTION typeid_create_tenant_policy(
+    relation regclass, "column" name DEFAULT 'id', setting text DEFAULT 'typeid.tenant',
+    execute boolean DEFAULT false
+) RETURNS SETOF text
+    STRICT COST 100
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_create_tenant_policy_wrapper';
+
+CREATE TABLE typeid_generation_audit (
+    audit_id bigserial PRIMARY KEY,
+    id typeid NOT NULL,
+    relation text NOT NULL,
+    backend_pid integer NOT NULL,
+    application_name text NOT NULL,
+    transaction_id bigint NOT NULL,
+    generated_at timestamptz NOT NULL DEFAULT clock_timestamp(),
+    generated_by text NOT NULL DEFAULT session_user
+);
+
+CREATE FUNCTION typeid_audit_generation() RETURNS trigger
+LANGUAGE plpgsql
+SECURITY DEFINER
+SET search_path FROM CURRENT
+AS $$
+DECLARE
+    column_name text := coalesce(TG_ARGV[0], 'id');
+    new_id typeid := to_jsonb(NEW) ->> column_name;
+BEGIN
+    IF new_id IS NULL THEN
+        RAISE EXCEPTION 'column % of % is missing or NULL', column_name, TG_TABLE_NAME;
+    END IF;
+    INSERT INTO typeid_generation_audit (id, relation, backend_pid, application_name, transaction_id)
+    VALUES (
+        new_id,
+        format('%I.%I', TG_TABLE_SCHEMA, TG_TABLE_NAME),
+        pg_backend_pid(),
+        current_setting('application_name'),
+        txid_current()
+    );
+    RETURN NULL;
+END
+$$;
+
+CREATE FUNCTION typeid_generation_audit_append_only() RETURNS trigger
+LANGUAGE plpgsql
+AS $$
+BEGIN
+    RAISE EXCEPTION 'typeid_generation_audit is append-only';
+END
+$$;
+
+CREATE TRIGGER typeid_generation_audit_append_only
+BEFORE UPDATE OR DELETE ON typeid_generation_audit
+FOR EACH ROW EXECUTE FUNCTION typeid_generation_audit_append_only();
+
+CREATE TRIGGER typeid_generation_audit_no_truncate
+BEFORE TRUNCATE ON typeid_generation_audit
+FOR EACH STATEMENT EXECUTE FUNCTION typeid_generation_audit_append_only();
+
+SELECT pg_catalog.pg_extension_config_dump('typeid_generation_audit', '');
+SELECT pg_catalog.pg_extension_config_dump('typeid_generation_audit_audit_id_seq', '');
+
+CREATE FUNCTION typeid_verify_spec_vectors() RETURNS TABLE (vector text, input text, failure text)
+    COST 1000
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_verify_spec_vectors_wrapper';
+
+CREATE FUNCTION uuid_to_typeid_strict(prefix text, uuid uuid, version integer DEFAULT 7) RETURNS typeid
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'uuid_to_typeid_strict_wrapper';
+
+CREATE FUNCTION typeid_assert_same_prefix(a typeid, b typeid) RETURNS boolean
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_assert_same_prefix_wrapper';
+CREATE FUNCTION typeid_eq_same_prefix(a typeid, b typeid) RETURNS boolean
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_eq_same_prefix_wrapper';
+CREATE OPERATOR == (
+    LEFTARG = typeid,
+    RIGHTARG = typeid,
+    PROCEDURE = typeid_eq_same_prefix,
+    COMMUTATOR = '==',
+    RESTRICT = eqsel,
+    JOIN = eqjoinsel
+);
+
+CREATE FUNCTION typeid_type_oid() RETURNS oid
+    STRICT STABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_type_oid_wrapper';
+CREATE FUNCTION typeid_type_info() RETURNS TABLE (oid oid, array_oid oid, namespace text, binary_format integer)
+    STRICT STABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_type_info_wrapper';
+
+CREATE FUNCTION typeid_from_cdc(value text) RETURNS typeid
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_from_cdc_wrapper';
+
+CREATE FUNCTION typeid_create_fdw_view(
+    relation regclass, view_name text DEFAULT NULL, execute boolean DEFAULT false
+) RETURNS text
+    COST 100
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_create_fdw_view_wrapper';
+
+CREATE FUNCTION typeid_dump_format() RETURNS TABLE (
+    text_format_version integer, binary_format_version integer, storage_format_version integer,
+    config_relations text[]
+)
+    STRICT COST 100
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_dump_format_wrapper';
+
+CREATE FUNCTION typeid_bench_setup(scale integer) RETURNS TABLE (name text, script text)
+    STRICT COST 10000
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_bench_setup_wrapper';
+CREATE FUNCTION typeid_bench_teardown() RETURNS void
+    STRICT
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_bench_teardown_wrapper';
+
+CREATE FUNCTION to_char(typeid typeid, template text) RETURNS text
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_to_char_wrapper';
+
+CREATE VIEW typeid_columns AS
+SELECT
+    n.nspname AS table_schema,
+    c.relname AS table_name,
+    a.attname AS column_name,
+    format_type(a.atttypid, a.atttypmod) AS data_type,
+    a.atttypid = 'typeid[]'::regtype AS is_array,
+    coalesce(
+        modifier.prefix, domain_check.prefix, column_check.prefix, generated.prefix
+    ) AS expected_prefix,
+    CASE
+        WHEN modifier.prefix IS NOT NULL THEN 'typmod'
+        WHEN domain_check.prefix IS NOT NULL THEN 'domain'
+        WHEN column_check.prefix IS NOT NULL THEN 'check'
+        WHEN generated.prefix IS NOT NULL THEN 'default'
+    END AS prefix_source,
+    pg_get_expr(d.adbin, d.adrelid) AS column_default,
+    NOT a.attnotnull AS is_nullable
+FROM pg_catalog.pg_attribute a
+JOIN pg_catalog.pg_class c ON c.oid = a.attrelid
+JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+JOIN pg_catalog.pg_type t ON t.oid = a.atttypid
+LEFT JOIN pg_catalog.pg_attrdef d ON d.adrelid = a.attrelid AND d.adnum = a.attnum
+CROSS JOIN LATERAL (
+    SELECT substring(
+        format_type('typeid'::regtype, greatest(a.atttypmod, t.typtypmod))
+        FROM $$^typeid\('([a-z_]*)'\)$$
+    ) AS prefix
+) modifier
+LEFT JOIN LATERAL (
+    SELECT substring(
+        pg_get_constraintdef(con.oid)
+        FROM $$typeid_(?:has_prefix|prefix_like)\(VALUE, '([a-z_]*)'::text\)$$
+    ) AS prefix
+    FROM pg_catalog.pg_constraint con
+    WHERE con.contypid = a.atttypid AND con.contype = 'c'
+    ORDER BY prefix NULLS LAST
+    LIMIT 1
+) domain_check ON true
+LEFT JOIN LATERAL (
+    SELECT substring(
+        pg_get_constraintdef(con.oid)
+        FROM $$typeid_(?:has_prefix|prefix_like)\([^,]+, '([a-z_]*)'::text\)$$
+    ) AS prefix
+    FROM pg_catalog.pg_constraint con
+    WHERE con.conrelid = a.attrelid AND con.conkey = ARRAY[a.attnum] AND con.contype = 'c'
+    ORDER BY prefix NULLS LAST
+    LIMIT 1
+) column_check ON true
+CROSS JOIN LATERAL (
+    SELECT substring(
+        pg_get_expr(d.adbin, d.adrelid) FROM $$typeid_generate\w*\('([a-z_]*)'::text$$
+    ) AS prefix
+) generated
+WHERE a.attnum > 0
+  AND NOT a.attisdropped
+  AND c.relkind IN ('r', 'p', 'v', 'm', 'f')
+  AND (
+    a.atttypid IN ('typeid'::regtype, 'typeid[]'::regtype)
+    OR (t.typtype = 'd' AND t.typbasetype = 'typeid'::regtype)
+  )
+  AND has_column_privilege(c.oid, a.attnum, 'SELECT, INSERT, UPDATE, REFERENCES');
+
+CREATE FUNCTION typeid_prefix(typeid typeid) RETURNS text
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_prefix_wrapper';
+CREATE FUNCTION typeid_add_prefix_column(
+    relation regclass, "column" name DEFAULT 'id', prefix_column name DEFAULT NULL,
+    execute boolean DEFAULT false
+) RETURNS SETOF text
+    COST 100
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_add_prefix_column_wrapper';
+
+CREATE FUNCTION typeid_generate_mixed(weights jsonb, count bigint) RETURNS SETOF typeid
+    STRICT COST 1000
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_generate_mixed_wrapper';
+
+CREATE FUNCTION typeid_suggest_indexes(relation regclass, use_stat_statements boolean DEFAULT true) RETURNS TABLE (
+    column_name text,
+    statement text,
+    reason text
+)
+    STRICT STABLE COST 1000
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_suggest_indexes_wrapper';
+
+-- Opt-in storage of prefixes as dictionary codes
+CREATE TABLE typeid_prefix_dictionary (
+    code smallint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    prefix text NOT NULL UNIQUE
+);
+-- Reading typeid_dict values looks up their prefixes as the current user
+GRANT SELECT ON typeid_prefix_dictionary TO PUBLIC;
+
+INSERT INTO typeid_prefix_dictionary (prefix)
+SELECT prefix FROM typeid_prefix_registry ORDER BY registered_at, prefix;
+
+CREATE FUNCTION typeid_prefix_dictionary_add() RETURNS trigger
+LANGUAGE plpgsql
+SET search_path FROM CURRENT
+AS $$
+BEGIN
+    INSERT INTO typeid_prefix_dictionary (prefix) VALUES (NEW.prefix) ON CONFLICT (prefix) DO NOTHING;
+    RETURN NULL;
+END
+$$;
+
+CREATE TRIGGER typeid_prefix_dictionary_add
+AFTER INSERT OR UPDATE OF prefix ON typeid_prefix_registry
+FOR EACH ROW EXECUTE FUNCTION typeid_prefix_dictionary_add();
+
+CREATE FUNCTION typeid_prefix_dictionary_append_only() RETURNS trigger
+LANGUAGE plpgsql
+AS $$
+BEGIN
+    RAISE EXCEPTION 'typeid_prefix_dictionary is append-only, stored values refer to its codes';
+END
+$$;
+
+CREATE TRIGGER typeid_prefix_dictionary_append_only
+BEFORE UPDATE OR DELETE ON typeid_prefix_dictionary
+FOR EACH ROW EXECUTE FUNCTION typeid_prefix_dictionary_append_only();
+
+CREATE TRIGGER typeid_prefix_dictionary_no_truncate
+BEFORE TRUNCATE ON typeid_prefix_dictionary
+FOR EACH STATEMENT EXECUTE FUNCTION typeid_prefix_dictionary_append_only();
+
+
+CREATE TYPE typeid_dict;
+
+CREATE FUNCTION typeid_dict_in(input cstring) RETURNS typeid_dict
+    STRICT VOLATILE PARALLEL UNSAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_dict_in_wrapper';
+CREATE FUNCTION typeid_dict_out(id typeid_dict) RETURNS cstring
+    STRICT STABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_dict_out_wrapper';
+CREATE FUNCTION typeid_dict_recv(buf internal) RETURNS typeid_dict
+    STRICT VOLATILE PARALLEL UNSAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_dict_recv_wrapper';
+CREATE FUNCTION typeid_dict_send(id typeid_dict) RETURNS bytea
+    STRICT STABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_dict_send_wrapper';
+CREATE TYPE typeid_dict (
+    INPUT = typeid_dict_in,
+    OUTPUT = typeid_dict_out,
+    RECEIVE = typeid_dict_recv,
+    SEND = typeid_dict_send,
+    INTERNALLENGTH = 18,
+    ALIGNMENT = char,
+    STORAGE = plain
+);
+
+CREATE FUNCTION typeid_to_dict(typeid typeid) RETURNS typeid_dict
+    STRICT VOLATILE PARALLEL UNSAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_to_dict_wrapper';
+CREATE FUNCTION typeid_from_dict(id typeid_dict) RETURNS typeid
+    STRICT STABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_from_dict_wrapper';
+CREATE FUNCTION typeid_dict_cmp(a typeid_dict, b typeid_dict) RETURNS integer
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_dict_cmp_wrapper';
+CREATE FUNCTION typeid_dict_lt(a typeid_dict, b typeid_dict) RETURNS boolean
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_dict_lt_wrapper';
+CREATE FUNCTION typeid_dict_le(a typeid_dict, b typeid_dict) RETURNS boolean
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_dict_le_wrapper';
+CREATE FUNCTION typeid_dict_eq(a typeid_dict, b typeid_dict) RETURNS boolean
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_dict_eq_wrapper';
+CREATE FUNCTION typeid_dict_ge(a typeid_dict, b typeid_dict) RETURNS boolean
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_dict_ge_wrapper';
+CREATE FUNCTION typeid_dict_gt(a typeid_dict, b typeid_dict) RETURNS boolean
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_dict_gt_wrapper';
+CREATE FUNCTION typeid_dict_ne(a typeid_dict, b typeid_dict) RETURNS boolean
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_dict_ne_wrapper';
+CREATE FUNCTION typeid_dict_hash(id typeid_dict) RETURNS integer
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_dict_hash_wrapper';
+CREATE FUNCTION typeid_dict_hash_extended(id typeid_dict, seed bigint) RETURNS bigint
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_dict_hash_extended_wrapper';
+
+CREATE CAST (typeid AS typeid_dict) WITH FUNCTION typeid_to_dict(typeid) AS ASSIGNMENT;
+CREATE CAST (typeid_dict AS typeid) WITH FUNCTION typeid_from_dict(typeid_dict) AS IMPLICIT;
+
+CREATE OPERATOR < (
+    LEFTARG = typeid_dict,
+    RIGHTARG = typeid_dict,
+    PROCEDURE = typeid_dict_lt,
+    RESTRICT = scalarltsel,
+    JOIN = scalarltjoinsel
+);
+CREATE OPERATOR <= (
+    LEFTARG = typeid_dict,
+    RIGHTARG = typeid_dict,
+    PROCEDURE = typeid_dict_le,
+    RESTRICT = scalarlesel,
+    JOIN = scalarlejoinsel
+);
+CREATE OPERATOR = (
+    LEFTARG = typeid_dict,
+    RIGHTARG = typeid_dict,
+    PROCEDURE = typeid_dict_eq,
+    COMMUTATOR = '=',
+    NEGATOR = '<>',
+    RESTRICT = eqsel,
+    JOIN = eqjoinsel,
+    HASHES,
+    MERGES
+);
+CREATE OPERATOR >= (
+    LEFTARG = typeid_dict,
+    RIGHTARG = typeid_dict,
+    PROCEDURE = typeid_dict_ge,
+    RESTRICT = scalargesel,
+    JOIN = scalargejoinsel
+);
+CREATE OPERATOR > (
+    LEFTARG = typeid_dict,
+    RIGHTARG = typeid_dict,
+    PROCEDURE = typeid_dict_gt,
+    RESTRICT = scalargtsel,
+    JOIN = scalargtjoinsel
+);
+CREATE OPERATOR <> (
+    LEFTARG = typeid_dict,
+    RIGHTARG = typeid_dict,
+    PROCEDURE = typeid_dict_ne,
+    COMMUTATOR = '<>',
+    NEGATOR = '=',
+    RESTRICT = neqsel,
+    JOIN = neqjoinsel
+);
+
+CREATE OPERATOR CLASS typeid_dict_ops DEFAULT FOR TYPE typeid_dict USING btree AS
+    OPERATOR 1 < (typeid_dict, typeid_dict),
+    OPERATOR 2 <= (typeid_dict, typeid_dict),
+    OPERATOR 3 = (typeid_dict, typeid_dict),
+    OPERATOR 4 >= (typeid_dict, typeid_dict),
+    OPERATOR 5 > (typeid_dict, typeid_dict),
+    FUNCTION 1 typeid_dict_cmp(typeid_dict, typeid_dict);
+
+CREATE OPERATOR CLASS typeid_dict_hash_ops DEFAULT FOR TYPE typeid_dict USING hash AS
+    OPERATOR 1 = (typeid_dict, typeid_dict),
+    FUNCTION 1 typeid_dict_hash(typeid_dict),
+    FUNCTION 2 typeid_dict_hash_extended(typeid_dict, bigint);
+
+CREATE FUNCTION typeid_older(a typeid, b typeid) RETURNS typeid
+    IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_older_wrapper';
+CREATE FUNCTION typeid_newer(a typeid, b typeid) RETURNS typeid
+    IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_newer_wrapper';
+
+CREATE FUNCTION typeid_shuffle_order(typeid typeid, seed bigint) RETURNS bigint
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_shuffle_order_wrapper';
+
+CREATE FUNCTION typeid_within(a typeid, b typeid, tolerance interval) RETURNS boolean
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_within_wrapper';
+
+DO $$
+BEGIN
+    IF current_setting('server_version_num')::int >= 120000 THEN
+        CREATE FUNCTION typeid_within_support(arg internal) RETURNS internal
+            IMMUTABLE PARALLEL SAFE
+            LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_within_support_wrapper';
+
+        ALTER FUNCTION typeid_within(typeid, typeid, interval) SUPPORT typeid_within_support;
+    END IF;
+END
+$$;
+
+CREATE FUNCTION typeid_array_unique(ids typeid[]) RETURNS typeid[]
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_array_unique_wrapper';
+CREATE FUNCTION typeid_array_diff(a typeid[], b typeid[]) RETURNS typeid[]
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_array_diff_wrapper';
+CREATE FUNCTION typeid_array_intersect(a typeid[], b typeid[]) RETURNS typeid[]
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_array_intersect_wrapper';
+
+CREATE FUNCTION typeid_first_per_prefix(
+    relation regclass, "column" name DEFAULT 'id', latest boolean DEFAULT false
+) RETURNS TABLE (prefix text, id typeid)
+    STRICT STABLE COST 1000
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_first_per_prefix_wrapper';
+
+CREATE FUNCTION typeid_prefix_typmod(prefix text) RETURNS integer
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_prefix_typmod_wrapper';
+
+CREATE TABLE typeid_typmods (
+    typmod integer PRIMARY KEY CHECK (typmod = typeid_prefix_typmod(prefix)),
+    prefix text NOT NULL UNIQUE CHECK (typeid_is_valid_prefix(prefix))
+);
+GRANT SELECT ON typeid_typmods TO PUBLIC;
+SELECT pg_catalog.pg_extension_config_dump('typeid_typmods', '');
+
+-- Declaring a modifier notes its prefix through this function, as the owner of the extension.
+-- Modifiers are the hashes of their prefixes and rows can't be updated or deleted, so noting
+-- prefixes can't change what existing modifiers show as.
+CREATE FUNCTION typeid_note_typmod(prefix text) RETURNS void
+LANGUAGE sql
+SECURITY DEFINER
+SET search_path FROM CURRENT
+AS $$
+    INSERT INTO typeid_typmods (typmod, prefix)
+    VALUES (typeid_prefix_typmod(prefix), prefix)
+    ON CONFLICT DO NOTHING
+$$;
+
+-- Restores create the tables declaring modifiers, noting their prefixes, before they load the
+-- table from the dump
+CREATE FUNCTION typeid_typmods_skip_noted() RETURNS trigger
+LANGUAGE plpgsql
+SET search_path FROM CURRENT
+AS $$
+BEGIN
+    IF EXISTS (SELECT FROM typeid_typmods WHERE typmod = NEW.typmod AND prefix = NEW.prefix) THEN
+        RETURN NULL;
+    END IF;
+    RETURN NEW;
+END
+$$;
+
+CREATE TRIGGER typeid_typmods_skip_noted
+BEFORE INSERT ON typeid_typmods
+FOR EACH ROW EXECUTE FUNCTION typeid_typmods_skip_noted();
+
+CREATE FUNCTION typeid_typmods_append_only() RETURNS trigger
+LANGUAGE plpgsql
+AS $$
+BEGIN
+    RAISE EXCEPTION 'typeid_typmods is append-only, column definitions are shown with its prefixes';
+END
+$$;
+
+CREATE TRIGGER typeid_typmods_append_only
+BEFORE UPDATE OR DELETE ON typeid_typmods
+FOR EACH ROW EXECUTE FUNCTION typeid_typmods_append_only();
+
+CREATE TRIGGER typeid_typmods_no_truncate
+BEFORE TRUNCATE ON typeid_typmods
+FOR EACH STATEMENT EXECUTE FUNCTION typeid_typmods_append_only();
+
+CREATE FUNCTION typeid_typmod_in(modifiers cstring[]) RETURNS integer
+    STRICT
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_typmod_in_wrapper';
+CREATE FUNCTION typeid_typmod_out(typmod integer) RETURNS cstring
+    STRICT STABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_typmod_out_wrapper';
+CREATE FUNCTION typeid(typeid typeid, typmod integer, _explicit boolean) RETURNS typeid
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_apply_typmod_wrapper';
+
+CREATE CAST (typeid AS typeid) WITH FUNCTION typeid(typeid, integer, boolean) AS IMPLICIT;
+
+DO $$
+BEGIN
+    IF current_setting('server_version_num')::int >= 130000 THEN
+        EXECUTE 'ALTER TYPE typeid SET (TYPMOD_IN = typeid_typmod_in, TYPMOD_OUT = typeid_typmod_out)';
+    ELSE
+        UPDATE pg_catalog.pg_type
+        SET typmodin = 'typeid_typmod_in(cstring[])'::regprocedure,
+            typmodout = 'typeid_typmod_out(integer)'::regprocedure
+        WHERE oid IN ('typeid'::regtype, 'typeid[]'::regtype);
+    END IF;
+END
+$$;
+-- Postgres passes the modifier of the column to the input function whatever its declared arguments
+CREATE OR REPLACE FUNCTION typeid_in(input cstring) RETURNS typeid
+    IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_input_wrapper';
+
+CREATE FUNCTION typeid_in_range(
+    val typeid, base typeid, "offset" interval, sub boolean, less boolean
+) RETURNS boolean
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_in_range_wrapper';
+ALTER OPERATOR FAMILY typeid_ops USING btree ADD
+    FUNCTION 3 (typeid, interval) typeid_in_range(typeid, typeid, interval, boolean, boolean);
+
+CREATE FUNCTION typeid_contains_prefix(typeid typeid, prefix text) RETURNS boolean
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_contains_prefix_wrapper';
+CREATE FUNCTION typeid_contains_prefix_sel(
+    root internal, _operator oid, args internal, var_relid integer
+) RETURNS double precision
+    STABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_contains_prefix_sel_wrapper';
+CREATE FUNCTION typeid_contains_prefix_joinsel(
+    root internal, _operator oid, args internal, join_type smallint, sjinfo internal
+) RETURNS double precision
+    STABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_contains_prefix_joinsel_wrapper';
+CREATE OPERATOR @> (
+    LEFTARG = typeid,
+    RIGHTARG = text,
+    PROCEDURE = typeid_contains_prefix,
+    RESTRICT = typeid_contains_prefix_sel,
+    JOIN = typeid_contains_prefix_joinsel
+);
+
+DO $$
+BEGIN
+    IF current_setting('server_version_num')::int >= 120000 THEN
+        CREATE FUNCTION typeid_contains_prefix_support(arg internal) RETURNS internal
+            IMMUTABLE PARALLEL SAFE
+            LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_contains_prefix_support_wrapper';
+
+        ALTER FUNCTION typeid_contains_prefix(typeid, text) SUPPORT typeid_contains_prefix_support;
+    END IF;
+END
+$$;
+
+-- Prefix statistics gathered by ANALYZE. `ALTER TYPE ... SET` only accepts an analyze function
+-- from Postgres 13 on.
+CREATE FUNCTION typeid_typanalyze(stats internal) RETURNS boolean
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_typanalyze_wrapper';
+DO $$
+BEGIN
+    IF current_setting('server_version_num')::int >= 130000 THEN
+        EXECUTE 'ALTER TYPE typeid SET (ANALYZE = typeid_typanalyze)';
+    ELSE
+        UPDATE pg_catalog.pg_type
+        SET typanalyze = 'typeid_typanalyze(internal)'::regprocedure
+        WHERE oid = 'typeid'::regtype;
+    END IF;
+END
+$$;
+CREATE FUNCTION typeid_column_prefixes(relation regclass, "column" name DEFAULT 'id') RETURNS TABLE (
+    prefix text,
+    frequency real,
+    min_id typeid,
+    max_id typeid
+)
+    STRICT STABLE COST 100
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_column_prefixes_wrapper';
+
+-- SP-GiST radix tree over the text form of typeids, whose leaves can only differ from the
+-- indexed type from Postgres 14 on
+DO $$
+BEGIN
+    IF current_setting('server_version_num')::int >= 140000 THEN
+        CREATE FUNCTION typeid_spg_config(config_in internal, config_out internal) RETURNS void
+            IMMUTABLE PARALLEL SAFE
+            LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_spg_config_wrapper';
+        CREATE FUNCTION typeid_spg_compress(typeid typeid) RETURNS text
+            STRICT IMMUTABLE PARALLEL SAFE
+            LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_spg_compress_wrapper';
+        CREATE FUNCTION typeid_spg_inner_consistent(inner_in internal, inner_out internal) RETURNS void
+            IMMUTABLE PARALLEL SAFE
+            LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_spg_inner_consistent_wrapper';
+        CREATE FUNCTION typeid_spg_leaf_consistent(leaf_in internal, leaf_out internal) RETURNS boolean
+            IMMUTABLE PARALLEL SAFE
+            LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_spg_leaf_consistent_wrapper';
+
+        CREATE OPERATOR CLASS typeid_ops DEFAULT FOR TYPE typeid USING spgist AS
+            OPERATOR 3 = (typeid, typeid),
+            OPERATOR 28 @> (typeid, text),
+            FUNCTION 1 typeid_spg_config(internal, internal),
+            FUNCTION 2 pg_catalog.spg_text_choose(internal, internal),
+            FUNCTION 3 pg_catalog.spg_text_picksplit(internal, internal),
+            FUNCTION 4 typeid_spg_inner_consistent(internal, internal),
+            FUNCTION 5 typeid_spg_leaf_consistent(internal, internal),
+            FUNCTION 6 typeid_spg_compress(typeid),
+            STORAGE text;
+    END IF;
+END
+$$;
+
+-- GiST operator class for exclusion constraints and multi-column GiST indexes
+CREATE FUNCTION typeid_gist_consistent(
+    entry internal, query typeid, strategy smallint, _subtype oid, recheck internal
+) RETURNS boolean
+    IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_gist_consistent_wrapper';
+CREATE FUNCTION typeid_gist_union(entries internal, size internal) RETURNS bytea
+    IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_gist_union_wrapper';
+CREATE FUNCTION typeid_gist_compress(entry internal) RETURNS internal
+    IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_gist_compress_wrapper';
+CREATE FUNCTION typeid_gist_fetch(entry internal) RETURNS internal
+    IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_gist_fetch_wrapper';
+CREATE FUNCTION typeid_gist_penalty(original internal, new internal, penalty internal) RETURNS internal
+    IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_gist_penalty_wrapper';
+CREATE FUNCTION typeid_gist_picksplit(entries internal, split internal) RETURNS internal
+    IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_gist_picksplit_wrapper';
+CREATE FUNCTION typeid_gist_same(a bytea, b bytea, result internal) RETURNS internal
+    IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_gist_same_wrapper';
+CREATE OPERATOR CLASS typeid_ops DEFAULT FOR TYPE typeid USING gist AS
+    OPERATOR 1 < (typeid, typeid),
+    OPERATOR 2 <= (typeid, typeid),
+    OPERATOR 3 = (typeid, typeid),
+    OPERATOR 4 >= (typeid, typeid),
+    OPERATOR 5 > (typeid, typeid),
+    OPERATOR 6 <> (typeid, typeid),
+    FUNCTION 1 typeid_gist_consistent(internal, typeid, smallint, oid, internal),
+    FUNCTION 2 typeid_gist_union(internal, internal),
+    FUNCTION 3 typeid_gist_compress(internal),
+    FUNCTION 5 typeid_gist_penalty(internal, internal, internal),
+    FUNCTION 6 typeid_gist_picksplit(internal, internal),
+    FUNCTION 7 typeid_gist_same(bytea, bytea, internal),
+    FUNCTION 9 typeid_gist_fetch(internal),
+    STORAGE bytea;
+
+-- contrib/bloom operator class, created now when bloom is installed before typeid
+CREATE FUNCTION typeid_enable_bloom() RETURNS boolean
+LANGUAGE plpgsql
+SET search_path FROM CURRENT
+AS $$
+DECLARE
+    opclass oid;
+BEGIN
+    IF NOT EXISTS (SELECT FROM pg_catalog.pg_am WHERE amname = 'bloom') THEN
+        RAISE EXCEPTION 'bloom is not installed'
+            USING HINT = 'Run CREATE EXTENSION bloom first.';
+    END IF;
+    IF EXISTS (
+        SELECT FROM pg_catalog.pg_opclass c JOIN pg_catalog.pg_am a ON a.oid = c.opcmethod
+        WHERE a.amname = 'bloom' AND c.opcintype = 'typeid'::regtype
+    ) THEN
+        RETURN false;
+    END IF;
+
+    CREATE OPERATOR CLASS typeid_ops DEFAULT FOR TYPE typeid USING bloom AS
+        OPERATOR 1 = (typeid, typeid),
+        FUNCTION 1 typeid_hash(typeid);
+
+    -- Objects created while installing typeid are part of it already
+    SELECT c.oid INTO opclass
+    FROM pg_catalog.pg_opclass c JOIN pg_catalog.pg_am a ON a.oid = c.opcmethod
+    WHERE a.amname = 'bloom' AND c.opcintype = 'typeid'::regtype;
+    IF NOT EXISTS (
+        SELECT FROM pg_catalog.pg_depend
+        WHERE classid = 'pg_catalog.pg_opclass'::regclass AND objid = opclass AND deptype = 'e'
+    ) THEN
+        ALTER EXTENSION typeid ADD OPERATOR CLASS typeid_ops USING bloom;
+    END IF;
+    RETURN true;
+END
+$$;
+
+DO $$
+BEGIN
+    IF EXISTS (SELECT FROM pg_catalog.pg_am WHERE amname = 'bloom') THEN
+        PERFORM typeid_enable_bloom();
+    END IF;
+END
+$$;
+
+-- typeid_hash and typeid_hash_extended hash with the hash function of Postgres since 0.2, so
+-- hash indexes and hash partitions built by 0.1 no longer find their rows
+DO $$
+DECLARE
+    relation regclass;
+BEGIN
+    FOR relation IN
+        SELECT i.indexrelid::regclass
+        FROM pg_catalog.pg_index i
+        JOIN pg_catalog.pg_opclass c ON c.oid = ANY (i.indclass)
+        WHERE c.opcname = 'typeid_hash_ops'
+    LOOP
+        RAISE WARNING 'hash index % must be rebuilt with REINDEX', relation;
+    END LOOP;
+    FOR relation IN
+        SELECT p.partrelid::regclass
+        FROM pg_catalog.pg_partitioned_table p
+        JOIN pg_catalog.pg_opclass c ON c.oid = ANY (p.partclass)
+        WHERE p.partstrat = 'h' AND c.opcname = 'typeid_hash_ops'
+    LOOP
+        RAISE WARNING 'rows of % must be moved to a new table hash-partitioned the same way', relation;
+    END LOOP;
+END
+$$;
+
+CREATE FUNCTION typeid_hash_partition(id typeid, modulus integer) RETURNS integer
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_hash_partition_wrapper';
+
+CREATE FUNCTION typeid_try_parse(input text) RETURNS typeid
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_try_parse_wrapper';
+
+CREATE FUNCTION typeid_parse(typeid text, OUT prefix text, OUT uuid uuid)
+STRICT IMMUTABLE PARALLEL SAFE
+LANGUAGE sql
+AS $$
+    SELECT @extschema@.typeid_prefix(id), @extschema@.typeid_to_uuid(id)
+    FROM (SELECT $1::@extschema@.typeid AS id) parsed
+$$;
+
+CREATE FUNCTION typeid_print(prefix text, uuid uuid) RETURNS text
+IMMUTABLE PARALLEL SAFE
+LANGUAGE sql
+AS $$
+    SELECT @extschema@.uuid_to_typeid(coalesce(prefix, ''), uuid)::text
+$$;
+
+CREATE FUNCTION typeid_from_composite(value anyelement) RETURNS typeid
+STRICT IMMUTABLE PARALLEL SAFE
+LANGUAGE plpgsql
+AS $$
+DECLARE
+    fields jsonb := to_jsonb(value);
+BEGIN
+    IF jsonb_typeof(fields) IS DISTINCT FROM 'object' OR NOT fields ? 'uuid' THEN
+        RAISE EXCEPTION '% is not a composite of a prefix and a uuid', pg_typeof(value)
+            USING ERRCODE = 'datatype_mismatch';
+    END IF;
+    RETURN @extschema@.uuid_to_typeid(
+        coalesce(fields->>'prefix', fields->>'type', ''),
+        (fields->>'uuid')::uuid
+    );
+END
+$$;
+
+CREATE FUNCTION typeid_create_composite_cast(composite regtype) RETURNS void
+LANGUAGE plpgsql
+AS $$
+BEGIN
+    IF (
+        SELECT count(*) FILTER (WHERE a.attname = 'uuid' AND a.atttypid = 'uuid'::regtype) = 0
+            OR count(*) FILTER (WHERE a.attname IN ('prefix', 'type')) = 0
+        FROM pg_catalog.pg_type t
+        JOIN pg_catalog.pg_attribute a
+            ON a.attrelid = t.typrelid AND a.attnum > 0 AND NOT a.attisdropped
+        WHERE t.oid = composite
+    ) THEN
+        RAISE EXCEPTION '% is not a composite of a prefix and a uuid', composite
+            USING ERRCODE = 'datatype_mismatch',
+                  HINT = 'Expected a field prefix or type and a field uuid of type uuid.';
+    END IF;
+    EXECUTE format(
+        'CREATE CAST (%s AS @extschema@.typeid)
+         WITH FUNCTION @extschema@.typeid_from_composite(anyelement) AS ASSIGNMENT',
+        composite
+    );
+END
+$$;
+
+CREATE FUNCTION typeid_timestamp(typeid typeid) RETURNS timestamptz
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_timestamp_wrapper';
+CREATE FUNCTION typeid_timestamp_millis(typeid typeid) RETURNS bigint
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_timestamp_millis_wrapper';
+
+CREATE FUNCTION typeid_age(typeid typeid) RETURNS interval
+    STRICT STABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_age_wrapper';
+
+CREATE FUNCTION typeid_suffix(typeid typeid) RETURNS text
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_suffix_wrapper';
+
+CREATE FUNCTION typeid_uuid_version(typeid typeid) RETURNS integer
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_uuid_version_wrapper';
+
+CREATE FUNCTION typeid_unpack(
+    id typeid,
+    OUT prefix text,
+    OUT uuid uuid,
+    OUT created_at timestamptz,
+    OUT uuid_version integer
+)
+STRICT IMMUTABLE PARALLEL SAFE
+LANGUAGE sql
+AS $$
+    SELECT @extschema@.typeid_prefix(id), @extschema@.typeid_to_uuid(id),
+           CASE WHEN version = 7 THEN @extschema@.typeid_timestamp(id) END, version
+    FROM (SELECT @extschema@.typeid_uuid_version(id) AS version) parts
+$$;
+
+CREATE FUNCTION typeid_bucket(typeid typeid, width interval) RETURNS typeid
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_bucket_wrapper';
+
+-- Subscripts reading the parts of an id, type subscripting handlers are new in Postgres 14
+DO $$
+BEGIN
+    IF current_setting('server_version_num')::int >= 140000 THEN
+        CREATE FUNCTION typeid_subscript_handler(_internal internal) RETURNS internal
+            IMMUTABLE PARALLEL SAFE
+            LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_subscript_handler_wrapper';
+        ALTER TYPE typeid SET (SUBSCRIPT = typeid_subscript_handler);
+    END IF;
+END
+$$;
+
+CREATE FUNCTION typeid_generate_v4(prefix text) RETURNS typeid
+    STRICT
+    COST 10
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_generate_v4_wrapper';
+
+CREATE FUNCTION typeid_generate_v8(prefix text, custom bytea) RETURNS typeid
+    STRICT
+    COST 10
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_generate_v8_wrapper';
+
+CREATE FUNCTION typeid_generate_series(prefix text, count bigint) RETURNS SETOF typeid
+    STRICT COST 1000
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_generate_series_wrapper';
+
+CREATE FUNCTION typeid_generate_sharded(prefix text, shard integer) RETURNS typeid
+    STRICT COST 10
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_generate_sharded_wrapper';
+CREATE FUNCTION typeid_shard(id typeid) RETURNS integer
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_shard_wrapper';
+
+CREATE FUNCTION gen_random_typeid() RETURNS typeid
+    STRICT
+    COST 10
+    LANGUAGE c AS 'MODULE_PATHNAME', 'gen_random_typeid_wrapper';
+
+CREATE FUNCTION typeid_min_for_prefix(prefix text) RETURNS typeid
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_min_for_prefix_wrapper';
+CREATE FUNCTION typeid_max_for_prefix(prefix text) RETURNS typeid
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_max_for_prefix_wrapper';
+
+CREATE FUNCTION typeid_reload_config() RETURNS timestamptz
+    STRICT
+    COST 100
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_reload_config_wrapper';
+CREATE FUNCTION typeid_reload_status() RETURNS TABLE (
+    pid integer,
+    backend_type text,
+    reloaded_at timestamptz,
+    reloaded boolean
+)
+    STRICT
+    COST 100
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_reload_status_wrapper';
+
+-- Comparison, hashing and extraction functions are immutable and leakproof
+ALTER FUNCTION typeid_cmp(typeid, typeid) IMMUTABLE PARALLEL SAFE LEAKPROOF;
+ALTER FUNCTION typeid_lt(typeid, typeid) IMMUTABLE PARALLEL SAFE LEAKPROOF;
+ALTER FUNCTION typeid_le(typeid, typeid) IMMUTABLE PARALLEL SAFE LEAKPROOF;
+ALTER FUNCTION typeid_eq(typeid, typeid) IMMUTABLE PARALLEL SAFE LEAKPROOF;
+ALTER FUNCTION typeid_ge(typeid, typeid) IMMUTABLE PARALLEL SAFE LEAKPROOF;
+ALTER FUNCTION typeid_gt(typeid, typeid) IMMUTABLE PARALLEL SAFE LEAKPROOF;
+ALTER FUNCTION typeid_ne(typeid, typeid) IMMUTABLE PARALLEL SAFE LEAKPROOF;
+ALTER FUNCTION typeid_hash(typeid) IMMUTABLE PARALLEL SAFE LEAKPROOF;
+ALTER FUNCTION typeid_hash_extended(typeid, bigint) IMMUTABLE PARALLEL SAFE LEAKPROOF;
+ALTER FUNCTION typeid_to_uuid(typeid) IMMUTABLE PARALLEL SAFE LEAKPROOF;
+ALTER FUNCTION typeid_eq_any(typeid, typeid[]) LEAKPROOF;
+ALTER FUNCTION typeid_tenant_of(typeid) LEAKPROOF;
+ALTER FUNCTION typeid_shard(typeid) LEAKPROOF;
+ALTER FUNCTION typeid_prefix(typeid) LEAKPROOF;
+ALTER FUNCTION uuid_to_typeid(text, uuid) IMMUTABLE PARALLEL SAFE;
+ALTER FUNCTION typeid_generate(text) COST 10;
+
+-- Predefined roles, keep last
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT FROM pg_catalog.pg_roles WHERE rolname = 'typeid_usage') THEN
+        CREATE ROLE typeid_usage NOLOGIN;
+    END IF;
+    IF NOT EXISTS (SELECT FROM pg_catalog.pg_roles WHERE rolname = 'typeid_admin') THEN
+        CREATE ROLE typeid_admin NOLOGIN;
+    END IF;
+END
+$$;
+
+GRANT typeid_usage TO typeid_admin;
+GRANT USAGE ON SCHEMA @extschema@ TO typeid_usage;
+
+GRANT EXECUTE ON FUNCTION
+    typeid_generate(text),
+    typeid_generate_map(jsonb),
+    typeid_coalesce_generate(typeid, text),
+    typeid_generate_history(text, timestamptz, timestamptz, bigint, text),
+    typeid_generate_batch(text, bigint),
+    typeid_generate_mixed(jsonb, bigint),
+    typeid_generate_for_tenant(text, bigint),
+    typeid_tenant_of(typeid),
+    typeid_time(internal),
+    typeid_to_uuid(typeid),
+    uuid_to_typeid(text, uuid),
+    uuid_to_typeid_strict(text, uuid, integer),
+    typeid_uuid_generate_v7(),
+    typeid_clamp(typeid, typeid, typeid),
+    typeid_is_valid(text),
+    typeid_is_valid_prefix(text),
+    typeid_prefix_like(typeid, text),
+    typeid_has_prefix(typeid, text),
+    typeid_assert_same_prefix(typeid, typeid),
+    typeid_eq_same_prefix(typeid, typeid),
+    typeid_eq_any(typeid, typeid[]),
+    typeid_series_between(typeid, typeid, interval),
+    typeid_session_gap(typeid, interval),
+    typeid_strictly_increasing(typeid),
+    typeid_prefix_stats(typeid),
+    typeid_prefix_enum(typeid, anyelement),
+    typeid_from_enum(anyelement, uuid),
+    typeid_timestamp_at(typeid, text),
+    typeid_older(typeid, typeid),
+    typeid_newer(typeid, typeid),
+    typeid_shuffle_order(typeid, bigint),
+    typeid_within(typeid, typeid, interval),
+    typeid_array_unique(typeid[]),
+    typeid_array_diff(typeid[], typeid[]),
+    typeid_array_intersect(typeid[], typeid[]),
+    typeid_contains_prefix(typeid, text),
+    typeid_column_prefixes(regclass, name),
+    typeid_hash_partition(typeid, integer),
+    typeid_try_parse(text),
+    typeid_parse(text),
+    typeid_print(text, uuid),
+    typeid_from_composite(anyelement),
+    typeid_timestamp(typeid),
+    typeid_timestamp_millis(typeid),
+    typeid_age(typeid),
+    typeid_suffix(typeid),
+    typeid_unpack(typeid),
+    typeid_uuid_version(typeid),
+    typeid_bucket(typeid, interval),
+    typeid_generate_v4(text),
+    typeid_generate_v8(text, bytea),
+    typeid_generate_series(text, bigint),
+    typeid_generate_sharded(text, integer),
+    typeid_shard(typeid),
+    gen_random_typeid(),
+    typeid_min_for_prefix(text),
+    typeid_max_for_prefix(text),
+    typeid_to_jsonb_parts(typeid),
+    typeid_from_jsonb(jsonb),
+    typeid_from_cdc(text),
+    to_char(typeid, text),
+    typeid_prefix(typeid),
+    typeid_to_struct(typeid),
+    typeid_to_structs(typeid[]),
+    typeid_type_oid(),
+    typeid_type_info(),
+    typeid_dump_format(),
+    typeid_version()
+TO typeid_usage;
+GRANT SELECT ON typeid_columns TO typeid_usage;
+
+REVOKE EXECUTE ON FUNCTION
+    typeid_register_prefix(text, text),
+    typeid_rename_prefix(text, text),
+    typeid_retire_prefix(text),
+    typeid_registry_history(text),
+    typeid_validate_batch(),
+    typeid_sync_prefix_enum(text),
+    typeid_create_monthly_partitions(regclass, text, date, date, boolean),
+    typeid_diff(regclass, name, regclass, name, text, timestamptz, timestamptz),
+    typeid_estimate_rows(regclass, name, tstzrange),
+    typeid_tokenize(typeid, text),
+    typeid_detokenize(typeid, text),
+    typeid_create_tenant_policy(regclass, name, text, boolean),
+    typeid_create_fdw_view(regclass, text, boolean),
+    typeid_bench_setup(integer),
+    typeid_bench_teardown(),
+    typeid_add_prefix_column(regclass, name, name, boolean),
+    typeid_suggest_indexes(regclass, boolean),
+    typeid_first_per_prefix(regclass, name, boolean),
+    typeid_create_composite_cast(regtype),
+    typeid_reload_config(),
+    typeid_reload_status()
+FROM PUBLIC;
+
+GRANT EXECUTE ON FUNCTION
+    typeid_register_prefix(text, text),
+    typeid_rename_prefix(text, text),
+    typeid_retire_prefix(text),
+    typeid_registry_history(text),
+    typeid_validate_batch(),
+    typeid_sync_prefix_enum(text),
+    typeid_create_monthly_partitions(regclass, text, date, date, boolean),
+    typeid_diff(regclass, name, regclass, name, text, timestamptz, timestamptz),
+    typeid_estimate_rows(regclass, name, tstzrange),
+    typeid_tokenize(typeid, text),
+    typeid_detokenize(typeid, text),
+    typeid_create_tenant_policy(regclass, name, text, boolean),
+    typeid_create_fdw_view(regclass, text, boolean),
+    typeid_bench_setup(integer),
+    typeid_bench_teardown(),
+    typeid_add_prefix_column(regclass, name, name, boolean),
+    typeid_suggest_indexes(regclass, boolean),
+    typeid_first_per_prefix(regclass, name, boolean),
+    typeid_create_composite_cast(regtype),
+    typeid_reload_config(),
+    typeid_reload_status(),
+    typeid_settings(),
+    typeid_extension_health(),
+    typeid_verify_spec_vectors()
+TO typeid_admin;
+
+GRANT SELECT, INSERT, UPDATE, DELETE ON typeid_prefix_registry TO typeid_admin;
+-- INSERT is needed by the audit and dictionary triggers, which run as the user changing the
+-- registry
+GRANT SELECT, INSERT ON typeid_registry_history, typeid_prefix_dictionary TO typeid_admin;
+GRANT SELECT, INSERT, UPDATE, DELETE
+    ON typeid_validation_results, typeid_validation_progress
+    TO typeid_admin;
+GRANT SELECT, INSERT, UPDATE (token) ON typeid_tokens TO typeid_admin;
+GRANT SELECT ON typeid_generation_audit TO typeid_admin;
+GRANT USAGE ON SEQUENCE typeid_registry_history_id_seq, typeid_validation_results_id_seq
+    TO typeid_admin;
+
@@ -0,0 +1,309 @@
+//! Suggesting indexes for the typeid columns of a table.
+//!
+//! The suggestions come from the catalogs, the statistics collected by `ANALYZE` and, when the
+//! extension is installed, the statements recorded by pg_stat_statements. Nothing is created, the
+//! returned statements are meant to be reviewed first.
+
+use std::collections::BTreeSet;
+
+use pgrx::prelude::*;
+use pgrx::PgRelation;
+
+use crate::typeid::TypeID;
+
+/// Correlation between the physical row order and the ids from which BRIN indexes pay off
+const BRIN_MIN_CORRELATION: f64 = 0.9;
+/// Pages below which a table is small enough to not need a BRIN index
+const BRIN_MIN_PAGES: i64 = 1000;
+
+/// What the catalogs and statistics tell about a typeid column
+#[derive(Debug, Default)]
+struct ColumnFacts {
+    /// The table, quoted and qualified as needed
+    table: String,
+    /// The column, quoted as needed
+    column: String,
+    /// The schema of the extension, quoted as needed
+    schema: String,
+    pages: i64,
+    has_btree: bool,
+    has_time_index: bool,
+    has_brin: bool,
+    has_prefix_index: bool,
+    /// Number of distinct prefixes seen by `ANALYZE`, None before the column was analyzed
+    prefixes: Option<usize>,
+    correlation: Option<f64>,
+    /// Statements in pg_stat_statements grouping or filtering on the prefix of the column
+    prefix_statements: i64,
+}
+
+/// The suggested statements and the reasons for them
+fn suggestions(facts: &ColumnFacts) -> Vec<(String, String)> {
+    let ColumnFacts {
+        table,
+        column,
+        schema,
+        ..
+    } = facts;
+    let mut suggestions = Vec::new();
+
+    if !facts.has_btree {
+        suggestions.push((
+            format!("CREATE INDEX ON {table} ({column})"),
+            format!(
+                "no btree index starts with {column}, so lookups, prefix filters and keyset \
+                 pagination by id scan the table"
+            ),
+        ));
+    }
+
+    if let Some(prefixes) = facts.prefixes.filter(|prefixes| *prefixes > 1) {
+        if !facts.has_time_index {
+            suggestions.push((
+                format!("CREATE INDEX ON {table} ({schema}.typeid_to_uuid({column}))"),
+                format!(
+                    "{column} holds ids of {prefixes} prefixes and typeids sort by prefix first, \
+                     an index on typeid_to_uuid({column}) sorts them by time across prefixes for \
+                     time range queries filtering on it"
+                ),
+            ));
+        }
+    }
+
+    if let Some(correlation) = facts
+        .correlation
+        .filter(|correlation| *correlation >= BRIN_MIN_CORRELATION)
+    {
+        if !facts.has_brin && facts.pages >= BRIN_MIN_PAGES {
+            suggestions.push((
+                format!("CREATE INDEX ON {table} USING brin ({schema}.typeid_to_uuid({column}))"),
+                format!(
+                    "rows are stored in the order of {column} (correlation {correlation:.2}), as in \
+                     append-only tables, so a BRIN index serves time range queries on \
+                     typeid_to_uuid({column}) at a fraction of the size of a btree"
+                ),
+            ));
+        }
+    }
+
+    if facts.prefix_statements > 0 && !facts.has_prefix_index {
+        suggestions.push((
+            format!("CREATE INDEX ON {table} ({schema}.typeid_prefix({column}))"),
+            format!(
+                "{} statements in pg_stat_statements group or filter on typeid_prefix({column})",
+                facts.prefix_statements
+            ),
+        ));
+    }
+
+    suggestions
+}
+
+/// The distinct prefixes of the most common values and histogram bounds of a column
+fn distinct_prefixes(values: &[String]) -> usize {
+    values
+        .iter()
+        .filter_map(|value| TypeID::from_string(value).ok())
+        .map(|typeid| typeid.type_prefix().to_string())
+        .collect::<BTreeSet<_>>()
+        .len()
+}
+
+/// Suggests indexes for the typeid columns of a table with the reasons for them: a btree index
+/// where none starts with the column, a time-ordered index on `typeid_to_uuid` for columns mixing
+/// several prefixes, a BRIN index for append-only tables and, from the statements recorded by
+/// pg_stat_statements unless `use_stat_statements` is false, an index on `typeid_prefix`.
+/// Statistics come from `ANALYZE`, so analyze the table first.
+#[pg_extern(stable, cost = 1000)]
+fn typeid_suggest_indexes(
+    relation: PgRelation,
+    use_stat_statements: default!(bool, true),
+) -> TableIterator<
+    'static,
+    (
+        name!(column_name, String),
+        name!(statement, String),
+        name!(reason, String),
+    ),
+> {
+    let rows = Spi::connect(|client| {
+        let stat_statements = if use_stat_statements {
+            client
+                .select(
+                    "SELECT (SELECT format('%s.pg_stat_statements', extnamespace::regnamespace)
+                             FROM pg_catalog.pg_extension WHERE extname = 'pg_stat_statements')",
+                    None,
+                    None,
+                )?
+                .first()
+                .get_one::<String>()?
+        } else {
+            None
+        };
+
+        let columns = client.select(
+            "SELECT a.attname::text, quote_ident(a.attname), $1::regclass::text,
+                    (SELECT extnamespace::regnamespace::text FROM pg_catalog.pg_extension WHERE extname = 'typeid'),
+                    c.relpages::int8,
+                    bool_or(am.amname = 'btree' AND i.indkey[0] = a.attnum),
+                    bool_or(am.amname = 'btree' AND strpos(def, format('typeid_to_uuid(%I)', a.attname)) > 0),
+                    bool_or(am.amname = 'brin' AND strpos(def, format('typeid_to_uuid(%I)', a.attname)) > 0),
+                    bool_or(strpos(def, format('typeid_prefix(%I)', a.attname)) > 0),
+                    (SELECT s.most_common_vals::text::text[] || s.histogram_bounds::text::text[]
+                     FROM pg_catalog.pg_stats s
+                     WHERE s.schemaname = n.nspname AND s.tablename = c.relname AND s.attname = a.attname
+                     ORDER BY s.inherited DESC LIMIT 1),
+                    (SELECT s.correlation::float8
+                     FROM pg_catalog.pg_stats s
+                     WHERE s.schemaname = n.nspname AND s.tablename = c.relname AND s.attname = a.attname
+                     ORDER BY s.inherited DESC LIMIT 1)
+             FROM pg_catalog.pg_class c
+             JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+             JOIN pg_catalog.pg_attribute a ON a.attrelid = c.oid
+             LEFT JOIN pg_catalog.pg_index i ON i.indrelid = c.oid
+             LEFT JOIN pg_catalog.pg_class ic ON ic.oid = i.indexrelid
+             LEFT JOIN pg_catalog.pg_am am ON am.oid = ic.relam
+             LEFT JOIN LATERAL pg_catalog.pg_get_indexdef(i.indexrelid) def ON true
+             WHERE c.oid = $1 AND a.atttypid = $2 AND a.attnum > 0 AND NOT a.attisdropped
+             GROUP BY a.attnum, a.attname, n.nspname, c.relname, c.relpages
+             ORDER BY a.attnum",
+            None,
+            Some(vec![
+                (PgBuiltInOids::OIDOID.oid(), relation.oid().into_datum()),
+                crate::catalog::typeid_oid_arg(),
+            ]),
+        )?;
+
+        let mut rows = Vec::new();
+        for column in columns {
+            let name = column.get::<String>(1)?.unwrap_or_default();
+            let mut facts = ColumnFacts {
+                column: column.get::<String>(2)?.unwrap_or_default(),
+                table: column.get::<String>(3)?.unwrap_or_default(),
+                schema: column.get::<String>(4)?.unwrap_or_default(),
+                pages: column.get::<i64>(5)?.unwrap_or_default(),
+                has_btree: column.get::<bool>(6)?.unwrap_or_default(),
+                has_time_index: column.get::<bool>(7)?.unwrap_or_default(),
+                has_brin: column.get::<bool>(8)?.unwrap_or_default(),
+                has_prefix_index: column.get::<bool>(9)?.unwrap_or_default(),
+                prefixes: column
+                    .get::<Vec<Option<String>>>(10)?
+                    .map(|values| distinct_prefixes(&values.into_iter().flatten().collect::<Vec<_>>())),
+                correlation: column.get::<f64>(11)?,
+                prefix_statements: 0,
+            };
+
+            if let Some(stat_statements) = &stat_statements {
+                facts.prefix_statements = client
+                    .select(
+                        &format!(
+                            "SELECT count(*) FROM {stat_statements}
+                             WHERE strpos(query, format('typeid_prefix(%s)', $1)) > 0
+                               AND strpos(query, $2) > 0"
+                        ),
+                        None,
+                        Some(vec![
+                            (PgBuiltInOids::TEXTOID.oid(), name.clone().into_datum()),
+                            (PgBuiltInOids::TEXTOID.oid(), relation.name().into_datum()),
+                        ]),
+                    )?
+                    .first()
+                    .get_one::<i64>()?
+                    .unwrap_or_default();
+            }
+
+            for (statement, reason) in suggestions(&facts) {
+                rows.push((name.clone(), statement, reason));
+            }
+        }
+        Ok::<_, pgrx::spi::Error>(rows)
+    })
+    .unwrap();
+
+    TableIterator::new(rows)
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    use super::ColumnFacts;
+
+    #[test]
+    fn test_suggestions() {
+        let facts = ColumnFacts {
+            table: "events".to_string(),
+            column: "id".to_string(),
+            schema: "public".to_string(),
+            pages: 5000,
+            prefixes: Some(3),
+            correlation: Some(0.99),
+            prefix_statements: 2,
+            ..ColumnFacts::default()
+        };
+        let statements = super::suggestions(&facts)
+            .into_iter()
+            .map(|(statement, _)| statement)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            statements,
+            [
+                "CREATE INDEX ON events (id)",
+                "CREATE INDEX ON events (public.typeid_to_uuid(id))",
+                "CREATE INDEX ON events USING brin (public.typeid_to_uuid(id))",
+                "CREATE INDEX ON events (public.typeid_prefix(id))",
+            ]
+        );
+
+        // Nothing left to suggest once the indexes exist
+        let facts = ColumnFacts {
+            has_btree: true,
+            has_time_index: true,
+            has_brin: true,
+            has_prefix_index: true,
+            ..facts
+        };
+        assert!(super::suggestions(&facts).is_empty());
+
+        // Small tables, a single prefix and shuffled rows need no more than the btree
+        let facts = ColumnFacts {
+            has_btree: true,
+            pages: 10,
+            prefixes: Some(1),
+            correlation: Some(0.1),
+            ..ColumnFacts::default()
+        };
+        assert!(super::suggestions(&facts).is_empty());
+    }
+
+    #[pg_test]
+    fn test_typeid_suggest_indexes() {
+        Spi::run("CREATE TABLE items (id typeid, owner_id typeid PRIMARY KEY)").unwrap();
+        Spi::run(
+            "INSERT INTO items
+             SELECT typeid_generate(CASE WHEN n % 2 = 0 THEN 'book' ELSE 'film' END), typeid_generate('user')
+             FROM generate_series(1, 1000) n",
+        )
+        .unwrap();
+        Spi::run("ANALYZE items").unwrap();
+
+        let suggestions = Spi::get_one::<Vec<String>>(
+            "SELECT array_agg(column_name || ': ' || statement ORDER BY column_name, statement)
+             FROM typeid_suggest_indexes('items', use_stat_statements => false)",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(suggestions.len(), 2, "{suggestions:?}");
+        assert_eq!(suggestions[0], "id: CREATE INDEX ON items (id)");
+        assert!(
+            suggestions[1].starts_with("id: CREATE INDEX ON items (")
+                && suggestions[1].ends_with(".typeid_to_uuid(id))"),
+            "{suggestions:?}"
+        );
+
+        // pg_stat_statements isn't installed in the test database
+        let count = Spi::get_one::<i64>("SELECT count(*) FROM typeid_suggest_indexes('items')");
+        assert_eq!(count, Ok(Some(2)));
+    }
+}
@@ -1,9 +1,33 @@
-use pgrx::{aggregate::*, pg_aggregate, pg_sys};
+use std::collections::BTreeMap;
+
+use pgrx::{aggregate::*, name, pg_aggregate, pg_sys, Internal, Interval, JsonB};
+use serde_json::json;
 
 use crate::typeid::TypeID;
 
 pub struct TypeIDMin;
 pub struct TypeIDMax;
+pub struct TypeIDSessionGap;
+pub struct TypeIDStrictlyIncreasing;
+pub struct TypeIDPrefixStats;
+
+#[derive(Default)]
+struct SessionState {
+    last_millis: Option<u64>,
+    session: i64,
+}
+
+#[derive(Default)]
+struct MonotonicityState {
+    last_millis: Option<u64>,
+    violation: Option<TypeID>,
+}
+
+struct PrefixStats {
+    min: TypeID,
+    max: TypeID,
+    count: i64,
+}
 
 #[pg_aggregate]
 impl Aggregate for TypeIDMin {
@@ -41,6 +65,196 @@ impl Aggregate for TypeIDMax {
     }
 }
 
+/// Numbers sessions of rows ordered by id, starting a new one whenever the embedded timestamps of
+/// two consecutive ids are further apart than `gap`. Meant to be used as a window function:
+/// `typeid_session_gap(id, '30 minutes') OVER (PARTITION BY user_id ORDER BY id)`
+#[pg_aggregate]
+impl Aggregate for TypeIDSessionGap {
+    const NAME: &'static str = "typeid_session_gap";
+    type Args = (name!(id, Option<TypeID>), name!(gap, Option<Interval>));
+    type State = Internal;
+    type Finalize = Option<i64>;
+
+    fn state(
+        mut current: Self::State,
+        (id, gap): Self::Args,
+        fcinfo: pg_sys::FunctionCallInfo,
+    ) -> Self::State {
+        let (Some(id), Some(gap)) = (id, gap) else {
+            return current;
+        };
+        let millis = id.timestamp_millis();
+        let gap_micros = gap.as_micros();
+
+        let state = match unsafe { current.get_mut::<SessionState>() } {
+            Some(state) => state as *mut SessionState,
+            None => {
+                // The state has to outlive the per-row memory context the transition runs in
+                let state = Self::in_memory_context(fcinfo, |context| {
+                    context.leak_and_drop_on_delete(SessionState::default())
+                });
+                current = Internal::from(Some(pg_sys::Datum::from(state)));
+                state
+            }
+        };
+        let state = unsafe { &mut *state };
+
+        match state.last_millis {
+            Some(last) if (millis.saturating_sub(last) as i128) * 1000 > gap_micros => {
+                state.session += 1
+            }
+            Some(_) => {}
+            None => state.session = 1,
+        }
+        state.last_millis = Some(millis);
+
+        current
+    }
+
+    fn finalize(
+        current: Self::State,
+        _direct_args: Self::OrderedSetArgs,
+        _fcinfo: pg_sys::FunctionCallInfo,
+    ) -> Self::Finalize {
+        unsafe { current.get::<SessionState>() }.map(|state| state.session)
+    }
+}
+
+/// Returns the first id, in aggregation order, whose embedded timestamp is earlier than the one of
+/// the id before it, or NULL when the timestamps never decrease. Equal timestamps are allowed, as
+/// ids generated in the same millisecond share them:
+/// `typeid_strictly_increasing(id ORDER BY imported_at)`
+#[pg_aggregate]
+impl Aggregate for TypeIDStrictlyIncreasing {
+    const NAME: &'static str = "typeid_strictly_increasing";
+    type Args = name!(id, Option<TypeID>);
+    type State = Internal;
+    type Finalize = Option<TypeID>;
+
+    fn state(
+        mut current: Self::State,
+        id: Self::Args,
+        fcinfo: pg_sys::FunctionCallInfo,
+    ) -> Self::State {
+        let Some(id) = id else {
+            return current;
+        };
+
+        let state = match unsafe { current.get_mut::<MonotonicityState>() } {
+            Some(state) => state as *mut MonotonicityState,
+            None => {
+                // The state has to outlive the per-row memory context the transition runs in
+                let state = Self::in_memory_context(fcinfo, |context| {
+                    context.leak_and_drop_on_delete(MonotonicityState::default())
+                });
+                current = Internal::from(Some(pg_sys::Datum::from(state)));
+                state
+            }
+        };
+        let state = unsafe { &mut *state };
+        if state.violation.is_some() {
+            return current;
+        }
+
+        let millis = id.timestamp_millis();
+        if state.last_millis.is_some_and(|last| millis < last) {
+            state.violation = Some(id);
+        } else {
+            state.last_millis = Some(millis);
+        }
+
+        current
+    }
+
+    fn finalize(
+        current: Self::State,
+        _direct_args: Self::OrderedSetArgs,
+        _fcinfo: pg_sys::FunctionCallInfo,
+    ) -> Self::Finalize {
+        unsafe { current.get::<MonotonicityState>() }.and_then(|state| state.violation.clone())
+    }
+}
+
+/// Returns the smallest and largest id and the number of ids of every prefix in a single scan, as
+/// a jsonb object keyed by prefix: `{"user": {"min": "user_...", "max": "user_...", "count": 3}}`.
+/// Use `jsonb_each` to get a row per prefix
+#[pg_aggregate]
+impl Aggregate for TypeIDPrefixStats {
+    const NAME: &'static str = "typeid_prefix_stats";
+    type Args = name!(id, Option<TypeID>);
+    type State = Internal;
+    type Finalize = Option<JsonB>;
+
+    fn state(
+        mut current: Self::State,
+        id: Self::Args,
+        fcinfo: pg_sys::FunctionCallInfo,
+    ) -> Self::State {
+        let Some(id) = id else {
+            return current;
+        };
+
+        let state = match unsafe { current.get_mut::<BTreeMap<String, PrefixStats>>() } {
+            Some(state) => state as *mut BTreeMap<String, PrefixStats>,
+            None => {
+                // The state has to outlive the per-row memory context the transition runs in
+                let state = Self::in_memory_context(fcinfo, |context| {
+                    context.leak_and_drop_on_delete(BTreeMap::<String, PrefixStats>::new())
+                });
+                current = Internal::from(Some(pg_sys::Datum::from(state)));
+                state
+            }
+        };
+        let state = unsafe { &mut *state };
+
+        match state.get_mut(id.type_prefix()) {
+            Some(stats) => {
+                if id < stats.min {
+                    stats.min = id.clone();
+                }
+                if id > stats.max {
+                    stats.max = id;
+                }
+                stats.count += 1;
+            }
+            None => {
+                state.insert(
+                    id.type_prefix().to_string(),
+                    PrefixStats {
+                        min: id.clone(),
+                        max: id,
+                        count: 1,
+                    },
+                );
+            }
+        }
+
+        current
+    }
+
+    fn finalize(
+        current: Self::State,
+        _direct_args: Self::OrderedSetArgs,
+        _fcinfo: pg_sys::FunctionCallInfo,
+    ) -> Self::Finalize {
+        let state = unsafe { current.get::<BTreeMap<String, PrefixStats>>() }?;
+        let stats = state
+            .iter()
+            .map(|(prefix, stats)| {
+                (
+                    prefix.clone(),
+                    json!({
+                        "min": stats.min.to_string(),
+                        "max": stats.max.to_string(),
+                        "count": stats.count,
+                    }),
+                )
+            })
+            .collect();
+        Some(JsonB(serde_json::Value::Object(stats)))
+    }
+}
+
 #[cfg(any(test, feature = "pg_test"))]
 #[pgrx::pg_schema]
 mod tests {
@@ -127,4 +341,91 @@ mod tests {
             assert!(max_typeid.unwrap().type_prefix() == "user");
         })
     }
+
+    #[pg_test]
+    fn test_typeid_session_gap() {
+        Spi::run("CREATE TABLE clicks (id typeid)").unwrap();
+        Spi::run(
+            "INSERT INTO clicks VALUES
+                (uuid_to_typeid('click', '018f0000-0000-7000-8000-000000000000')),
+                (uuid_to_typeid('click', '018f0000-ea60-7000-8000-000000000000')),
+                (uuid_to_typeid('click', '018f006d-dd00-7000-8000-000000000000')),
+                (uuid_to_typeid('click', '018f006d-dd01-7000-8000-000000000000'))",
+        )
+        .unwrap();
+
+        let sessions = Spi::get_one::<Vec<i64>>(
+            "SELECT array_agg(session ORDER BY id) FROM (
+                SELECT id, typeid_session_gap(id, '30 minutes') OVER (ORDER BY id) AS session FROM clicks
+            ) s",
+        )
+        .unwrap();
+        assert_eq!(sessions, Some(vec![1, 1, 2, 2]));
+    }
+
+    #[pg_test]
+    fn test_typeid_strictly_increasing() {
+        Spi::run("CREATE TABLE imports (seq int, id typeid)").unwrap();
+        Spi::run(
+            "INSERT INTO imports VALUES
+                (1, uuid_to_typeid('order', '018f0000-0000-7000-8000-000000000000')),
+                (2, uuid_to_typeid('order', '018f0000-0000-7000-8000-000000000001')),
+                (3, uuid_to_typeid('order', '018f0000-ea60-7000-8000-000000000000')),
+                (4, uuid_to_typeid('order', '018f0000-0001-7000-8000-000000000000')),
+                (5, uuid_to_typeid('order', '018f0000-0000-7000-8000-000000000000'))",
+        )
+        .unwrap();
+
+        let violation = Spi::get_one::<String>(
+            "SELECT typeid_strictly_increasing(id ORDER BY seq)::text FROM imports",
+        )
+        .unwrap();
+        assert_eq!(
+            violation.as_deref(),
+            Some("order_01hw000001e008000000000000")
+        );
+
+        let violation = Spi::get_one::<String>(
+            "SELECT typeid_strictly_increasing(id ORDER BY seq)::text FROM imports WHERE seq <= 3",
+        )
+        .unwrap();
+        assert_eq!(violation, None);
+    }
+
+    #[pg_test]
+    fn test_typeid_prefix_stats() {
+        Spi::run("CREATE TABLE events (id typeid)").unwrap();
+        Spi::run(
+            "INSERT INTO events VALUES
+                (uuid_to_typeid('user', '018f0000-0000-7000-8000-000000000002')),
+                (uuid_to_typeid('user', '018f0000-0000-7000-8000-000000000000')),
+                (uuid_to_typeid('user', '018f0000-0000-7000-8000-000000000001')),
+                (uuid_to_typeid('', '018f0000-0000-7000-8000-000000000000')),
+                (NULL)",
+        )
+        .unwrap();
+
+        let stats = Spi::get_one::<JsonB>("SELECT typeid_prefix_stats(id) FROM events")
+            .unwrap()
+            .map(|JsonB(stats)| stats);
+        assert_eq!(
+            stats,
+            Some(json!({
+                "": {
+                    "min": "01hw000000e008000000000000",
+                    "max": "01hw000000e008000000000000",
+                    "count": 1,
+                },
+                "user": {
+                    "min": "user_01hw000000e008000000000000",
+                    "max": "user_01hw000000e008000000000002",
+                    "count": 3,
+                },
+            }))
+        );
+
+        let empty = Spi::get_one::<JsonB>("SELECT typeid_prefix_stats(id) FROM events WHERE false")
+            .unwrap();
+        assert!(empty.is_none());
+    }
 }
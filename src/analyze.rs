@@ -0,0 +1,413 @@
+//! Statistics on the prefixes of typeid columns, gathered by `ANALYZE` next to the standard ones.
+//!
+//! The typanalyze function of typeid runs the standard analysis, then fills two more slots of
+//! `pg_statistic` from the same sample: the share of the rows of every prefix, and the smallest and
+//! largest ids of every prefix, which bound the times its ids were generated. The standard
+//! histogram can't tell how many rows a prefix has when it falls within a bucket, so the `@>`
+//! estimator reads the shares, and `typeid_column_prefixes` shows both slots.
+
+use std::collections::HashMap;
+use std::ffi::c_void;
+
+use pgrx::prelude::*;
+use pgrx::{FromDatum, Internal, IntoDatum, PgMemoryContexts, PgRelation};
+
+use crate::catalog::{check_typeid_column, ColumnName};
+use crate::datum::{TypeIDDatum, TypeIDRef};
+use crate::typeid::TypeID;
+
+/// Kind of the slot with the prefixes and their shares of the rows, the most frequent first
+pub const STATISTIC_KIND_PREFIX_FREQUENCIES: i16 = 20_260;
+/// Kind of the slot with the smallest and largest id of every prefix, in the order of the prefixes
+pub const STATISTIC_KIND_PREFIX_BOUNDS: i16 = 20_261;
+
+/// Prefixes kept in the statistics, the rarer ones are left out beyond it
+const MAX_PREFIXES: usize = 1000;
+
+/// What the standard analysis set up, restored before it computes its statistics
+struct StandardAnalysis {
+    compute_stats: pg_sys::AnalyzeAttrComputeStatsFunc,
+    extra_data: *mut c_void,
+}
+
+/// Rows of a prefix in the sample
+struct PrefixSample {
+    rows: usize,
+    min: [u8; 16],
+    max: [u8; 16],
+}
+
+#[pg_extern(requires = [TypeID])]
+fn typeid_typanalyze(stats: Internal) -> bool {
+    unsafe {
+        let Some(stats) = stats.get_mut::<pg_sys::VacAttrStats>() else {
+            return false;
+        };
+        if !pg_sys::std_typanalyze(stats) {
+            return false;
+        }
+
+        // Allocated in the analyze context, which outlives the computation
+        let standard = pg_sys::palloc(std::mem::size_of::<StandardAnalysis>()).cast();
+        std::ptr::write(
+            standard,
+            StandardAnalysis {
+                compute_stats: stats.compute_stats,
+                extra_data: stats.extra_data,
+            },
+        );
+        stats.extra_data = standard.cast();
+        stats.compute_stats = Some(compute_prefix_stats);
+        true
+    }
+}
+
+#[pg_guard]
+unsafe extern "C" fn compute_prefix_stats(
+    stats: *mut pg_sys::VacAttrStats,
+    fetchfunc: pg_sys::AnalyzeAttrFetchFunc,
+    samplerows: i32,
+    totalrows: f64,
+) {
+    let standard = std::ptr::read((*stats).extra_data.cast::<StandardAnalysis>());
+    (*stats).extra_data = standard.extra_data;
+    if let Some(compute_stats) = standard.compute_stats {
+        compute_stats(stats, fetchfunc, samplerows, totalrows);
+    }
+    let Some(fetch) = fetchfunc else {
+        return;
+    };
+    if !(*stats).stats_valid || samplerows <= 0 {
+        return;
+    }
+
+    let mut prefixes = HashMap::<String, PrefixSample>::new();
+    for row in 0..samplerows {
+        pg_sys::vacuum_delay_point();
+        let mut is_null = false;
+        let datum = fetch(stats, row, &mut is_null);
+        let Some(typeid) = TypeIDRef::from_datum(datum, is_null) else {
+            continue;
+        };
+
+        let uuid = *typeid.uuid_bytes();
+        match prefixes.get_mut(typeid.type_prefix()) {
+            Some(sample) => {
+                sample.rows += 1;
+                sample.min = sample.min.min(uuid);
+                sample.max = sample.max.max(uuid);
+            }
+            None => {
+                prefixes.insert(
+                    typeid.type_prefix().to_string(),
+                    PrefixSample {
+                        rows: 1,
+                        min: uuid,
+                        max: uuid,
+                    },
+                );
+            }
+        }
+    }
+
+    let mut prefixes = prefixes.into_iter().collect::<Vec<_>>();
+    prefixes.sort_by(|(a, a_sample), (b, b_sample)| {
+        b_sample.rows.cmp(&a_sample.rows).then_with(|| a.cmp(b))
+    });
+    prefixes.truncate(MAX_PREFIXES);
+    if prefixes.is_empty() {
+        return;
+    }
+
+    let mut free_slots = (0..pg_sys::STATISTIC_NUM_SLOTS as usize)
+        .filter(|&slot| (*stats).stakind[slot] == 0)
+        .collect::<Vec<_>>()
+        .into_iter();
+
+    PgMemoryContexts::For((*stats).anl_context).switch_to(|_| {
+        if let Some(slot) = free_slots.next() {
+            let values = prefixes
+                .iter()
+                .map(|(prefix, _)| prefix.as_str().into_datum().unwrap())
+                .collect::<Vec<_>>();
+            let numbers = prefixes
+                .iter()
+                .map(|(_, sample)| sample.rows as f32 / samplerows as f32)
+                .collect::<Vec<_>>();
+
+            (*stats).stakind[slot] = STATISTIC_KIND_PREFIX_FREQUENCIES;
+            (*stats).stavalues[slot] = palloc_slice(&values);
+            (*stats).numvalues[slot] = values.len() as i32;
+            (*stats).stanumbers[slot] = palloc_slice(&numbers);
+            (*stats).numnumbers[slot] = numbers.len() as i32;
+            (*stats).statypid[slot] = pg_sys::TEXTOID;
+            (*stats).statyplen[slot] = -1;
+            (*stats).statypbyval[slot] = false;
+            (*stats).statypalign[slot] = b'i' as std::os::raw::c_char;
+        }
+
+        if let Some(slot) = free_slots.next() {
+            let values = prefixes
+                .iter()
+                .flat_map(|(prefix, sample)| [(prefix, &sample.min), (prefix, &sample.max)])
+                .map(|(prefix, uuid)| TypeIDDatum::new(prefix, uuid).into_datum().unwrap())
+                .collect::<Vec<_>>();
+
+            // Typed like the column, which may be a domain over typeid
+            let attrtype = (*stats).attrtype;
+            (*stats).stakind[slot] = STATISTIC_KIND_PREFIX_BOUNDS;
+            (*stats).stavalues[slot] = palloc_slice(&values);
+            (*stats).numvalues[slot] = values.len() as i32;
+            (*stats).statypid[slot] = (*stats).attrtypid;
+            (*stats).statyplen[slot] = (*attrtype).typlen;
+            (*stats).statypbyval[slot] = (*attrtype).typbyval;
+            (*stats).statypalign[slot] = (*attrtype).typalign;
+        }
+    });
+}
+
+/// Copies a slice into memory allocated in the current context
+unsafe fn palloc_slice<T: Copy>(slice: &[T]) -> *mut T {
+    let copy = pg_sys::palloc(std::mem::size_of_val(slice)).cast::<T>();
+    std::ptr::copy_nonoverlapping(slice.as_ptr(), copy, slice.len());
+    copy
+}
+
+/// The prefixes and their shares of the rows in the statistics tuple of a column, None without a
+/// prefix slot
+unsafe fn read_frequencies(stats_tuple: pg_sys::HeapTuple) -> Option<Vec<(String, f64)>> {
+    let mut slot = pg_sys::AttStatsSlot::default();
+    if stats_tuple.is_null()
+        || !pg_sys::get_attstatsslot(
+            &mut slot,
+            stats_tuple,
+            STATISTIC_KIND_PREFIX_FREQUENCIES as i32,
+            pg_sys::InvalidOid,
+            (pg_sys::ATTSTATSSLOT_VALUES | pg_sys::ATTSTATSSLOT_NUMBERS) as i32,
+        )
+    {
+        return None;
+    }
+
+    let count = slot.nvalues.min(slot.nnumbers) as usize;
+    let frequencies = (0..count)
+        .filter_map(|i| {
+            let prefix = String::from_datum(*slot.values.add(i), false)?;
+            Some((prefix, *slot.numbers.add(i) as f64))
+        })
+        .collect();
+    pg_sys::free_attstatsslot(&mut slot);
+    Some(frequencies)
+}
+
+/// The smallest and largest ids of the prefixes in the statistics tuple of a column
+unsafe fn read_bounds(stats_tuple: pg_sys::HeapTuple) -> Vec<(TypeID, TypeID)> {
+    let mut slot = pg_sys::AttStatsSlot::default();
+    if stats_tuple.is_null()
+        || !pg_sys::get_attstatsslot(
+            &mut slot,
+            stats_tuple,
+            STATISTIC_KIND_PREFIX_BOUNDS as i32,
+            pg_sys::InvalidOid,
+            pg_sys::ATTSTATSSLOT_VALUES as i32,
+        )
+    {
+        return Vec::new();
+    }
+
+    let values = std::slice::from_raw_parts(slot.values, slot.nvalues as usize);
+    let bounds = values
+        .chunks_exact(2)
+        .filter_map(|bounds| {
+            let min = TypeIDRef::from_datum(bounds[0], false)?.to_typeid();
+            let max = TypeIDRef::from_datum(bounds[1], false)?.to_typeid();
+            Some((min, max))
+        })
+        .collect();
+    pg_sys::free_attstatsslot(&mut slot);
+    bounds
+}
+
+/// Runs `f` on the statistics of an expression, releasing them afterwards
+pub(crate) unsafe fn with_statistics<T>(
+    root: *mut pg_sys::PlannerInfo,
+    expression: *mut pg_sys::Node,
+    var_relid: i32,
+    f: impl FnOnce(&mut pg_sys::VariableStatData) -> T,
+) -> T {
+    let mut vardata = pg_sys::VariableStatData::default();
+    pg_sys::examine_variable(root, expression, var_relid, &mut vardata);
+    let result = f(&mut vardata);
+    if !vardata.statsTuple.is_null() {
+        if let Some(free) = vardata.freefunc {
+            free(vardata.statsTuple);
+        }
+    }
+    result
+}
+
+/// Share of the rows with `prefix` in a typeid expression, when `ANALYZE` gathered the prefixes of
+/// the column it reads. Prefixes missing from a complete list are rarer than the sample could
+/// tell, which is reported as 0.
+pub(crate) unsafe fn prefix_frequency(
+    root: *mut pg_sys::PlannerInfo,
+    expression: *mut pg_sys::Node,
+    var_relid: i32,
+    prefix: &str,
+) -> Option<f64> {
+    let frequencies = with_statistics(root, expression, var_relid, |vardata| {
+        read_frequencies(vardata.statsTuple)
+    })?;
+    match frequencies.iter().find(|(sampled, _)| sampled == prefix) {
+        Some((_, frequency)) => Some(*frequency),
+        None if frequencies.len() < MAX_PREFIXES => Some(0.0),
+        None => None,
+    }
+}
+
+/// Number of prefixes `ANALYZE` found in a typeid expression, at least as many as it keeps when
+/// it left out rarer ones
+pub(crate) unsafe fn prefix_count(
+    root: *mut pg_sys::PlannerInfo,
+    expression: *mut pg_sys::Node,
+    var_relid: i32,
+) -> Option<usize> {
+    with_statistics(root, expression, var_relid, |vardata| {
+        read_frequencies(vardata.statsTuple)
+    })
+    .map(|frequencies| frequencies.len())
+}
+
+/// The prefixes `ANALYZE` found in a typeid column, with their share of the rows and the smallest
+/// and largest ids it sampled of each, the most frequent prefixes first. Empty until the table is
+/// analyzed. Like `pg_stats`, only shows columns the current user can read.
+#[pg_extern(stable, cost = 100)]
+fn typeid_column_prefixes(
+    relation: PgRelation,
+    column: default!(ColumnName, "'id'"),
+) -> TableIterator<
+    'static,
+    (
+        name!(prefix, String),
+        name!(frequency, f32),
+        name!(min_id, Option<TypeID>),
+        name!(max_id, Option<TypeID>),
+    ),
+> {
+    check_typeid_column(&relation, column.as_str());
+    let (attnum, readable, inherited) = Spi::connect(|client| {
+        client
+            .select(
+                "SELECT a.attnum, has_column_privilege($1, a.attnum, 'SELECT'), c.relkind = 'p'
+                 FROM pg_catalog.pg_attribute a JOIN pg_catalog.pg_class c ON c.oid = a.attrelid
+                 WHERE a.attrelid = $1 AND a.attname = $2",
+                None,
+                Some(vec![
+                    (PgBuiltInOids::OIDOID.oid(), relation.oid().into_datum()),
+                    (PgBuiltInOids::TEXTOID.oid(), column.as_str().into_datum()),
+                ]),
+            )?
+            .first()
+            .get_three::<i16, bool, bool>()
+    })
+    .unwrap();
+    if readable != Some(true) {
+        ereport!(
+            ERROR,
+            PgSqlErrorCode::ERRCODE_INSUFFICIENT_PRIVILEGE,
+            format!("permission denied for table {}", relation.name())
+        );
+    }
+
+    // Partitioned tables only have statistics over their partitions
+    let rows = unsafe {
+        let stats_tuple = pg_sys::SearchSysCache3(
+            pg_sys::SysCacheIdentifier_STATRELATTINH as i32,
+            relation.oid().into_datum().unwrap(),
+            attnum.unwrap_or_default().into_datum().unwrap(),
+            inherited.unwrap_or_default().into_datum().unwrap(),
+        );
+        if stats_tuple.is_null() {
+            Vec::new()
+        } else {
+            let frequencies = read_frequencies(stats_tuple).unwrap_or_default();
+            let mut bounds = read_bounds(stats_tuple).into_iter();
+            pg_sys::ReleaseSysCache(stats_tuple);
+
+            frequencies
+                .into_iter()
+                .map(|(prefix, frequency)| {
+                    let (min, max) = bounds.next().unzip();
+                    (prefix, frequency as f32, min, max)
+                })
+                .collect::<Vec<_>>()
+        }
+    };
+
+    TableIterator::new(rows)
+}
+
+// `ALTER TYPE ... SET` only accepts an analyze function from Postgres 13 on
+extension_sql!(
+    r#"
+    DO $$
+    BEGIN
+        IF current_setting('server_version_num')::int >= 130000 THEN
+            EXECUTE 'ALTER TYPE typeid SET (ANALYZE = typeid_typanalyze)';
+        ELSE
+            UPDATE pg_catalog.pg_type
+            SET typanalyze = 'typeid_typanalyze(internal)'::regprocedure
+            WHERE oid = 'typeid'::regtype;
+        END IF;
+    END
+    $$;
+    "#,
+    name = "set_typeid_typanalyze",
+    requires = [typeid_typanalyze],
+);
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    #[pg_test]
+    fn test_typeid_column_prefixes() {
+        Spi::run("CREATE TABLE items (id typeid)").unwrap();
+        Spi::run(
+            "INSERT INTO items
+             SELECT uuid_to_typeid(p, ('018f0000-' || lpad(to_hex(n), 4, '0') || '-7000-8000-000000000000')::uuid)
+             FROM unnest(ARRAY['org', 'team', 'user']) p, generate_series(1, 1000) n
+             WHERE n <= CASE p WHEN 'org' THEN 100 WHEN 'team' THEN 10 ELSE 1000 END",
+        )
+        .unwrap();
+        Spi::run("INSERT INTO items VALUES (NULL)").unwrap();
+
+        let count = Spi::get_one::<i64>("SELECT count(*) FROM typeid_column_prefixes('items')");
+        assert_eq!(count, Ok(Some(0)));
+
+        Spi::run("ANALYZE items").unwrap();
+        let prefixes = Spi::get_one::<Vec<String>>(
+            "SELECT array_agg(format('%s %s %s %s', prefix, round(frequency::numeric, 3), min_id, max_id))
+             FROM typeid_column_prefixes('items')",
+        );
+        assert_eq!(
+            prefixes,
+            Ok(Some(vec![
+                "user 0.899 user_01hw000001e008000000000000 user_01hw0000z8e008000000000000"
+                    .to_string(),
+                "org 0.090 org_01hw000001e008000000000000 org_01hw000034e008000000000000"
+                    .to_string(),
+                "team 0.009 team_01hw000001e008000000000000 team_01hw00000ae008000000000000"
+                    .to_string(),
+            ]))
+        );
+
+        // The shares of the prefixes are the estimates of `@>`
+        let rows = Spi::explain("SELECT * FROM items WHERE id @> 'team'")
+            .unwrap()
+            .0[0]["Plan"]["Plan Rows"]
+            .as_f64();
+        assert_eq!(rows, Some(10.0));
+    }
+}
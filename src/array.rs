@@ -0,0 +1,211 @@
+use pgrx::prelude::*;
+
+use crate::datum::{TypeIDDatum, TypeIDRef};
+use crate::typeid::TypeID;
+
+/// Sorted, deduplicated copy of a typeid array, kept around for the duration of a query
+struct SortedIds {
+    ids: Vec<TypeID>,
+    has_null: bool,
+}
+
+impl SortedIds {
+    fn new(array: &Array<TypeID>) -> Self {
+        let mut has_null = false;
+        let mut ids: Vec<TypeID> = array
+            .iter()
+            .filter_map(|id| {
+                has_null |= id.is_none();
+                id
+            })
+            .collect();
+
+        ids.sort_unstable();
+        ids.dedup();
+
+        Self { ids, has_null }
+    }
+
+    /// Follows the semantics of `= ANY(...)`: NULL when nothing matched but the array had NULLs
+    fn contains(&self, typeid: &TypeID) -> Option<bool> {
+        match self.ids.binary_search(typeid) {
+            Ok(_) => Some(true),
+            Err(_) if self.has_null => None,
+            Err(_) => Some(false),
+        }
+    }
+}
+
+/// Equivalent of `id = ANY(ids)`. When `ids` is a constant (or a bound parameter) it's sorted once
+/// per query and every row is looked up with a binary search instead of a linear scan.
+/// The planner turns this into `id = ANY(ids)` when the btree index can be used.
+#[pg_extern(immutable, parallel_safe, cost = 10)]
+fn typeid_eq_any(
+    typeid: TypeID,
+    ids: Array<TypeID>,
+    fcinfo: pg_sys::FunctionCallInfo,
+) -> Option<bool> {
+    let stable = unsafe { pg_sys::get_fn_expr_arg_stable((*fcinfo).flinfo, 1) };
+
+    if !stable {
+        let mut has_null = false;
+        for id in ids.iter() {
+            match id {
+                Some(id) if id == typeid => return Some(true),
+                Some(_) => {}
+                None => has_null = true,
+            }
+        }
+        return if has_null { None } else { Some(false) };
+    }
+
+    let sorted = unsafe { pgrx::fcinfo::pg_func_extra(fcinfo, || SortedIds::new(&ids)) };
+    sorted.contains(&typeid)
+}
+
+/// The ids of an array borrowed in place, sorted and deduplicated, without NULLs. Arrays built
+/// from ordered queries or returned by the functions below are already sorted and skip the sort.
+fn sorted_refs<'a>(array: &Array<'_, TypeIDRef<'a>>) -> Vec<TypeIDRef<'a>> {
+    let mut ids: Vec<_> = array.iter().flatten().collect();
+    if !ids.is_sorted() {
+        ids.sort_unstable();
+    }
+    ids.dedup();
+    ids
+}
+
+/// Merges two sorted, deduplicated lists, keeping the ids only in `a` or in both
+fn merge<'a>(a: &[TypeIDRef<'a>], b: &[TypeIDRef<'_>], keep_common: bool) -> Vec<TypeIDRef<'a>> {
+    let mut merged = Vec::with_capacity(a.len());
+    let mut b = b.iter().peekable();
+    for id in a {
+        while b.next_if(|other| *other < id).is_some() {}
+        let common = b.next_if(|other| *other == id).is_some();
+        if common == keep_common {
+            merged.push(*id);
+        }
+    }
+    merged
+}
+
+fn into_datums(ids: Vec<TypeIDRef<'_>>) -> Vec<TypeIDDatum> {
+    ids.iter()
+        .map(|id| TypeIDDatum::new(id.type_prefix(), id.uuid_bytes()))
+        .collect()
+}
+
+/// The distinct ids of an array in ascending order, without NULLs
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_array_unique(ids: Array<'_, TypeIDRef<'_>>) -> Vec<TypeIDDatum> {
+    into_datums(sorted_refs(&ids))
+}
+
+/// The distinct ids of `a` missing from `b` in ascending order, without NULLs
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_array_diff(a: Array<'_, TypeIDRef<'_>>, b: Array<'_, TypeIDRef<'_>>) -> Vec<TypeIDDatum> {
+    into_datums(merge(&sorted_refs(&a), &sorted_refs(&b), false))
+}
+
+/// The distinct ids in both `a` and `b` in ascending order, without NULLs
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_array_intersect(
+    a: Array<'_, TypeIDRef<'_>>,
+    b: Array<'_, TypeIDRef<'_>>,
+) -> Vec<TypeIDDatum> {
+    into_datums(merge(&sorted_refs(&a), &sorted_refs(&b), true))
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    #[pg_test]
+    fn test_typeid_eq_any() {
+        Spi::run("CREATE TABLE events (id typeid primary key)").unwrap();
+        Spi::run(
+            "INSERT INTO events SELECT typeid_generate('event') FROM generate_series(1, 1000)",
+        )
+        .unwrap();
+        Spi::run("ANALYZE events").unwrap();
+
+        let count = Spi::get_one::<i64>(
+            "SELECT count(*) FROM events WHERE typeid_eq_any(id, (SELECT array_agg(id) FROM (SELECT id FROM events ORDER BY id DESC LIMIT 100) ids))",
+        )
+        .unwrap();
+        assert_eq!(count, Some(100));
+
+        let matched = Spi::get_one::<bool>(
+            "SELECT typeid_eq_any('user_01h455vb4pex5vsknk084sn02q', ARRAY['user_01h455vb4pex5vsknk084sn02q', NULL]::typeid[])",
+        )
+        .unwrap();
+        assert_eq!(matched, Some(true));
+
+        let matched = Spi::get_one::<bool>(
+            "SELECT typeid_eq_any('user_01h455vb4pex5vsknk084sn02q', ARRAY['post_01h455vb4pex5vsknk084sn02q', NULL]::typeid[])",
+        )
+        .unwrap();
+        assert_eq!(matched, None);
+
+        Spi::run("SET enable_seqscan = off").unwrap();
+        let plan = Spi::explain(
+            "SELECT * FROM events WHERE typeid_eq_any(id, ARRAY['event_01h455vb4pex5vsknk084sn02q', 'event_01h455vb4pex5vsknk084sn02r']::typeid[])",
+        )
+        .unwrap()
+        .0
+        .to_string();
+        assert!(plan.contains("Index"), "expected an index scan: {plan}");
+    }
+
+    #[pg_test]
+    fn test_typeid_array_functions() {
+        let arrays = Spi::get_three::<Vec<String>, Vec<String>, Vec<String>>(
+            "WITH ids(a, b) AS (SELECT
+                ARRAY['user_01h455vb4pex5vsknk084sn02r', NULL, 'post_01h455vb4pex5vsknk084sn02q',
+                      'user_01h455vb4pex5vsknk084sn02q', 'user_01h455vb4pex5vsknk084sn02r']::typeid[],
+                ARRAY['user_01h455vb4pex5vsknk084sn02r', 'user_01h455vb4pex5vsknk084sn02s']::typeid[]
+             )
+             SELECT typeid_array_unique(a)::text[], typeid_array_diff(a, b)::text[],
+                    typeid_array_intersect(a, b)::text[]
+             FROM ids",
+        )
+        .unwrap();
+        assert_eq!(
+            arrays,
+            (
+                Some(
+                    [
+                        "post_01h455vb4pex5vsknk084sn02q",
+                        "user_01h455vb4pex5vsknk084sn02q",
+                        "user_01h455vb4pex5vsknk084sn02r",
+                    ]
+                    .map(String::from)
+                    .to_vec()
+                ),
+                Some(
+                    [
+                        "post_01h455vb4pex5vsknk084sn02q",
+                        "user_01h455vb4pex5vsknk084sn02q",
+                    ]
+                    .map(String::from)
+                    .to_vec()
+                ),
+                Some(vec!["user_01h455vb4pex5vsknk084sn02r".to_string()]),
+            )
+        );
+
+        // Same results as the generic set operations on larger arrays
+        let same = Spi::get_one::<bool>(
+            "WITH ids(a) AS (SELECT array_agg(typeid_generate('item')) FROM generate_series(1, 500)),
+                  halves(a, b) AS (SELECT a, a[250:] || (SELECT array_agg(typeid_generate('item')) FROM generate_series(1, 100)) FROM ids)
+             SELECT typeid_array_unique(a || a) = (SELECT array_agg(DISTINCT x ORDER BY x) FROM unnest(a) x)
+                AND typeid_array_diff(a, b) = (SELECT array_agg(x ORDER BY x) FROM (SELECT unnest(a) EXCEPT SELECT unnest(b)) d(x))
+                AND typeid_array_intersect(b, a) = (SELECT array_agg(x ORDER BY x) FROM (SELECT unnest(a) INTERSECT SELECT unnest(b)) i(x))
+             FROM halves",
+        );
+        assert_eq!(same, Ok(Some(true)));
+
+        let empty = Spi::get_one::<i32>("SELECT cardinality(typeid_array_unique('{}'))");
+        assert_eq!(empty, Ok(Some(0)));
+    }
+}
@@ -0,0 +1,118 @@
+//! Opt-in audit of id generation, for environments that must attribute the creation of every
+//! identifier. Tables are audited by adding the `typeid_audit_generation` trigger, which records
+//! the new id of every inserted row with the backend, application and transaction inserting it.
+
+use pgrx::prelude::*;
+
+// The trigger function is a security definer, so inserting into an audited table doesn't need any
+// privileges on the audit table and rows can't be forged by writing to it directly
+extension_sql!(
+    r#"
+    CREATE TABLE typeid_generation_audit (
+        audit_id bigserial PRIMARY KEY,
+        id typeid NOT NULL,
+        relation text NOT NULL,
+        backend_pid integer NOT NULL,
+        application_name text NOT NULL,
+        transaction_id bigint NOT NULL,
+        generated_at timestamptz NOT NULL DEFAULT clock_timestamp(),
+        generated_by text NOT NULL DEFAULT session_user
+    );
+
+    CREATE FUNCTION typeid_audit_generation() RETURNS trigger
+    LANGUAGE plpgsql
+    SECURITY DEFINER
+    SET search_path FROM CURRENT
+    AS $$
+    DECLARE
+        column_name text := coalesce(TG_ARGV[0], 'id');
+        new_id typeid := to_jsonb(NEW) ->> column_name;
+    BEGIN
+        IF new_id IS NULL THEN
+            RAISE EXCEPTION 'column % of % is missing or NULL', column_name, TG_TABLE_NAME;
+        END IF;
+        INSERT INTO typeid_generation_audit (id, relation, backend_pid, application_name, transaction_id)
+        VALUES (
+            new_id,
+            format('%I.%I', TG_TABLE_SCHEMA, TG_TABLE_NAME),
+            pg_backend_pid(),
+            current_setting('application_name'),
+            txid_current()
+        );
+        RETURN NULL;
+    END
+    $$;
+
+    CREATE FUNCTION typeid_generation_audit_append_only() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+    BEGIN
+        RAISE EXCEPTION 'typeid_generation_audit is append-only';
+    END
+    $$;
+
+    CREATE TRIGGER typeid_generation_audit_append_only
+    BEFORE UPDATE OR DELETE ON typeid_generation_audit
+    FOR EACH ROW EXECUTE FUNCTION typeid_generation_audit_append_only();
+
+    CREATE TRIGGER typeid_generation_audit_no_truncate
+    BEFORE TRUNCATE ON typeid_generation_audit
+    FOR EACH STATEMENT EXECUTE FUNCTION typeid_generation_audit_append_only();
+
+    SELECT pg_catalog.pg_extension_config_dump('typeid_generation_audit', '');
+    SELECT pg_catalog.pg_extension_config_dump('typeid_generation_audit_audit_id_seq', '');
+    "#,
+    name = "create_generation_audit",
+    requires = ["create_typeid_operator_class"],
+);
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    #[pg_test]
+    fn test_typeid_audit_generation() {
+        Spi::run("CREATE TABLE orders (order_id typeid PRIMARY KEY)").unwrap();
+        Spi::run(
+            "CREATE TRIGGER audit AFTER INSERT ON orders
+             FOR EACH ROW EXECUTE FUNCTION typeid_audit_generation('order_id')",
+        )
+        .unwrap();
+        Spi::run("SET application_name = 'checkout'").unwrap();
+        Spi::run("INSERT INTO orders SELECT typeid_generate_batch('order', 3)").unwrap();
+
+        let audited = Spi::get_one::<bool>(
+            "SELECT count(*) = 3
+                AND bool_and(a.relation = format('%I.orders', current_schema()))
+                AND bool_and(a.backend_pid = pg_backend_pid())
+                AND bool_and(a.application_name = 'checkout')
+                AND bool_and(a.transaction_id = txid_current())
+                AND bool_and(a.generated_by = session_user)
+             FROM typeid_generation_audit a
+             JOIN orders o ON o.order_id = a.id",
+        );
+        assert_eq!(audited, Ok(Some(true)));
+    }
+
+    #[pg_test(error = "column id of orders is missing or NULL")]
+    fn test_typeid_audit_generation_missing_column() {
+        Spi::run("CREATE TABLE orders (order_id typeid PRIMARY KEY)").unwrap();
+        Spi::run(
+            "CREATE TRIGGER audit AFTER INSERT ON orders
+             FOR EACH ROW EXECUTE FUNCTION typeid_audit_generation()",
+        )
+        .unwrap();
+        Spi::run("INSERT INTO orders SELECT typeid_generate('order')").unwrap();
+    }
+
+    #[pg_test(error = "typeid_generation_audit is append-only")]
+    fn test_typeid_generation_audit_append_only() {
+        Spi::run(
+            "INSERT INTO typeid_generation_audit (id, relation, backend_pid, application_name, transaction_id)
+             VALUES (typeid_generate('order'), 'public.orders', pg_backend_pid(), '', txid_current())",
+        )
+        .unwrap();
+        Spi::run("DELETE FROM typeid_generation_audit").unwrap();
+    }
+}
@@ -39,7 +39,8 @@ fn encode_u128_to_base32(data: u128) -> String {
     unsafe { String::from_utf8_unchecked(buf.to_vec()) }
 }
 
-const CROCKFORD: &[u8; 32] = b"0123456789abcdefghjkmnpqrstvwxyz";
+/// The base32 alphabet of suffixes, in ascending byte order
+pub const CROCKFORD: &[u8; 32] = b"0123456789abcdefghjkmnpqrstvwxyz";
 const CROCKFORD_INV: &[u8; 256] = &{
     let mut output = [255; 256];
 
@@ -52,6 +53,14 @@ const CROCKFORD_INV: &[u8; 256] = &{
     output
 };
 
+/// Whether `encoded` decodes to a UUID, without decoding it
+pub fn is_valid_base32_uuid(encoded: &str) -> bool {
+    let encoded = encoded.as_bytes();
+    encoded.len() == 26
+        && CROCKFORD_INV[encoded[0] as usize] <= 7
+        && encoded.iter().all(|b| CROCKFORD_INV[*b as usize] < 32)
+}
+
 pub fn encode_base32_uuid(uuid: &Uuid) -> String {
     encode_u128_to_base32(uuid.as_u128())
 }
@@ -74,4 +83,24 @@ mod tests {
         let decoded = decode_base32_uuid(&encoded).unwrap();
         assert_eq!(uuid, decoded);
     }
+
+    #[test]
+    fn test_is_valid_base32_uuid() {
+        for encoded in [
+            "01h455vb4pex5vsknk084sn02q",
+            "7zzzzzzzzzzzzzzzzzzzzzzzzz",
+            "8zzzzzzzzzzzzzzzzzzzzzzzzz",
+            "01h455vb4pex5vsknk084sn02",
+            "01h455vb4pex5vsknk084sn02qq",
+            "01h455vb4pex5vsknk084sn02u",
+            "01H455VB4PEX5VSKNK084SN02Q",
+            "",
+        ] {
+            assert_eq!(
+                is_valid_base32_uuid(encoded),
+                decode_base32_uuid(encoded).is_ok(),
+                "{encoded}"
+            );
+        }
+    }
 }
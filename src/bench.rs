@@ -0,0 +1,162 @@
+//! pgbench scenarios comparing typeid and uuid primary keys.
+//!
+//! `typeid_bench_setup` creates a typeid-keyed and a uuid-keyed table holding the same UUIDv7s and
+//! returns pgbench scripts running the same transactions against each, so both can be measured on
+//! the hardware they'll run on.
+
+use pgrx::prelude::*;
+
+/// Rows per unit of scale, as for the tables of `pgbench -i`
+const ROWS_PER_SCALE: i64 = 100_000;
+
+const TABLES: [&str; 3] = [
+    "typeid_bench_typeid",
+    "typeid_bench_uuid",
+    "typeid_bench_keys",
+];
+
+/// The pgbench scripts of the scenarios, as pairs of a name and the script
+fn scripts(rows: i64) -> Vec<(String, String)> {
+    let mut scripts = Vec::new();
+    for kind in ["typeid", "uuid"] {
+        let insert =
+            format!("INSERT INTO typeid_bench_{kind} (payload) VALUES (md5(random()::text));\n");
+        let lookup = format!(
+            "\\set n random(1, {rows})\n\
+             SELECT payload FROM typeid_bench_{kind} WHERE id = (SELECT {kind} FROM typeid_bench_keys WHERE n = :n);\n"
+        );
+        let range = format!(
+            "\\set n random(1, {})\n\
+             SELECT count(*) FROM typeid_bench_{kind}\n\
+             WHERE id BETWEEN (SELECT {kind} FROM typeid_bench_keys WHERE n = :n)\n\
+             AND (SELECT {kind} FROM typeid_bench_keys WHERE n = :n + 99);\n",
+            (rows - 99).max(1)
+        );
+        scripts.push((format!("{kind}_insert"), insert));
+        scripts.push((format!("{kind}_lookup"), lookup));
+        scripts.push((format!("{kind}_range"), range));
+    }
+    scripts
+}
+
+/// Creates the benchmark tables with `scale` times 100000 rows in the current schema, replacing
+/// earlier ones, and returns the pgbench scripts to run against them
+#[pg_extern(cost = 10000)]
+fn typeid_bench_setup(
+    scale: i32,
+) -> TableIterator<'static, (name!(name, String), name!(script, String))> {
+    if scale < 1 {
+        error!("scale must be at least 1, got {scale}");
+    }
+    let rows = scale as i64 * ROWS_PER_SCALE;
+
+    typeid_bench_teardown();
+    Spi::connect(|mut client| {
+        client.update(
+            "CREATE TABLE typeid_bench_keys (n bigint PRIMARY KEY, uuid uuid NOT NULL, typeid typeid NOT NULL)",
+            None,
+            None,
+        )?;
+        client.update(
+            "INSERT INTO typeid_bench_keys
+             SELECT row_number() OVER (ORDER BY u), u, uuid_to_typeid('bench', u)
+             FROM (SELECT typeid_uuid_generate_v7() FROM generate_series(1, $1)) g(u)",
+            None,
+            Some(vec![(PgBuiltInOids::INT8OID.oid(), rows.into_datum())]),
+        )?;
+        client.update(
+            "CREATE TABLE typeid_bench_typeid (
+                id typeid PRIMARY KEY DEFAULT typeid_generate('bench'),
+                payload text NOT NULL
+             )",
+            None,
+            None,
+        )?;
+        client.update(
+            "CREATE TABLE typeid_bench_uuid (
+                id uuid PRIMARY KEY DEFAULT typeid_uuid_generate_v7(),
+                payload text NOT NULL
+             )",
+            None,
+            None,
+        )?;
+        for kind in ["typeid", "uuid"] {
+            client.update(
+                &format!(
+                    "INSERT INTO typeid_bench_{kind}
+                     SELECT {kind}, md5(n::text) FROM typeid_bench_keys ORDER BY n"
+                ),
+                None,
+                None,
+            )?;
+        }
+        for table in TABLES {
+            client.update(&format!("ANALYZE {table}"), None, None)?;
+        }
+        Ok::<_, pgrx::spi::Error>(())
+    })
+    .unwrap();
+
+    TableIterator::new(scripts(rows))
+}
+
+/// Drops the tables created by `typeid_bench_setup`
+#[pg_extern]
+fn typeid_bench_teardown() {
+    for table in TABLES {
+        Spi::run(&format!("DROP TABLE IF EXISTS {table}")).unwrap();
+    }
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    #[test]
+    fn test_scripts() {
+        let scripts = super::scripts(100_000);
+        assert_eq!(scripts.len(), 6);
+        let (name, lookup) = &scripts[1];
+        assert_eq!(name, "typeid_lookup");
+        assert!(lookup.starts_with("\\set n random(1, 100000)\n"));
+        assert!(scripts[5].1.starts_with("\\set n random(1, 99901)\n"));
+    }
+
+    #[pg_test]
+    fn test_typeid_bench_setup() {
+        let names = Spi::get_one::<Vec<String>>(
+            "SELECT array_agg(name ORDER BY name) FROM typeid_bench_setup(1)",
+        );
+        assert_eq!(
+            names,
+            Ok(Some(vec![
+                "typeid_insert".to_string(),
+                "typeid_lookup".to_string(),
+                "typeid_range".to_string(),
+                "uuid_insert".to_string(),
+                "uuid_lookup".to_string(),
+                "uuid_range".to_string(),
+            ]))
+        );
+
+        // Both tables hold the same ids in the same order
+        let same = Spi::get_one::<bool>(
+            "SELECT count(*) = 100000 AND bool_and(typeid_to_uuid(t.id) = u.id)
+             FROM (SELECT id, row_number() OVER (ORDER BY id) FROM typeid_bench_typeid) t
+             JOIN (SELECT id, row_number() OVER (ORDER BY id) FROM typeid_bench_uuid) u USING (row_number)",
+        );
+        assert_eq!(same, Ok(Some(true)));
+
+        Spi::run("SELECT typeid_bench_teardown()").unwrap();
+        let left = Spi::get_one::<i64>(
+            "SELECT count(*) FROM pg_class WHERE relname LIKE 'typeid_bench_%'",
+        );
+        assert_eq!(left, Ok(Some(0)));
+    }
+
+    #[pg_test(error = "scale must be at least 1, got 0")]
+    fn test_typeid_bench_setup_scale() {
+        Spi::run("SELECT typeid_bench_setup(0)").unwrap();
+    }
+}
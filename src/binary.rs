@@ -0,0 +1,263 @@
+//! Binary I/O of TypeIDs, used by binary COPY, the binary wire protocol and logical replication.
+//!
+//! A typeid is sent as one byte of prefix length, the prefix and the 16 bytes of the UUID.
+//! `typeid_type_info()` tells client drivers which format version to expect.
+
+use pgrx::prelude::*;
+use pgrx::Internal;
+
+use crate::datum::{TypeIDDatum, TypeIDRef};
+use crate::interop::TYPEID_PREFIX_MAX_LEN;
+use crate::typeid::{TypeID, TypeIDPrefix};
+use crate::typmod;
+
+/// Version of the binary format, bumped on any change drivers would have to follow
+pub const BINARY_FORMAT_VERSION: i32 = 1;
+
+/// Splits a binary typeid into its prefix and UUID, borrowing both from the message. The prefix
+/// is held to the same rules as in text input, binary COPY and replication mustn't be a way around
+/// them.
+pub(crate) fn parse(message: &[u8]) -> Result<(&str, &[u8; 16]), String> {
+    let Some((&prefix_len, rest)) = message.split_first() else {
+        return Err("message is empty".into());
+    };
+    let prefix_len = prefix_len as usize;
+    if prefix_len > TYPEID_PREFIX_MAX_LEN {
+        return Err(format!(
+            "prefix length {prefix_len} exceeds {TYPEID_PREFIX_MAX_LEN}"
+        ));
+    }
+    if rest.len() != prefix_len + 16 {
+        return Err(format!(
+            "expected {} bytes for a prefix of {prefix_len} bytes, got {}",
+            prefix_len + 17,
+            message.len()
+        ));
+    }
+
+    let (prefix, uuid) = rest.split_at(prefix_len);
+    let prefix = match std::str::from_utf8(prefix) {
+        Ok(prefix) if TypeIDPrefix::is_valid(prefix) => prefix,
+        _ => {
+            return Err(format!(
+                "invalid prefix {:?}",
+                String::from_utf8_lossy(prefix)
+            ))
+        }
+    };
+    Ok((prefix, uuid.try_into().unwrap()))
+}
+
+/// Encodes a typeid as a binary message
+pub(crate) fn encode(prefix: &str, uuid: &[u8; 16]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(1 + prefix.len() + 16);
+    message.push(prefix.len() as u8);
+    message.extend_from_slice(prefix.as_bytes());
+    message.extend_from_slice(uuid);
+    message
+}
+
+/// Reads the binary typeid making up the rest of the buffer of a receive function, in place
+pub(crate) fn receive(buf: &Internal) -> (&str, &[u8; 16]) {
+    let Some(buf) = (unsafe { buf.get_mut::<pg_sys::StringInfoData>() }) else {
+        error!("binary typeid input requires a StringInfo");
+    };
+
+    // The message is the rest of the buffer, read in place
+    let message = unsafe {
+        std::slice::from_raw_parts(
+            buf.data.add(buf.cursor as usize).cast::<u8>(),
+            (buf.len - buf.cursor) as usize,
+        )
+    };
+    let parts = match parse(message) {
+        Ok(parts) => parts,
+        Err(err) => {
+            ereport!(
+                ERROR,
+                PgSqlErrorCode::ERRCODE_INVALID_BINARY_REPRESENTATION,
+                format!("invalid binary typeid: {err}")
+            );
+        }
+    };
+    buf.cursor = buf.len;
+    parts
+}
+
+#[pg_extern(immutable, parallel_safe, requires = [TypeID])]
+fn typeid_send(typeid: TypeIDRef<'_>) -> Vec<u8> {
+    encode(typeid.type_prefix(), typeid.uuid_bytes())
+}
+
+/// Receive function of typeid, taking the modifier of the column being read into like the input
+/// function
+#[pg_extern(immutable, parallel_safe, requires = [TypeID])]
+fn typeid_recv(buf: Internal, _typioparam: pg_sys::Oid, typmod: i32) -> TypeIDDatum {
+    let (prefix, uuid) = receive(&buf);
+    typmod::check(prefix, typmod);
+    TypeIDDatum::new(prefix, uuid)
+}
+
+/// The oid of the typeid type, for drivers registering a codec for it
+#[pg_extern(stable, parallel_safe, requires = [TypeID])]
+fn typeid_type_oid() -> pg_sys::Oid {
+    TypeID::type_oid()
+}
+
+/// Everything a driver needs to register codecs for typeid and typeid[] in one round trip
+#[pg_extern(stable, parallel_safe, requires = [TypeID])]
+fn typeid_type_info() -> TableIterator<
+    'static,
+    (
+        name!(oid, pg_sys::Oid),
+        name!(array_oid, pg_sys::Oid),
+        name!(namespace, String),
+        name!(binary_format, i32),
+    ),
+> {
+    let (array_oid, namespace) = Spi::get_two_with_args::<pg_sys::Oid, String>(
+        "SELECT typarray, typnamespace::regnamespace::text FROM pg_catalog.pg_type WHERE oid = $1",
+        vec![(PgBuiltInOids::OIDOID.oid(), TypeID::type_oid().into_datum())],
+    )
+    .unwrap();
+
+    TableIterator::once((
+        TypeID::type_oid(),
+        array_oid.unwrap_or(pg_sys::InvalidOid),
+        namespace.unwrap_or_default(),
+        BINARY_FORMAT_VERSION,
+    ))
+}
+
+// `ALTER TYPE ... SET` only accepts send and receive functions from Postgres 13 on
+extension_sql!(
+    r#"
+    DO $$
+    BEGIN
+        IF current_setting('server_version_num')::int >= 130000 THEN
+            EXECUTE 'ALTER TYPE typeid SET (SEND = typeid_send, RECEIVE = typeid_recv)';
+        ELSE
+            UPDATE pg_catalog.pg_type
+            SET typsend = 'typeid_send(typeid)'::regprocedure,
+                typreceive = 'typeid_recv(internal, oid, integer)'::regprocedure
+            WHERE oid = 'typeid'::regtype;
+        END IF;
+    END
+    $$;
+    "#,
+    name = "set_typeid_binary_io",
+    requires = [typeid_send, typeid_recv],
+);
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    #[test]
+    fn test_parse() {
+        let mut message = vec![4];
+        message.extend_from_slice(b"user");
+        message.extend_from_slice(&[7; 16]);
+        assert_eq!(super::parse(&message), Ok(("user", &[7; 16])));
+
+        assert_eq!(super::parse(&[0; 17]), Ok(("", &[0; 16])));
+        assert!(super::parse(&[]).is_err());
+        assert!(super::parse(&message[..message.len() - 1]).is_err());
+        assert!(super::parse(&[message.as_slice(), &[0]].concat()).is_err());
+
+        for prefix in [&b"User"[..], b"_usr", b"us3r", b"\xffusr"] {
+            let invalid = [&[4][..], prefix, &[0; 16]].concat();
+            assert!(super::parse(&invalid).is_err(), "{prefix:?}");
+        }
+
+        message[0] = 64;
+        assert_eq!(
+            super::parse(&message),
+            Err("prefix length 64 exceeds 63".to_string())
+        );
+    }
+
+    #[pg_test]
+    fn test_typeid_send() {
+        let message = Spi::get_one::<Vec<u8>>(
+            "SELECT typeid_send(uuid_to_typeid('user', '0193addd-9400-7000-8000-000000000000'))",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            message,
+            [
+                &[4][..],
+                b"user",
+                &[0x01, 0x93, 0xad, 0xdd, 0x94, 0x00, 0x70, 0x00, 0x80, 0, 0, 0, 0, 0, 0, 0]
+            ]
+            .concat()
+        );
+
+        let binary_io = Spi::get_one::<bool>(
+            "SELECT typsend = 'typeid_send'::regproc AND typreceive = 'typeid_recv'::regproc
+             FROM pg_type WHERE oid = 'typeid'::regtype",
+        );
+        assert_eq!(binary_io, Ok(Some(true)));
+    }
+
+    #[pg_test]
+    fn test_typeid_type_info() {
+        let matches = Spi::get_one::<bool>(
+            "SELECT i.oid = 'typeid'::regtype
+                AND i.oid = typeid_type_oid()
+                AND i.array_oid = 'typeid[]'::regtype
+                AND i.namespace = (SELECT extnamespace::regnamespace::text FROM pg_extension WHERE extname = 'typeid')
+                AND i.binary_format = 1
+             FROM typeid_type_info() i",
+        );
+        assert_eq!(matches, Ok(Some(true)));
+    }
+
+    #[pg_test(error = "invalid binary typeid: invalid prefix \"us3r\"")]
+    fn test_typeid_recv_invalid_prefix() {
+        let message = [&[4][..], b"us3r", &[0x01; 16]].concat();
+        unsafe {
+            let buf = pg_sys::makeStringInfo();
+            pg_sys::appendBinaryStringInfo(buf, message.as_ptr().cast(), message.len() as i32);
+            super::typeid_recv(
+                pgrx::Internal::from(Some(pg_sys::Datum::from(buf))),
+                pg_sys::InvalidOid,
+                -1,
+            );
+        }
+    }
+
+    #[pg_test(error = "typeid('user') requires prefix \"user\", got \"org\"")]
+    fn test_typeid_recv_typmod() {
+        Spi::run("CREATE TABLE users (id typeid('user'))").unwrap();
+        let message = [&[3][..], b"org", &[0x01; 16]].concat();
+        unsafe {
+            let buf = pg_sys::makeStringInfo();
+            pg_sys::appendBinaryStringInfo(buf, message.as_ptr().cast(), message.len() as i32);
+            super::typeid_recv(
+                pgrx::Internal::from(Some(pg_sys::Datum::from(buf))),
+                pg_sys::InvalidOid,
+                crate::typmod::typmod_of("user"),
+            );
+        }
+    }
+
+    #[pg_test]
+    fn test_typeid_recv() {
+        let message = [&[4][..], b"user", &[0x01; 16]].concat();
+        let typeid = unsafe {
+            let buf = pg_sys::makeStringInfo();
+            pg_sys::appendBinaryStringInfo(buf, message.as_ptr().cast(), message.len() as i32);
+            let datum = super::typeid_recv(
+                pgrx::Internal::from(Some(pg_sys::Datum::from(buf))),
+                pg_sys::InvalidOid,
+                -1,
+            );
+            crate::typeid::TypeID::from_datum(datum.into_datum().unwrap(), false).unwrap()
+        };
+        assert_eq!(typeid.type_prefix(), "user");
+        assert_eq!(typeid.uuid().as_bytes(), &[0x01; 16]);
+    }
+}
@@ -0,0 +1,254 @@
+//! Catalog lookups shared by the maintenance helpers taking tables and columns as arguments.
+//!
+//! Helpers take tables as `regclass` and columns as `name`, never as SQL text. Identifiers only
+//! get into statements through `format()` with `%I` or a `regclass` cast, so arguments coming from
+//! application tooling can't inject SQL.
+
+use std::ffi::CStr;
+
+use pgrx::pgrx_sql_entity_graph::metadata::{
+    ArgumentError, Returns, ReturnsError, SqlMapping, SqlTranslatable,
+};
+use pgrx::prelude::*;
+use pgrx::spi::{self, SpiClient};
+use pgrx::{PgOid, PgRelation};
+
+use crate::typeid::TypeID;
+
+/// The oid of the typeid type as a query argument
+pub fn typeid_oid_arg() -> (PgOid, Option<pg_sys::Datum>) {
+    (
+        PgBuiltInOids::OIDOID.oid(),
+        PgOid::from(TypeID::type_oid()).value().into_datum(),
+    )
+}
+
+/// The schema of the extension, quoted as needed, to reach its tables whatever the `search_path`
+pub fn extension_schema(client: &SpiClient<'_>) -> spi::Result<String> {
+    let schema = client
+        .select(
+            "SELECT (SELECT extnamespace::regnamespace::text FROM pg_catalog.pg_extension WHERE extname = 'typeid')",
+            None,
+            None,
+        )?
+        .first()
+        .get_one::<String>()?;
+    Ok(schema.unwrap_or_else(|| error!("extension typeid is not installed")))
+}
+
+/// A column argument, declared as `name` like the columns of the system catalogs
+pub struct ColumnName(String);
+
+impl ColumnName {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromDatum for ColumnName {
+    unsafe fn from_polymorphic_datum(
+        datum: pg_sys::Datum,
+        is_null: bool,
+        _typoid: pg_sys::Oid,
+    ) -> Option<Self> {
+        if is_null {
+            return None;
+        }
+
+        let name = datum.cast_mut_ptr::<pg_sys::NameData>();
+        let name = CStr::from_ptr((*name).data.as_ptr());
+        Some(Self(name.to_string_lossy().into_owned()))
+    }
+}
+
+unsafe impl SqlTranslatable for ColumnName {
+    fn argument_sql() -> Result<SqlMapping, ArgumentError> {
+        Ok(SqlMapping::literal("name"))
+    }
+    fn return_sql() -> Result<Returns, ReturnsError> {
+        Ok(Returns::One(SqlMapping::literal("name")))
+    }
+}
+
+/// Errors unless `column` is a typeid column of the relation
+pub fn check_typeid_column(relation: &PgRelation, column: &str) {
+    let is_typeid = Spi::get_one_with_args::<bool>(
+        "SELECT atttypid = $3 FROM pg_catalog.pg_attribute
+         WHERE attrelid = $1 AND attname = $2 AND attnum > 0 AND NOT attisdropped",
+        vec![
+            (PgBuiltInOids::OIDOID.oid(), relation.oid().into_datum()),
+            (PgBuiltInOids::TEXTOID.oid(), column.into_datum()),
+            typeid_oid_arg(),
+        ],
+    )
+    .unwrap_or(None);
+
+    match is_typeid {
+        Some(true) => {}
+        Some(false) => error!(
+            "column {column:?} of {:?} is not of type typeid",
+            relation.name()
+        ),
+        None => error!("column {column:?} of {:?} does not exist", relation.name()),
+    }
+}
+
+// Like `information_schema.columns`, only lists the columns the current user has some privilege on.
+// Prefixes are recognized in type modifiers, in domain and column checks of `typeid_has_prefix`
+// and `typeid_prefix_like` without wildcards, and in defaults calling one of the generators.
+extension_sql!(
+    r#"
+    CREATE VIEW typeid_columns AS
+    SELECT
+        n.nspname AS table_schema,
+        c.relname AS table_name,
+        a.attname AS column_name,
+        format_type(a.atttypid, a.atttypmod) AS data_type,
+        a.atttypid = 'typeid[]'::regtype AS is_array,
+        coalesce(
+            modifier.prefix, domain_check.prefix, column_check.prefix, generated.prefix
+        ) AS expected_prefix,
+        CASE
+            WHEN modifier.prefix IS NOT NULL THEN 'typmod'
+            WHEN domain_check.prefix IS NOT NULL THEN 'domain'
+            WHEN column_check.prefix IS NOT NULL THEN 'check'
+            WHEN generated.prefix IS NOT NULL THEN 'default'
+        END AS prefix_source,
+        pg_get_expr(d.adbin, d.adrelid) AS column_default,
+        NOT a.attnotnull AS is_nullable
+    FROM pg_catalog.pg_attribute a
+    JOIN pg_catalog.pg_class c ON c.oid = a.attrelid
+    JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+    JOIN pg_catalog.pg_type t ON t.oid = a.atttypid
+    LEFT JOIN pg_catalog.pg_attrdef d ON d.adrelid = a.attrelid AND d.adnum = a.attnum
+    CROSS JOIN LATERAL (
+        SELECT substring(
+            format_type('typeid'::regtype, greatest(a.atttypmod, t.typtypmod))
+            FROM $$^typeid\('([a-z_]*)'\)$$
+        ) AS prefix
+    ) modifier
+    LEFT JOIN LATERAL (
+        SELECT substring(
+            pg_get_constraintdef(con.oid)
+            FROM $$typeid_(?:has_prefix|prefix_like)\(VALUE, '([a-z_]*)'::text\)$$
+        ) AS prefix
+        FROM pg_catalog.pg_constraint con
+        WHERE con.contypid = a.atttypid AND con.contype = 'c'
+        ORDER BY prefix NULLS LAST
+        LIMIT 1
+    ) domain_check ON true
+    LEFT JOIN LATERAL (
+        SELECT substring(
+            pg_get_constraintdef(con.oid)
+            FROM $$typeid_(?:has_prefix|prefix_like)\([^,]+, '([a-z_]*)'::text\)$$
+        ) AS prefix
+        FROM pg_catalog.pg_constraint con
+        WHERE con.conrelid = a.attrelid AND con.conkey = ARRAY[a.attnum] AND con.contype = 'c'
+        ORDER BY prefix NULLS LAST
+        LIMIT 1
+    ) column_check ON true
+    CROSS JOIN LATERAL (
+        SELECT substring(
+            pg_get_expr(d.adbin, d.adrelid) FROM $$typeid_generate\w*\('([a-z_]*)'::text$$
+        ) AS prefix
+    ) generated
+    WHERE a.attnum > 0
+      AND NOT a.attisdropped
+      AND c.relkind IN ('r', 'p', 'v', 'm', 'f')
+      AND (
+        a.atttypid IN ('typeid'::regtype, 'typeid[]'::regtype)
+        OR (t.typtype = 'd' AND t.typbasetype = 'typeid'::regtype)
+      )
+      AND has_column_privilege(c.oid, a.attnum, 'SELECT, INSERT, UPDATE, REFERENCES');
+    "#,
+    name = "create_typeid_columns_view",
+    requires = [TypeID],
+);
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    #[pg_test]
+    fn test_typeid_columns() {
+        Spi::run("CREATE DOMAIN user_id AS typeid CHECK (typeid_has_prefix(VALUE, 'user'))")
+            .unwrap();
+        Spi::run(
+            "CREATE TABLE memberships (
+                id typeid PRIMARY KEY DEFAULT typeid_generate('member'),
+                user_id user_id NOT NULL,
+                org_id typeid CHECK (typeid_prefix_like(org_id, 'org')),
+                invited_by typeid('user'),
+                tags typeid[],
+                note text
+             )",
+        )
+        .unwrap();
+
+        let columns = Spi::connect(|client| {
+            client
+                .select(
+                    "SELECT column_name::text, data_type, is_array, expected_prefix, prefix_source
+                     FROM typeid_columns
+                     WHERE table_name = 'memberships'
+                     ORDER BY column_name",
+                    None,
+                    None,
+                )?
+                .map(|row| {
+                    Ok((
+                        row.get::<String>(1)?.unwrap(),
+                        row.get::<String>(2)?.unwrap(),
+                        row.get::<bool>(3)?.unwrap(),
+                        row.get::<String>(4)?,
+                        row.get::<String>(5)?,
+                    ))
+                })
+                .collect::<Result<Vec<_>, pgrx::spi::Error>>()
+        })
+        .unwrap();
+
+        let some = |s: &str| Some(s.to_string());
+        assert_eq!(
+            columns,
+            vec![
+                (
+                    "id".into(),
+                    "typeid".into(),
+                    false,
+                    some("member"),
+                    some("default")
+                ),
+                (
+                    "invited_by".into(),
+                    "typeid('user')".into(),
+                    false,
+                    some("user"),
+                    some("typmod")
+                ),
+                (
+                    "org_id".into(),
+                    "typeid".into(),
+                    false,
+                    some("org"),
+                    some("check")
+                ),
+                ("tags".into(), "typeid[]".into(), true, None, None),
+                (
+                    "user_id".into(),
+                    "user_id".into(),
+                    false,
+                    some("user"),
+                    some("domain")
+                ),
+            ]
+        );
+
+        let default = Spi::get_one::<bool>(
+            "SELECT column_default LIKE '%typeid_generate(''member''::text)'
+             FROM typeid_columns WHERE table_name = 'memberships' AND column_name = 'id'",
+        );
+        assert_eq!(default, Ok(Some(true)));
+    }
+}
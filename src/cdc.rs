@@ -0,0 +1,118 @@
+//! Reading TypeIDs back from change data capture pipelines.
+//!
+//! Logical decoding always emits the canonical string of a typeid in text mode, wal2json and
+//! pgoutput both use the output function. Binary pgoutput subscribers and the connectors feeding
+//! Kafka re-encode values on the way though, so what arrives downstream can be a JSON string, the
+//! object of `typeid_to_jsonb_parts` or the hex of the binary format.
+
+use pgrx::prelude::*;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::binary;
+use crate::json;
+use crate::typeid::{TypeID, TypeIDPrefix};
+
+/// Decodes the `\x` hex text of a bytea
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Parses a typeid in any of the shapes CDC pipelines emit it in: the canonical string, surrounded
+/// by whitespace or not, a JSON string, a `{"prefix": ..., "uuid": ...}` object or the hex bytea of
+/// the binary format
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_from_cdc(value: &str) -> TypeID {
+    let value = value.trim();
+
+    if let Some(hex) = value.strip_prefix("\\x") {
+        let Some(message) = decode_hex(hex) else {
+            error!("invalid hex in typeid {value:?}");
+        };
+        return match binary::parse(&message) {
+            Ok((prefix, uuid)) => {
+                TypeID::new(TypeIDPrefix::try_unsafe(prefix), Uuid::from_bytes(*uuid))
+            }
+            Err(err) => error!("invalid binary typeid: {err}"),
+        };
+    }
+
+    if value.starts_with(['"', '{']) {
+        return match serde_json::from_str::<Value>(value) {
+            Ok(Value::String(id)) => typeid_from_cdc(&id),
+            Ok(parts @ Value::Object(_)) => json::from_parts(&parts),
+            _ => error!("typeid {value} is not a valid JSON string or object"),
+        };
+    }
+
+    match TypeID::from_string(value) {
+        Ok(typeid) => typeid,
+        Err(err) => error!("invalid typeid {value:?}: {err}"),
+    }
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    #[test]
+    fn test_decode_hex() {
+        assert_eq!(super::decode_hex("00ff7A"), Some(vec![0, 255, 122]));
+        assert_eq!(super::decode_hex("abc"), None);
+        assert_eq!(super::decode_hex("zz"), None);
+    }
+
+    #[pg_test]
+    fn test_typeid_from_cdc() {
+        let variants = Spi::get_one::<bool>(
+            r#"WITH t(id) AS (SELECT typeid_generate('user'))
+               SELECT bool_and(typeid_from_cdc(v) = t.id)
+               FROM t, LATERAL (VALUES
+                   (t.id::text),
+                   (format(E' %s\n', t.id)),
+                   (to_json(t.id)::text),
+                   (to_json(to_json(t.id)::text)::text),
+                   (typeid_to_jsonb_parts(t.id)::text),
+                   (typeid_send(t.id)::text)
+               ) v(v)"#,
+        );
+        assert_eq!(variants, Ok(Some(true)));
+
+        let unprefixed = Spi::get_one::<String>(
+            "SELECT typeid_from_cdc('\"00000000000000000000000000\"')::text",
+        );
+        assert_eq!(
+            unprefixed,
+            Ok(Some("00000000000000000000000000".to_string()))
+        );
+    }
+
+    #[pg_test]
+    fn test_canonical_text_for_decoding() {
+        // Output plugins format values with the output function, as these casts do
+        let canonical = Spi::get_one::<bool>(
+            "SELECT id::text = 'user_01jepxv500e008000000000000'
+                AND to_json(id)::text = format('\"%s\"', id)
+                AND to_jsonb(ARRAY[id]) ->> 0 = id::text
+             FROM uuid_to_typeid('user', '0193addd-9400-7000-8000-000000000000') id",
+        );
+        assert_eq!(canonical, Ok(Some(true)));
+    }
+
+    #[pg_test(error = "typeid {\"prefix\" is not a valid JSON string or object")]
+    fn test_typeid_from_cdc_invalid_json() {
+        Spi::run(r#"SELECT typeid_from_cdc('{"prefix"')"#).unwrap();
+    }
+
+    #[pg_test(error = "invalid binary typeid: message is empty")]
+    fn test_typeid_from_cdc_empty_hex() {
+        Spi::run(r"SELECT typeid_from_cdc('\x')").unwrap();
+    }
+}
@@ -0,0 +1,189 @@
+//! Functions of the pure SQL implementation of typeids, so queries and views written against it
+//! keep working after switching to the extension. Its `typeid` type is a composite of the prefix
+//! and the UUID, which these functions take apart and put together as records. Columns of the
+//! composite are converted by `typeid_from_composite`, or by the assignment cast
+//! `typeid_create_composite_cast` creates for the composite type of a database.
+
+use pgrx::prelude::*;
+
+extension_sql!(
+    r#"
+    CREATE FUNCTION typeid_parse(typeid text, OUT prefix text, OUT uuid uuid)
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE sql
+    AS $$
+        SELECT @extschema@.typeid_prefix(id), @extschema@.typeid_to_uuid(id)
+        FROM (SELECT $1::@extschema@.typeid AS id) parsed
+    $$;
+    "#,
+    name = "create_typeid_parse",
+    requires = [prefix::typeid_prefix, typeid_to_uuid],
+);
+
+// Postgres can't overload on the return type alone, so the typeid of a pair stays
+// `uuid_to_typeid(prefix, uuid)`. Like in the SQL implementation, a NULL prefix is the empty one.
+extension_sql!(
+    r#"
+    CREATE FUNCTION typeid_print(prefix text, uuid uuid) RETURNS text
+    IMMUTABLE PARALLEL SAFE
+    LANGUAGE sql
+    AS $$
+        SELECT @extschema@.uuid_to_typeid(coalesce(prefix, ''), uuid)::text
+    $$;
+    "#,
+    name = "create_typeid_print",
+    requires = [uuid_to_typeid],
+);
+
+// The composite type is created by each database, so the extension can't declare casts from it.
+// Fields are read through jsonb, which works for any composite: `prefix`, or `type` as in some
+// versions of the SQL implementation, and `uuid`.
+extension_sql!(
+    r#"
+    CREATE FUNCTION typeid_from_composite(value anyelement) RETURNS typeid
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE plpgsql
+    AS $$
+    DECLARE
+        fields jsonb := to_jsonb(value);
+    BEGIN
+        IF jsonb_typeof(fields) IS DISTINCT FROM 'object' OR NOT fields ? 'uuid' THEN
+            RAISE EXCEPTION '% is not a composite of a prefix and a uuid', pg_typeof(value)
+                USING ERRCODE = 'datatype_mismatch';
+        END IF;
+        RETURN @extschema@.uuid_to_typeid(
+            coalesce(fields->>'prefix', fields->>'type', ''),
+            (fields->>'uuid')::uuid
+        );
+    END
+    $$;
+
+    CREATE FUNCTION typeid_create_composite_cast(composite regtype) RETURNS void
+    LANGUAGE plpgsql
+    AS $$
+    BEGIN
+        IF (
+            SELECT count(*) FILTER (WHERE a.attname = 'uuid' AND a.atttypid = 'uuid'::regtype) = 0
+                OR count(*) FILTER (WHERE a.attname IN ('prefix', 'type')) = 0
+            FROM pg_catalog.pg_type t
+            JOIN pg_catalog.pg_attribute a
+                ON a.attrelid = t.typrelid AND a.attnum > 0 AND NOT a.attisdropped
+            WHERE t.oid = composite
+        ) THEN
+            RAISE EXCEPTION '% is not a composite of a prefix and a uuid', composite
+                USING ERRCODE = 'datatype_mismatch',
+                      HINT = 'Expected a field prefix or type and a field uuid of type uuid.';
+        END IF;
+        EXECUTE format(
+            'CREATE CAST (%s AS @extschema@.typeid)
+             WITH FUNCTION @extschema@.typeid_from_composite(anyelement) AS ASSIGNMENT',
+            composite
+        );
+    END
+    $$;
+    "#,
+    name = "create_typeid_from_composite",
+    requires = [uuid_to_typeid],
+);
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    #[pg_test]
+    fn test_typeid_parse() {
+        let parsed = Spi::get_two::<String, String>(
+            "SELECT prefix, uuid::text FROM typeid_parse('user_01h455vb4pex5vsknk084sn02q')",
+        );
+        assert_eq!(
+            parsed,
+            Ok((
+                Some("user".to_string()),
+                Some("01890a5d-ac96-774b-bcce-b302099a8057".to_string())
+            ))
+        );
+
+        let prefix =
+            Spi::get_one::<String>("SELECT (typeid_parse('01h455vb4pex5vsknk084sn02q')).prefix");
+        assert_eq!(prefix, Ok(Some(String::new())));
+    }
+
+    #[pg_test]
+    fn test_typeid_print() {
+        let printed = Spi::get_one::<Vec<Option<String>>>(
+            "SELECT ARRAY[
+                typeid_print('user', '01890a5d-ac96-774b-bcce-b302099a8057'),
+                typeid_print('', '01890a5d-ac96-774b-bcce-b302099a8057'),
+                typeid_print(NULL, '01890a5d-ac96-774b-bcce-b302099a8057'),
+                typeid_print('user', NULL)
+            ]",
+        );
+        assert_eq!(
+            printed,
+            Ok(Some(vec![
+                Some("user_01h455vb4pex5vsknk084sn02q".to_string()),
+                Some("01h455vb4pex5vsknk084sn02q".to_string()),
+                Some("01h455vb4pex5vsknk084sn02q".to_string()),
+                None
+            ]))
+        );
+
+        let round_trip = Spi::get_one::<bool>(
+            "SELECT bool_and(typeid_print(p.prefix, p.uuid) = id::text)
+             FROM (SELECT typeid_generate('user_account') id FROM generate_series(1, 10)) ids,
+                  typeid_parse(id::text) p",
+        );
+        assert_eq!(round_trip, Ok(Some(true)));
+    }
+
+    #[pg_test(error = "invalid typeid prefix \"User\"")]
+    fn test_typeid_print_invalid_prefix() {
+        Spi::run("SELECT typeid_print('User', '01890a5d-ac96-774b-bcce-b302099a8057')").unwrap();
+    }
+
+    #[pg_test]
+    fn test_typeid_composite_migration() {
+        Spi::run("CREATE TYPE old_typeid AS (prefix text, uuid uuid)").unwrap();
+        Spi::run("CREATE TYPE jet_typeid AS (type varchar(63), uuid uuid)").unwrap();
+        Spi::run("CREATE TABLE users (id old_typeid, team_id jet_typeid)").unwrap();
+        Spi::run(
+            "INSERT INTO users VALUES
+                (('user', '01890a5d-ac96-774b-bcce-b302099a8057'),
+                 ('team', '01890a5d-ac96-774b-bcce-b302099a8057')),
+                (('', '01890a5d-ac96-774b-bcce-b302099a8057'),
+                 (NULL, '01890a5d-ac96-774b-bcce-b302099a8057')),
+                (NULL, NULL)",
+        )
+        .unwrap();
+
+        Spi::run("SELECT typeid_create_composite_cast('old_typeid')").unwrap();
+        Spi::run("ALTER TABLE users ALTER id TYPE typeid").unwrap();
+        Spi::run(
+            "ALTER TABLE users ALTER team_id TYPE typeid USING typeid_from_composite(team_id)",
+        )
+        .unwrap();
+
+        let ids = Spi::get_one::<Vec<Option<String>>>(
+            "SELECT array_agg(id::text || ' ' || team_id::text ORDER BY id NULLS LAST) FROM users",
+        );
+        assert_eq!(
+            ids,
+            Ok(Some(vec![
+                Some("01h455vb4pex5vsknk084sn02q 01h455vb4pex5vsknk084sn02q".to_string()),
+                Some("user_01h455vb4pex5vsknk084sn02q team_01h455vb4pex5vsknk084sn02q".to_string()),
+                None
+            ]))
+        );
+    }
+
+    #[pg_test(error = "integer is not a composite of a prefix and a uuid")]
+    fn test_typeid_create_composite_cast_invalid() {
+        Spi::run("SELECT typeid_create_composite_cast('integer')").unwrap();
+    }
+
+    #[pg_test(error = "invalid input syntax for type typeid: \"user_nope\"")]
+    fn test_typeid_parse_invalid() {
+        Spi::run("SELECT typeid_parse('user_nope')").unwrap();
+    }
+}
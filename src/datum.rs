@@ -0,0 +1,368 @@
+//! Reading typeid datums in place.
+//!
+//! A typeid is stored as the length of its prefix, the prefix and the 16 bytes of the UUID, the
+//! same layout as its binary format:
+//!
+//! ```text
+//! n                         one byte, the length of the prefix
+//! <prefix>                  n bytes
+//! <uuid>                    16 bytes
+//! ```
+//!
+//! Storage format 1, written by 0.1, was the CBOR encoding of `TypeID(prefix, uuid)`. Its first
+//! byte is above any prefix length, so those values are still read, while new values are always
+//! written in the current format:
+//!
+//! ```text
+//! 0x82                      array of two items
+//! 0x60 + n | 0x78 n         text string of n bytes, the prefix
+//! <prefix>
+//! 0x50                      byte string of 16 bytes
+//! <uuid>
+//! ```
+//!
+//! `TypeIDRef` borrows the prefix and UUID straight from the datum, so comparisons, hashing and
+//! prefix checks don't have to deserialize and copy their arguments first.
+
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+
+use pgrx::pgrx_sql_entity_graph::metadata::{
+    ArgumentError, Returns, ReturnsError, SqlMapping, SqlTranslatable,
+};
+use pgrx::prelude::*;
+use pgrx::{vardata_any, varsize_any_exhdr, FromDatum, IntoDatum};
+
+use crate::typeid::{TypeID, TypeIDPrefix, STORAGE_FORMAT_VERSION};
+
+const CBOR_ARRAY_OF_TWO: u8 = 0x82;
+const CBOR_SHORT_TEXT: u8 = 0x60;
+const CBOR_TEXT_U8_LENGTH: u8 = 0x78;
+const CBOR_BYTES_OF_16: u8 = 0x50;
+
+#[derive(Debug, Clone, Copy)]
+pub struct TypeIDRef<'a> {
+    prefix: &'a str,
+    uuid: &'a [u8; 16],
+    /// The whole encoded typeid, when it's in the current storage format
+    encoded: Option<&'a [u8]>,
+}
+
+impl<'a> TypeIDRef<'a> {
+    /// Splits an encoded typeid into its prefix and UUID
+    pub fn parse(encoded: &'a [u8]) -> Option<Self> {
+        let (&len, rest) = encoded.split_first()?;
+        if len == CBOR_ARRAY_OF_TWO {
+            return Self::parse_cbor(rest);
+        }
+
+        let len = len as usize;
+        if rest.len() != len + 16 {
+            return None;
+        }
+        let (prefix, uuid) = rest.split_at(len);
+        Some(Self {
+            prefix: std::str::from_utf8(prefix).ok()?,
+            uuid: uuid.try_into().ok()?,
+            encoded: Some(encoded),
+        })
+    }
+
+    /// Splits a typeid in storage format 1, following its CBOR array header
+    fn parse_cbor(encoded: &'a [u8]) -> Option<Self> {
+        let (len, rest) = match encoded.split_first()? {
+            (&text, rest) if (CBOR_SHORT_TEXT..CBOR_TEXT_U8_LENGTH).contains(&text) => {
+                ((text - CBOR_SHORT_TEXT) as usize, rest)
+            }
+            (&CBOR_TEXT_U8_LENGTH, rest) => {
+                let (&len, rest) = rest.split_first()?;
+                (len as usize, rest)
+            }
+            _ => return None,
+        };
+        if rest.len() != len + 17 || rest[len] != CBOR_BYTES_OF_16 {
+            return None;
+        }
+
+        Some(Self {
+            prefix: std::str::from_utf8(&rest[..len]).ok()?,
+            uuid: rest[len + 1..].try_into().ok()?,
+            encoded: None,
+        })
+    }
+
+    pub fn type_prefix(&self) -> &'a str {
+        self.prefix
+    }
+
+    pub fn uuid_bytes(&self) -> &'a [u8; 16] {
+        self.uuid
+    }
+
+    /// Runs `f` on the id in the current storage format, which is its binary format as well.
+    /// Borrowed from the datum unless it's in storage format 1.
+    pub fn with_encoded<T>(&self, f: impl FnOnce(&[u8]) -> T) -> T {
+        if let Some(encoded) = self.encoded {
+            return f(encoded);
+        }
+        // Prefixes of storage format 1 are at most 255 bytes long as well
+        let mut buffer = [0; 1 + u8::MAX as usize + 16];
+        let encoded = &mut buffer[..encoded_len(self.prefix.len())];
+        encode_into(self.prefix, self.uuid, encoded);
+        f(encoded)
+    }
+
+    /// Copies the id out of the datum
+    pub fn to_typeid(&self) -> TypeID {
+        TypeID::new(
+            TypeIDPrefix::try_unsafe(self.prefix),
+            uuid::Uuid::from_bytes(*self.uuid),
+        )
+    }
+}
+
+impl PartialEq for TypeIDRef<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        // Values of both storage formats can be compared, so not their bytes
+        self.uuid == other.uuid && self.prefix == other.prefix
+    }
+}
+
+impl Eq for TypeIDRef<'_> {}
+
+impl PartialOrd for TypeIDRef<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TypeIDRef<'_> {
+    /// Same order as `TypeID`: by prefix, then by UUID
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.prefix
+            .as_bytes()
+            .cmp(other.prefix.as_bytes())
+            .then_with(|| self.uuid.cmp(other.uuid))
+    }
+}
+
+impl Hash for TypeIDRef<'_> {
+    /// Same hash as `TypeID`
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.prefix.as_bytes().hash(state);
+        // `Uuid` hashes its bytes without a length
+        state.write(self.uuid);
+    }
+}
+
+impl FromDatum for TypeIDRef<'_> {
+    unsafe fn from_polymorphic_datum(
+        datum: pg_sys::Datum,
+        is_null: bool,
+        _typoid: pg_sys::Oid,
+    ) -> Option<Self> {
+        if is_null {
+            return None;
+        }
+
+        // Only toasted or compressed values get copied, inline ones are borrowed as they are
+        let varlena = pg_sys::pg_detoast_datum_packed(datum.cast_mut_ptr());
+        let encoded = std::slice::from_raw_parts(
+            vardata_any(varlena).cast::<u8>(),
+            varsize_any_exhdr(varlena),
+        );
+
+        match Self::parse(encoded) {
+            Some(typeid) => Some(typeid),
+            None => error!("typeid datum is not in storage format {STORAGE_FORMAT_VERSION}"),
+        }
+    }
+}
+
+unsafe impl SqlTranslatable for TypeIDRef<'_> {
+    fn argument_sql() -> Result<SqlMapping, ArgumentError> {
+        Ok(SqlMapping::literal("typeid"))
+    }
+    fn return_sql() -> Result<Returns, ReturnsError> {
+        Ok(Returns::One(SqlMapping::literal("typeid")))
+    }
+}
+
+/// Length of the encoded typeid with a prefix of `prefix_len` bytes
+fn encoded_len(prefix_len: usize) -> usize {
+    1 + prefix_len + 16
+}
+
+/// Writes the encoded typeid into `out`, which must be `encoded_len` bytes long
+fn encode_into(prefix: &str, uuid: &[u8; 16], out: &mut [u8]) {
+    let len = prefix.len();
+    out[0] = len as u8;
+    out[1..=len].copy_from_slice(prefix.as_bytes());
+    out[len + 1..].copy_from_slice(uuid);
+}
+
+/// A typeid datum built straight from a prefix and UUID, without going through `TypeID`
+pub struct TypeIDDatum(pg_sys::Datum);
+
+impl TypeIDDatum {
+    /// The prefix must be valid, from 130 bytes on it would read as storage format 1
+    pub fn new(prefix: &str, uuid: &[u8; 16]) -> Self {
+        if prefix.len() >= CBOR_ARRAY_OF_TWO as usize {
+            error!("typeid prefix of {} bytes is too long", prefix.len());
+        }
+
+        let len = encoded_len(prefix.len());
+        unsafe {
+            let varlena = pg_sys::palloc(pg_sys::VARHDRSZ + len).cast::<pg_sys::varlena>();
+            pgrx::set_varsize(varlena, (pg_sys::VARHDRSZ + len) as i32);
+            let data = std::slice::from_raw_parts_mut(
+                pgrx::vardata_4b(varlena).cast::<u8>().cast_mut(),
+                len,
+            );
+            encode_into(prefix, uuid, data);
+            Self(pg_sys::Datum::from(varlena))
+        }
+    }
+}
+
+impl IntoDatum for TypeIDDatum {
+    fn into_datum(self) -> Option<pg_sys::Datum> {
+        Some(self.0)
+    }
+
+    fn type_oid() -> pg_sys::Oid {
+        TypeID::type_oid()
+    }
+}
+
+unsafe impl SqlTranslatable for TypeIDDatum {
+    fn argument_sql() -> Result<SqlMapping, ArgumentError> {
+        Ok(SqlMapping::literal("typeid"))
+    }
+    fn return_sql() -> Result<Returns, ReturnsError> {
+        Ok(Returns::One(SqlMapping::literal("typeid")))
+    }
+}
+
+// Replaces the CBOR encoding pgrx derives for serde types, which `TypeID` isn't
+impl FromDatum for TypeID {
+    unsafe fn from_polymorphic_datum(
+        datum: pg_sys::Datum,
+        is_null: bool,
+        typoid: pg_sys::Oid,
+    ) -> Option<Self> {
+        TypeIDRef::from_polymorphic_datum(datum, is_null, typoid).map(|typeid| typeid.to_typeid())
+    }
+}
+
+impl IntoDatum for TypeID {
+    fn into_datum(self) -> Option<pg_sys::Datum> {
+        TypeIDDatum::new(self.type_prefix(), self.uuid().as_bytes()).into_datum()
+    }
+
+    fn type_oid() -> pg_sys::Oid {
+        pgrx::rust_regtypein::<Self>()
+    }
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    use pgrx::prelude::*;
+
+    use super::TypeIDRef;
+    use crate::typeid::TypeID;
+
+    const IDS: [&str; 6] = [
+        "01h455vb4pex5vsknk084sn02q",
+        "a_01h455vb4pex5vsknk084sn02q",
+        "user_01h455vb4pex5vsknk084sn02q",
+        "user_01h455vb4pex5vsknk084sn02r",
+        "abcdefghijklmnopqrstuvwx_01h455vb4pex5vsknk084sn02q",
+        "abcdefghijklmnopqrstuvwxyzabcdefghijklmnopqrstuvwxyzabcdefghijk_01h455vb4pex5vsknk084sn02q",
+    ];
+
+    fn encode(id: &str) -> (TypeID, Vec<u8>) {
+        let typeid = TypeID::from_string(id).unwrap();
+        let mut encoded = vec![0; super::encoded_len(typeid.type_prefix().len())];
+        super::encode_into(typeid.type_prefix(), typeid.uuid().as_bytes(), &mut encoded);
+        (typeid, encoded)
+    }
+
+    /// Storage format 1, the serde encoding of `TypeID(prefix, uuid)` derived by pgrx
+    fn encode_cbor(id: &str) -> (TypeID, Vec<u8>) {
+        let typeid = TypeID::from_string(id).unwrap();
+        let encoded = serde_cbor::to_vec(&(typeid.type_prefix(), typeid.uuid())).unwrap();
+        (typeid, encoded)
+    }
+
+    fn hash(value: impl Hash) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_typeid_ref_matches_typeid() {
+        let encoded = [IDS.map(encode), IDS.map(encode_cbor)].concat();
+        for (a, a_bytes) in &encoded {
+            let a_ref = TypeIDRef::parse(a_bytes).unwrap();
+            assert_eq!(a_ref.type_prefix(), a.type_prefix());
+            assert_eq!(a_ref.uuid_bytes(), a.uuid().as_bytes());
+            assert_eq!(&a_ref.to_typeid(), a);
+            assert_eq!(hash(a_ref), hash(a));
+            // Both storage formats hash the same encoding
+            let binary = crate::binary::encode(a.type_prefix(), a.uuid().as_bytes());
+            assert_eq!(a_ref.with_encoded(<[u8]>::to_vec), binary);
+
+            for (b, b_bytes) in &encoded {
+                let b_ref = TypeIDRef::parse(b_bytes).unwrap();
+                assert_eq!(a_ref.cmp(&b_ref), a.cmp(b), "{a} vs {b}");
+                assert_eq!(a_ref == b_ref, a == b, "{a} vs {b}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_encode() {
+        let (_, encoded) = encode("user_01h455vb4pex5vsknk084sn02q");
+        assert_eq!(
+            encoded,
+            [
+                &[4][..],
+                b"user",
+                &[0x01, 0x89, 0x0a, 0x5d, 0xac, 0x96, 0x77, 0x4b],
+                &[0xbc, 0xce, 0xb3, 0x02, 0x09, 0x9a, 0x80, 0x57]
+            ]
+            .concat()
+        );
+
+        // Two bytes less than storage format 1
+        let (_, cbor) = encode_cbor("user_01h455vb4pex5vsknk084sn02q");
+        assert_eq!(cbor.len(), encoded.len() + 2);
+    }
+
+    #[test]
+    fn test_typeid_ref_rejects_other_layouts() {
+        for (_, encoded) in [
+            encode("user_01h455vb4pex5vsknk084sn02q"),
+            encode_cbor("user_01h455vb4pex5vsknk084sn02q"),
+        ] {
+            assert!(TypeIDRef::parse(&encoded[..encoded.len() - 1]).is_none());
+            assert!(TypeIDRef::parse(&[encoded.as_slice(), &[0]].concat()).is_none());
+        }
+        assert!(TypeIDRef::parse(&[]).is_none());
+    }
+
+    #[pg_test]
+    fn test_typeid_storage_size() {
+        let size = Spi::get_one::<i32>(
+            "SELECT pg_column_size(id) FROM (VALUES ('user_01h455vb4pex5vsknk084sn02q'::typeid)) t(id)",
+        );
+        // Header, prefix length, prefix and UUID
+        assert_eq!(size, Ok(Some(4 + 1 + 4 + 16)));
+    }
+}
@@ -0,0 +1,231 @@
+use pgrx::prelude::*;
+use pgrx::JsonB;
+
+use crate::binary::BINARY_FORMAT_VERSION;
+use crate::typeid::{TypeID, STORAGE_FORMAT_VERSION, TEXT_FORMAT_VERSION};
+
+/// Describes the installed extension, its settings and optional components in a single jsonb
+/// document, which is the first thing to look at when something misbehaves.
+#[pg_extern(cost = 1000)]
+fn typeid_extension_health() -> JsonB {
+    let query = r#"
+        SELECT jsonb_build_object(
+            'version', $1,
+            'installed_version', (SELECT extversion FROM pg_extension WHERE extname = 'typeid'),
+            'schema', (SELECT extnamespace::regnamespace::text FROM pg_extension WHERE extname = 'typeid'),
+            'storage_format', $2,
+            'settings', (
+                SELECT coalesce(jsonb_object_agg(name, setting), '{}')
+                FROM pg_settings
+                WHERE name LIKE 'typeid.%'
+            ),
+            'opclasses', (
+                SELECT coalesce(jsonb_object_agg(amname, opclasses), '{}')
+                FROM (
+                    SELECT am.amname, jsonb_agg(opc.opcname::text ORDER BY opc.opcname) AS opclasses
+                    FROM pg_opclass opc
+                    JOIN pg_am am ON am.oid = opc.opcmethod
+                    WHERE opc.opcintype = $3
+                    GROUP BY am.amname
+                ) o
+            ),
+            'casts', (
+                SELECT coalesce(jsonb_agg(c.cast ORDER BY c.cast), '[]')
+                FROM (
+                    SELECT format('%s -> %s', castsource::regtype, casttarget::regtype) AS cast
+                    FROM pg_cast
+                    WHERE castsource = $3 OR casttarget = $3
+                ) c
+            ),
+            'preloaded', 'typeid' = ANY(string_to_array(replace(current_setting('shared_preload_libraries'), ' ', ''), ',')),
+            'background_workers', (
+                SELECT coalesce(jsonb_agg(jsonb_build_object('pid', pid, 'type', backend_type, 'started', backend_start)), '[]')
+                FROM pg_stat_activity
+                WHERE backend_type LIKE 'typeid%'
+            )
+        )
+    "#;
+
+    Spi::get_one_with_args::<JsonB>(
+        query,
+        vec![
+            (
+                PgBuiltInOids::TEXTOID.oid(),
+                env!("CARGO_PKG_VERSION").into_datum(),
+            ),
+            (
+                PgBuiltInOids::INT4OID.oid(),
+                STORAGE_FORMAT_VERSION.into_datum(),
+            ),
+            (PgBuiltInOids::OIDOID.oid(), TypeID::type_oid().into_datum()),
+        ],
+    )
+    .unwrap()
+    .expect("health query returned no rows")
+}
+
+/// Version of the loaded library next to the version of the SQL objects installed in the current
+/// database. They differ until `ALTER EXTENSION typeid UPDATE` is run after upgrading the library.
+#[pg_extern(cost = 100)]
+fn typeid_version() -> TableIterator<
+    'static,
+    (
+        name!(library_version, String),
+        name!(schema_version, Option<String>),
+    ),
+> {
+    let schema_version =
+        Spi::get_one::<String>("SELECT extversion FROM pg_extension WHERE extname = 'typeid'")
+            .unwrap();
+
+    TableIterator::once((env!("CARGO_PKG_VERSION").to_string(), schema_version))
+}
+
+/// Every `typeid.*` setting with its current value, where that value comes from and what it does
+#[pg_extern(cost = 100)]
+fn typeid_settings() -> TableIterator<
+    'static,
+    (
+        name!(name, String),
+        name!(value, String),
+        name!(source, String),
+        name!(description, String),
+    ),
+> {
+    let settings = Spi::connect(|client| {
+        client
+            .select(
+                "SELECT name, current_setting(name), source, concat_ws(' ', short_desc, extra_desc)
+                 FROM pg_settings
+                 WHERE name LIKE 'typeid.%'
+                 ORDER BY name",
+                None,
+                None,
+            )?
+            .map(|row| {
+                Ok((
+                    row.get::<String>(1)?.unwrap_or_default(),
+                    row.get::<String>(2)?.unwrap_or_default(),
+                    row.get::<String>(3)?.unwrap_or_default(),
+                    row.get::<String>(4)?.unwrap_or_default(),
+                ))
+            })
+            .collect::<Result<Vec<_>, pgrx::spi::Error>>()
+    })
+    .unwrap();
+
+    TableIterator::new(settings)
+}
+
+/// The formats dumps of typeid data are written in and the extension tables and sequences whose
+/// contents `pg_dump` includes, to check a dump against the installation it's restored into
+#[pg_extern(cost = 100)]
+fn typeid_dump_format() -> TableIterator<
+    'static,
+    (
+        name!(text_format_version, i32),
+        name!(binary_format_version, i32),
+        name!(storage_format_version, i32),
+        name!(config_relations, Vec<String>),
+    ),
+> {
+    let config_relations = Spi::get_one::<Vec<String>>(
+        "SELECT coalesce(array_agg(c::regclass::text ORDER BY c::regclass::text), '{}')
+         FROM pg_extension e, unnest(e.extconfig) c
+         WHERE e.extname = 'typeid'",
+    )
+    .unwrap()
+    .unwrap_or_default();
+
+    TableIterator::once((
+        TEXT_FORMAT_VERSION,
+        BINARY_FORMAT_VERSION,
+        STORAGE_FORMAT_VERSION,
+        config_relations,
+    ))
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+    use pgrx::JsonB;
+
+    #[pg_test]
+    fn test_typeid_extension_health() {
+        let JsonB(health) = Spi::get_one::<JsonB>("SELECT typeid_extension_health()")
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(health["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(
+            health["storage_format"],
+            crate::typeid::STORAGE_FORMAT_VERSION
+        );
+        assert_eq!(health["opclasses"]["btree"][0], "typeid_ops");
+        assert_eq!(health["opclasses"]["hash"][0], "typeid_hash_ops");
+        assert!(health["background_workers"].is_array());
+    }
+
+    #[pg_test]
+    fn test_typeid_version() {
+        let (library, schema) = Spi::get_two::<String, String>(
+            "SELECT library_version, schema_version FROM typeid_version()",
+        )
+        .unwrap();
+
+        assert_eq!(library.as_deref(), Some(env!("CARGO_PKG_VERSION")));
+        assert_eq!(schema, library);
+    }
+
+    #[pg_test]
+    fn test_typeid_settings() {
+        Spi::run("SET typeid.validation_batch_size = 42").unwrap();
+
+        let (value, source) = Spi::get_two::<String, String>(
+            "SELECT value, source FROM typeid_settings() WHERE name = 'typeid.validation_batch_size'",
+        )
+        .unwrap();
+        assert_eq!(value.as_deref(), Some("42"));
+        assert_eq!(source.as_deref(), Some("session"));
+
+        let interval = Spi::get_one::<String>(
+            "SELECT value FROM typeid_settings() WHERE name = 'typeid.validation_interval'",
+        )
+        .unwrap();
+        assert_eq!(interval.as_deref(), Some("1min"));
+
+        // The tests don't preload the library, which leaves out the settings of the workers
+        let worker_settings = Spi::get_one::<i64>(
+            "SELECT count(*) FROM typeid_settings() WHERE name IN (
+                'typeid.validation_worker', 'typeid.validation_database',
+                'typeid.pool_worker', 'typeid.pool_size', 'typeid.pool_refill_threshold'
+             )",
+        );
+        assert_eq!(worker_settings, Ok(Some(0)));
+    }
+
+    #[pg_test]
+    fn test_typeid_dump_format() {
+        let (text, binary, config) = Spi::get_three::<i32, i32, Vec<String>>(
+            "SELECT text_format_version, binary_format_version, config_relations FROM typeid_dump_format()",
+        )
+        .unwrap();
+        assert_eq!(text, Some(crate::typeid::TEXT_FORMAT_VERSION));
+        assert_eq!(binary, Some(crate::binary::BINARY_FORMAT_VERSION));
+
+        // Restored rows keep their serial ids, so the sequences must be restored with them
+        let config = config.unwrap();
+        for relation in [
+            "typeid_generation_audit",
+            "typeid_generation_audit_audit_id_seq",
+            "typeid_registry_history_id_seq",
+            "typeid_validation_results_id_seq",
+        ] {
+            assert!(
+                config.iter().any(|c| c.ends_with(relation)),
+                "{relation} missing from {config:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,611 @@
+//! `typeid_dict`, an opt-in storage mode keeping the prefix as a code from a dictionary.
+//!
+//! Tables with a single prefix store the same string in every row and every index entry. A
+//! `typeid_dict` stores a two byte code and the UUID instead, 18 bytes whatever the prefix. Every
+//! prefix added to the registry gets a code in `typeid_prefix_dictionary`, which is append-only:
+//! renaming a prefix gives the new name a new code, and the old code keeps the prefix values were
+//! written with. Input and output translate between prefixes and codes, so values read and write
+//! as regular TypeIDs.
+//!
+//! Dumps hold the values with their prefixes, and the dictionary itself isn't dumped. Input adds
+//! prefixes without a code to the dictionary for users allowed to insert into it, so a restore
+//! doesn't depend on the order the registry, the dictionary and the tables using it load in.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::hash::Hash;
+
+use pgrx::pgrx_sql_entity_graph::metadata::{
+    ArgumentError, Returns, ReturnsError, SqlMapping, SqlTranslatable,
+};
+use pgrx::prelude::*;
+use pgrx::{FromDatum, InOutFuncs, Internal, IntoDatum, PgOid, StringInfo};
+use uuid::Uuid;
+
+use crate::binary;
+use crate::catalog::extension_schema;
+use crate::datum::{TypeIDDatum, TypeIDRef};
+use crate::typeid::{TypeID, TypeIDPrefix};
+
+/// Length of a `typeid_dict`: the big-endian code followed by the UUID
+const DICT_LEN: usize = 18;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TypeIDDict([u8; DICT_LEN]);
+
+impl TypeIDDict {
+    fn new(code: u16, uuid: &[u8; 16]) -> Self {
+        let mut bytes = [0; DICT_LEN];
+        bytes[..2].copy_from_slice(&code.to_be_bytes());
+        bytes[2..].copy_from_slice(uuid);
+        Self(bytes)
+    }
+
+    fn code(&self) -> u16 {
+        u16::from_be_bytes([self.0[0], self.0[1]])
+    }
+
+    fn uuid_bytes(&self) -> &[u8; 16] {
+        self.0[2..].try_into().unwrap()
+    }
+}
+
+impl FromDatum for TypeIDDict {
+    unsafe fn from_polymorphic_datum(
+        datum: pg_sys::Datum,
+        is_null: bool,
+        _typoid: pg_sys::Oid,
+    ) -> Option<Self> {
+        if is_null {
+            return None;
+        }
+        // Fixed-length values are passed by reference and aligned to single bytes
+        let bytes = std::slice::from_raw_parts(datum.cast_mut_ptr::<u8>(), DICT_LEN);
+        Some(Self(bytes.try_into().unwrap()))
+    }
+}
+
+impl IntoDatum for TypeIDDict {
+    fn into_datum(self) -> Option<pg_sys::Datum> {
+        unsafe {
+            let ptr = pg_sys::palloc(DICT_LEN).cast::<u8>();
+            std::ptr::copy_nonoverlapping(self.0.as_ptr(), ptr, DICT_LEN);
+            Some(pg_sys::Datum::from(ptr))
+        }
+    }
+
+    fn type_oid() -> pg_sys::Oid {
+        pgrx::wrappers::regtypein("typeid_dict")
+    }
+}
+
+unsafe impl SqlTranslatable for TypeIDDict {
+    fn argument_sql() -> Result<SqlMapping, ArgumentError> {
+        Ok(SqlMapping::literal("typeid_dict"))
+    }
+    fn return_sql() -> Result<Returns, ReturnsError> {
+        Ok(Returns::One(SqlMapping::literal("typeid_dict")))
+    }
+}
+
+/// Dictionary entries committed by other transactions. They never change, so they're kept until
+/// the dictionary table itself is invalidated, e.g. dropped with the extension.
+#[derive(Default)]
+struct Dictionary {
+    relid: pg_sys::Oid,
+    prefixes: HashMap<u16, String>,
+    codes: HashMap<String, u16>,
+}
+
+thread_local! {
+    static DICTIONARY: RefCell<Dictionary> = RefCell::new(Dictionary::default());
+    static CALLBACK_REGISTERED: Cell<bool> = const { Cell::new(false) };
+}
+
+extern "C" {
+    // Not among the bindings of pgrx, from `utils/inval.h`
+    fn CacheRegisterRelcacheCallback(
+        func: unsafe extern "C" fn(arg: pg_sys::Datum, relid: pg_sys::Oid),
+        arg: pg_sys::Datum,
+    );
+}
+
+/// Forgets the cached entries when the relcache entry of the dictionary table is invalidated. A
+/// dictionary created again by `DROP EXTENSION` and `CREATE EXTENSION` hands out codes anew.
+#[pg_guard]
+unsafe extern "C" fn invalidate(_arg: pg_sys::Datum, relid: pg_sys::Oid) {
+    DICTIONARY.with(|dictionary| {
+        let mut dictionary = dictionary.borrow_mut();
+        if relid == pg_sys::InvalidOid || relid == dictionary.relid {
+            *dictionary = Dictionary::default();
+        }
+    });
+}
+
+/// Looks up the dictionary entry matching `condition` on `$1`, caching it unless this transaction
+/// added it and may still roll back
+fn lookup(condition: &str, arg: (PgOid, Option<pg_sys::Datum>)) -> Option<(u16, String)> {
+    let entry = Spi::connect(|client| {
+        let schema = extension_schema(&client)?;
+        let row = client
+            .select(
+                &format!(
+                    "SELECT code, prefix, xmin::text::int8, tableoid FROM {schema}.typeid_prefix_dictionary WHERE {condition} = $1"
+                ),
+                None,
+                Some(vec![arg]),
+            )?
+            .first();
+        if row.is_empty() {
+            return Ok(None);
+        }
+        Ok::<_, pgrx::spi::Error>(Some((
+            row.get::<i16>(1)?.unwrap_or_default() as u16,
+            row.get::<String>(2)?.unwrap_or_default(),
+            row.get::<i64>(3)?.unwrap_or_default() as pg_sys::TransactionId,
+            row.get::<pg_sys::Oid>(4)?.unwrap_or_default(),
+        )))
+    })
+    .unwrap()?;
+
+    let (code, prefix, xmin, relid) = entry;
+    if !unsafe { pg_sys::TransactionIdIsCurrentTransactionId(xmin) } {
+        if !CALLBACK_REGISTERED.get() {
+            unsafe {
+                pg_sys::ffi::pg_guard_ffi_boundary(|| {
+                    CacheRegisterRelcacheCallback(invalidate, pg_sys::Datum::from(0))
+                })
+            };
+            CALLBACK_REGISTERED.set(true);
+        }
+        DICTIONARY.with(|dictionary| {
+            let mut dictionary = dictionary.borrow_mut();
+            dictionary.relid = relid;
+            dictionary.prefixes.insert(code, prefix.clone());
+            dictionary.codes.insert(prefix.clone(), code);
+        });
+    }
+    Some((code, prefix))
+}
+
+/// Adds a prefix to the dictionary when the current user may insert into it. Read-only and
+/// parallel transactions can't, and get the error for unknown prefixes instead.
+fn register(prefix: &str) {
+    if unsafe { pg_sys::XactReadOnly || pg_sys::IsInParallelMode() } {
+        return;
+    }
+    Spi::connect(|mut client| {
+        let table = format!("{}.typeid_prefix_dictionary", extension_schema(&client)?);
+        client.update(
+            &format!(
+                "INSERT INTO {table} (prefix) SELECT $1 WHERE has_table_privilege($2, 'INSERT')
+                 ON CONFLICT (prefix) DO NOTHING"
+            ),
+            None,
+            Some(vec![
+                (PgBuiltInOids::TEXTOID.oid(), prefix.into_datum()),
+                (PgBuiltInOids::TEXTOID.oid(), table.as_str().into_datum()),
+            ]),
+        )?;
+        Ok::<_, pgrx::spi::Error>(())
+    })
+    .unwrap();
+}
+
+/// The code of a prefix, added to the dictionary if needed and allowed
+fn code_of(prefix: &str) -> u16 {
+    if let Some(code) = DICTIONARY.with(|dictionary| dictionary.borrow().codes.get(prefix).copied())
+    {
+        return code;
+    }
+    let lookup_prefix = || {
+        lookup(
+            "prefix",
+            (PgBuiltInOids::TEXTOID.oid(), prefix.into_datum()),
+        )
+    };
+    let entry = lookup_prefix().or_else(|| {
+        register(prefix);
+        lookup_prefix()
+    });
+    match entry {
+        Some((code, _)) => code,
+        None => {
+            ereport!(
+                ERROR,
+                PgSqlErrorCode::ERRCODE_INVALID_TEXT_REPRESENTATION,
+                format!("prefix {prefix:?} is not in the typeid dictionary"),
+                "Prefixes get a code when they're registered with typeid_register_prefix, or when users allowed to insert into typeid_prefix_dictionary write them."
+            );
+        }
+    }
+}
+
+/// The prefix of a code
+fn prefix_of(code: u16) -> String {
+    if let Some(prefix) =
+        DICTIONARY.with(|dictionary| dictionary.borrow().prefixes.get(&code).cloned())
+    {
+        return prefix;
+    }
+    match lookup(
+        "code",
+        (PgBuiltInOids::INT2OID.oid(), (code as i16).into_datum()),
+    ) {
+        Some((_, prefix)) => prefix,
+        None => error!("typeid dictionary has no code {code}"),
+    }
+}
+
+fn to_typeid(id: TypeIDDict) -> TypeID {
+    TypeID::new(
+        TypeIDPrefix::try_unsafe(&prefix_of(id.code())),
+        Uuid::from_bytes(*id.uuid_bytes()),
+    )
+}
+
+// Input, receive and the cast may add the prefix to the dictionary, so they're volatile, and
+// unsafe in parallel mode, where they couldn't
+#[pg_extern(parallel_unsafe, requires = ["typeid_dict_shell"])]
+fn typeid_dict_in(input: &CStr) -> TypeIDDict {
+    let typeid = TypeID::input(input);
+    TypeIDDict::new(code_of(typeid.type_prefix()), typeid.uuid().as_bytes())
+}
+
+#[pg_extern(stable, parallel_safe, requires = ["typeid_dict_shell"])]
+fn typeid_dict_out(id: TypeIDDict) -> &'static CStr {
+    let mut buffer = StringInfo::new();
+    buffer.push_str(&to_typeid(id).to_string());
+    buffer.into()
+}
+
+/// Sends the expanded typeid, so drivers read and binary COPY writes `typeid_dict` like `typeid`
+#[pg_extern(stable, parallel_safe, requires = ["typeid_dict_shell"])]
+fn typeid_dict_send(id: TypeIDDict) -> Vec<u8> {
+    binary::encode(&prefix_of(id.code()), id.uuid_bytes())
+}
+
+#[pg_extern(parallel_unsafe, requires = ["typeid_dict_shell"])]
+fn typeid_dict_recv(buf: Internal) -> TypeIDDict {
+    let (prefix, uuid) = binary::receive(&buf);
+    TypeIDDict::new(code_of(prefix), uuid)
+}
+
+/// Stores the prefix of the id as its dictionary code
+#[pg_extern(parallel_unsafe, requires = ["create_typeid_dict"])]
+fn typeid_to_dict(typeid: TypeIDRef<'_>) -> TypeIDDict {
+    TypeIDDict::new(code_of(typeid.type_prefix()), typeid.uuid_bytes())
+}
+
+#[pg_extern(stable, parallel_safe, requires = ["create_typeid_dict"])]
+fn typeid_from_dict(id: TypeIDDict) -> TypeIDDatum {
+    TypeIDDatum::new(&prefix_of(id.code()), id.uuid_bytes())
+}
+
+// Codes are compared instead of prefixes, which keeps the order independent of the dictionary.
+// Ids with the same prefix sort like typeids.
+
+#[pg_extern(immutable, parallel_safe, requires = ["create_typeid_dict"])]
+fn typeid_dict_cmp(a: TypeIDDict, b: TypeIDDict) -> i32 {
+    a.cmp(&b) as i32
+}
+
+#[pg_extern(immutable, parallel_safe, requires = ["create_typeid_dict"])]
+fn typeid_dict_lt(a: TypeIDDict, b: TypeIDDict) -> bool {
+    a < b
+}
+
+#[pg_extern(immutable, parallel_safe, requires = ["create_typeid_dict"])]
+fn typeid_dict_le(a: TypeIDDict, b: TypeIDDict) -> bool {
+    a <= b
+}
+
+#[pg_extern(immutable, parallel_safe, requires = ["create_typeid_dict"])]
+fn typeid_dict_eq(a: TypeIDDict, b: TypeIDDict) -> bool {
+    a == b
+}
+
+#[pg_extern(immutable, parallel_safe, requires = ["create_typeid_dict"])]
+fn typeid_dict_ge(a: TypeIDDict, b: TypeIDDict) -> bool {
+    a >= b
+}
+
+#[pg_extern(immutable, parallel_safe, requires = ["create_typeid_dict"])]
+fn typeid_dict_gt(a: TypeIDDict, b: TypeIDDict) -> bool {
+    a > b
+}
+
+#[pg_extern(immutable, parallel_safe, requires = ["create_typeid_dict"])]
+fn typeid_dict_ne(a: TypeIDDict, b: TypeIDDict) -> bool {
+    a != b
+}
+
+#[pg_extern(immutable, parallel_safe, requires = ["create_typeid_dict"])]
+fn typeid_dict_hash(id: TypeIDDict) -> i32 {
+    crate::seeded_hash(&id.0, 0) as i32
+}
+
+#[pg_extern(immutable, parallel_safe, requires = ["create_typeid_dict"])]
+fn typeid_dict_hash_extended(id: TypeIDDict, seed: i64) -> i64 {
+    crate::seeded_hash(&id.0, seed) as i64
+}
+
+extension_sql!(
+    r#"
+    CREATE TABLE typeid_prefix_dictionary (
+        code smallint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+        prefix text NOT NULL UNIQUE
+    );
+    -- Reading typeid_dict values looks up their prefixes as the current user
+    GRANT SELECT ON typeid_prefix_dictionary TO PUBLIC;
+
+    INSERT INTO typeid_prefix_dictionary (prefix)
+    SELECT prefix FROM typeid_prefix_registry ORDER BY registered_at, prefix;
+
+    CREATE FUNCTION typeid_prefix_dictionary_add() RETURNS trigger
+    LANGUAGE plpgsql
+    SET search_path FROM CURRENT
+    AS $$
+    BEGIN
+        INSERT INTO typeid_prefix_dictionary (prefix) VALUES (NEW.prefix) ON CONFLICT (prefix) DO NOTHING;
+        RETURN NULL;
+    END
+    $$;
+
+    CREATE TRIGGER typeid_prefix_dictionary_add
+    AFTER INSERT OR UPDATE OF prefix ON typeid_prefix_registry
+    FOR EACH ROW EXECUTE FUNCTION typeid_prefix_dictionary_add();
+
+    CREATE FUNCTION typeid_prefix_dictionary_append_only() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+    BEGIN
+        RAISE EXCEPTION 'typeid_prefix_dictionary is append-only, stored values refer to its codes';
+    END
+    $$;
+
+    CREATE TRIGGER typeid_prefix_dictionary_append_only
+    BEFORE UPDATE OR DELETE ON typeid_prefix_dictionary
+    FOR EACH ROW EXECUTE FUNCTION typeid_prefix_dictionary_append_only();
+
+    CREATE TRIGGER typeid_prefix_dictionary_no_truncate
+    BEFORE TRUNCATE ON typeid_prefix_dictionary
+    FOR EACH STATEMENT EXECUTE FUNCTION typeid_prefix_dictionary_append_only();
+
+    CREATE TYPE typeid_dict;
+    "#,
+    name = "typeid_dict_shell",
+    requires = ["create_prefix_registry"],
+);
+
+extension_sql!(
+    r#"
+    CREATE TYPE typeid_dict (
+        INPUT = typeid_dict_in,
+        OUTPUT = typeid_dict_out,
+        RECEIVE = typeid_dict_recv,
+        SEND = typeid_dict_send,
+        INTERNALLENGTH = 18,
+        ALIGNMENT = char,
+        STORAGE = plain
+    );
+    "#,
+    name = "create_typeid_dict",
+    requires = [
+        typeid_dict_in,
+        typeid_dict_out,
+        typeid_dict_recv,
+        typeid_dict_send
+    ],
+);
+
+extension_sql!(
+    r#"
+    CREATE CAST (typeid AS typeid_dict) WITH FUNCTION typeid_to_dict(typeid) AS ASSIGNMENT;
+    CREATE CAST (typeid_dict AS typeid) WITH FUNCTION typeid_from_dict(typeid_dict) AS IMPLICIT;
+
+    CREATE OPERATOR < (
+        LEFTARG = typeid_dict,
+        RIGHTARG = typeid_dict,
+        PROCEDURE = typeid_dict_lt,
+        RESTRICT = scalarltsel,
+        JOIN = scalarltjoinsel
+    );
+    CREATE OPERATOR <= (
+        LEFTARG = typeid_dict,
+        RIGHTARG = typeid_dict,
+        PROCEDURE = typeid_dict_le,
+        RESTRICT = scalarlesel,
+        JOIN = scalarlejoinsel
+    );
+    CREATE OPERATOR = (
+        LEFTARG = typeid_dict,
+        RIGHTARG = typeid_dict,
+        PROCEDURE = typeid_dict_eq,
+        COMMUTATOR = '=',
+        NEGATOR = '<>',
+        RESTRICT = eqsel,
+        JOIN = eqjoinsel,
+        HASHES,
+        MERGES
+    );
+    CREATE OPERATOR >= (
+        LEFTARG = typeid_dict,
+        RIGHTARG = typeid_dict,
+        PROCEDURE = typeid_dict_ge,
+        RESTRICT = scalargesel,
+        JOIN = scalargejoinsel
+    );
+    CREATE OPERATOR > (
+        LEFTARG = typeid_dict,
+        RIGHTARG = typeid_dict,
+        PROCEDURE = typeid_dict_gt,
+        RESTRICT = scalargtsel,
+        JOIN = scalargtjoinsel
+    );
+    CREATE OPERATOR <> (
+        LEFTARG = typeid_dict,
+        RIGHTARG = typeid_dict,
+        PROCEDURE = typeid_dict_ne,
+        COMMUTATOR = '<>',
+        NEGATOR = '=',
+        RESTRICT = neqsel,
+        JOIN = neqjoinsel
+    );
+
+    CREATE OPERATOR CLASS typeid_dict_ops DEFAULT FOR TYPE typeid_dict USING btree AS
+        OPERATOR 1 < (typeid_dict, typeid_dict),
+        OPERATOR 2 <= (typeid_dict, typeid_dict),
+        OPERATOR 3 = (typeid_dict, typeid_dict),
+        OPERATOR 4 >= (typeid_dict, typeid_dict),
+        OPERATOR 5 > (typeid_dict, typeid_dict),
+        FUNCTION 1 typeid_dict_cmp(typeid_dict, typeid_dict);
+
+    CREATE OPERATOR CLASS typeid_dict_hash_ops DEFAULT FOR TYPE typeid_dict USING hash AS
+        OPERATOR 1 = (typeid_dict, typeid_dict),
+        FUNCTION 1 typeid_dict_hash(typeid_dict),
+        FUNCTION 2 typeid_dict_hash_extended(typeid_dict, bigint);
+    "#,
+    name = "create_typeid_dict_operators",
+    requires = [
+        typeid_to_dict,
+        typeid_from_dict,
+        typeid_dict_cmp,
+        typeid_dict_lt,
+        typeid_dict_le,
+        typeid_dict_eq,
+        typeid_dict_ge,
+        typeid_dict_gt,
+        typeid_dict_ne,
+        typeid_dict_hash,
+        typeid_dict_hash_extended,
+    ],
+);
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    use super::TypeIDDict;
+
+    #[test]
+    fn test_typeid_dict_layout() {
+        let uuid = [0xff; 16];
+        let id = TypeIDDict::new(258, &uuid);
+        assert_eq!(id.code(), 258);
+        assert_eq!(id.uuid_bytes(), &uuid);
+        // Ordered by code, then by UUID
+        assert!(TypeIDDict::new(1, &uuid) < TypeIDDict::new(2, &[0; 16]));
+        assert!(TypeIDDict::new(1, &[0; 16]) < TypeIDDict::new(1, &uuid));
+    }
+
+    #[pg_test]
+    fn test_typeid_dict() {
+        Spi::run("SELECT typeid_register_prefix('organization')").unwrap();
+        Spi::run("CREATE TABLE orgs (id typeid_dict PRIMARY KEY)").unwrap();
+        Spi::run(
+            "INSERT INTO orgs VALUES
+                ('organization_01jepxv500e008000000000000'),
+                (uuid_to_typeid('organization', '0193addd-9400-7000-8000-000000000001'))",
+        )
+        .unwrap();
+
+        let ids = Spi::get_one::<Vec<String>>("SELECT array_agg(id::text ORDER BY id) FROM orgs");
+        assert_eq!(
+            ids,
+            Ok(Some(vec![
+                "organization_01jepxv500e008000000000000".to_string(),
+                "organization_01jepxv500e008000000000001".to_string(),
+            ]))
+        );
+
+        let (size, typeid_size) = Spi::get_two::<i32, i32>(
+            "SELECT pg_column_size(id), pg_column_size(id::typeid) FROM orgs LIMIT 1",
+        )
+        .unwrap();
+        assert_eq!(size, Some(18));
+        assert!(typeid_size > Some(30), "{typeid_size:?}");
+
+        // typeid functions take the values through the implicit cast
+        let found = Spi::get_one::<bool>(
+            "SELECT typeid_has_prefix(id, 'organization') FROM orgs
+             WHERE id = 'organization_01jepxv500e008000000000000'",
+        );
+        assert_eq!(found, Ok(Some(true)));
+
+        // Old values keep their prefix after a rename
+        Spi::run("SELECT typeid_rename_prefix('organization', 'org')").unwrap();
+        Spi::run("INSERT INTO orgs VALUES (uuid_to_typeid('org', '0193addd-9400-7000-8000-000000000000'))")
+            .unwrap();
+        let prefixes = Spi::get_one::<Vec<String>>(
+            "SELECT array_agg(DISTINCT typeid_prefix(id) ORDER BY typeid_prefix(id)) FROM orgs",
+        );
+        assert_eq!(
+            prefixes,
+            Ok(Some(vec!["org".to_string(), "organization".to_string()]))
+        );
+    }
+
+    #[pg_test]
+    fn test_typeid_dict_send() {
+        Spi::run("SELECT typeid_register_prefix('organization')").unwrap();
+        let same = Spi::get_one::<bool>(
+            "SELECT typeid_dict_send(id::typeid_dict) = typeid_send(id)
+             FROM (SELECT 'organization_01jepxv500e008000000000000'::typeid) t(id)",
+        );
+        assert_eq!(same, Ok(Some(true)));
+
+        let binary_io = Spi::get_one::<bool>(
+            "SELECT typsend = 'typeid_dict_send'::regproc AND typreceive = 'typeid_dict_recv'::regproc
+             FROM pg_type WHERE oid = 'typeid_dict'::regtype",
+        );
+        assert_eq!(binary_io, Ok(Some(true)));
+    }
+
+    #[pg_test]
+    fn test_typeid_dict_adds_prefix() {
+        // As when restoring a table before the registry, which adds the prefix again afterwards
+        Spi::run("CREATE TABLE users (id typeid_dict)").unwrap();
+        Spi::run("INSERT INTO users VALUES ('user_01jepxv500e008000000000000')").unwrap();
+        Spi::run("SELECT typeid_register_prefix('user')").unwrap();
+
+        let (id, codes) = Spi::get_two::<String, i64>(
+            "SELECT (SELECT id::text FROM users),
+                    (SELECT count(*) FROM typeid_prefix_dictionary WHERE prefix = 'user')",
+        )
+        .unwrap();
+        assert_eq!(id.as_deref(), Some("user_01jepxv500e008000000000000"));
+        assert_eq!(codes, Some(1));
+    }
+
+    #[pg_test]
+    fn test_typeid_dict_read_only() {
+        let volatile = Spi::get_one::<bool>(
+            "SELECT bool_and(provolatile = 'v' AND proparallel = 'u') FROM pg_proc
+             WHERE oid IN ('typeid_dict_in'::regproc, 'typeid_dict_recv'::regproc, 'typeid_to_dict'::regproc)",
+        );
+        assert_eq!(volatile, Ok(Some(true)));
+
+        // Prefixes with a code are only looked up
+        Spi::run("SELECT typeid_register_prefix('user')").unwrap();
+        Spi::run("SET LOCAL transaction_read_only = on").unwrap();
+        let id =
+            Spi::get_one::<String>("SELECT 'user_01jepxv500e008000000000000'::typeid_dict::text");
+        assert_eq!(id, Ok(Some("user_01jepxv500e008000000000000".into())));
+    }
+
+    #[pg_test(error = "prefix \"user\" is not in the typeid dictionary")]
+    fn test_typeid_dict_unregistered_prefix() {
+        Spi::run("CREATE ROLE typeid_dict_test").unwrap();
+        Spi::run("SET ROLE typeid_dict_test").unwrap();
+        Spi::run("SELECT 'user_01jepxv500e008000000000000'::typeid_dict").unwrap();
+    }
+
+    #[pg_test(error = "typeid_prefix_dictionary is append-only, stored values refer to its codes")]
+    fn test_typeid_prefix_dictionary_append_only() {
+        Spi::run("SELECT typeid_register_prefix('user')").unwrap();
+        Spi::run("DELETE FROM typeid_prefix_dictionary").unwrap();
+    }
+}
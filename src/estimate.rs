@@ -0,0 +1,472 @@
+//! Estimating row counts of typeid columns, by the embedded timestamps or the prefix, based on the
+//! statistics collected by `ANALYZE`.
+
+use pgrx::prelude::*;
+use pgrx::{is_a, FromDatum, Internal, PgList, PgRelation, Range, RangeBound};
+use uuid::Uuid;
+
+use crate::analyze;
+use crate::catalog::{check_typeid_column, ColumnName};
+use crate::time::POSTGRES_EPOCH_MICROS;
+use crate::typeid::{TypeID, TypeIDPrefix};
+
+/// Selectivity of `id @> prefix` when the prefix isn't a constant, the default of `LIKE` patterns
+const DEFAULT_PREFIX_SEL: f64 = 0.005;
+
+/// Share of the rows described by the statistics with timestamps in `start..end`. The histogram
+/// buckets hold equal shares of the rows which aren't NULL or among the most common values, and
+/// timestamps are assumed to be spread evenly within a bucket.
+fn fraction_in_range(
+    null_frac: f64,
+    most_common: &[(TypeID, f64)],
+    histogram: &[TypeID],
+    start: u64,
+    end: u64,
+) -> f64 {
+    let contains = |millis: u64| start <= millis && millis < end;
+
+    let mut fraction = 0.0;
+    for (typeid, frequency) in most_common {
+        if contains(typeid.timestamp_millis()) {
+            fraction += frequency;
+        }
+    }
+
+    if histogram.len() < 2 {
+        return fraction;
+    }
+
+    let histogram_frac =
+        (1.0 - null_frac - most_common.iter().map(|(_, f)| f).sum::<f64>()).max(0.0);
+    let bucket_frac = histogram_frac / (histogram.len() - 1) as f64;
+
+    for bucket in histogram.windows(2) {
+        let (from, to) = (bucket[0].timestamp_millis(), bucket[1].timestamp_millis());
+
+        let overlap = if bucket[0].type_prefix() != bucket[1].type_prefix() || to <= from {
+            // Ids of different prefixes aren't ordered by time, split the bucket between its bounds
+            (f64::from(u8::from(contains(from))) + f64::from(u8::from(contains(to)))) / 2.0
+        } else {
+            let overlap = to.min(end).saturating_sub(from.max(start));
+            overlap as f64 / (to - from) as f64
+        };
+        fraction += bucket_frac * overlap;
+    }
+
+    fraction
+}
+
+/// Where a bound of a time range falls among the milliseconds of ids, as the start or end of the
+/// half-open range `start..end` checked by `fraction_in_range`: the first millisecond a lower bound
+/// admits, or the first one past an upper bound
+fn bound_millis(micros: i64, inclusive: bool, lower: bool) -> u64 {
+    let micros = micros + POSTGRES_EPOCH_MICROS;
+    let floor = micros.div_euclid(1000);
+    let on_millisecond = micros.rem_euclid(1000) == 0;
+    let millis = if on_millisecond && inclusive == lower {
+        floor
+    } else {
+        floor + 1
+    };
+    u64::try_from(millis).unwrap_or(0)
+}
+
+fn parse_all(values: Vec<String>) -> Vec<TypeID> {
+    values
+        .iter()
+        .filter_map(|value| TypeID::from_string(value).ok())
+        .collect()
+}
+
+/// Estimates how many rows of a table have ids generated within `range`, from the statistics of
+/// the typeid column alone. Returns NULL when the column hasn't been analyzed yet.
+#[pg_extern(stable, parallel_safe, cost = 100)]
+fn typeid_estimate_rows(
+    relation: PgRelation,
+    column: ColumnName,
+    range: Range<TimestampWithTimeZone>,
+) -> Option<i64> {
+    check_typeid_column(&relation, column.as_str());
+
+    let millis = |bound: Option<&RangeBound<TimestampWithTimeZone>>, lower: bool| {
+        let infinite = if lower { 0 } else { u64::MAX };
+        match bound.and_then(|bound| Some((bound.get()?, bound.is_inclusive()))) {
+            Some((timestamp, inclusive)) if timestamp.is_finite() => {
+                bound_millis(timestamp.into_inner(), inclusive, lower)
+            }
+            Some((timestamp, _)) if *timestamp == TimestampWithTimeZone::negative_infinity() => 0,
+            _ => infinite,
+        }
+    };
+    let (start, end) = if range.is_empty() {
+        (0, 0)
+    } else {
+        (millis(range.lower(), true), millis(range.upper(), false))
+    };
+
+    let (reltuples, null_frac, most_common_vals, most_common_freqs, histogram) =
+        Spi::connect(|client| {
+            let row = client
+                .select(
+                    "SELECT c.reltuples::float8, s.null_frac::float8,
+                            s.most_common_vals::text::text[], s.most_common_freqs::float8[],
+                            s.histogram_bounds::text::text[]
+                     FROM pg_catalog.pg_class c
+                     JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+                     JOIN pg_catalog.pg_stats s
+                       ON s.schemaname = n.nspname AND s.tablename = c.relname AND s.attname = $2
+                     WHERE c.oid = $1
+                     ORDER BY s.inherited DESC
+                     LIMIT 1",
+                    None,
+                    Some(vec![
+                        (PgBuiltInOids::OIDOID.oid(), relation.oid().into_datum()),
+                        (PgBuiltInOids::TEXTOID.oid(), column.as_str().into_datum()),
+                    ]),
+                )?
+                .first();
+
+            if row.is_empty() {
+                return Ok(None);
+            }
+            Ok::<_, pgrx::spi::Error>(Some((
+                row.get::<f64>(1)?.unwrap_or(-1.0),
+                row.get::<f64>(2)?.unwrap_or(0.0),
+                row.get::<Vec<String>>(3)?.unwrap_or_default(),
+                row.get::<Vec<f64>>(4)?.unwrap_or_default(),
+                row.get::<Vec<String>>(5)?.unwrap_or_default(),
+            )))
+        })
+        .unwrap()?;
+
+    // Never vacuumed or analyzed
+    if reltuples < 0.0 {
+        return None;
+    }
+
+    let most_common = parse_all(most_common_vals)
+        .into_iter()
+        .zip(most_common_freqs)
+        .collect::<Vec<_>>();
+    let histogram = parse_all(histogram);
+    let fraction = fraction_in_range(null_frac, &most_common, &histogram, start, end);
+
+    Some((reltuples * fraction).round() as i64)
+}
+
+/// Restriction estimator of `id @> prefix`: the share of the prefix gathered by `ANALYZE` or, for
+/// prefixes too rare to be kept, the rows between the smallest and largest ids of the prefix,
+/// estimated by the selectivities of `>=` and `<=` like the planner does for `LIKE` prefixes, and
+/// no less than the rows of a single id
+#[pg_extern(stable, parallel_safe)]
+fn typeid_contains_prefix_sel(
+    root: Internal,
+    _operator: pg_sys::Oid,
+    args: Internal,
+    var_relid: i32,
+) -> f64 {
+    unsafe {
+        let root = root.unwrap().map_or(std::ptr::null_mut(), |datum| {
+            datum.cast_mut_ptr::<pg_sys::PlannerInfo>()
+        });
+        let args =
+            PgList::<pg_sys::Node>::from_pg(args.unwrap().map_or(std::ptr::null_mut(), |datum| {
+                datum.cast_mut_ptr::<pg_sys::List>()
+            }));
+        let (Some(id), Some(prefix)) = (args.get_ptr(0), args.get_ptr(1)) else {
+            return DEFAULT_PREFIX_SEL;
+        };
+        if !is_a(prefix, pg_sys::NodeTag::T_Const) {
+            return DEFAULT_PREFIX_SEL;
+        }
+        let prefix = prefix.cast::<pg_sys::Const>();
+        let Some(prefix) = String::from_datum((*prefix).constvalue, (*prefix).constisnull) else {
+            return 0.0;
+        };
+        if !TypeIDPrefix::is_valid(&prefix) {
+            return 0.0;
+        }
+
+        let typoid = TypeID::type_oid();
+        let typcache = pg_sys::lookup_type_cache(typoid, pg_sys::TYPECACHE_BTREE_OPFAMILY as i32);
+        let operator = |strategy: u32| {
+            pg_sys::get_opfamily_member((*typcache).btree_opf, typoid, typoid, strategy as i16)
+        };
+        let selectivity = |operator: pg_sys::Oid, bound: Uuid| {
+            let bound = TypeID::new(TypeIDPrefix::try_unsafe(&prefix), bound);
+            let constant = pg_sys::makeConst(
+                typoid,
+                -1,
+                pg_sys::InvalidOid,
+                -1,
+                bound.into_datum().unwrap(),
+                false,
+                false,
+            );
+            let mut args = PgList::<pg_sys::Node>::new();
+            args.push(id);
+            args.push(constant.cast());
+            pg_sys::restriction_selectivity(
+                root,
+                operator,
+                args.into_pg(),
+                pg_sys::InvalidOid,
+                var_relid,
+            )
+        };
+
+        let (ge, le, eq) = (
+            operator(pg_sys::BTGreaterEqualStrategyNumber),
+            operator(pg_sys::BTLessEqualStrategyNumber),
+            operator(pg_sys::BTEqualStrategyNumber),
+        );
+        if ge == pg_sys::InvalidOid || le == pg_sys::InvalidOid || eq == pg_sys::InvalidOid {
+            return DEFAULT_PREFIX_SEL;
+        }
+
+        let single = selectivity(eq, Uuid::nil());
+        if let Some(frequency) = analyze::prefix_frequency(root, id, var_relid, &prefix) {
+            return frequency.max(single).clamp(0.0, 1.0);
+        }
+
+        // Both inequalities hold for the rows of the prefix, one of them for all others
+        let not_null = pg_sys::nulltestsel(
+            root,
+            pg_sys::NullTestType_IS_NOT_NULL,
+            id,
+            var_relid,
+            pg_sys::JoinType_JOIN_INNER,
+            std::ptr::null_mut(),
+        );
+        let in_range = selectivity(ge, Uuid::nil()) + selectivity(le, Uuid::max()) - not_null;
+        in_range.max(single).clamp(0.0, 1.0)
+    }
+}
+
+/// Join estimator of `id @> prefix`, estimated like `=` without most common values: the rows on
+/// each side which aren't NULL match one in as many prefixes as `ANALYZE` found in the ids, or as
+/// the prefixes take distinct values when there are more of those
+#[pg_extern(stable, parallel_safe)]
+fn typeid_contains_prefix_joinsel(
+    root: Internal,
+    _operator: pg_sys::Oid,
+    args: Internal,
+    join_type: i16,
+    sjinfo: Internal,
+) -> f64 {
+    unsafe {
+        let root = root.unwrap().map_or(std::ptr::null_mut(), |datum| {
+            datum.cast_mut_ptr::<pg_sys::PlannerInfo>()
+        });
+        let sjinfo = sjinfo.unwrap().map_or(std::ptr::null_mut(), |datum| {
+            datum.cast_mut_ptr::<pg_sys::SpecialJoinInfo>()
+        });
+        let args =
+            PgList::<pg_sys::Node>::from_pg(args.unwrap().map_or(std::ptr::null_mut(), |datum| {
+                datum.cast_mut_ptr::<pg_sys::List>()
+            }));
+        let (Some(id), Some(prefix)) = (args.get_ptr(0), args.get_ptr(1)) else {
+            return DEFAULT_PREFIX_SEL;
+        };
+        let Some(prefixes) = analyze::prefix_count(root, id, 0) else {
+            return DEFAULT_PREFIX_SEL;
+        };
+
+        let (distinct, is_default) = analyze::with_statistics(root, prefix, 0, |vardata| {
+            let mut is_default = false;
+            let distinct = pg_sys::get_variable_numdistinct(vardata, &mut is_default);
+            (distinct, is_default)
+        });
+        let distinct = if is_default {
+            prefixes as f64
+        } else {
+            distinct.max(prefixes as f64)
+        };
+
+        let not_null = |expression: *mut pg_sys::Node| {
+            pg_sys::nulltestsel(
+                root,
+                pg_sys::NullTestType_IS_NOT_NULL,
+                expression,
+                0,
+                join_type as pg_sys::JoinType,
+                sjinfo,
+            )
+        };
+        (not_null(id) * not_null(prefix) / distinct.max(1.0)).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+    use uuid::Uuid;
+
+    use crate::typeid::{TypeID, TypeIDPrefix};
+
+    fn at(prefix: &str, millis: u64) -> TypeID {
+        TypeID::new(
+            TypeIDPrefix::new(prefix).unwrap(),
+            Uuid::from_u128((millis as u128) << 80),
+        )
+    }
+
+    #[test]
+    fn test_fraction_in_range() {
+        let histogram = [
+            at("event", 0),
+            at("event", 100),
+            at("event", 200),
+            at("event", 300),
+            at("event", 400),
+        ];
+
+        let fraction = super::fraction_in_range(0.0, &[], &histogram, 0, u64::MAX);
+        assert!((fraction - 1.0).abs() < 1e-9);
+
+        let fraction = super::fraction_in_range(0.0, &[], &histogram, 150, 300);
+        assert!((fraction - 0.375).abs() < 1e-9);
+
+        let fraction = super::fraction_in_range(0.2, &[(at("event", 50), 0.2)], &histogram, 0, 100);
+        assert!((fraction - (0.2 + 0.6 / 4.0)).abs() < 1e-9);
+
+        // A bucket between two prefixes is split between its bounds
+        let histogram = [at("event", 1000), at("user", 0)];
+        let fraction = super::fraction_in_range(0.0, &[], &histogram, 0, 10);
+        assert!((fraction - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_bound_millis() {
+        // 2024-01-01 00:00:00.005 UTC, and a microsecond after it
+        let micros = 757_382_400_005_000;
+        assert_eq!(super::bound_millis(micros, true, true), 1_704_067_200_005);
+        assert_eq!(super::bound_millis(micros, false, true), 1_704_067_200_006);
+        assert_eq!(super::bound_millis(micros, true, false), 1_704_067_200_006);
+        assert_eq!(super::bound_millis(micros, false, false), 1_704_067_200_005);
+        for (inclusive, lower) in [(true, true), (false, true), (true, false), (false, false)] {
+            assert_eq!(
+                super::bound_millis(micros + 1, inclusive, lower),
+                1_704_067_200_006
+            );
+        }
+
+        // Bounds before 1970 admit no ids or all of them
+        assert_eq!(super::bound_millis(-946_684_800_000_001, true, true), 0);
+        assert_eq!(super::bound_millis(-946_684_800_000_001, true, false), 0);
+    }
+
+    #[pg_test]
+    fn test_typeid_estimate_rows() {
+        Spi::run("CREATE TABLE events (id typeid PRIMARY KEY)").unwrap();
+        assert_eq!(
+            Spi::get_one::<i64>(
+                "SELECT typeid_estimate_rows('events', 'id', tstzrange(NULL, NULL))"
+            )
+            .unwrap(),
+            None
+        );
+
+        Spi::run(
+            "INSERT INTO events
+             SELECT typeid_generate_history('event', '2024-01-01 UTC', '2024-02-01 UTC', 2000)
+             UNION ALL
+             SELECT typeid_generate_history('event', '2024-03-01 UTC', '2024-04-01 UTC', 1000)",
+        )
+        .unwrap();
+        Spi::run("ANALYZE events").unwrap();
+
+        let estimate = Spi::get_one::<i64>(
+            "SELECT typeid_estimate_rows('events', 'id', tstzrange('2024-02-15 UTC', NULL))",
+        )
+        .unwrap()
+        .unwrap();
+        assert!((900..=1100).contains(&estimate), "estimated {estimate}");
+
+        let estimate =
+            Spi::get_one::<i64>("SELECT typeid_estimate_rows('events', 'id', 'empty'::tstzrange)")
+                .unwrap();
+        assert_eq!(estimate, Some(0));
+    }
+
+    #[pg_test]
+    fn test_typeid_estimate_rows_bounds() {
+        Spi::run("CREATE TABLE events (id typeid)").unwrap();
+        Spi::run(
+            "INSERT INTO events
+             SELECT uuid_to_typeid('event', '018cc251-f405-7000-8000-000000000000')
+             FROM generate_series(1, 100)",
+        )
+        .unwrap();
+        Spi::run("ANALYZE events").unwrap();
+
+        // All ids were generated at 2024-01-01 00:00:00.005 UTC
+        for (range, rows) in [
+            ("[2024-01-01 00:00:00.005 UTC,)", 100),
+            ("(2024-01-01 00:00:00.005 UTC,)", 0),
+            ("(,2024-01-01 00:00:00.005 UTC]", 100),
+            ("(,2024-01-01 00:00:00.005 UTC)", 0),
+        ] {
+            let estimate = Spi::get_one_with_args::<i64>(
+                "SELECT typeid_estimate_rows('events', 'id', $1::tstzrange)",
+                vec![(PgBuiltInOids::TEXTOID.oid(), range.into_datum())],
+            );
+            assert_eq!(estimate, Ok(Some(rows)), "{range}");
+        }
+    }
+
+    #[pg_test]
+    fn test_typeid_operator_selectivity() {
+        Spi::run("CREATE TABLE items (id typeid)").unwrap();
+        Spi::run(
+            "INSERT INTO items
+             SELECT typeid_generate(p) FROM unnest(ARRAY['org', 'team', 'user']) p, generate_series(1, 1000) n
+             WHERE n <= CASE p WHEN 'org' THEN 100 WHEN 'team' THEN 10 ELSE 1000 END",
+        )
+        .unwrap();
+        Spi::run("ANALYZE items").unwrap();
+
+        let rows = |condition: &str| {
+            let plan = Spi::explain(&format!("SELECT * FROM items WHERE {condition}"))
+                .unwrap()
+                .0;
+            plan[0]["Plan"]["Plan Rows"].as_f64().unwrap()
+        };
+
+        // Ids sort by prefix, so the orgs come first
+        let org_max = "uuid_to_typeid('org', 'ffffffff-ffff-ffff-ffff-ffffffffffff')";
+        for (condition, min, max) in [
+            ("id @> 'org'".to_string(), 50.0, 200.0),
+            ("id @> 'user'".to_string(), 800.0, 1110.0),
+            ("id @> 'team'".to_string(), 1.0, 30.0),
+            ("id @> 'group'".to_string(), 1.0, 5.0),
+            (format!("id <= {org_max}"), 50.0, 200.0),
+            (format!("id > {org_max}"), 900.0, 1110.0),
+        ] {
+            let rows = rows(&condition);
+            assert!(min <= rows && rows <= max, "{condition}: {rows} rows");
+        }
+    }
+
+    #[pg_test]
+    fn test_typeid_contains_prefix_join_estimate() {
+        Spi::run("CREATE TABLE items (id typeid)").unwrap();
+        Spi::run("CREATE TABLE kinds (prefix text)").unwrap();
+        Spi::run(
+            "INSERT INTO items
+             SELECT typeid_generate(p) FROM unnest(ARRAY['org', 'team', 'user', 'invoice']) p,
+                 generate_series(1, 250)",
+        )
+        .unwrap();
+        Spi::run("INSERT INTO kinds VALUES ('org'), ('team'), ('user'), ('invoice')").unwrap();
+        Spi::run("ANALYZE items, kinds").unwrap();
+
+        // Every kind joins its 250 items, rather than a fixed share of all pairs
+        let plan = Spi::explain("SELECT * FROM items i JOIN kinds k ON i.id @> k.prefix")
+            .unwrap()
+            .0;
+        let rows = plan[0]["Plan"]["Plan Rows"].as_f64().unwrap();
+        assert!((500.0..=1500.0).contains(&rows), "{rows} rows");
+    }
+}
@@ -0,0 +1,75 @@
+//! Columnar shape of TypeIDs for analytical exports (DuckDB, Parquet foreign tables and the like),
+//! so the base32 text doesn't have to be parsed again downstream.
+
+use pgrx::prelude::*;
+
+use crate::typeid::TypeID;
+
+extension_sql!(
+    r#"
+    CREATE TYPE typeid_struct AS (prefix text, uuid uuid, ts_millis bigint);
+    "#,
+    name = "create_typeid_struct",
+);
+
+fn to_struct(typeid: &TypeID) -> pgrx::composite_type!('static, "typeid_struct") {
+    let mut row = PgHeapTuple::new_composite_type("typeid_struct").unwrap();
+    row.set_by_name("prefix", typeid.type_prefix()).unwrap();
+    row.set_by_name("uuid", pgrx::Uuid::from_bytes(*typeid.uuid().as_bytes()))
+        .unwrap();
+    row.set_by_name("ts_millis", typeid.timestamp_millis() as i64)
+        .unwrap();
+    row
+}
+
+/// The id as a `(prefix, uuid, ts_millis)` row, with the embedded timestamp in milliseconds since
+/// the Unix epoch
+#[pg_extern(immutable, parallel_safe, requires = ["create_typeid_struct"])]
+fn typeid_to_struct(typeid: TypeID) -> pgrx::composite_type!('static, "typeid_struct") {
+    to_struct(&typeid)
+}
+
+/// `typeid_to_struct` of every element, keeping NULLs in place
+#[pg_extern(immutable, parallel_safe, requires = ["create_typeid_struct"])]
+fn typeid_to_structs(
+    ids: Array<TypeID>,
+) -> Vec<Option<pgrx::composite_type!('static, "typeid_struct")>> {
+    ids.iter()
+        .map(|typeid| typeid.as_ref().map(to_struct))
+        .collect()
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    #[pg_test]
+    fn test_typeid_to_struct() {
+        let (prefix, uuid, ts_millis) = Spi::get_three::<String, pgrx::Uuid, i64>(
+            "SELECT (s).prefix, (s).uuid, (s).ts_millis
+             FROM typeid_to_struct(uuid_to_typeid('user', '0193addd-9400-7000-8000-000000000000')) s",
+        )
+        .unwrap();
+        assert_eq!(prefix.as_deref(), Some("user"));
+        assert_eq!(
+            uuid.map(|uuid| uuid.to_string()).as_deref(),
+            Some("0193addd-9400-7000-8000-000000000000")
+        );
+        assert_eq!(ts_millis, Some(1_733_788_800_000));
+    }
+
+    #[pg_test]
+    fn test_typeid_to_structs() {
+        let rows = Spi::get_one::<String>(
+            "SELECT string_agg(coalesce(s.prefix, 'null') || ':' || coalesce(s.ts_millis::text, 'null'), ',')
+             FROM unnest(typeid_to_structs(ARRAY[
+                 uuid_to_typeid('user', '0193addd-9400-7000-8000-000000000000'),
+                 NULL,
+                 uuid_to_typeid('', '00000000-0000-7000-8000-000000000000')
+             ])) s",
+        )
+        .unwrap();
+        assert_eq!(rows.as_deref(), Some("user:1733788800000,null:null,:0"));
+    }
+}
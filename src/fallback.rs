@@ -0,0 +1,244 @@
+//! SQL-only fallback of the core functions, for hosts that can't load C extensions.
+//!
+//! The fallback declares `typeid` as a domain over `text` in the "C" collation. Suffixes use an
+//! alphabet in ascending byte order, so the text of ids sorts like the ids themselves. The alphabet
+//! and prefix rules come from the Rust implementation, and `fallback/typeid_pgtle.sql` is generated
+//! from `pgtle_script()`; `TYPEID_UPDATE_FALLBACK=1 cargo test --test fallback` rewrites it.
+
+use crate::base32::CROCKFORD;
+use crate::interop::TYPEID_PREFIX_MAX_LEN;
+
+const DEFINITIONS: &str = r#"
+CREATE FUNCTION typeid_is_valid(input text) RETURNS boolean
+IMMUTABLE STRICT PARALLEL SAFE
+LANGUAGE sql
+AS $$
+    SELECT input ~ '^([a-z]([a-z_]{0,@PREFIX_INNER_MAX_LEN@}[a-z])?_)?[0-7][@ALPHABET@]{25}$'
+$$;
+
+CREATE DOMAIN typeid AS text COLLATE "C" CHECK (typeid_is_valid(VALUE));
+
+CREATE FUNCTION typeid_base32_encode(uuid uuid) RETURNS text
+IMMUTABLE STRICT PARALLEL SAFE
+LANGUAGE plpgsql
+AS $$
+DECLARE
+    hex text := replace(uuid::text, '-', '');
+    n numeric := 0;
+    encoded text := '';
+BEGIN
+    FOR i IN 1..32 LOOP
+        n := n * 16 + strpos('0123456789abcdef', substr(hex, i, 1)) - 1;
+    END LOOP;
+    FOR i IN 1..26 LOOP
+        encoded := substr('@ALPHABET@', (n % 32)::integer + 1, 1) || encoded;
+        n := div(n, 32);
+    END LOOP;
+    RETURN encoded;
+END
+$$;
+
+CREATE FUNCTION typeid_base32_decode(suffix text) RETURNS uuid
+IMMUTABLE STRICT PARALLEL SAFE
+LANGUAGE plpgsql
+AS $$
+DECLARE
+    n numeric := 0;
+    hex text := '';
+BEGIN
+    FOR i IN 1..26 LOOP
+        n := n * 32 + strpos('@ALPHABET@', substr(suffix, i, 1)) - 1;
+    END LOOP;
+    FOR i IN 1..32 LOOP
+        hex := substr('0123456789abcdef', (n % 16)::integer + 1, 1) || hex;
+        n := div(n, 16);
+    END LOOP;
+    RETURN hex::uuid;
+END
+$$;
+
+CREATE FUNCTION typeid_uuid_generate_v7() RETURNS uuid
+LANGUAGE plpgsql
+AS $$
+DECLARE
+    -- Random bits with the variant already set
+    bytes bytea := uuid_send(gen_random_uuid());
+    millis bigint := floor(extract(epoch FROM clock_timestamp()) * 1000);
+    -- The 12 bits after the timestamp count up within a millisecond, starting from a random value
+    -- below half their range
+    counter integer := (get_byte(bytes, 6) & 7) << 8 | get_byte(bytes, 7);
+    -- Timestamp and counter of the last id of the session
+    last text[] := string_to_array(nullif(current_setting('typeid_fallback.clock', true), ''), ':');
+BEGIN
+    IF last IS NOT NULL AND millis <= last[1]::bigint THEN
+        millis := last[1]::bigint;
+        counter := last[2]::integer + 1;
+        IF counter > 4095 THEN
+            millis := millis + 1;
+            counter := 0;
+        END IF;
+    END IF;
+    PERFORM set_config('typeid_fallback.clock', millis || ':' || counter, false);
+
+    bytes := overlay(bytes PLACING substring(int8send(millis) FROM 3) FROM 1);
+    bytes := set_byte(bytes, 6, 112 | counter >> 8);
+    bytes := set_byte(bytes, 7, counter & 255);
+    RETURN encode(bytes, 'hex')::uuid;
+END
+$$;
+
+CREATE FUNCTION uuid_to_typeid(prefix text, uuid uuid) RETURNS typeid
+IMMUTABLE STRICT PARALLEL SAFE
+LANGUAGE sql
+AS $$
+    SELECT (CASE WHEN prefix = '' THEN '' ELSE prefix || '_' END || typeid_base32_encode(uuid))::typeid
+$$;
+
+CREATE FUNCTION typeid_to_uuid(typeid typeid) RETURNS uuid
+IMMUTABLE STRICT PARALLEL SAFE
+LANGUAGE sql
+AS $$
+    SELECT typeid_base32_decode(right(typeid, 26))
+$$;
+
+CREATE FUNCTION typeid_generate(prefix text) RETURNS typeid
+LANGUAGE sql
+AS $$
+    SELECT uuid_to_typeid(prefix, typeid_uuid_generate_v7())
+$$;
+
+CREATE FUNCTION typeid_has_prefix(id typeid, prefix text) RETURNS boolean
+IMMUTABLE PARALLEL SAFE
+LANGUAGE sql
+AS $$
+    SELECT CASE WHEN prefix ~ '^([a-z]([a-z_]{0,@PREFIX_INNER_MAX_LEN@}[a-z])?)?$' THEN
+        id >= uuid_to_typeid(prefix, '00000000-0000-0000-0000-000000000000')
+        AND id <= uuid_to_typeid(prefix, 'ffffffff-ffff-ffff-ffff-ffffffffffff')
+    ELSE false END
+$$;
+"#;
+
+/// The SQL creating the fallback domain and functions in the first schema of the `search_path`
+pub fn definitions() -> String {
+    DEFINITIONS
+        .replace("@ALPHABET@", std::str::from_utf8(CROCKFORD).unwrap())
+        .replace(
+            "@PREFIX_INNER_MAX_LEN@",
+            &(TYPEID_PREFIX_MAX_LEN - 2).to_string(),
+        )
+}
+
+/// The fallback packaged as a pg_tle extension named `typeid`, so `CREATE EXTENSION typeid`
+/// works the same on hosts with and without the C extension
+pub fn pgtle_script() -> String {
+    format!(
+        "-- Generated from src/fallback.rs, don't edit by hand\n\
+         SELECT pgtle.install_extension(\n    \
+             'typeid',\n    \
+             '{}',\n    \
+             'SQL-only fallback of the typeid extension',\n\
+         $_pgtle_${}$_pgtle_$\n\
+         );\n",
+        env!("CARGO_PKG_VERSION"),
+        definitions()
+    )
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+    use serde::Deserialize;
+    use uuid::Uuid;
+
+    #[derive(Deserialize)]
+    struct Valid {
+        typeid: String,
+        prefix: String,
+        uuid: Uuid,
+    }
+
+    #[derive(Deserialize)]
+    struct Invalid {
+        typeid: String,
+    }
+
+    #[test]
+    fn test_definitions() {
+        let definitions = super::definitions();
+        assert!(!definitions.contains('@'), "{definitions}");
+        assert!(definitions.contains("[a-z_]{0,61}[a-z]"));
+    }
+
+    #[pg_test]
+    fn test_fallback_spec_vectors() {
+        Spi::run("CREATE SCHEMA typeid_fallback").unwrap();
+        Spi::run("SET LOCAL search_path TO typeid_fallback, pg_catalog").unwrap();
+        Spi::run(&super::definitions()).unwrap();
+
+        let valid: Vec<Valid> =
+            serde_yaml::from_str(include_str!("../tests/spec/valid.yml")).unwrap();
+        for test in valid {
+            let args = || {
+                vec![
+                    (
+                        PgBuiltInOids::TEXTOID.oid(),
+                        test.prefix.clone().into_datum(),
+                    ),
+                    (
+                        PgBuiltInOids::UUIDOID.oid(),
+                        pgrx::Uuid::from_bytes(*test.uuid.as_bytes()).into_datum(),
+                    ),
+                    (
+                        PgBuiltInOids::TEXTOID.oid(),
+                        test.typeid.clone().into_datum(),
+                    ),
+                ]
+            };
+            let encoded =
+                Spi::get_one_with_args::<String>("SELECT uuid_to_typeid($1, $2)::text", args());
+            assert_eq!(encoded, Ok(Some(test.typeid.clone())));
+            let decoded = Spi::get_one_with_args::<bool>(
+                "SELECT typeid_to_uuid($3::typeid) = $2 AND typeid_has_prefix($3::typeid, $1)",
+                args(),
+            );
+            assert_eq!(decoded, Ok(Some(true)), "{}", test.typeid);
+        }
+
+        let invalid: Vec<Invalid> =
+            serde_yaml::from_str(include_str!("../tests/spec/invalid.yml")).unwrap();
+        for test in invalid {
+            let accepted = Spi::get_one_with_args::<bool>(
+                "SELECT typeid_is_valid($1)",
+                vec![(
+                    PgBuiltInOids::TEXTOID.oid(),
+                    test.typeid.clone().into_datum(),
+                )],
+            );
+            assert_eq!(accepted, Ok(Some(false)), "{}", test.typeid);
+        }
+
+        // Generated ids are UUIDv7s in the order they were generated, also within a millisecond
+        let ordered = Spi::get_one::<bool>(
+            "SELECT bool_and(get_byte(uuid_send(typeid_to_uuid(id)), 6) >> 4 = 7)
+                AND array_agg(id ORDER BY n) = array_agg(id ORDER BY id)
+             FROM (SELECT n, typeid_generate('user') AS id FROM generate_series(1, 1000) n) g",
+        );
+        assert_eq!(ordered, Ok(Some(true)));
+
+        // An exhausted counter moves on to the next millisecond, even with the clock behind
+        Spi::run("SELECT set_config('typeid_fallback.clock', '4102444800000:4095', false)")
+            .unwrap();
+        let uuids = Spi::get_one::<Vec<String>>(
+            "SELECT ARRAY[left(typeid_uuid_generate_v7()::text, 19),
+                          left(typeid_uuid_generate_v7()::text, 19)]",
+        );
+        assert_eq!(
+            uuids,
+            Ok(Some(vec![
+                "03bb2cc3-d801-7000".to_string(),
+                "03bb2cc3-d801-7001".to_string()
+            ]))
+        );
+    }
+}
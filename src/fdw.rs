@@ -0,0 +1,131 @@
+//! Exposing typeid tables to postgres_fdw servers without the extension.
+//!
+//! A remote server lacking the extension declares typeid columns of its foreign tables as `text`.
+//! Reads and writes of the values work as they are sent as text, but the remote pushes its filters
+//! down as comparisons to `text`, which no typeid operator accepts. Such remotes read from a view
+//! exposing the typeid columns as text instead.
+
+use pgrx::prelude::*;
+use pgrx::PgRelation;
+
+use crate::typeid::TypeID;
+
+/// The statement creating a view of the relation for postgres_fdw servers without the extension,
+/// with its typeid and typeid[] columns as text and text[]. The view is named after the relation
+/// with a `_text` suffix unless `view_name` is given, and is created as well when `execute` is
+/// true.
+#[pg_extern(cost = 100)]
+fn typeid_create_fdw_view(
+    relation: PgRelation,
+    view_name: default!(Option<&str>, "NULL"),
+    execute: default!(bool, false),
+) -> String {
+    let typeid_oid = TypeID::type_oid();
+    let typeid_array_oid = unsafe { pg_sys::get_array_type(typeid_oid) };
+
+    let statement = Spi::connect(|mut client| {
+        let statement = client
+            .select(
+                "SELECT format(
+                    'CREATE VIEW %I.%I AS SELECT %s FROM %s',
+                    n.nspname,
+                    coalesce($2, c.relname || '_text'),
+                    string_agg(
+                        CASE a.atttypid
+                            WHEN $3 THEN format('%I::text AS %I', a.attname, a.attname)
+                            WHEN $4 THEN format('%I::text[] AS %I', a.attname, a.attname)
+                            ELSE quote_ident(a.attname)
+                        END,
+                        ', ' ORDER BY a.attnum
+                    ),
+                    $1::regclass
+                 )
+                 FROM pg_catalog.pg_class c
+                 JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+                 JOIN pg_catalog.pg_attribute a ON a.attrelid = c.oid
+                 WHERE c.oid = $1 AND a.attnum > 0 AND NOT a.attisdropped
+                 GROUP BY n.nspname, c.relname
+                 HAVING bool_or(a.atttypid IN ($3, $4))",
+                None,
+                Some(vec![
+                    (PgBuiltInOids::OIDOID.oid(), relation.oid().into_datum()),
+                    (PgBuiltInOids::TEXTOID.oid(), view_name.into_datum()),
+                    (PgBuiltInOids::OIDOID.oid(), typeid_oid.into_datum()),
+                    (PgBuiltInOids::OIDOID.oid(), typeid_array_oid.into_datum()),
+                ]),
+            )?
+            .first()
+            .get_one::<String>()?;
+
+        let Some(statement) = statement else {
+            error!("relation {} has no typeid columns", relation.name());
+        };
+        if execute {
+            client.update(&statement, None, None)?;
+        }
+        Ok::<_, pgrx::spi::Error>(statement)
+    })
+    .unwrap();
+
+    statement
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    #[pg_test]
+    fn test_typeid_create_fdw_view() {
+        Spi::run("CREATE TABLE users (id typeid PRIMARY KEY, email text, friends typeid[])")
+            .unwrap();
+        Spi::run(
+            "INSERT INTO users VALUES (
+                uuid_to_typeid('user', '0193addd-9400-7000-8000-000000000000'),
+                'a@example.com',
+                ARRAY[typeid_generate('user')]
+             )",
+        )
+        .unwrap();
+
+        let statement =
+            Spi::get_one::<String>("SELECT typeid_create_fdw_view('users', execute => true)")
+                .unwrap()
+                .unwrap();
+        assert!(
+            statement.ends_with(
+                "users_text AS SELECT id::text AS id, email, friends::text[] AS friends FROM users"
+            ),
+            "{statement}"
+        );
+
+        // The filters a remote without the extension pushes down compare to text
+        let email = Spi::get_one::<String>(
+            "SELECT email FROM users_text WHERE id = 'user_01jepxv500e008000000000000'::text",
+        );
+        assert_eq!(email, Ok(Some("a@example.com".to_string())));
+    }
+
+    #[pg_test]
+    fn test_typeid_create_fdw_view_quotes_identifiers() {
+        Spi::run(r#"CREATE TABLE "Users" ("User Id" typeid PRIMARY KEY)"#).unwrap();
+
+        let statement = Spi::get_one::<String>(
+            r#"SELECT typeid_create_fdw_view('"Users"', 'Users; DROP TABLE x')"#,
+        )
+        .unwrap()
+        .unwrap();
+        assert!(
+            statement.ends_with(
+                r#"."Users; DROP TABLE x" AS SELECT "User Id"::text AS "User Id" FROM "Users""#
+            ),
+            "{statement}"
+        );
+    }
+
+    #[pg_test(error = "relation notes has no typeid columns")]
+    fn test_typeid_create_fdw_view_without_typeid_columns() {
+        Spi::run("CREATE TABLE notes (id bigint, body text)").unwrap();
+        Spi::run("SELECT typeid_create_fdw_view('notes')").unwrap();
+    }
+}
@@ -0,0 +1,134 @@
+//! `to_char(typeid, template)`, rendering ids in the formats reports and support tools ask for.
+//!
+//! Templates work like the ones of the built-in `to_char`: patterns are replaced, double quoted
+//! text is copied without the quotes and anything else is copied as is.
+//!
+//! | Pattern  | Replaced by                                      |
+//! |----------|--------------------------------------------------|
+//! | `PREFIX` | the prefix                                       |
+//! | `SUFFIX` | the base32 suffix                                |
+//! | `FIRSTn` | the first `n` characters of the suffix           |
+//! | `LASTn`  | the last `n` characters of the suffix            |
+//! | `UUID`   | the UUID in its hyphenated form                  |
+//! | `YYYY`, `MM`, `DD`, `HH24`, `MI`, `SS`, `MS` | fields of the embedded timestamp in UTC |
+//!
+//! Masked renderings put literal characters in place of the rest of the suffix, e.g.
+//! `PREFIX_****LAST4`.
+
+use pgrx::prelude::*;
+
+use crate::base32::encode_base32_uuid;
+use crate::time::civil_date_utc;
+use crate::typeid::TypeID;
+
+/// Renders `typeid` according to `template`
+fn render(typeid: &TypeID, template: &str) -> String {
+    let suffix = encode_base32_uuid(typeid.uuid());
+    let millis = typeid.timestamp_millis();
+    let (year, month, day) = civil_date_utc(millis);
+    let millis_of_day = millis % 86_400_000;
+
+    let mut output = String::with_capacity(template.len() + suffix.len());
+    let mut rest = template;
+    while let Some(c) = rest.chars().next() {
+        if c == '"' {
+            let literal = &rest[1..];
+            let end = literal.find('"').unwrap_or(literal.len());
+            output.push_str(&literal[..end]);
+            rest = literal.get(end + 1..).unwrap_or_default();
+            continue;
+        }
+
+        let fields: [(&str, &dyn Fn() -> String); 10] = [
+            ("PREFIX", &|| typeid.type_prefix().to_string()),
+            ("SUFFIX", &|| suffix.clone()),
+            ("UUID", &|| typeid.uuid().to_string()),
+            ("YYYY", &|| format!("{year:04}")),
+            ("HH24", &|| format!("{:02}", millis_of_day / 3_600_000)),
+            ("MM", &|| format!("{month:02}")),
+            ("DD", &|| format!("{day:02}")),
+            ("MI", &|| format!("{:02}", millis_of_day / 60_000 % 60)),
+            ("SS", &|| format!("{:02}", millis_of_day / 1000 % 60)),
+            ("MS", &|| format!("{:03}", millis_of_day % 1000)),
+        ];
+        if let Some((pattern, value)) = fields.iter().find(|(p, _)| rest.starts_with(p)) {
+            output.push_str(&value());
+            rest = &rest[pattern.len()..];
+            continue;
+        }
+
+        if let Some((pattern, from_end)) = [("FIRST", false), ("LAST", true)]
+            .into_iter()
+            .find(|(p, _)| rest.starts_with(p))
+        {
+            let digits = rest[pattern.len()..]
+                .bytes()
+                .take(2)
+                .take_while(u8::is_ascii_digit)
+                .count();
+            if let Ok(n) = rest[pattern.len()..pattern.len() + digits].parse::<usize>() {
+                let n = n.min(suffix.len());
+                output.push_str(if from_end {
+                    &suffix[suffix.len() - n..]
+                } else {
+                    &suffix[..n]
+                });
+                rest = &rest[pattern.len() + digits..];
+                continue;
+            }
+        }
+
+        output.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    output
+}
+
+/// Formats the id according to a template of `PREFIX`, `SUFFIX`, `FIRSTn`, `LASTn`, `UUID` and the
+/// `YYYY`, `MM`, `DD`, `HH24`, `MI`, `SS` and `MS` fields of its timestamp in UTC
+#[pg_extern(name = "to_char", immutable, parallel_safe)]
+fn typeid_to_char(typeid: TypeID, template: &str) -> String {
+    render(&typeid, template)
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+    use uuid::Uuid;
+
+    use crate::typeid::{TypeID, TypeIDPrefix};
+
+    #[test]
+    fn test_render() {
+        let typeid = TypeID::new(
+            TypeIDPrefix::new("user").unwrap(),
+            Uuid::parse_str("0193addd-9400-7000-8000-000000000000").unwrap(),
+        );
+        let render = |template| super::render(&typeid, template);
+
+        assert_eq!(render("PREFIX_SUFFIX"), "user_01jepxv500e008000000000000");
+        assert_eq!(render("UUID"), "0193addd-9400-7000-8000-000000000000");
+        assert_eq!(
+            render("YYYY-MM-DD HH24:MI:SS.MS"),
+            "2024-12-10 00:00:00.000"
+        );
+        assert_eq!(render("PREFIX/FIRST4…LAST4"), "user/01je…0000");
+        assert_eq!(render("****LAST99"), "****01jepxv500e008000000000000");
+        assert_eq!(render(r#""PREFIX" PREFIX"#), "PREFIX user");
+        assert_eq!(render("FIRSTx LAST"), "FIRSTx LAST");
+        assert_eq!(render(r#"unterminated "SUFFIX"#), "unterminated SUFFIX");
+    }
+
+    #[pg_test]
+    fn test_to_char() {
+        let rendered = Spi::get_one::<String>(
+            "SELECT to_char(uuid_to_typeid('order', '0193addd-9400-7000-8000-000000000000'), 'PREFIX-YYYYMMDD-****LAST6')",
+        );
+        assert_eq!(rendered, Ok(Some("order-20241210-****000000".to_string())));
+
+        // The built-in overloads are still picked for their types
+        let date = Spi::get_one::<String>("SELECT to_char(date '2024-12-10', 'YYYY')");
+        assert_eq!(date, Ok(Some("2024".to_string())));
+    }
+}
@@ -0,0 +1,542 @@
+//! Generating TypeIDs in bulk and in shapes other than a single id.
+
+use std::collections::BTreeMap;
+use std::f64::consts::TAU;
+use std::str::FromStr;
+
+use pgrx::prelude::*;
+use pgrx::JsonB;
+use rand::distributions::{Distribution as _, WeightedIndex};
+use rand::Rng;
+use serde_json::Value;
+
+use crate::monotonic::Clock;
+use crate::seeded::{self, SeededRng};
+use crate::time::timestamptz_to_millis;
+use crate::typeid::{TypeID, TypeIDPrefix};
+use crate::{monotonic, rate_limit};
+
+/// Generates an id for every key of an object mapping aliases to prefixes, e.g.
+/// `typeid_generate_map('{"owner": "user", "home": "org"}')` returns
+/// `{"owner": "user_...", "home": "org_..."}`
+#[pg_extern(cost = 10)]
+fn typeid_generate_map(prefixes: JsonB) -> JsonB {
+    let JsonB(prefixes) = prefixes;
+    let Value::Object(prefixes) = prefixes else {
+        error!("typeid_generate_map expects a jsonb object of aliases to prefixes");
+    };
+
+    let mut counts = BTreeMap::new();
+    let mut aliases = Vec::with_capacity(prefixes.len());
+    for (alias, prefix) in prefixes {
+        let Value::String(prefix) = prefix else {
+            error!("prefix of {alias:?} must be a string");
+        };
+        let type_prefix = match TypeIDPrefix::new(&prefix) {
+            Ok(type_prefix) => type_prefix,
+            Err(err) => error!("invalid prefix of {alias:?}: {err}"),
+        };
+        *counts.entry(prefix).or_insert(0) += 1;
+        aliases.push((alias, type_prefix));
+    }
+    for (prefix, count) in &counts {
+        rate_limit::check(prefix, *count);
+    }
+
+    let ids = aliases
+        .into_iter()
+        .map(|(alias, prefix)| {
+            let id = TypeID::new(prefix, monotonic::now_v7());
+            (alias, Value::String(id.to_string()))
+        })
+        .collect();
+
+    JsonB(Value::Object(ids))
+}
+
+/// Returns `typeid` when it's not NULL and otherwise generates a new id with the prefix, e.g. for
+/// upserts which may or may not receive an id. A provided id must have the given prefix.
+#[pg_extern(cost = 10)]
+fn typeid_coalesce_generate(typeid: Option<TypeID>, prefix: &str) -> TypeID {
+    match typeid {
+        Some(typeid) if typeid.type_prefix() == prefix => typeid,
+        Some(typeid) => error!(
+            "expected an id with prefix {prefix:?}, got {:?}",
+            typeid.type_prefix()
+        ),
+        None => {
+            let prefix = match TypeIDPrefix::new(prefix) {
+                Ok(prefix) => prefix,
+                Err(err) => error!("invalid prefix: {err}"),
+            };
+            let id = TypeID::new(prefix, monotonic::now_v7());
+            rate_limit::check(id.type_prefix(), 1);
+            id
+        }
+    }
+}
+
+/// How `typeid_generate_history` spreads ids over the time range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Distribution {
+    /// Every moment is equally likely
+    Uniform,
+    /// Exponentially distributed gaps between consecutive ids, i.e. arrivals of a Poisson process
+    Poisson,
+    /// More ids during the day than at night, peaking at 15:00 UTC
+    Diurnal,
+}
+
+impl FromStr for Distribution {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "uniform" => Ok(Self::Uniform),
+            "poisson" => Ok(Self::Poisson),
+            "diurnal" => Ok(Self::Diurnal),
+            _ => Err(format!(
+                "unknown distribution {name:?}, expected uniform, poisson or diurnal"
+            )),
+        }
+    }
+}
+
+const DAY_MILLIS: f64 = 86_400_000.0;
+const THREE_HOURS_MILLIS: f64 = 10_800_000.0;
+
+impl Distribution {
+    /// `count` timestamps in `start..end` milliseconds, in ascending order. They're drawn one at a
+    /// time, so memory use doesn't grow with `count`.
+    fn timestamps<R: Rng>(self, rng: R, start: u64, end: u64, count: usize) -> Timestamps<R> {
+        Timestamps {
+            distribution: self,
+            rng,
+            start,
+            end,
+            remaining: count,
+            quantile: 0.0,
+        }
+    }
+
+    /// The timestamp in `start..end` below which the given share of the ids falls
+    fn at_quantile(self, start: u64, end: u64, quantile: f64) -> u64 {
+        match self {
+            // With their count known, arrivals of a Poisson process are spread like uniform ones
+            Self::Uniform | Self::Poisson => {
+                (start + (quantile * (end - start) as f64) as u64).min(end - 1)
+            }
+            Self::Diurnal => {
+                let target =
+                    quantile * (Self::diurnal_integral(end) - Self::diurnal_integral(start));
+                let (mut low, mut high) = (start, end - 1);
+                while low < high {
+                    let mid = low + (high - low) / 2;
+                    if Self::diurnal_integral(mid) - Self::diurnal_integral(start) >= target {
+                        high = mid;
+                    } else {
+                        low = mid + 1;
+                    }
+                }
+                low
+            }
+        }
+    }
+
+    /// Integral of the relative activity up to `millis`, which goes from 0.2 at 03:00 UTC to 1 at
+    /// 15:00 UTC
+    fn diurnal_integral(millis: u64) -> f64 {
+        let millis = millis as f64;
+        0.6 * millis
+            - 0.4 * DAY_MILLIS / TAU * (TAU * (millis - THREE_HOURS_MILLIS) / DAY_MILLIS).sin()
+    }
+}
+
+/// Sorted random timestamps following a distribution
+struct Timestamps<R> {
+    distribution: Distribution,
+    rng: R,
+    start: u64,
+    end: u64,
+    remaining: usize,
+    /// Quantile of the previous timestamp
+    quantile: f64,
+}
+
+impl<R: Rng> Iterator for Timestamps<R> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        // The smallest of n uniform samples in 0..1 is distributed like 1 - U^(1/n), taking it
+        // over the rest of the interval every time yields the samples in order
+        let u = self.rng.gen::<f64>();
+        self.quantile = 1.0 - (1.0 - self.quantile) * u.powf(1.0 / self.remaining as f64);
+        self.remaining -= 1;
+
+        Some(
+            self.distribution
+                .at_quantile(self.start, self.end, self.quantile),
+        )
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+/// Generates `count` ids with timestamps between `start` and `end`, e.g. for realistic load test
+/// and demo datasets. `distribution` is one of `uniform`, `poisson` or `diurnal`. The ids are
+/// returned in ascending order, one at a time: ids sharing a millisecond are numbered by the
+/// counter bits of their UUIDs.
+#[pg_extern(cost = 1000)]
+fn typeid_generate_history(
+    prefix: &str,
+    start: TimestampWithTimeZone,
+    end: TimestampWithTimeZone,
+    count: i64,
+    distribution: default!(&str, "'uniform'"),
+) -> SetOfIterator<'static, TypeID> {
+    let type_prefix = match TypeIDPrefix::new(prefix) {
+        Ok(prefix) => prefix,
+        Err(err) => error!("invalid prefix: {err}"),
+    };
+    let distribution = Distribution::from_str(distribution).unwrap_or_else(|err| error!("{err}"));
+    let (Some(start), Some(end)) = (timestamptz_to_millis(start), timestamptz_to_millis(end))
+    else {
+        error!("time range must be finite and after 1970");
+    };
+    if start >= end {
+        error!("start must be before end");
+    }
+    let Ok(count) = usize::try_from(count) else {
+        error!("count must not be negative");
+    };
+
+    let timestamps = distribution.timestamps(SeededRng, start, end, count);
+    let prefix = prefix.to_string();
+    let mut clock = Clock::default();
+
+    SetOfIterator::new(timestamps.enumerate().map(move |(n, millis)| {
+        if (n as u64).is_multiple_of(BATCH_CHUNK) {
+            rate_limit::check(&prefix, (count - n).min(BATCH_CHUNK as usize) as u32);
+        }
+        TypeID::new(type_prefix.clone(), clock.next(millis, seeded::random()))
+    }))
+}
+
+/// Number of ids `typeid_generate_batch` accounts to the rate limit at once
+const BATCH_CHUNK: u64 = 1024;
+
+/// Generates `count` ids with the prefix. They're produced one row at a time and accounted to the
+/// rate limit in chunks, so large batches never have to be held in memory as a whole. The clock is
+/// read once for the batch, and the ids are numbered by the counter bits of their UUIDs.
+#[pg_extern(cost = 1000)]
+fn typeid_generate_batch(prefix: &str, count: i64) -> SetOfIterator<'static, TypeID> {
+    let type_prefix = match TypeIDPrefix::new(prefix) {
+        Ok(type_prefix) => type_prefix,
+        Err(err) => error!("invalid prefix: {err}"),
+    };
+    let Ok(count) = u64::try_from(count) else {
+        error!("count must not be negative");
+    };
+    let prefix = prefix.to_string();
+    let mut sequence = monotonic::Sequence::start();
+
+    SetOfIterator::new((0..count).map(move |n| {
+        if n % BATCH_CHUNK == 0 {
+            rate_limit::check(&prefix, (count - n).min(BATCH_CHUNK) as u32);
+        }
+        TypeID::new(type_prefix.clone(), sequence.next_uuid())
+    }))
+}
+
+/// Generates `count` ids with the prefix, under the name of Postgres' `generate_series`. The ids
+/// stream like those of `typeid_generate_batch`, e.g. into `INSERT ... SELECT`.
+#[pg_extern(cost = 1000)]
+fn typeid_generate_series(prefix: &str, count: i64) -> SetOfIterator<'static, TypeID> {
+    typeid_generate_batch(prefix, count)
+}
+
+/// Generates `count` ids with prefixes drawn at random according to an object of prefixes to
+/// weights, e.g. `typeid_generate_mixed('{"user": 1, "order": 20}', 1000)` returns about 20 orders
+/// for every user. Like `typeid_generate_batch`, the ids are produced one row at a time.
+#[pg_extern(cost = 1000)]
+fn typeid_generate_mixed(weights: JsonB, count: i64) -> SetOfIterator<'static, TypeID> {
+    let JsonB(weights) = weights;
+    let Value::Object(weights) = weights else {
+        error!("typeid_generate_mixed expects a jsonb object of prefixes to weights");
+    };
+    let Ok(count) = u64::try_from(count) else {
+        error!("count must not be negative");
+    };
+
+    let mut prefixes = Vec::with_capacity(weights.len());
+    let mut shares = Vec::with_capacity(weights.len());
+    for (prefix, weight) in weights {
+        let weight = match weight.as_f64() {
+            Some(weight) if weight.is_finite() && weight >= 0.0 => weight,
+            _ => error!("weight of {prefix:?} must be a non-negative number"),
+        };
+        let type_prefix = match TypeIDPrefix::new(&prefix) {
+            Ok(type_prefix) => type_prefix,
+            Err(err) => error!("invalid prefix {prefix:?}: {err}"),
+        };
+        prefixes.push((prefix, type_prefix));
+        shares.push(weight);
+    }
+    let index = match WeightedIndex::new(&shares) {
+        Ok(index) => index,
+        Err(_) => error!("typeid_generate_mixed needs at least one prefix with a positive weight"),
+    };
+
+    let mut rng = SeededRng;
+    let chunks = (0..count).step_by(BATCH_CHUNK as usize);
+    SetOfIterator::new(chunks.flat_map(move |n| {
+        // Draw a chunk of prefixes at once, so every prefix is accounted to the rate limit once
+        // per chunk
+        let chunk = (0..(count - n).min(BATCH_CHUNK))
+            .map(|_| index.sample(&mut rng))
+            .collect::<Vec<_>>();
+        let mut counts = vec![0; prefixes.len()];
+        for &i in &chunk {
+            counts[i] += 1;
+        }
+        for (i, &n) in counts.iter().enumerate() {
+            if n > 0 {
+                rate_limit::check(&prefixes[i].0, n);
+            }
+        }
+        let prefixes = prefixes.clone();
+        chunk
+            .into_iter()
+            .map(move |i| TypeID::new(prefixes[i].1.clone(), monotonic::now_v7()))
+    }))
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    #[pg_test]
+    fn test_typeid_generate_map() {
+        let (owner, home) = Spi::get_two::<String, String>(
+            r#"SELECT ids->>'owner', ids->>'home'
+               FROM typeid_generate_map('{"owner": "user", "home": "org"}') ids"#,
+        )
+        .unwrap();
+        assert!(owner.unwrap().starts_with("user_"));
+        assert!(home.unwrap().starts_with("org_"));
+
+        let valid = Spi::get_one::<bool>(
+            r#"SELECT bool_and(value::typeid IS NOT NULL)
+               FROM jsonb_each_text(typeid_generate_map('{"a": "user", "b": "user"}'))"#,
+        )
+        .unwrap();
+        assert_eq!(valid, Some(true));
+    }
+
+    // Generating writes nothing, so it works in read-only transactions and on hot standbys alike
+    #[pg_test]
+    fn test_generate_read_only() {
+        Spi::run("SET transaction_read_only = on").unwrap();
+
+        let generated = Spi::get_one::<Vec<bool>>(
+            r#"SELECT ARRAY[
+                typeid_generate('user') IS NOT NULL,
+                typeid_uuid_generate_v7() IS NOT NULL,
+                typeid_coalesce_generate(NULL, 'user') IS NOT NULL,
+                typeid_generate_map('{"owner": "user"}') ? 'owner',
+                typeid_generate_for_tenant('doc', 42) IS NOT NULL,
+                (SELECT count(*) = 10 FROM typeid_generate_batch('event', 10)),
+                (SELECT count(*) = 10 FROM typeid_generate_history('event', now() - interval '1 day', now(), 10)),
+                (SELECT count(*) = 10 FROM typeid_generate_mixed('{"event": 1}', 10))
+            ]"#,
+        );
+        assert_eq!(generated, Ok(Some(vec![true; 8])));
+    }
+
+    #[test]
+    fn test_distribution_timestamps() {
+        use super::Distribution;
+
+        // 2024-01-01 00:00 UTC
+        let start = 1_704_067_200_000;
+        let end = start + 86_400_000;
+        for distribution in [
+            Distribution::Uniform,
+            Distribution::Poisson,
+            Distribution::Diurnal,
+        ] {
+            let timestamps = distribution
+                .timestamps(rand::thread_rng(), start, end, 1000)
+                .collect::<Vec<_>>();
+            assert_eq!(timestamps.len(), 1000);
+            assert!(timestamps.windows(2).all(|pair| pair[0] <= pair[1]));
+            assert!(timestamps.iter().all(|t| (start..end).contains(t)));
+        }
+
+        // Far more ids in the afternoon than at night
+        let hours = |t: &u64| (t - start) / 3_600_000;
+        let timestamps = Distribution::Diurnal
+            .timestamps(rand::thread_rng(), start, end, 10_000)
+            .collect::<Vec<_>>();
+        let night = timestamps.iter().filter(|t| hours(t) < 6).count();
+        let afternoon = timestamps
+            .iter()
+            .filter(|t| (12..18).contains(&hours(t)))
+            .count();
+        assert!(afternoon > night * 2, "{afternoon} vs {night}");
+
+        // Spread evenly over the range
+        let timestamps = Distribution::Uniform
+            .timestamps(rand::thread_rng(), start, end, 10_000)
+            .collect::<Vec<_>>();
+        let morning = timestamps.iter().filter(|t| hours(t) < 12).count();
+        assert!((4_500..5_500).contains(&morning), "{morning}");
+    }
+
+    #[pg_test]
+    fn test_typeid_generate_batch() {
+        let (count, distinct) = Spi::get_two::<i64, i64>(
+            "SELECT count(*), count(DISTINCT id) FROM typeid_generate_batch('event', 5000) id",
+        )
+        .unwrap();
+        assert_eq!(count, Some(5000));
+        assert_eq!(distinct, Some(5000));
+
+        let increasing = Spi::get_one::<bool>(
+            "SELECT bool_and(id > previous) FROM (
+                SELECT id, lag(id) OVER (ORDER BY n) previous
+                FROM typeid_generate_batch('event', 10000) WITH ORDINALITY ids (id, n)
+             ) ids",
+        );
+        assert_eq!(increasing, Ok(Some(true)));
+
+        // The batch runs ahead of the clock, and the ids generated after it continue after it
+        Spi::run("CREATE TABLE events AS SELECT id FROM typeid_generate_batch('event', 100000) id")
+            .unwrap();
+        let after = Spi::get_one::<bool>(
+            "SELECT typeid_generate('event') > (SELECT max(id) FROM events)
+                AND typeid_generate_batch('event', 1) > (SELECT max(id) FROM events)",
+        );
+        assert_eq!(after, Ok(Some(true)));
+    }
+
+    #[pg_test]
+    fn test_typeid_generate_series() {
+        Spi::run("CREATE TABLE users (id typeid PRIMARY KEY)").unwrap();
+        Spi::run("INSERT INTO users SELECT typeid_generate_series('user', 5000)").unwrap();
+        let (count, prefixes) = Spi::get_two::<i64, i64>(
+            "SELECT count(*), count(DISTINCT typeid_prefix(id)) FROM users",
+        )
+        .unwrap();
+        assert_eq!(count, Some(5000));
+        assert_eq!(prefixes, Some(1));
+    }
+
+    #[pg_test(error = "count must not be negative")]
+    fn test_typeid_generate_series_negative() {
+        Spi::run("SELECT typeid_generate_series('user', -1)").unwrap();
+    }
+
+    #[pg_test]
+    fn test_typeid_generate_history() {
+        let (count, in_range, ordered) = Spi::get_three::<i64, bool, bool>(
+            "SELECT count(*), bool_and(t >= '2024-01-01' AND t < '2024-02-01'), bool_and(id >= prev)
+             FROM (
+                SELECT id, typeid_timestamp_at(id, 'UTC') AS t, lag(id, 1, id) OVER () AS prev
+                FROM typeid_generate_history('event', '2024-01-01 UTC', '2024-02-01 UTC', 500, 'diurnal') id
+             ) s",
+        )
+        .unwrap();
+        assert_eq!(count, Some(500));
+        assert_eq!(in_range, Some(true));
+        assert_eq!(ordered, Some(true));
+    }
+
+    #[pg_test]
+    fn test_typeid_generate_history_strictly_ascending() {
+        // Far more ids than milliseconds in the range
+        let ascending = Spi::get_one::<bool>(
+            "SELECT bool_and(id > previous) FROM (
+                SELECT id, lag(id) OVER (ORDER BY n) previous
+                FROM typeid_generate_history('event', '2024-01-01 UTC', '2024-01-01 00:00:00.010 UTC', 5000)
+                    WITH ORDINALITY ids (id, n)
+             ) ids",
+        );
+        assert_eq!(ascending, Ok(Some(true)));
+    }
+
+    #[pg_test(
+        error = "generating TypeIDs with prefix \"event\" exceeded typeid.rate_limit of 10 per second"
+    )]
+    fn test_typeid_generate_history_rate_limit() {
+        Spi::run("SET typeid.rate_limit = 10").unwrap();
+        Spi::run(
+            "SELECT count(*) FROM typeid_generate_history('event', '2024-01-01 UTC', '2024-02-01 UTC', 100)",
+        )
+        .unwrap();
+    }
+
+    #[pg_test(error = "unknown distribution \"normal\", expected uniform, poisson or diurnal")]
+    fn test_typeid_generate_history_unknown_distribution() {
+        Spi::run("SELECT typeid_generate_history('event', now() - interval '1 day', now(), 10, 'normal')")
+            .unwrap();
+    }
+
+    #[pg_test]
+    fn test_typeid_coalesce_generate() {
+        let id = Spi::get_one::<bool>(
+            "SELECT typeid_coalesce_generate(id, 'user') = id
+             FROM uuid_to_typeid('user', '0193addd-9400-7000-8000-000000000000') id",
+        )
+        .unwrap();
+        assert_eq!(id, Some(true));
+
+        let generated =
+            Spi::get_one::<String>("SELECT typeid_coalesce_generate(NULL, 'user')::text").unwrap();
+        assert!(generated.unwrap().starts_with("user_"));
+    }
+
+    #[pg_test(error = "expected an id with prefix \"user\", got \"org\"")]
+    fn test_typeid_coalesce_generate_prefix_mismatch() {
+        Spi::run("SELECT typeid_coalesce_generate(typeid_generate('org'), 'user')").unwrap();
+    }
+
+    #[pg_test]
+    fn test_typeid_generate_mixed() {
+        let counts = Spi::get_three::<i64, i64, i64>(
+            r#"SELECT count(*),
+                      count(*) FILTER (WHERE typeid_has_prefix(id, 'user')),
+                      count(*) FILTER (WHERE typeid_has_prefix(id, 'order'))
+               FROM typeid_generate_mixed('{"user": 1, "order": 3, "refund": 0}', 4000) id"#,
+        )
+        .unwrap();
+        let (Some(total), Some(users), Some(orders)) = counts else {
+            panic!("{counts:?}");
+        };
+        assert_eq!(total, 4000);
+        assert_eq!(users + orders, 4000);
+        assert!((2700..3300).contains(&orders), "{orders}");
+    }
+
+    #[pg_test(error = "weight of \"user\" must be a non-negative number")]
+    fn test_typeid_generate_mixed_invalid_weight() {
+        Spi::run(r#"SELECT typeid_generate_mixed('{"user": -1}', 10)"#).unwrap();
+    }
+
+    #[pg_test(error = "typeid_generate_mixed needs at least one prefix with a positive weight")]
+    fn test_typeid_generate_mixed_without_weights() {
+        Spi::run(r#"SELECT typeid_generate_mixed('{}', 10)"#).unwrap();
+    }
+
+    #[pg_test(error = "prefix of \"owner\" must be a string")]
+    fn test_typeid_generate_map_invalid() {
+        Spi::run(r#"SELECT typeid_generate_map('{"owner": 1}')"#).unwrap();
+    }
+}
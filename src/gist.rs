@@ -0,0 +1,398 @@
+//! GiST operator class indexing typeids by the range of ids under each node, like the btree_gist
+//! operator classes of scalar types. It serves `=`, `<>` and the inequalities, so typeid columns
+//! can be part of exclusion constraints and multi-column GiST indexes:
+//!
+//! ```sql
+//! EXCLUDE USING gist (room_id WITH =, during WITH &&)
+//! ```
+//!
+//! Keys are the smallest and largest id under a node, one after the other in the binary format of
+//! typeids, and leaves hold the same id twice.
+
+use pgrx::prelude::*;
+use pgrx::{FromDatum, Internal, IntoDatum};
+
+use crate::binary;
+use crate::datum::{TypeIDDatum, TypeIDRef};
+
+/// Strategy of `<>` in the btree_gist operator classes, after those of the btree ones
+const NOT_EQUAL_STRATEGY: i16 = 6;
+
+/// The smallest and largest id of a key
+fn decode_key(key: &[u8]) -> Option<(TypeIDRef<'_>, TypeIDRef<'_>)> {
+    let len = 1 + *key.first()? as usize + 16;
+    if key.len() < len {
+        return None;
+    }
+    let (lower, upper) = key.split_at(len);
+    Some((TypeIDRef::parse(lower)?, TypeIDRef::parse(upper)?))
+}
+
+fn encode_key(lower: TypeIDRef<'_>, upper: TypeIDRef<'_>) -> Vec<u8> {
+    let mut key = binary::encode(lower.type_prefix(), lower.uuid_bytes());
+    key.extend(binary::encode(upper.type_prefix(), upper.uuid_bytes()));
+    key
+}
+
+unsafe fn read_key<'a>(datum: pg_sys::Datum) -> (TypeIDRef<'a>, TypeIDRef<'a>) {
+    <&[u8]>::from_datum(datum, false)
+        .and_then(decode_key)
+        .unwrap_or_else(|| error!("invalid typeid GiST key"))
+}
+
+/// The smallest and largest of the ids of keys
+fn union<'a>(
+    keys: impl IntoIterator<Item = (TypeIDRef<'a>, TypeIDRef<'a>)>,
+) -> Option<(TypeIDRef<'a>, TypeIDRef<'a>)> {
+    keys.into_iter()
+        .reduce(|(lower, upper), (other_lower, other_upper)| {
+            (lower.min(other_lower), upper.max(other_upper))
+        })
+}
+
+/// Distance between two ids as a share of the UUIDs, or 1 between prefixes, which ids of
+/// different prefixes are sorted by first
+fn distance(a: TypeIDRef<'_>, b: TypeIDRef<'_>) -> f64 {
+    if a.type_prefix() != b.type_prefix() {
+        return 1.0;
+    }
+    let (a, b) = (
+        u128::from_be_bytes(*a.uuid_bytes()),
+        u128::from_be_bytes(*b.uuid_bytes()),
+    );
+    a.abs_diff(b) as f64 / 2f64.powi(128)
+}
+
+/// How much the range of a key grows to take in another one
+fn penalty(
+    (lower, upper): (TypeIDRef<'_>, TypeIDRef<'_>),
+    (new_lower, new_upper): (TypeIDRef<'_>, TypeIDRef<'_>),
+) -> f64 {
+    let below = if new_lower < lower {
+        distance(new_lower, lower)
+    } else {
+        0.0
+    };
+    let above = if new_upper > upper {
+        distance(upper, new_upper)
+    } else {
+        0.0
+    };
+    below + above
+}
+
+/// Whether a key may hold ids matching `id <strategy> query`, exactly for leaves, whose ids are
+/// the same. None for strategies the operator class doesn't have.
+fn consistent(
+    (lower, upper): (TypeIDRef<'_>, TypeIDRef<'_>),
+    query: TypeIDRef<'_>,
+    strategy: i16,
+) -> Option<bool> {
+    let consistent = match strategy as u32 {
+        pg_sys::BTLessStrategyNumber => lower < query,
+        pg_sys::BTLessEqualStrategyNumber => lower <= query,
+        pg_sys::BTEqualStrategyNumber => lower <= query && query <= upper,
+        pg_sys::BTGreaterEqualStrategyNumber => upper >= query,
+        pg_sys::BTGreaterStrategyNumber => upper > query,
+        _ if strategy == NOT_EQUAL_STRATEGY => !(lower == query && upper == query),
+        _ => return None,
+    };
+    Some(consistent)
+}
+
+/// A new entry of the same index tuple holding `key`
+unsafe fn replace_key(entry: *const pg_sys::GISTENTRY, key: pg_sys::Datum) -> Internal {
+    let replaced = pg_sys::palloc(std::mem::size_of::<pg_sys::GISTENTRY>()).cast();
+    std::ptr::write(
+        replaced,
+        pg_sys::GISTENTRY {
+            key,
+            leafkey: false,
+            ..*entry
+        },
+    );
+    Internal::from(Some(pg_sys::Datum::from(replaced)))
+}
+
+#[pg_extern(immutable, parallel_safe, requires = [TypeID])]
+fn typeid_gist_consistent(
+    entry: Internal,
+    query: TypeIDRef<'_>,
+    strategy: i16,
+    _subtype: pg_sys::Oid,
+    recheck: Internal,
+) -> bool {
+    unsafe {
+        let Some(entry) = entry.get::<pg_sys::GISTENTRY>() else {
+            return false;
+        };
+        if let Some(recheck) = recheck.get_mut::<bool>() {
+            *recheck = false;
+        }
+        consistent(read_key(entry.key), query, strategy)
+            .unwrap_or_else(|| error!("unknown typeid GiST strategy {strategy}"))
+    }
+}
+
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_gist_union(entries: Internal, size: Internal) -> Vec<u8> {
+    unsafe {
+        let Some(entries) = entries.get::<pg_sys::GistEntryVector>() else {
+            error!("typeid_gist_union requires a GistEntryVector");
+        };
+        let keys = entries
+            .vector
+            .as_slice(entries.n as usize)
+            .iter()
+            .map(|entry| read_key(entry.key));
+        let (lower, upper) = union(keys).unwrap_or_else(|| error!("no typeid GiST keys to merge"));
+
+        let key = encode_key(lower, upper);
+        if let Some(size) = size.get_mut::<i32>() {
+            *size = (pg_sys::VARHDRSZ + key.len()) as i32;
+        }
+        key
+    }
+}
+
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_gist_compress(entry: Internal) -> Internal {
+    unsafe {
+        let Some(leaf) = entry.get::<pg_sys::GISTENTRY>() else {
+            return entry;
+        };
+        if !leaf.leafkey {
+            return entry;
+        }
+        let Some(typeid) = TypeIDRef::from_datum(leaf.key, false) else {
+            return entry;
+        };
+        replace_key(leaf, encode_key(typeid, typeid).into_datum().unwrap())
+    }
+}
+
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_gist_fetch(entry: Internal) -> Internal {
+    unsafe {
+        let Some(leaf) = entry.get::<pg_sys::GISTENTRY>() else {
+            return entry;
+        };
+        let (typeid, _) = read_key(leaf.key);
+        let datum = TypeIDDatum::new(typeid.type_prefix(), typeid.uuid_bytes());
+        replace_key(leaf, datum.into_datum().unwrap())
+    }
+}
+
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_gist_penalty(original: Internal, new: Internal, penalty: Internal) -> Internal {
+    unsafe {
+        let (Some(original), Some(new), Some(result)) = (
+            original.get::<pg_sys::GISTENTRY>(),
+            new.get::<pg_sys::GISTENTRY>(),
+            penalty.get_mut::<f32>(),
+        ) else {
+            error!("typeid_gist_penalty requires two entries and a penalty");
+        };
+        *result = self::penalty(read_key(original.key), read_key(new.key)) as f32;
+    }
+    penalty
+}
+
+/// Splits the entries in half in the order of their ranges, keeping neighbouring ids together
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_gist_picksplit(entries: Internal, split: Internal) -> Internal {
+    unsafe {
+        let (Some(entries), Some(result)) = (
+            entries.get::<pg_sys::GistEntryVector>(),
+            split.get_mut::<pg_sys::GIST_SPLITVEC>(),
+        ) else {
+            error!("typeid_gist_picksplit requires a GistEntryVector and a GIST_SPLITVEC");
+        };
+
+        // Entries are numbered from 1
+        let vector = entries.vector.as_slice(entries.n as usize);
+        let mut keys = (pg_sys::FirstOffsetNumber..entries.n as u16)
+            .map(|offset| (offset, read_key(vector[offset as usize].key)))
+            .collect::<Vec<_>>();
+        keys.sort_by_key(|(_, key)| *key);
+        let (left, right) = keys.split_at(keys.len() / 2);
+
+        let side = |entries: &[(pg_sys::OffsetNumber, (TypeIDRef<'_>, TypeIDRef<'_>))]| {
+            let offsets =
+                pg_sys::palloc(std::mem::size_of::<pg_sys::OffsetNumber>() * entries.len().max(1))
+                    .cast::<pg_sys::OffsetNumber>();
+            for (i, (offset, _)) in entries.iter().enumerate() {
+                *offsets.add(i) = *offset;
+            }
+            let (lower, upper) = union(entries.iter().map(|(_, key)| *key)).unwrap();
+            let key = encode_key(lower, upper).into_datum().unwrap();
+            (offsets, entries.len() as i32, key)
+        };
+        (result.spl_left, result.spl_nleft, result.spl_ldatum) = side(left);
+        (result.spl_right, result.spl_nright, result.spl_rdatum) = side(right);
+    }
+    split
+}
+
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_gist_same(a: &[u8], b: &[u8], result: Internal) -> Internal {
+    unsafe {
+        if let Some(same) = result.get_mut::<bool>() {
+            *same = a == b;
+        }
+    }
+    result
+}
+
+extension_sql!(
+    r#"
+    CREATE OPERATOR CLASS typeid_ops DEFAULT FOR TYPE typeid USING gist AS
+        OPERATOR 1 < (typeid, typeid),
+        OPERATOR 2 <= (typeid, typeid),
+        OPERATOR 3 = (typeid, typeid),
+        OPERATOR 4 >= (typeid, typeid),
+        OPERATOR 5 > (typeid, typeid),
+        OPERATOR 6 <> (typeid, typeid),
+        FUNCTION 1 typeid_gist_consistent(internal, typeid, smallint, oid, internal),
+        FUNCTION 2 typeid_gist_union(internal, internal),
+        FUNCTION 3 typeid_gist_compress(internal),
+        FUNCTION 5 typeid_gist_penalty(internal, internal, internal),
+        FUNCTION 6 typeid_gist_picksplit(internal, internal),
+        FUNCTION 7 typeid_gist_same(bytea, bytea, internal),
+        FUNCTION 9 typeid_gist_fetch(internal),
+        STORAGE bytea;
+    "#,
+    name = "create_typeid_gist_operator_class",
+    requires = [
+        "create_typeid_operator_class",
+        typeid_gist_consistent,
+        typeid_gist_union,
+        typeid_gist_compress,
+        typeid_gist_penalty,
+        typeid_gist_picksplit,
+        typeid_gist_same,
+        typeid_gist_fetch
+    ],
+);
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    use crate::binary;
+    use crate::datum::TypeIDRef;
+
+    #[test]
+    fn test_gist_keys() {
+        let user = binary::encode("user", &[1; 16]);
+        let later_user = binary::encode("user", &[2; 16]);
+        let org = binary::encode("org", &[3; 16]);
+        let (user, later_user, org) = (
+            TypeIDRef::parse(&user).unwrap(),
+            TypeIDRef::parse(&later_user).unwrap(),
+            TypeIDRef::parse(&org).unwrap(),
+        );
+
+        let key = super::encode_key(org, user);
+        assert_eq!(super::decode_key(&key), Some((org, user)));
+        assert_eq!(super::decode_key(&key[..10]), None);
+
+        let union = super::union([(user, user), (org, org), (later_user, later_user)]);
+        assert_eq!(union, Some((org, later_user)));
+
+        // Taking in ids of another prefix costs more than any UUID range
+        let penalty = super::penalty((user, user), (later_user, later_user));
+        assert!(0.0 < penalty && penalty < 0.01);
+        assert_eq!(super::penalty((org, later_user), (user, user)), 0.0);
+        assert_eq!(super::penalty((user, user), (org, org)), 1.0);
+
+        let strategy = |strategy: u32| strategy as i16;
+        let equal = strategy(pg_sys::BTEqualStrategyNumber);
+        assert_eq!(
+            super::consistent((org, later_user), user, equal),
+            Some(true)
+        );
+        let less_equal = strategy(pg_sys::BTLessEqualStrategyNumber);
+        assert_eq!(
+            super::consistent((user, later_user), org, less_equal),
+            Some(false)
+        );
+        let not_equal = super::NOT_EQUAL_STRATEGY;
+        assert_eq!(
+            super::consistent((user, user), user, not_equal),
+            Some(false)
+        );
+        assert_eq!(super::consistent((user, user), user, 7), None);
+    }
+
+    #[pg_test]
+    fn test_typeid_gist_index() {
+        Spi::run("CREATE TABLE items (id typeid)").unwrap();
+        Spi::run(
+            "INSERT INTO items
+             SELECT typeid_generate(p) FROM unnest(ARRAY['org', 'user']) p, generate_series(1, 1000)",
+        )
+        .unwrap();
+        Spi::run("INSERT INTO items VALUES ('user_01h455vb4pex5vsknk084sn02q')").unwrap();
+        Spi::run("CREATE INDEX items_id_gist ON items USING gist (id)").unwrap();
+        Spi::run("SET enable_seqscan = off").unwrap();
+
+        let org_max = "uuid_to_typeid('org', 'ffffffff-ffff-ffff-ffff-ffffffffffff')";
+        for (condition, expected) in [
+            ("id = 'user_01h455vb4pex5vsknk084sn02q'".to_string(), 1),
+            (format!("id < {org_max}"), 1000),
+            (format!("id > {org_max}"), 1001),
+        ] {
+            let plan = Spi::explain(&format!("SELECT id FROM items WHERE {condition}"))
+                .unwrap()
+                .0
+                .to_string();
+            assert!(plan.contains("items_id_gist"), "{condition}: {plan}");
+
+            let count =
+                Spi::get_one::<i64>(&format!("SELECT count(*) FROM items WHERE {condition}"));
+            assert_eq!(count, Ok(Some(expected)), "{condition}");
+        }
+
+        // Index-only scans read the ids back from the keys
+        Spi::run("SET enable_bitmapscan = off").unwrap();
+        let plan =
+            Spi::explain("SELECT id FROM items WHERE id = 'user_01h455vb4pex5vsknk084sn02q'")
+                .unwrap()
+                .0;
+        assert_eq!(
+            plan[0]["Plan"]["Node Type"].as_str(),
+            Some("Index Only Scan"),
+            "{plan}"
+        );
+        let id = Spi::get_one::<String>(
+            "SELECT id::text FROM items WHERE id = 'user_01h455vb4pex5vsknk084sn02q'",
+        );
+        assert_eq!(id, Ok(Some("user_01h455vb4pex5vsknk084sn02q".to_string())));
+    }
+
+    #[pg_test(
+        error = "conflicting key value violates exclusion constraint \"bookings_room_id_during_excl\""
+    )]
+    fn test_typeid_exclusion_constraint() {
+        Spi::run(
+            "CREATE TABLE bookings (
+                room_id typeid,
+                during tstzrange,
+                EXCLUDE USING gist (room_id WITH =, during WITH &&)
+            )",
+        )
+        .unwrap();
+        Spi::run(
+            "INSERT INTO bookings VALUES
+                ('room_01h455vb4pex5vsknk084sn02q', '[2024-01-01, 2024-01-03)'),
+                ('room_01h455vb4pex5vsknk084sn02r', '[2024-01-02, 2024-01-04)')",
+        )
+        .unwrap();
+        Spi::run(
+            "INSERT INTO bookings VALUES ('room_01h455vb4pex5vsknk084sn02q', '[2024-01-02, 2024-01-05)')",
+        )
+        .unwrap();
+    }
+}
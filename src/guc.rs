@@ -0,0 +1,174 @@
+//! Settings of the extension, all of them live under the `typeid.` namespace.
+
+use std::ffi::CStr;
+
+use pgrx::{pg_sys, GucContext, GucFlags, GucRegistry, GucSetting, PostgresGucEnum};
+
+use crate::pool::POOL_CAPACITY;
+
+#[derive(PostgresGucEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RateLimitAction {
+    Error,
+    Warning,
+}
+
+pub static VALIDATION_WORKER: GucSetting<bool> = GucSetting::<bool>::new(false);
+pub static VALIDATION_DATABASE: GucSetting<Option<&'static CStr>> =
+    GucSetting::<Option<&'static CStr>>::new(Some(c"postgres"));
+pub static VALIDATION_INTERVAL: GucSetting<i32> = GucSetting::<i32>::new(60);
+pub static VALIDATION_TARGETS: GucSetting<Option<&'static CStr>> =
+    GucSetting::<Option<&'static CStr>>::new(None);
+pub static VALIDATION_BATCH_SIZE: GucSetting<i32> = GucSetting::<i32>::new(1000);
+pub static VALIDATION_MAX_CLOCK_SKEW: GucSetting<i32> = GucSetting::<i32>::new(60_000);
+pub static DEFAULT_PREFIX: GucSetting<Option<&'static CStr>> =
+    GucSetting::<Option<&'static CStr>>::new(None);
+pub static TEST_SEED: GucSetting<i32> = GucSetting::<i32>::new(0);
+pub static POOL_WORKER: GucSetting<bool> = GucSetting::<bool>::new(false);
+pub static POOL_SIZE: GucSetting<i32> = GucSetting::<i32>::new(4096);
+pub static POOL_REFILL_THRESHOLD: GucSetting<i32> = GucSetting::<i32>::new(1024);
+pub static RATE_LIMIT: GucSetting<i32> = GucSetting::<i32>::new(0);
+pub static RATE_LIMIT_ACTION: GucSetting<RateLimitAction> =
+    GucSetting::<RateLimitAction>::new(RateLimitAction::Error);
+
+/// Whether the library is being loaded through `shared_preload_libraries`. Subsystems needing
+/// shared memory, background workers or postmaster settings only set themselves up then, so the
+/// extension works on hosts that don't allow preloading it.
+pub fn preloading() -> bool {
+    unsafe { pg_sys::process_shared_preload_libraries_in_progress }
+}
+
+pub fn init() {
+    // Postgres refuses to define postmaster settings once it's running
+    if preloading() {
+        GucRegistry::define_bool_guc(
+            "typeid.validation_worker",
+            "Starts the background validation worker.",
+            "Requires typeid in shared_preload_libraries.",
+            &VALIDATION_WORKER,
+            GucContext::Postmaster,
+            GucFlags::default(),
+        );
+        GucRegistry::define_string_guc(
+            "typeid.validation_database",
+            "Database the background validation worker connects to.",
+            "The typeid extension has to be installed in it.",
+            &VALIDATION_DATABASE,
+            GucContext::Postmaster,
+            GucFlags::default(),
+        );
+        GucRegistry::define_bool_guc(
+            "typeid.pool_worker",
+            "Starts the background worker keeping a pool of pre-generated UUIDs.",
+            "Requires typeid in shared_preload_libraries.",
+            &POOL_WORKER,
+            GucContext::Postmaster,
+            GucFlags::default(),
+        );
+        GucRegistry::define_int_guc(
+            "typeid.pool_size",
+            "Number of UUIDs the pool worker keeps ready.",
+            "",
+            &POOL_SIZE,
+            1,
+            POOL_CAPACITY as i32,
+            GucContext::Sighup,
+            GucFlags::default(),
+        );
+        GucRegistry::define_int_guc(
+            "typeid.pool_refill_threshold",
+            "Number of UUIDs left in the pool below which the pool worker refills it.",
+            "",
+            &POOL_REFILL_THRESHOLD,
+            0,
+            POOL_CAPACITY as i32,
+            GucContext::Sighup,
+            GucFlags::default(),
+        );
+    }
+    GucRegistry::define_int_guc(
+        "typeid.validation_interval",
+        "Time between two validation batches.",
+        "",
+        &VALIDATION_INTERVAL,
+        1,
+        i32::MAX,
+        GucContext::Sighup,
+        GucFlags::UNIT_S,
+    );
+    GucRegistry::define_string_guc(
+        "typeid.validation_targets",
+        "Columns checked by the validation worker.",
+        "Comma separated list of [schema.]table.column entries, optionally followed by =prefix \
+         to report values with any other prefix, e.g. public.users.id=user.",
+        &VALIDATION_TARGETS,
+        GucContext::Suset,
+        GucFlags::default(),
+    );
+    GucRegistry::define_int_guc(
+        "typeid.validation_batch_size",
+        "Number of values of every column checked in one validation batch.",
+        "",
+        &VALIDATION_BATCH_SIZE,
+        1,
+        i32::MAX,
+        GucContext::Suset,
+        GucFlags::default(),
+    );
+    GucRegistry::define_int_guc(
+        "typeid.validation_max_clock_skew",
+        "How far in the future a TypeID timestamp may be before it's reported.",
+        "",
+        &VALIDATION_MAX_CLOCK_SKEW,
+        0,
+        i32::MAX,
+        GucContext::Suset,
+        GucFlags::UNIT_MS,
+    );
+    GucRegistry::define_string_guc(
+        "typeid.default_prefix",
+        "Prefix of the TypeIDs generated by gen_random_typeid().",
+        "Empty generates ids without a prefix.",
+        &DEFAULT_PREFIX,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+    GucRegistry::define_int_guc(
+        "typeid.test_seed",
+        "Seeds the generation of TypeIDs for reproducible tests.",
+        "Zero generates random ids at the current time. Otherwise ids are drawn from a PRNG with \
+         this seed and timestamps step by a millisecond from 2024-01-01, so sessions with the \
+         same seed generate colliding ids.",
+        &TEST_SEED,
+        0,
+        i32::MAX,
+        GucContext::Suset,
+        GucFlags::default(),
+    );
+    GucRegistry::define_int_guc(
+        "typeid.rate_limit",
+        "Maximum number of TypeIDs generated per second for a single prefix.",
+        "Zero disables the limit. Counted across all connections when typeid is in \
+         shared_preload_libraries, per connection otherwise.",
+        &RATE_LIMIT,
+        0,
+        i32::MAX,
+        GucContext::Suset,
+        GucFlags::default(),
+    );
+    GucRegistry::define_enum_guc(
+        "typeid.rate_limit_action",
+        "What happens when typeid.rate_limit is exceeded.",
+        "Either raise an error or log a warning and continue.",
+        &RATE_LIMIT_ACTION,
+        GucContext::Suset,
+        GucFlags::default(),
+    );
+}
+
+/// Reads a string setting, treating an empty value like an unset one
+pub fn string(setting: &GucSetting<Option<&'static CStr>>) -> Option<String> {
+    setting
+        .get()
+        .map(|value| value.to_string_lossy().into_owned())
+        .filter(|value| !value.is_empty())
+}
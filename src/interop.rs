@@ -0,0 +1,135 @@
+//! Datum level constructor and accessor for other extensions, which can call them through fmgr
+//! instead of going through text I/O. `include/typeid.h` describes the same layout for C.
+
+use pgrx::prelude::*;
+use pgrx::Internal;
+use uuid::Uuid;
+
+use crate::typeid::{TypeID, TypeIDPrefix};
+
+pub const TYPEID_PARTS_VERSION: u32 = 1;
+pub const TYPEID_PREFIX_MAX_LEN: usize = 63;
+
+/// Mirrors `TypeIdParts` from `include/typeid.h`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct TypeIDParts {
+    pub version: u32,
+    pub uuid: [u8; 16],
+    pub prefix_len: u32,
+    pub prefix: [u8; TYPEID_PREFIX_MAX_LEN + 1],
+}
+
+impl Default for TypeIDParts {
+    fn default() -> Self {
+        Self {
+            version: TYPEID_PARTS_VERSION,
+            uuid: [0; 16],
+            prefix_len: 0,
+            prefix: [0; TYPEID_PREFIX_MAX_LEN + 1],
+        }
+    }
+}
+
+fn check_version(parts: &TypeIDParts) {
+    if parts.version != TYPEID_PARTS_VERSION {
+        error!(
+            "unsupported TypeIdParts version {}, expected {TYPEID_PARTS_VERSION}",
+            parts.version
+        );
+    }
+}
+
+/// Builds a TypeID from a `TypeIdParts` pointer
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_construct(parts: Internal) -> Option<TypeID> {
+    let parts = unsafe { parts.get::<TypeIDParts>() }?;
+    check_version(parts);
+
+    let prefix = parts
+        .prefix
+        .get(..parts.prefix_len as usize)
+        .unwrap_or_else(|| error!("prefix_len {} is out of range", parts.prefix_len));
+    let prefix = std::str::from_utf8(prefix)
+        .ok()
+        .and_then(|prefix| TypeIDPrefix::new(prefix).ok())
+        .unwrap_or_else(|| error!("invalid prefix {:?}", String::from_utf8_lossy(prefix)));
+
+    Some(TypeID::new(prefix, Uuid::from_bytes(parts.uuid)))
+}
+
+/// Fills the `TypeIdParts` the second argument points to
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_deconstruct(typeid: TypeID, parts: Internal) {
+    let Some(parts) = (unsafe { parts.get_mut::<TypeIDParts>() }) else {
+        error!("typeid_deconstruct requires a TypeIdParts pointer");
+    };
+    check_version(parts);
+
+    let prefix = typeid.type_prefix().as_bytes();
+    parts.uuid = *typeid.uuid().as_bytes();
+    parts.prefix_len = prefix.len() as u32;
+    parts.prefix = [0; TYPEID_PREFIX_MAX_LEN + 1];
+    parts.prefix[..prefix.len()].copy_from_slice(prefix);
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    use super::TypeIDParts;
+    use crate::typeid::TypeID;
+
+    fn function_oid(signature: &str) -> pg_sys::Oid {
+        Spi::get_one_with_args::<pg_sys::Oid>(
+            "SELECT to_regprocedure($1)::oid",
+            vec![(PgBuiltInOids::TEXTOID.oid(), signature.into_datum())],
+        )
+        .unwrap()
+        .expect("function is missing")
+    }
+
+    #[pg_test]
+    fn test_typeid_construct_deconstruct() {
+        let typeid = TypeID::from_string("user_01h455vb4pex5vsknk084sn02q").unwrap();
+
+        let mut parts = TypeIDParts::default();
+        let datum = unsafe {
+            pg_sys::OidFunctionCall2Coll(
+                function_oid("typeid_deconstruct(typeid, internal)"),
+                pg_sys::InvalidOid,
+                typeid.clone().into_datum().unwrap(),
+                pg_sys::Datum::from(&mut parts as *mut TypeIDParts),
+            );
+            pg_sys::OidFunctionCall1Coll(
+                function_oid("typeid_construct(internal)"),
+                pg_sys::InvalidOid,
+                pg_sys::Datum::from(&mut parts as *mut TypeIDParts),
+            )
+        };
+
+        assert_eq!(&parts.prefix[..parts.prefix_len as usize], b"user");
+        assert_eq!(&parts.uuid, typeid.uuid().as_bytes());
+
+        let constructed = unsafe { TypeID::from_datum(datum, false) };
+        assert_eq!(constructed, Some(typeid));
+    }
+
+    #[pg_test(error = "invalid prefix \"User\"")]
+    fn test_typeid_construct_invalid_prefix() {
+        let mut parts = TypeIDParts {
+            prefix_len: 4,
+            ..Default::default()
+        };
+        parts.prefix[..4].copy_from_slice(b"User");
+
+        unsafe {
+            pg_sys::OidFunctionCall1Coll(
+                function_oid("typeid_construct(internal)"),
+                pg_sys::InvalidOid,
+                pg_sys::Datum::from(&mut parts as *mut TypeIDParts),
+            );
+        }
+    }
+}
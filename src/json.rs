@@ -0,0 +1,86 @@
+//! Structured jsonb representation of TypeIDs, for APIs preferring structured identifiers over
+//! opaque strings.
+
+use pgrx::prelude::*;
+use pgrx::JsonB;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::time::format_millis_utc;
+use crate::typeid::{TypeID, TypeIDPrefix};
+
+/// The id as `{"prefix": ..., "uuid": ..., "timestamp": ...}`, with the embedded timestamp in
+/// UTC
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_to_jsonb_parts(typeid: TypeID) -> JsonB {
+    JsonB(json!({
+        "prefix": typeid.type_prefix(),
+        "uuid": typeid.uuid().to_string(),
+        "timestamp": format_millis_utc(typeid.timestamp_millis()),
+    }))
+}
+
+/// Builds a TypeID from the `prefix` and `uuid` keys of an object as returned by
+/// `typeid_to_jsonb_parts`. The timestamp is derived from the UUID, so any `timestamp` key is
+/// ignored.
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_from_jsonb(parts: JsonB) -> TypeID {
+    let JsonB(parts) = parts;
+    from_parts(&parts)
+}
+
+/// Builds a TypeID from the `prefix` and `uuid` keys of an object
+pub(crate) fn from_parts(parts: &Value) -> TypeID {
+    let field = |name| match parts.get(name) {
+        Some(Value::String(value)) => value.as_str(),
+        Some(_) => error!("typeid key {name:?} must be a string"),
+        None => error!("typeid object is missing the {name:?} key"),
+    };
+
+    let prefix = match TypeIDPrefix::new(field("prefix")) {
+        Ok(prefix) => prefix,
+        Err(err) => error!("invalid prefix: {err}"),
+    };
+    let uuid = match Uuid::parse_str(field("uuid")) {
+        Ok(uuid) => uuid,
+        Err(err) => error!("invalid uuid: {err}"),
+    };
+
+    TypeID::new(prefix, uuid)
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+    use pgrx::JsonB;
+    use serde_json::json;
+
+    #[pg_test]
+    fn test_typeid_to_jsonb_parts() {
+        let JsonB(parts) = Spi::get_one::<JsonB>(
+            "SELECT typeid_to_jsonb_parts(uuid_to_typeid('user', '0193addd-9400-7000-8000-000000000000'))",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            parts,
+            json!({
+                "prefix": "user",
+                "uuid": "0193addd-9400-7000-8000-000000000000",
+                "timestamp": "2024-12-10T00:00:00.000Z",
+            })
+        );
+
+        let roundtrip = Spi::get_one::<bool>(
+            "SELECT typeid_from_jsonb(typeid_to_jsonb_parts(id)) = id FROM typeid_generate('user') id",
+        )
+        .unwrap();
+        assert_eq!(roundtrip, Some(true));
+    }
+
+    #[pg_test(error = "typeid object is missing the \"uuid\" key")]
+    fn test_typeid_from_jsonb_missing_uuid() {
+        Spi::run(r#"SELECT typeid_from_jsonb('{"prefix": "user"}')"#).unwrap();
+    }
+}
@@ -1,84 +1,340 @@
+pub mod advisor;
 pub mod aggregate;
+pub mod analyze;
+pub mod array;
+pub mod audit;
 pub mod base32;
+pub mod bench;
+pub mod binary;
+pub mod catalog;
+pub mod cdc;
+pub mod compat;
+pub mod datum;
+pub mod diagnostics;
+pub mod dictionary;
+pub mod estimate;
+pub mod export;
+pub mod fallback;
+pub mod fdw;
+pub mod format;
+pub mod generate;
+pub mod gist;
+pub mod guc;
+pub mod interop;
+pub mod json;
+pub mod monotonic;
+pub mod partition;
+pub mod pattern;
+pub mod pool;
+pub mod prefix;
+pub mod prefix_enum;
+pub mod rate_limit;
+pub mod reconcile;
+pub mod registry;
+pub mod reload;
+pub mod roles;
+pub mod seeded;
+pub mod spec;
+#[cfg(any(feature = "pg14", feature = "pg15", feature = "pg16"))]
+pub mod spgist;
+pub mod stream;
+#[cfg(any(feature = "pg14", feature = "pg15", feature = "pg16"))]
+pub mod subscript;
+#[cfg(not(feature = "pg11"))]
+pub mod support;
+pub mod tablesample;
+pub mod tenant;
+pub mod time;
+pub mod tokenize;
 pub mod typeid;
+pub mod typmod;
+pub mod validation;
 
+use datum::TypeIDRef;
 use pgrx::pg_extern;
+use typeid::checked_prefix;
 use typeid::TypeID;
-use typeid::TypeIDPrefix;
 use uuid::Uuid;
 
 use pgrx::prelude::*;
 
-use std::hash::{Hash, Hasher};
-
 pgrx::pg_module_magic!();
 
-#[pg_extern]
+#[pg_guard]
+pub extern "C" fn _PG_init() {
+    guc::init();
+    rate_limit::init();
+    pool::init();
+    reload::init();
+    validation::init();
+}
+
+#[pg_extern(cost = 10)]
 fn typeid_generate(prefix: &str) -> TypeID {
-    TypeID::new(TypeIDPrefix::new(prefix).unwrap(), Uuid::now_v7())
+    let type_prefix = checked_prefix(prefix);
+    rate_limit::check(prefix, 1);
+    TypeID::new(type_prefix, monotonic::now_v7())
 }
 
-#[pg_extern]
+/// Generates an id with the prefix in `typeid.default_prefix`, named after `gen_random_uuid()`.
+/// Without the setting the id has no prefix.
+#[pg_extern(cost = 10)]
+fn gen_random_typeid() -> TypeID {
+    let prefix = guc::string(&guc::DEFAULT_PREFIX).unwrap_or_default();
+    typeid_generate(&prefix)
+}
+
+/// Generates an id with a random UUIDv4, for ids that must not reveal when they were created.
+/// They don't sort by creation time, and have no timestamp for the time functions to read.
+#[pg_extern(cost = 10)]
+fn typeid_generate_v4(prefix: &str) -> TypeID {
+    let type_prefix = checked_prefix(prefix);
+    rate_limit::check(prefix, 1);
+    TypeID::new(
+        type_prefix,
+        uuid::Builder::from_random_bytes(seeded::random()).into_uuid(),
+    )
+}
+
+/// Bytes of application data fitting into the 74 bits of a UUIDv8 following its timestamp
+const V8_CUSTOM_BYTES: usize = 9;
+
+/// A UUIDv8 in the layout of RFC 9562, with the Unix timestamp in milliseconds in the first 48 bits
+/// like a UUIDv7. `custom` fills the remaining 74 bits around the version and variant from the
+/// most significant bit on, and `random` the bits after it.
+fn v8_uuid(millis: u64, custom: &[u8], random: u128) -> Uuid {
+    const CUSTOM_BITS: u32 = 74;
+    let custom_len = custom.len() as u32 * 8;
+    let custom = custom
+        .iter()
+        .fold(0u128, |bits, byte| bits << 8 | *byte as u128);
+    let bits =
+        (custom << (CUSTOM_BITS - custom_len)) | (random & ((1 << (CUSTOM_BITS - custom_len)) - 1));
+
+    Uuid::from_u128(
+        ((millis & 0xffff_ffff_ffff) as u128) << 80
+            | 0x8 << 76
+            | (bits >> 62) << 64
+            | 0b10 << 62
+            | (bits & ((1 << 62) - 1)),
+    )
+}
+
+/// Generates an id with a UUIDv8 embedding up to 9 bytes of application data, e.g. a region code,
+/// after the timestamp, which keeps ids sorting by creation time
+#[pg_extern(cost = 10)]
+fn typeid_generate_v8(prefix: &str, custom: &[u8]) -> TypeID {
+    let type_prefix = checked_prefix(prefix);
+    if custom.len() > V8_CUSTOM_BYTES {
+        ereport!(
+            ERROR,
+            PgSqlErrorCode::ERRCODE_INVALID_PARAMETER_VALUE,
+            format!(
+                "custom data of a UUIDv8 is at most {V8_CUSTOM_BYTES} bytes, got {}",
+                custom.len()
+            )
+        );
+    }
+    rate_limit::check(prefix, 1);
+
+    let millis = seeded::unix_millis();
+    TypeID::new(type_prefix, v8_uuid(millis, custom, seeded::random()))
+}
+
+#[pg_extern(immutable, parallel_safe)]
 fn typeid_to_uuid(typeid: TypeID) -> pgrx::Uuid {
     pgrx::Uuid::from_bytes(*typeid.uuid().as_bytes())
 }
 
-#[pg_extern]
+#[pg_extern(immutable, parallel_safe)]
 fn uuid_to_typeid(prefix: &str, uuid: pgrx::Uuid) -> TypeID {
-    TypeID::new(
-        TypeIDPrefix::new(prefix).unwrap(),
-        Uuid::from_slice(uuid.as_bytes()).unwrap(),
+    TypeID::new(checked_prefix(prefix), Uuid::from_bytes(*uuid.as_bytes()))
+}
+
+/// Like `uuid_to_typeid`, but only accepts UUIDs of the given version, e.g. to keep random v4 UUIDs
+/// out of columns relying on the time ordering of v7
+#[pg_extern(immutable, parallel_safe)]
+fn uuid_to_typeid_strict(prefix: &str, uuid: pgrx::Uuid, version: default!(i32, 7)) -> TypeID {
+    let prefix = checked_prefix(prefix);
+    let uuid = Uuid::from_bytes(*uuid.as_bytes());
+    let actual = uuid.get_version_num();
+    if actual as i32 != version {
+        ereport!(
+            ERROR,
+            PgSqlErrorCode::ERRCODE_INVALID_PARAMETER_VALUE,
+            format!("uuid {uuid} is version {actual}, expected version {version}")
+        );
+    }
+    TypeID::new(prefix, uuid)
+}
+
+/// Version of the UUID of the id, e.g. to find imported ids wrapping random v4 UUIDs, which
+/// don't sort by creation time
+#[pg_extern(immutable, parallel_safe, requires = [TypeID])]
+fn typeid_uuid_version(typeid: TypeIDRef<'_>) -> i32 {
+    Uuid::from_bytes(*typeid.uuid_bytes()).get_version_num() as i32
+}
+
+// All the parts of an id in a single call, for reporting and debugging views. Only UUIDv7 embed a
+// creation time, other versions have a NULL `created_at`.
+extension_sql!(
+    r#"
+    CREATE FUNCTION typeid_unpack(
+        id typeid,
+        OUT prefix text,
+        OUT uuid uuid,
+        OUT created_at timestamptz,
+        OUT uuid_version integer
     )
+    STRICT IMMUTABLE PARALLEL SAFE
+    LANGUAGE sql
+    AS $$
+        SELECT @extschema@.typeid_prefix(id), @extschema@.typeid_to_uuid(id),
+               CASE WHEN version = 7 THEN @extschema@.typeid_timestamp(id) END, version
+        FROM (SELECT @extschema@.typeid_uuid_version(id) AS version) parts
+    $$;
+    "#,
+    name = "create_typeid_unpack",
+    requires = [
+        typeid_to_uuid,
+        prefix::typeid_prefix,
+        time::typeid_timestamp
+    ],
+);
+
+/// Whether the text is a valid TypeID, e.g. for CHECK constraints on text columns. Cheaper than
+/// casting, as nothing is allocated or decoded.
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_is_valid(input: &str) -> bool {
+    TypeID::is_valid(input)
 }
 
-#[pg_extern]
-fn typeid_cmp(a: TypeID, b: TypeID) -> i32 {
+/// Whether the text is a valid TypeID prefix, e.g. for CHECK constraints on prefix columns
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_is_valid_prefix(prefix: &str) -> bool {
+    typeid::TypeIDPrefix::new(prefix).is_ok()
+}
+
+/// The text as a typeid, NULL when it isn't a valid one
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_try_parse(input: &str) -> Option<TypeID> {
+    TypeID::from_string(input).ok()
+}
+
+#[pg_extern(immutable, parallel_safe, requires = [TypeID])]
+fn typeid_cmp(a: TypeIDRef<'_>, b: TypeIDRef<'_>) -> i32 {
     a.cmp(&b) as i32
 }
 
-#[pg_extern]
-fn typeid_lt(a: TypeID, b: TypeID) -> bool {
-    typeid_cmp(a, b) < 0
+#[pg_extern(immutable, parallel_safe, requires = [TypeID])]
+fn typeid_lt(a: TypeIDRef<'_>, b: TypeIDRef<'_>) -> bool {
+    a < b
 }
 
-#[pg_extern]
-fn typeid_le(a: TypeID, b: TypeID) -> bool {
-    typeid_cmp(a, b) <= 0
+#[pg_extern(immutable, parallel_safe, requires = [TypeID])]
+fn typeid_le(a: TypeIDRef<'_>, b: TypeIDRef<'_>) -> bool {
+    a <= b
 }
 
-#[pg_extern]
-fn typeid_eq(a: TypeID, b: TypeID) -> bool {
-    typeid_cmp(a, b) == 0
+#[pg_extern(immutable, parallel_safe, requires = [TypeID])]
+fn typeid_eq(a: TypeIDRef<'_>, b: TypeIDRef<'_>) -> bool {
+    a == b
 }
 
-#[pg_extern]
-fn typeid_ge(a: TypeID, b: TypeID) -> bool {
-    typeid_cmp(a, b) >= 0
+#[pg_extern(immutable, parallel_safe, requires = [TypeID])]
+fn typeid_ge(a: TypeIDRef<'_>, b: TypeIDRef<'_>) -> bool {
+    a >= b
 }
 
-#[pg_extern]
-fn typeid_gt(a: TypeID, b: TypeID) -> bool {
-    typeid_cmp(a, b) > 0
+#[pg_extern(immutable, parallel_safe, requires = [TypeID])]
+fn typeid_gt(a: TypeIDRef<'_>, b: TypeIDRef<'_>) -> bool {
+    a > b
 }
 
-#[pg_extern]
-fn typeid_ne(a: TypeID, b: TypeID) -> bool {
-    typeid_cmp(a, b) != 0
+#[pg_extern(immutable, parallel_safe, requires = [TypeID])]
+fn typeid_ne(a: TypeIDRef<'_>, b: TypeIDRef<'_>) -> bool {
+    a != b
 }
 
-#[pg_extern]
-fn typeid_hash(typeid: TypeID) -> i32 {
-    let mut hasher = gxhash::GxHasher::default();
-    typeid.hash(&mut hasher);
-    hasher.finish() as i32
+/// Constrains the id to `[lower, upper]`, a NULL bound leaves that side unbounded
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_clamp(typeid: TypeID, lower: Option<TypeID>, upper: Option<TypeID>) -> TypeID {
+    match (lower, upper) {
+        (Some(lower), Some(upper)) if lower > upper => {
+            error!("lower bound {lower} must not be greater than upper bound {upper}")
+        }
+        (Some(lower), _) if typeid < lower => lower,
+        (_, Some(upper)) if typeid > upper => upper,
+        _ => typeid,
+    }
 }
 
-#[pg_extern]
-fn typeid_hash_extended(typeid: TypeID, seed: i64) -> i64 {
-    let mut hasher = gxhash::GxHasher::with_seed(seed);
+#[cfg(not(feature = "pg11"))]
+extern "C" {
+    // The hash function of the built-in types, from `common/hashfn.h`
+    fn hash_bytes_extended(k: *const u8, keylen: std::os::raw::c_int, seed: u64) -> u64;
+}
+
+#[cfg(feature = "pg11")]
+unsafe fn hash_bytes_extended(k: *const u8, keylen: std::os::raw::c_int, seed: u64) -> u64 {
+    extern "C" {
+        // Renamed in Postgres 12, when it started returning the hash itself
+        fn hash_any_extended(k: *const u8, keylen: std::os::raw::c_int, seed: u64)
+            -> pg_sys::Datum;
+    }
+    hash_any_extended(k, keylen, seed).value() as u64
+}
 
-    typeid.hash(&mut hasher);
-    hasher.finish() as i64
+/// Hash of the bytes of a value as Postgres expects it from extended hash functions: seed 0 gives
+/// the hash of non-extended ones in its low 32 bits, other seeds give unrelated hashes. It's the
+/// hash function of the built-in types, which gives the same hashes on every platform of the same
+/// byte order, so hash indexes and hash partitions survive physical replicas and pg_upgrade.
+fn seeded_hash(key: &[u8], seed: i64) -> u64 {
+    unsafe { hash_bytes_extended(key.as_ptr(), key.len() as std::os::raw::c_int, seed as u64) }
+}
+
+/// Hashes the prefix and UUID in the binary format, whichever storage format the datum has. Hash
+/// joins and aggregates call this for every row, so values in the current format are hashed in
+/// place.
+pub(crate) fn typeid_seeded_hash(typeid: TypeIDRef<'_>, seed: i64) -> u64 {
+    typeid.with_encoded(|encoded| seeded_hash(encoded, seed))
+}
+
+#[pg_extern(immutable, parallel_safe, requires = [TypeID])]
+fn typeid_hash(typeid: TypeIDRef<'_>) -> i32 {
+    typeid_seeded_hash(typeid, 0) as i32
+}
+
+#[pg_extern(immutable, parallel_safe, requires = [TypeID])]
+fn typeid_hash_extended(typeid: TypeIDRef<'_>, seed: i64) -> i64 {
+    typeid_seeded_hash(typeid, seed) as i64
+}
+
+/// The splitmix64 finalizer, a fixed mix of 64 bits which doesn't depend on the platform
+pub(crate) fn splitmix64(value: u64) -> u64 {
+    let mut hash = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^ (hash >> 31)
+}
+
+/// A pseudo-random sort key of the id, the same for the same id and seed on every platform and
+/// version, e.g. for reproducible samples with `ORDER BY typeid_shuffle_order(id, 42) LIMIT 100`
+/// or A/B buckets with `typeid_shuffle_order(id, 7) & 1`
+#[pg_extern(immutable, parallel_safe, requires = [TypeID])]
+fn typeid_shuffle_order(typeid: TypeIDRef<'_>, seed: i64) -> i64 {
+    shuffle_order(typeid.type_prefix(), typeid.uuid_bytes(), seed)
+}
+
+fn shuffle_order(prefix: &str, uuid: &[u8; 16], seed: i64) -> i64 {
+    let prefix = prefix.as_bytes();
+    let mut hash = splitmix64(seed as u64 ^ prefix.len() as u64);
+    for chunk in prefix.chunks(8).chain(uuid.chunks(8)) {
+        let mut word = [0; 8];
+        word[..chunk.len()].copy_from_slice(chunk);
+        hash = splitmix64(hash ^ u64::from_le_bytes(word));
+    }
+    hash as i64
 }
 
 extension_sql! {
@@ -86,13 +342,17 @@ r#"
    CREATE OPERATOR < (
         LEFTARG = typeid,
         RIGHTARG = typeid,
-        PROCEDURE = typeid_lt
+        PROCEDURE = typeid_lt,
+        RESTRICT = scalarltsel,
+        JOIN = scalarltjoinsel
     );
 
     CREATE OPERATOR <= (
         LEFTARG = typeid,
         RIGHTARG = typeid,
-        PROCEDURE = typeid_le
+        PROCEDURE = typeid_le,
+        RESTRICT = scalarlesel,
+        JOIN = scalarlejoinsel
     );
 
     CREATE OPERATOR = (
@@ -101,6 +361,8 @@ r#"
         PROCEDURE = typeid_eq,
         COMMUTATOR = '=',
         NEGATOR = '<>',
+        RESTRICT = eqsel,
+        JOIN = eqjoinsel,
         HASHES,
         MERGES
     );
@@ -108,19 +370,27 @@ r#"
     CREATE OPERATOR >= (
         LEFTARG = typeid,
         RIGHTARG = typeid,
-        PROCEDURE = typeid_ge
+        PROCEDURE = typeid_ge,
+        RESTRICT = scalargesel,
+        JOIN = scalargejoinsel
     );
 
     CREATE OPERATOR > (
         LEFTARG = typeid,
         RIGHTARG = typeid,
-        PROCEDURE = typeid_gt
+        PROCEDURE = typeid_gt,
+        RESTRICT = scalargtsel,
+        JOIN = scalargtjoinsel
     );
 
     CREATE OPERATOR <> (
         LEFTARG = typeid,
         RIGHTARG = typeid,
-        PROCEDURE = typeid_ne
+        PROCEDURE = typeid_ne,
+        COMMUTATOR = '<>',
+        NEGATOR = '=',
+        RESTRICT = neqsel,
+        JOIN = neqjoinsel
     );
 
     CREATE OPERATOR CLASS typeid_ops DEFAULT FOR TYPE typeid USING btree AS
@@ -139,13 +409,115 @@ r#"
             FUNCTION 2 typeid_hash_extended(typeid, bigint);
     "#,
   name = "create_typeid_operator_class",
-  finalize,
+  requires = [
+    typeid_lt,
+    typeid_le,
+    typeid_eq,
+    typeid_ge,
+    typeid_gt,
+    typeid_ne,
+    typeid_cmp,
+    typeid_hash,
+    typeid_hash_extended,
+  ],
 }
 
+// contrib/bloom sets the bits of each column by the hash function of its operator class, which
+// can only be created once bloom is installed. It's created along with typeid when bloom is
+// installed first, otherwise by `typeid_enable_bloom()` afterwards, which adds it to the extension
+// like the other operator classes. Creating operator classes takes a superuser.
+extension_sql!(
+    r#"
+    CREATE FUNCTION typeid_enable_bloom() RETURNS boolean
+    LANGUAGE plpgsql
+    SET search_path FROM CURRENT
+    AS $$
+    DECLARE
+        opclass oid;
+    BEGIN
+        IF NOT EXISTS (SELECT FROM pg_catalog.pg_am WHERE amname = 'bloom') THEN
+            RAISE EXCEPTION 'bloom is not installed'
+                USING HINT = 'Run CREATE EXTENSION bloom first.';
+        END IF;
+        IF EXISTS (
+            SELECT FROM pg_catalog.pg_opclass c JOIN pg_catalog.pg_am a ON a.oid = c.opcmethod
+            WHERE a.amname = 'bloom' AND c.opcintype = 'typeid'::regtype
+        ) THEN
+            RETURN false;
+        END IF;
+
+        CREATE OPERATOR CLASS typeid_ops DEFAULT FOR TYPE typeid USING bloom AS
+            OPERATOR 1 = (typeid, typeid),
+            FUNCTION 1 typeid_hash(typeid);
+
+        -- Objects created while installing typeid are part of it already
+        SELECT c.oid INTO opclass
+        FROM pg_catalog.pg_opclass c JOIN pg_catalog.pg_am a ON a.oid = c.opcmethod
+        WHERE a.amname = 'bloom' AND c.opcintype = 'typeid'::regtype;
+        IF NOT EXISTS (
+            SELECT FROM pg_catalog.pg_depend
+            WHERE classid = 'pg_catalog.pg_opclass'::regclass AND objid = opclass AND deptype = 'e'
+        ) THEN
+            ALTER EXTENSION typeid ADD OPERATOR CLASS typeid_ops USING bloom;
+        END IF;
+        RETURN true;
+    END
+    $$;
+
+    DO $$
+    BEGIN
+        IF EXISTS (SELECT FROM pg_catalog.pg_am WHERE amname = 'bloom') THEN
+            PERFORM typeid_enable_bloom();
+        END IF;
+    END
+    $$;
+    "#,
+    name = "create_typeid_bloom_operator_class",
+    requires = ["create_typeid_operator_class"],
+);
+
+// Comparison, hashing and extraction functions can't fail or reveal anything about their
+// arguments, which lets the planner push quals through security barrier views and RLS policies
+extension_sql!(
+    r#"
+    ALTER FUNCTION typeid_cmp(typeid, typeid) LEAKPROOF;
+    ALTER FUNCTION typeid_lt(typeid, typeid) LEAKPROOF;
+    ALTER FUNCTION typeid_le(typeid, typeid) LEAKPROOF;
+    ALTER FUNCTION typeid_eq(typeid, typeid) LEAKPROOF;
+    ALTER FUNCTION typeid_ge(typeid, typeid) LEAKPROOF;
+    ALTER FUNCTION typeid_gt(typeid, typeid) LEAKPROOF;
+    ALTER FUNCTION typeid_ne(typeid, typeid) LEAKPROOF;
+    ALTER FUNCTION typeid_hash(typeid) LEAKPROOF;
+    ALTER FUNCTION typeid_hash_extended(typeid, bigint) LEAKPROOF;
+    ALTER FUNCTION typeid_to_uuid(typeid) LEAKPROOF;
+    ALTER FUNCTION typeid_eq_any(typeid, typeid[]) LEAKPROOF;
+    ALTER FUNCTION typeid_tenant_of(typeid) LEAKPROOF;
+    ALTER FUNCTION typeid_shard(typeid) LEAKPROOF;
+    ALTER FUNCTION typeid_prefix(typeid) LEAKPROOF;
+    "#,
+    name = "mark_leakproof_functions",
+    requires = [
+        typeid_cmp,
+        typeid_lt,
+        typeid_le,
+        typeid_eq,
+        typeid_ge,
+        typeid_gt,
+        typeid_ne,
+        typeid_hash,
+        typeid_hash_extended,
+        typeid_to_uuid,
+        array::typeid_eq_any,
+        tenant::typeid_tenant_of,
+        tenant::typeid_shard,
+        prefix::typeid_prefix,
+    ],
+);
+
 /// Generate a UUID v7, producing a Postgres uuid object
 #[pg_extern]
 fn typeid_uuid_generate_v7() -> pgrx::Uuid {
-    pgrx::Uuid::from_bytes(*Uuid::now_v7().as_bytes())
+    pgrx::Uuid::from_bytes(*monotonic::now_v7().as_bytes())
 }
 
 #[cfg(any(test, feature = "pg_test"))]
@@ -155,6 +527,40 @@ mod tests {
     use pgrx::prelude::*;
     use uuid::Uuid;
 
+    #[test]
+    fn test_splitmix64() {
+        // Reference values of the splitmix64 generator seeded with 0
+        assert_eq!(crate::splitmix64(0), 0xe220_a839_7b1d_cdaf);
+        assert_eq!(
+            crate::splitmix64(0x9e37_79b9_7f4a_7c15),
+            0x6e78_9e6a_a1b9_65f4
+        );
+
+        // Sort keys must not change between versions and platforms
+        let typeid = TypeID::from_string("user_01h455vb4pex5vsknk084sn02q").unwrap();
+        assert_eq!(
+            crate::shuffle_order(typeid.type_prefix(), typeid.uuid().as_bytes(), 42),
+            -3_298_799_255_692_707_960
+        );
+    }
+
+    #[pg_test]
+    fn test_typeid_shuffle_order() {
+        let (stable, reordered, seeded) = Spi::get_three::<bool, bool, bool>(
+            "WITH ids AS (SELECT typeid_generate_batch('user', 1000) AS id)
+             SELECT
+                bool_and(typeid_shuffle_order(id, 42) = typeid_shuffle_order(id::text::typeid, 42)),
+                array_agg(id ORDER BY typeid_shuffle_order(id, 42)) <> array_agg(id ORDER BY id),
+                array_agg(id ORDER BY typeid_shuffle_order(id, 42)) <> array_agg(id ORDER BY typeid_shuffle_order(id, 43))
+             FROM ids",
+        )
+        .unwrap();
+        assert_eq!(
+            (stable, reordered, seeded),
+            (Some(true), Some(true), Some(true))
+        );
+    }
+
     #[pg_test]
     fn test_typeid_generate() {
         let typeid = crate::typeid_generate("test");
@@ -172,21 +578,397 @@ mod tests {
     }
 
     #[pg_test]
-    fn test_hashing() {
-        use crate::typeid_hash;
-        use crate::TypeID;
+    fn test_uuid_to_typeid_strict() {
+        let typeid = Spi::get_one::<String>(
+            "SELECT uuid_to_typeid_strict('user', '0193addd-9400-7000-8000-000000000000')::text",
+        );
+        assert_eq!(
+            typeid,
+            Ok(Some("user_01jepxv500e008000000000000".to_string()))
+        );
+
+        let v4 = Spi::get_one::<bool>(
+            "SELECT uuid_to_typeid_strict('user', '4b8e5c2a-9d3f-4e1b-8a7c-6f0d2e9b1c3a', 4)
+                    = uuid_to_typeid('user', '4b8e5c2a-9d3f-4e1b-8a7c-6f0d2e9b1c3a')",
+        );
+        assert_eq!(v4, Ok(Some(true)));
+    }
+
+    #[pg_test]
+    fn test_typeid_try_parse() {
+        let parsed = Spi::get_one::<Vec<Option<String>>>(
+            "SELECT array_agg(typeid_try_parse(t)::text ORDER BY n)
+             FROM unnest(ARRAY[
+                 'user_01h455vb4pex5vsknk084sn02q', 'user_nope', '01h455vb4pex5vsknk084sn02q', '', NULL
+             ]) WITH ORDINALITY u(t, n)",
+        );
+        assert_eq!(
+            parsed,
+            Ok(Some(vec![
+                Some("user_01h455vb4pex5vsknk084sn02q".to_string()),
+                None,
+                Some("01h455vb4pex5vsknk084sn02q".to_string()),
+                None,
+                None
+            ]))
+        );
+    }
+
+    #[pg_test]
+    fn test_input_errors() {
+        Spi::run(
+            "CREATE FUNCTION error_of(query text) RETURNS text[] LANGUAGE plpgsql AS $$
+            DECLARE
+                state text;
+                detail text;
+                hint text;
+            BEGIN
+                EXECUTE query;
+                RETURN NULL;
+            EXCEPTION WHEN OTHERS THEN
+                GET STACKED DIAGNOSTICS
+                    state = RETURNED_SQLSTATE, detail = PG_EXCEPTION_DETAIL, hint = PG_EXCEPTION_HINT;
+                RETURN ARRAY[state, detail, hint];
+            END
+            $$",
+        )
+        .unwrap();
 
-        let id = TypeID::from_string("qual_01j1acv2aeehk8hcapaw7qyjvq").unwrap();
-        let id2 = TypeID::from_string("qual_01j1acv2aeehk8hcapaw7qyjvq").unwrap();
+        let suffix_hint = "Typeids look like user_01h455vb4pex5vsknk084sn02q, or just the suffix without a prefix.";
+        let prefix_hint = "Prefixes are up to 63 lowercase letters a-z and underscores, not starting or ending with an underscore.";
+        for (query, expected) in [
+            (
+                "SELECT 'user_nope'::typeid",
+                [
+                    "22P02",
+                    "The suffix must be 26 base32 characters, the first one 0 to 7.",
+                    suffix_hint,
+                ],
+            ),
+            (
+                "SELECT '_01h455vb4pex5vsknk084sn02q'::typeid",
+                [
+                    "22P02",
+                    "The prefix before the underscore is empty.",
+                    suffix_hint,
+                ],
+            ),
+            (
+                "SELECT 'User_01h455vb4pex5vsknk084sn02q'::typeid",
+                ["22P02", "Prefix \"User\" is invalid.", prefix_hint],
+            ),
+            (
+                "SELECT typeid_generate('User')",
+                ["22023", "Prefix \"User\" is invalid.", prefix_hint],
+            ),
+            (
+                "SELECT uuid_to_typeid('user_', '0193addd-9400-7000-8000-000000000000')",
+                ["22023", "Prefix \"user_\" is invalid.", prefix_hint],
+            ),
+        ] {
+            let error = Spi::get_one_with_args::<Vec<String>>(
+                "SELECT error_of($1)",
+                vec![(PgBuiltInOids::TEXTOID.oid(), query.into_datum())],
+            );
+            assert_eq!(
+                error,
+                Ok(Some(expected.map(String::from).to_vec())),
+                "{query}"
+            );
+        }
+    }
+
+    #[pg_test(error = "invalid input syntax for type typeid: \"user_nope\"")]
+    fn test_input_error_message() {
+        Spi::run("SELECT 'user_nope'::typeid").unwrap();
+    }
+
+    // Quoted like Postgres does, without escaping what's inside
+    #[pg_test(error = "invalid input syntax for type typeid: \"us\"er_ü\"")]
+    fn test_input_error_message_quoting() {
+        Spi::run("SELECT 'us\"er_ü'::typeid").unwrap();
+    }
+
+    #[pg_test]
+    fn test_gen_random_typeid() {
+        let prefix = Spi::get_one::<String>("SELECT typeid_prefix(gen_random_typeid())");
+        assert_eq!(prefix, Ok(Some("".to_string())));
+
+        Spi::run("SET typeid.default_prefix = 'user'").unwrap();
+        Spi::run("CREATE TABLE users (id typeid PRIMARY KEY DEFAULT gen_random_typeid())").unwrap();
+        Spi::run("INSERT INTO users DEFAULT VALUES").unwrap();
+        let prefix = Spi::get_one::<String>("SELECT typeid_prefix(id) FROM users");
+        assert_eq!(prefix, Ok(Some("user".to_string())));
+    }
+
+    #[pg_test(error = "invalid typeid prefix \"User\"")]
+    fn test_gen_random_typeid_invalid_prefix() {
+        Spi::run("SET typeid.default_prefix = 'User'").unwrap();
+        Spi::run("SELECT gen_random_typeid()").unwrap();
+    }
+
+    #[pg_test]
+    fn test_typeid_generate_v4() {
+        let (prefix, version, distinct) = Spi::get_three::<bool, bool, i64>(
+            "SELECT bool_and(typeid_prefix(id) = 'secret'), bool_and(typeid_uuid_version(id) = 4),
+                    count(DISTINCT id)
+             FROM (SELECT typeid_generate_v4('secret') id FROM generate_series(1, 100)) ids",
+        )
+        .unwrap();
+        assert_eq!(
+            (prefix, version, distinct),
+            (Some(true), Some(true), Some(100))
+        );
+
+        let variant = Spi::get_one::<bool>(
+            "SELECT get_byte(uuid_send(typeid_to_uuid(typeid_generate_v4(''))), 8) >> 6 = 2",
+        );
+        assert_eq!(variant, Ok(Some(true)));
+    }
+
+    #[test]
+    fn test_v8_uuid() {
+        let uuid = crate::v8_uuid(0x0193_addd_9400, &[0xab, 0xcd, 0xef], 0);
+        assert_eq!(uuid.get_version_num(), 8);
+        assert_eq!(uuid.to_string(), "0193addd-9400-8abc-b7bc-000000000000");
+
+        let full = crate::v8_uuid(0x0193_addd_9400, &[0xff; 9], 0);
+        assert_eq!(full.to_string(), "0193addd-9400-8fff-bfff-fffffffffffc");
+
+        let random = crate::v8_uuid(0x0193_addd_9400, &[], u128::MAX);
+        assert_eq!(random.to_string(), "0193addd-9400-8fff-bfff-ffffffffffff");
+    }
+
+    #[pg_test]
+    fn test_typeid_generate_v8() {
+        let (version, custom, ordered) = Spi::get_three::<i32, String, bool>(
+            "WITH ids AS (
+                SELECT typeid_generate_v8('event', '\\x01'::bytea) id
+                UNION ALL SELECT typeid_generate_v8('event', '\\x01'::bytea)
+             )
+             SELECT min(typeid_uuid_version(id)),
+                    min(substr(typeid_to_uuid(id)::text, 15, 4)),
+                    bool_and(typeid_timestamp(id) BETWEEN now() - interval '1 minute' AND clock_timestamp())
+             FROM ids",
+        )
+        .unwrap();
+        assert_eq!(version, Some(8));
+        assert!(custom.unwrap().starts_with("801"));
+        assert_eq!(ordered, Some(true));
+    }
+
+    #[pg_test(error = "custom data of a UUIDv8 is at most 9 bytes, got 10")]
+    fn test_typeid_generate_v8_too_long() {
+        Spi::run("SELECT typeid_generate_v8('event', '\\x00112233445566778899'::bytea)").unwrap();
+    }
+
+    #[pg_test]
+    fn test_typeid_uuid_version() {
+        let versions = Spi::get_one::<Vec<i32>>(
+            "SELECT ARRAY[
+                typeid_uuid_version(typeid_generate('user')),
+                typeid_uuid_version(uuid_to_typeid('user', '4b8e5c2a-9d3f-4e1b-8a7c-6f0d2e9b1c3a')),
+                typeid_uuid_version(uuid_to_typeid('user', '00000000-0000-0000-0000-000000000000'))
+             ]",
+        );
+        assert_eq!(versions, Ok(Some(vec![7, 4, 0])));
+    }
+
+    #[pg_test]
+    fn test_typeid_unpack() {
+        let parts = Spi::get_three::<String, String, bool>(
+            "SELECT prefix, uuid::text, created_at = '2024-12-10 00:00:00+00'
+             FROM typeid_unpack(uuid_to_typeid('event', '0193addd-9400-7000-8000-000000000000'))",
+        );
+        assert_eq!(
+            parts,
+            Ok((
+                Some("event".to_string()),
+                Some("0193addd-9400-7000-8000-000000000000".to_string()),
+                Some(true)
+            ))
+        );
+
+        let v4 = Spi::get_two::<i32, bool>(
+            "SELECT (p).uuid_version, (p).created_at IS NULL
+             FROM (SELECT typeid_unpack(uuid_to_typeid('', '4b8e5c2a-9d3f-4e1b-8a7c-6f0d2e9b1c3a')) p) v4",
+        );
+        assert_eq!(v4, Ok((Some(4), Some(true))));
+    }
 
-        let hash = typeid_hash(id);
-        let hash2 = typeid_hash(id2);
-        println!("UUID: {:?}", hash);
+    #[pg_test(error = "uuid 4b8e5c2a-9d3f-4e1b-8a7c-6f0d2e9b1c3a is version 4, expected version 7")]
+    fn test_uuid_to_typeid_strict_wrong_version() {
+        Spi::run("SELECT uuid_to_typeid_strict('user', '4b8e5c2a-9d3f-4e1b-8a7c-6f0d2e9b1c3a')")
+            .unwrap();
+    }
+
+    #[pg_test]
+    fn test_typeid_clamp() {
+        use crate::typeid_clamp;
 
+        let lower = TypeID::from_string("user_01h455vb4pex5vsknk084sn02q").unwrap();
+        let upper = TypeID::from_string("user_01j1acv2aeehk8hcapaw7qyjvq").unwrap();
+        let inside = TypeID::from_string("user_01hzzzzzzzzzzzzzzzzzzzzzzz").unwrap();
+        let below = TypeID::from_string("user_00000000000000000000000000").unwrap();
+
+        assert_eq!(
+            typeid_clamp(inside.clone(), Some(lower.clone()), Some(upper.clone())),
+            inside
+        );
+        assert_eq!(
+            typeid_clamp(below.clone(), Some(lower.clone()), Some(upper.clone())),
+            lower
+        );
+        assert_eq!(
+            typeid_clamp(below.clone(), None, Some(upper.clone())),
+            below
+        );
+        assert_eq!(
+            typeid_clamp(upper.clone(), None, Some(lower.clone())),
+            lower
+        );
+    }
+
+    #[pg_test(
+        error = "lower bound user_01j1acv2aeehk8hcapaw7qyjvq must not be greater than upper bound user_01h455vb4pex5vsknk084sn02q"
+    )]
+    fn test_typeid_clamp_inverted_bounds() {
+        Spi::run("SELECT typeid_clamp(typeid_generate('user'), 'user_01j1acv2aeehk8hcapaw7qyjvq', 'user_01h455vb4pex5vsknk084sn02q')").unwrap();
+    }
+
+    #[pg_test]
+    fn test_hashing() {
+        let hash = Spi::get_one::<i32>("SELECT typeid_hash('qual_01j1acv2aeehk8hcapaw7qyjvq')");
+        let hash2 = Spi::get_one::<i32>("SELECT typeid_hash('qual_01j1acv2aeehk8hcapaw7qyjvq')");
         assert_eq!(
             hash, hash2,
             "Hashes should be consistent for the same input"
         );
+
+        // The hash of the binary format, the same as text hashes the same bytes with. The UUID
+        // is chosen for its bytes to be the characters 0-9a-f.
+        let (same, same_extended) = Spi::get_two::<bool, bool>(
+            "WITH t AS (SELECT uuid_to_typeid('user', '30313233-3435-3637-3839-616263646566') AS id,
+                               chr(4) || 'user0123456789abcdef' COLLATE \"C\" AS bytes)
+             SELECT typeid_hash(id) = hashtext(bytes),
+                    typeid_hash_extended(id, 42) = hashtextextended(bytes, 42)
+             FROM t",
+        )
+        .unwrap();
+        assert_eq!((same, same_extended), (Some(true), Some(true)));
+    }
+
+    #[pg_test]
+    fn test_typeid_bloom_index() {
+        // contrib may not be installed where the tests run
+        let available = Spi::get_one::<bool>(
+            "SELECT EXISTS (SELECT FROM pg_available_extensions WHERE name = 'bloom')",
+        );
+        if available != Ok(Some(true)) {
+            return;
+        }
+
+        // Installed after typeid, so the operator class is created by the helper
+        Spi::run("CREATE EXTENSION bloom").unwrap();
+        assert_eq!(
+            Spi::get_one::<bool>("SELECT typeid_enable_bloom()"),
+            Ok(Some(true))
+        );
+        assert_eq!(
+            Spi::get_one::<bool>("SELECT typeid_enable_bloom()"),
+            Ok(Some(false))
+        );
+        let member = Spi::get_one::<bool>(
+            "SELECT EXISTS (
+                SELECT FROM pg_depend d JOIN pg_opclass c ON c.oid = d.objid
+                WHERE d.classid = 'pg_opclass'::regclass AND d.deptype = 'e'
+                    AND c.opcintype = 'typeid'::regtype
+                    AND c.opcmethod = (SELECT oid FROM pg_am WHERE amname = 'bloom')
+            )",
+        );
+        assert_eq!(member, Ok(Some(true)));
+        Spi::run("CREATE TABLE links (source_id typeid, target_id typeid)").unwrap();
+        Spi::run(
+            "INSERT INTO links
+             SELECT typeid_generate('page'), typeid_generate('page') FROM generate_series(1, 1000)",
+        )
+        .unwrap();
+        Spi::run(
+            "INSERT INTO links VALUES ('page_01h455vb4pex5vsknk084sn02q', 'page_01h455vb4pex5vsknk084sn02r')",
+        )
+        .unwrap();
+        Spi::run("CREATE INDEX links_bloom ON links USING bloom (source_id, target_id)").unwrap();
+        Spi::run("SET enable_seqscan = off").unwrap();
+
+        let count = Spi::get_one::<i64>(
+            "SELECT count(*) FROM links WHERE target_id = 'page_01h455vb4pex5vsknk084sn02r'",
+        );
+        assert_eq!(count, Ok(Some(1)));
+    }
+
+    #[pg_test]
+    fn test_seeded_hash() {
+        let ids = (0..4096u128)
+            .map(|n| {
+                let uuid = Uuid::from_u128((0x0190_0000_0000 + n) << 80 | n);
+                crate::binary::encode("event", uuid.as_bytes())
+            })
+            .collect::<Vec<_>>();
+
+        for id in &ids {
+            // Seed 0 gives the hash of `typeid_hash` in the low 32 bits
+            let typeid = crate::TypeIDRef::parse(id).unwrap();
+            assert_eq!(crate::seeded_hash(id, 0) as i32, crate::typeid_hash(typeid));
+        }
+
+        // Consecutive ids spread evenly over buckets, for any seed
+        for seed in [0, 1, 2, i64::MIN] {
+            let mut buckets = [0; 16];
+            for id in &ids {
+                buckets[(crate::seeded_hash(id, seed) % 16) as usize] += 1;
+            }
+            assert!(
+                buckets.iter().all(|count| (160..352).contains(count)),
+                "seed {seed}: {buckets:?}"
+            );
+        }
+
+        // Hashes of different seeds are unrelated, about half of their bits differ
+        for (a, b) in [(0, 1), (1, 2), (2, 3)] {
+            let differing = ids
+                .iter()
+                .map(|id| (crate::seeded_hash(id, a) ^ crate::seeded_hash(id, b)).count_ones())
+                .sum::<u32>() as f64
+                / ids.len() as f64;
+            assert!(
+                (28.0..36.0).contains(&differing),
+                "seeds {a} and {b}: {differing}"
+            );
+        }
+    }
+
+    #[pg_test]
+    fn test_typeid_is_valid() {
+        let valid = Spi::get_one::<Vec<bool>>(
+            "SELECT array_agg(typeid_is_valid(t) ORDER BY n)
+             FROM unnest(ARRAY[
+                'user_01h455vb4pex5vsknk084sn02q',
+                '01h455vb4pex5vsknk084sn02q',
+                'user_8zzzzzzzzzzzzzzzzzzzzzzzzz',
+                'User_01h455vb4pex5vsknk084sn02q',
+                '_01h455vb4pex5vsknk084sn02q'
+             ]) WITH ORDINALITY AS t(t, n)",
+        );
+        assert_eq!(valid, Ok(Some(vec![true, true, false, false, false])));
+    }
+
+    #[pg_test]
+    fn test_hash_extended_seed_zero() {
+        let matches = Spi::get_one::<bool>(
+            "SELECT bool_and(typeid_hash(id) = (typeid_hash_extended(id, 0)::bit(64) << 32)::bit(32)::int)
+             FROM typeid_generate_batch('event', 1000) id",
+        );
+        assert_eq!(matches, Ok(Some(true)));
     }
 
     #[pg_test]
@@ -214,6 +996,33 @@ mod tests {
         assert_eq!(result, Some(2));
     }
 
+    #[pg_test]
+    fn test_leakproof_quals_pass_security_barrier() {
+        let leakproof = Spi::get_one::<bool>(
+            "SELECT bool_and(proleakproof AND provolatile = 'i') FROM pg_proc
+             WHERE proname IN ('typeid_eq', 'typeid_lt', 'typeid_cmp', 'typeid_hash', 'typeid_to_uuid')",
+        )
+        .unwrap();
+        assert_eq!(leakproof, Some(true));
+
+        Spi::run("CREATE TABLE secrets (id typeid primary key, owner text)").unwrap();
+        Spi::run("INSERT INTO secrets SELECT typeid_generate('secret'), 'me' FROM generate_series(1, 1000)")
+            .unwrap();
+        Spi::run("ANALYZE secrets").unwrap();
+        Spi::run(
+            "CREATE VIEW my_secrets WITH (security_barrier) AS SELECT * FROM secrets WHERE owner = current_user",
+        )
+        .unwrap();
+
+        Spi::run("SET enable_seqscan = off").unwrap();
+        let plan =
+            Spi::explain("SELECT * FROM my_secrets WHERE id = 'secret_01h455vb4pex5vsknk084sn02q'")
+                .unwrap()
+                .0
+                .to_string();
+        assert!(plan.contains("Index"), "expected an index scan: {plan}");
+    }
+
     fn oid_for_type(type_name: &str) -> Result<Option<PgOid>, pgrx::spi::Error> {
         use crate::pg_sys::Oid;
 
@@ -0,0 +1,214 @@
+//! Monotonic UUIDv7 generation. `Uuid::now_v7` keeps a 42 bit counter after the timestamp that's
+//! reseeded at random every millisecond (methods 1 and 2 of RFC 9562), so ids generated by one
+//! connection sort in the order they were generated, even within the same millisecond. `Clock` does
+//! the same with a 12 bit counter for ids that don't come from the system clock, and for batches
+//! numbered from a single read of it. Since batches run ahead of the clock, every id of a
+//! connection continues after the last one it generated.
+
+use std::cell::Cell;
+
+use uuid::Uuid;
+
+use crate::{pool, seeded};
+
+const COUNTER_MAX: u16 = 0xfff;
+
+/// The timestamp and counter of the last id of a connection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Clock {
+    millis: u64,
+    counter: u16,
+}
+
+impl From<Uuid> for Clock {
+    /// The clock that generated `uuid`, reading its `rand_a` bits as the counter
+    fn from(uuid: Uuid) -> Self {
+        let uuid = uuid.as_u128();
+        Clock {
+            millis: (uuid >> 80) as u64,
+            counter: (uuid >> 64) as u16 & COUNTER_MAX,
+        }
+    }
+}
+
+impl Clock {
+    /// The UUID following the last one at `now`. A new millisecond starts the counter at a random
+    /// value below half its range, leaving room for the ids after it. An exhausted counter, or a
+    /// clock going backwards, keeps counting on the last timestamp and moves it forward as needed.
+    pub(crate) fn next(&mut self, now: u64, random: u128) -> Uuid {
+        if now > self.millis {
+            self.millis = now;
+            self.counter = (random >> 64) as u16 & (COUNTER_MAX >> 1);
+        } else if self.counter < COUNTER_MAX {
+            self.counter += 1;
+        } else {
+            self.millis += 1;
+            self.counter = 0;
+        }
+
+        Uuid::from_u128(
+            ((self.millis & 0xffff_ffff_ffff) as u128) << 80
+                | 0x7 << 76
+                | (self.counter as u128) << 64
+                | 0b10 << 62
+                | (random & ((1 << 62) - 1)),
+        )
+    }
+}
+
+thread_local! {
+    /// The last id generated by the connection, continued by every id after it
+    static LAST: Cell<Uuid> = const { Cell::new(Uuid::nil()) };
+}
+
+/// `uuid` when it sorts after the last id of the connection, which is the case unless a batch ran
+/// ahead of the clock, and otherwise the id following that one. Becomes the last id.
+fn continue_last(uuid: Uuid) -> Uuid {
+    LAST.with(|last| {
+        let uuid = if uuid > last.get() {
+            uuid
+        } else {
+            let mut clock = Clock::from(last.get());
+            clock.next(clock.millis, rand::random())
+        };
+        last.set(uuid);
+        uuid
+    })
+}
+
+/// UUIDv7s for a batch of ids, counting up from a single read of the clock. The ids are strictly
+/// increasing, and run ahead of the clock by a millisecond for every 4096 of them. They continue
+/// after the last id of the connection, and the ids generated after them continue after the batch.
+pub struct Sequence {
+    /// The clock of a seeded batch, which is reproducible on its own
+    seeded: Option<Clock>,
+    now: u64,
+}
+
+impl Sequence {
+    /// Starts a sequence at the current time
+    pub fn start() -> Self {
+        Sequence {
+            seeded: seeded::enabled().then(Clock::default),
+            now: seeded::unix_millis(),
+        }
+    }
+
+    /// The UUID following the last one of the sequence
+    pub fn next_uuid(&mut self) -> Uuid {
+        if let Some(clock) = &mut self.seeded {
+            return clock.next(self.now, seeded::random());
+        }
+        LAST.with(|last| {
+            let mut clock = Clock::from(last.get());
+            let uuid = clock.next(self.now, rand::random());
+            last.set(uuid);
+            uuid
+        })
+    }
+}
+
+/// A new UUIDv7, sorting after the ones generated before it by the connection
+pub fn now_v7() -> Uuid {
+    // The stepping clock of a seed never repeats a millisecond
+    if seeded::enabled() {
+        return Clock::default().next(seeded::unix_millis(), seeded::random());
+    }
+    // Pool UUIDs can be up to a second old, and are skipped while they sort before the ids the
+    // connection generated in place
+    let uuid = pool::pop()
+        .filter(|&uuid| uuid > LAST.get())
+        .unwrap_or_else(Uuid::now_v7);
+    continue_last(uuid)
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    use super::{continue_last, Clock, Sequence};
+
+    #[test]
+    fn test_clock() {
+        let mut clock = Clock::default();
+        let first = clock.next(1000, u128::MAX);
+        assert_eq!(first.get_version_num(), 7);
+        assert_eq!(
+            clock,
+            Clock {
+                millis: 1000,
+                counter: 0x7ff
+            }
+        );
+
+        // Within a millisecond and with the clock going backwards, ids keep counting up
+        let second = clock.next(1000, 0);
+        let third = clock.next(999, u128::MAX);
+        assert!(first < second && second < third);
+        assert_eq!(
+            clock,
+            Clock {
+                millis: 1000,
+                counter: 0x801
+            }
+        );
+
+        // An exhausted counter moves on to the next millisecond
+        clock.counter = 0xfff;
+        let fourth = clock.next(1000, 0);
+        assert!(third < fourth);
+        assert_eq!(
+            clock,
+            Clock {
+                millis: 1001,
+                counter: 0
+            }
+        );
+        assert_eq!(fourth.get_timestamp().unwrap().to_unix(), (1, 1_000_000));
+
+        let fifth = clock.next(1002, 0);
+        assert_eq!(
+            clock,
+            Clock {
+                millis: 1002,
+                counter: 0
+            }
+        );
+        assert!(fourth < fifth);
+    }
+
+    #[test]
+    fn test_sequence() {
+        let mut sequence = Sequence::start();
+        let uuids: Vec<_> = (0..10_000).map(|_| sequence.next_uuid()).collect();
+        assert!(uuids.windows(2).all(|pair| pair[0] < pair[1]));
+
+        // 10000 ids are numbered through at most 4 milliseconds
+        let millis = |uuid: &uuid::Uuid| (uuid.as_u128() >> 80) as u64;
+        assert_eq!(millis(&uuids[0]), sequence.now);
+        assert!(millis(&uuids[9999]) - sequence.now <= 3);
+
+        // A batch running ahead of the clock is continued by the ids after it
+        let tail = (0..100_000).map(|_| sequence.next_uuid()).last().unwrap();
+        assert!(millis(&tail) > sequence.now + 20);
+        assert!(continue_last(uuid::Uuid::now_v7()) > tail);
+        let mut next = Sequence::start();
+        assert!(next.next_uuid() > tail);
+    }
+
+    #[pg_test]
+    fn test_monotonic() {
+        Spi::run(
+            "CREATE TABLE events AS
+             SELECT n, typeid_generate('event') id FROM generate_series(1, 10000) n",
+        )
+        .unwrap();
+        let ordered = Spi::get_one::<bool>(
+            "SELECT bool_and(id > previous) FROM (
+                SELECT id, lag(id) OVER (ORDER BY n) previous FROM events
+             ) ids",
+        );
+        assert_eq!(ordered, Ok(Some(true)));
+    }
+}
@@ -0,0 +1,277 @@
+//! Helpers for partitioning tables by a typeid column.
+
+use pgrx::prelude::*;
+use pgrx::PgRelation;
+
+use crate::catalog::typeid_oid_arg;
+use crate::datum::TypeIDRef;
+use crate::typeid::{TypeID, TypeIDPrefix};
+
+/// Milliseconds since the Unix epoch at the start of a month in UTC
+fn month_start_millis(year: i32, month: u32) -> Option<u64> {
+    // Days since the epoch for a proleptic Gregorian date, with years starting in March
+    let (year, month) = (year as i64, month as i64);
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    u64::try_from(days * 86_400_000).ok()
+}
+
+/// Checks that the relation is range partitioned by a single typeid column
+fn check_partitioned_by_typeid(parent: &PgRelation) {
+    let partitioned = Spi::get_one_with_args::<bool>(
+        "SELECT p.partstrat = 'r' AND p.partnatts = 1 AND a.atttypid = $2
+         FROM pg_catalog.pg_partitioned_table p
+         JOIN pg_catalog.pg_attribute a ON a.attrelid = p.partrelid AND a.attnum = p.partattrs[0]
+         WHERE p.partrelid = $1",
+        vec![
+            (PgBuiltInOids::OIDOID.oid(), parent.oid().into_datum()),
+            typeid_oid_arg(),
+        ],
+    )
+    .unwrap_or(None);
+
+    if partitioned != Some(true) {
+        error!(
+            "{:?} is not range partitioned by a typeid column",
+            parent.name()
+        );
+    }
+}
+
+/// Statements creating a partition of `parent` for every month from `start_month` up to but not
+/// including `end_month` (in UTC) for ids with the given prefix, named `<parent>_<prefix>_<yyyy>_<mm>`.
+/// The statements are executed as well when `execute` is true, partitions which already exist are
+/// skipped. Names longer than Postgres keeps are an error rather than truncated, since truncated
+/// names of different partitions could be the same and the later ones would be skipped.
+#[pg_extern(cost = 100)]
+fn typeid_create_monthly_partitions(
+    parent: PgRelation,
+    prefix: &str,
+    start_month: Date,
+    end_month: Date,
+    execute: default!(bool, false),
+) -> SetOfIterator<'static, String> {
+    let type_prefix = match TypeIDPrefix::new(prefix) {
+        Ok(type_prefix) => type_prefix,
+        Err(err) => error!("invalid prefix: {err}"),
+    };
+    if !start_month.is_finite() || !end_month.is_finite() {
+        error!("month range must be finite");
+    }
+    check_partitioned_by_typeid(&parent);
+
+    let mut months = Vec::new();
+    let (mut year, mut month) = (start_month.year(), start_month.month() as u32);
+    while (year, month) < (end_month.year(), end_month.month() as u32) {
+        let next = if month == 12 {
+            (year + 1, 1)
+        } else {
+            (year, month + 1)
+        };
+        months.push((year, month, next));
+        (year, month) = next;
+    }
+
+    let suffix_prefix = if prefix.is_empty() {
+        String::new()
+    } else {
+        format!("_{prefix}")
+    };
+    let partition_name = |year, month| {
+        let name = format!("{}{suffix_prefix}_{year}_{month:02}", parent.name());
+        if name.len() >= pg_sys::NAMEDATALEN as usize {
+            ereport!(
+                ERROR,
+                PgSqlErrorCode::ERRCODE_NAME_TOO_LONG,
+                format!("partition name \"{name}\" is too long"),
+                format!(
+                    "Partition names are limited to {} bytes.",
+                    pg_sys::NAMEDATALEN - 1
+                )
+            );
+        }
+        name
+    };
+
+    let statements = Spi::connect(|mut client| {
+        let mut statements = Vec::with_capacity(months.len());
+        for (year, month, (next_year, next_month)) in months {
+            let bound = |year, month| {
+                let millis = month_start_millis(year, month)
+                    .unwrap_or_else(|| error!("{year}-{month:02} is before 1970"));
+                TypeID::lower_bound_at(type_prefix.clone(), millis).to_string()
+            };
+
+            let statement = client
+                .select(
+                    "SELECT format(
+                        'CREATE TABLE IF NOT EXISTS %I.%I PARTITION OF %s FOR VALUES FROM (%L) TO (%L)',
+                        n.nspname, $2, c.oid::regclass, $3, $4
+                     )
+                     FROM pg_catalog.pg_class c
+                     JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+                     WHERE c.oid = $1",
+                    None,
+                    Some(vec![
+                        (PgBuiltInOids::OIDOID.oid(), parent.oid().into_datum()),
+                        (
+                            PgBuiltInOids::TEXTOID.oid(),
+                            partition_name(year, month).into_datum(),
+                        ),
+                        (
+                            PgBuiltInOids::TEXTOID.oid(),
+                            bound(year, month).into_datum(),
+                        ),
+                        (
+                            PgBuiltInOids::TEXTOID.oid(),
+                            bound(next_year, next_month).into_datum(),
+                        ),
+                    ]),
+                )?
+                .first()
+                .get_one::<String>()?
+                .unwrap_or_default();
+
+            if execute {
+                client.update(&statement, None, None)?;
+            }
+            statements.push(statement);
+        }
+
+        Ok::<_, pgrx::spi::Error>(statements)
+    })
+    .unwrap();
+
+    SetOfIterator::new(statements)
+}
+
+/// Seed of the extended hash functions of partition keys, `HASH_PARTITION_SEED` of Postgres
+const HASH_PARTITION_SEED: u64 = 0x7a5b_2236_7996_dcfd;
+/// Added to the hash of the first partition key by `hash_combine64`, which combines the hashes of
+/// all partition keys starting from 0
+const HASH_COMBINE_OFFSET: u64 = 0x49a0_f4dd_15e5_a8e3;
+
+/// The remainder of the partition `PARTITION BY HASH (id)` routes an id to, when every partition
+/// has the given modulus, so applications can tell where a row lands before inserting it
+#[pg_extern(immutable, parallel_safe, requires = [TypeID])]
+fn typeid_hash_partition(id: TypeIDRef<'_>, modulus: i32) -> i32 {
+    if modulus <= 0 {
+        ereport!(
+            ERROR,
+            PgSqlErrorCode::ERRCODE_INVALID_PARAMETER_VALUE,
+            "modulus for hash partition must be an integer value greater than zero"
+        );
+    }
+
+    let hash = crate::typeid_seeded_hash(id, HASH_PARTITION_SEED as i64);
+    (hash.wrapping_add(HASH_COMBINE_OFFSET) % modulus as u64) as i32
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    #[test]
+    fn test_month_start_millis() {
+        assert_eq!(super::month_start_millis(1970, 1), Some(0));
+        assert_eq!(super::month_start_millis(2024, 3), Some(1_709_251_200_000));
+        assert_eq!(super::month_start_millis(2000, 1), Some(946_684_800_000));
+        assert_eq!(super::month_start_millis(1969, 12), None);
+    }
+
+    #[pg_test(
+        error = "partition name \"events_of_the_first_quarter_of_the_year_event_notification_2024_11\" is too long"
+    )]
+    fn test_typeid_create_monthly_partitions_long_name() {
+        Spi::run(
+            "CREATE TABLE events_of_the_first_quarter_of_the_year (id typeid NOT NULL)
+             PARTITION BY RANGE (id)",
+        )
+        .unwrap();
+        Spi::run(
+            "SELECT typeid_create_monthly_partitions('events_of_the_first_quarter_of_the_year', 'event_notification', '2024-11-01', '2025-02-01')",
+        )
+        .unwrap();
+    }
+
+    #[pg_test]
+    fn test_typeid_create_monthly_partitions() {
+        Spi::run("CREATE TABLE events (id typeid NOT NULL) PARTITION BY RANGE (id)").unwrap();
+
+        let statements = Spi::get_one::<Vec<String>>(
+            "SELECT array_agg(s) FROM typeid_create_monthly_partitions('events', 'event', '2024-11-15', '2025-02-01') s",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(statements.len(), 3);
+        assert!(statements[2].contains(".events_event_2025_01 PARTITION OF events FOR VALUES FROM"));
+        assert_eq!(
+            Spi::get_one::<i64>(
+                "SELECT count(*) FROM pg_inherits WHERE inhparent = 'events'::regclass"
+            )
+            .unwrap(),
+            Some(0)
+        );
+
+        Spi::run(
+            "SELECT typeid_create_monthly_partitions('events', 'event', '2024-11-01', '2025-02-01', execute => true)",
+        )
+        .unwrap();
+        Spi::run(
+            "INSERT INTO events VALUES (uuid_to_typeid('event', '0193addd-9400-7000-8000-000000000000'))",
+        )
+        .unwrap();
+
+        let partition =
+            Spi::get_one::<String>("SELECT tableoid::regclass::text FROM events").unwrap();
+        assert_eq!(partition.as_deref(), Some("events_event_2024_12"));
+    }
+
+    #[pg_test]
+    fn test_typeid_hash_partition() {
+        Spi::run("CREATE TABLE items (id typeid NOT NULL) PARTITION BY HASH (id)").unwrap();
+        for remainder in 0..4 {
+            Spi::run(&format!(
+                "CREATE TABLE items_{remainder} PARTITION OF items
+                 FOR VALUES WITH (MODULUS 4, REMAINDER {remainder})"
+            ))
+            .unwrap();
+        }
+        Spi::run(
+            "INSERT INTO items
+             SELECT typeid_generate(p) FROM unnest(ARRAY['user', 'org', '']) p, generate_series(1, 300)",
+        )
+        .unwrap();
+
+        let (routed, satisfied, partitions) = Spi::get_three::<i64, bool, i64>(
+            "SELECT count(*) FILTER (WHERE tableoid::regclass::text = 'items_' || typeid_hash_partition(id, 4)),
+                    bool_and(satisfies_hash_partition('items'::regclass, 4, typeid_hash_partition(id, 4), id)),
+                    count(DISTINCT tableoid)
+             FROM items",
+        )
+        .unwrap();
+        assert_eq!(routed, Some(900));
+        assert_eq!(satisfied, Some(true));
+        assert_eq!(partitions, Some(4));
+    }
+
+    #[pg_test(error = "modulus for hash partition must be an integer value greater than zero")]
+    fn test_typeid_hash_partition_modulus() {
+        Spi::run("SELECT typeid_hash_partition(typeid_generate('user'), 0)").unwrap();
+    }
+
+    #[pg_test(error = "\"items\" is not range partitioned by a typeid column")]
+    fn test_typeid_create_monthly_partitions_not_partitioned() {
+        Spi::run("CREATE TABLE items (id typeid)").unwrap();
+        Spi::run(
+            "SELECT typeid_create_monthly_partitions('items', 'item', '2024-01-01', '2024-02-01')",
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,133 @@
+#[non_exhaustive]
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The pattern ended with a lone escape character
+    #[error("LIKE pattern must not end with escape character")]
+    TrailingEscape,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    /// `%`, matches any sequence of characters
+    Any,
+    /// `_`, matches exactly one character
+    One,
+    /// A literal byte (possibly escaped with `\`)
+    Byte(u8),
+}
+
+/// A LIKE pattern matched against TypeID prefixes.
+///
+/// Follows the semantics of the SQL `LIKE` operator: `%` matches any sequence,
+/// `_` matches a single character and `\` escapes the next character.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrefixPattern(Vec<Token>);
+
+impl PrefixPattern {
+    pub fn parse(pattern: &str) -> Result<Self, Error> {
+        let mut tokens = Vec::with_capacity(pattern.len());
+        let mut bytes = pattern.bytes();
+
+        while let Some(b) = bytes.next() {
+            tokens.push(match b {
+                b'%' => Token::Any,
+                b'_' => Token::One,
+                b'\\' => Token::Byte(bytes.next().ok_or(Error::TrailingEscape)?),
+                b => Token::Byte(b),
+            });
+        }
+
+        Ok(Self(tokens))
+    }
+
+    pub fn matches(&self, prefix: &str) -> bool {
+        let text = prefix.as_bytes();
+        let tokens = &self.0;
+
+        let (mut t, mut p) = (0, 0);
+        // Position of the last `%` seen and the text offset it currently covers up to
+        let mut backtrack: Option<(usize, usize)> = None;
+
+        while t < text.len() {
+            match tokens.get(p) {
+                Some(Token::Any) => {
+                    backtrack = Some((p, t));
+                    p += 1;
+                    continue;
+                }
+                Some(Token::One) => {
+                    p += 1;
+                    t += 1;
+                    continue;
+                }
+                Some(Token::Byte(b)) if *b == text[t] => {
+                    p += 1;
+                    t += 1;
+                    continue;
+                }
+                _ => {}
+            }
+
+            match backtrack {
+                Some((any, covered)) => {
+                    backtrack = Some((any, covered + 1));
+                    p = any + 1;
+                    t = covered + 1;
+                }
+                None => return false,
+            }
+        }
+
+        tokens[p..].iter().all(|token| *token == Token::Any)
+    }
+
+    /// The literal bytes every matching prefix has to start with
+    pub fn literal_prefix(&self) -> Vec<u8> {
+        self.0
+            .iter()
+            .map_while(|token| match token {
+                Token::Byte(b) => Some(*b),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Whether the pattern contains no wildcards and therefore matches a single prefix
+    pub fn is_exact(&self) -> bool {
+        self.0.iter().all(|token| matches!(token, Token::Byte(_)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefix_pattern() {
+        let pattern = PrefixPattern::parse("inv\\_%").unwrap();
+        assert!(pattern.matches("inv_item"));
+        assert!(pattern.matches("inv_"));
+        assert!(!pattern.matches("inv"));
+        assert!(!pattern.matches("invoice"));
+        assert_eq!(pattern.literal_prefix(), b"inv_");
+        assert!(!pattern.is_exact());
+
+        let pattern = PrefixPattern::parse("inv_%").unwrap();
+        assert!(pattern.matches("invoice"));
+        assert_eq!(pattern.literal_prefix(), b"inv");
+
+        let pattern = PrefixPattern::parse("%_item").unwrap();
+        assert!(pattern.matches("inv_item"));
+        assert!(!pattern.matches("item"));
+        assert!(!pattern.matches("inv_items"));
+
+        let pattern = PrefixPattern::parse("user").unwrap();
+        assert!(pattern.is_exact());
+        assert!(pattern.matches("user"));
+        assert!(!pattern.matches("users"));
+
+        assert!(PrefixPattern::parse("").unwrap().matches(""));
+        assert!(PrefixPattern::parse("%").unwrap().matches(""));
+        assert!(PrefixPattern::parse("user\\").is_err());
+    }
+}
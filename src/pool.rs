@@ -0,0 +1,236 @@
+//! Pool of pre-generated UUIDv7s in shared memory, kept filled by a background worker.
+//!
+//! With `typeid.pool_worker` on, `typeid_generate` and the other single id generators pop their
+//! UUIDs from a ring buffer instead of reading the clock and drawing random bits themselves.
+//! Connections take `CHUNK` UUIDs at a time, so they don't contend for the lock of the pool on
+//! every id. The worker fills the pool up to `typeid.pool_size` whenever it runs below
+//! `typeid.pool_refill_threshold`, and at least twice per `MAX_AGE_MILLIS`. The UUIDs come from a
+//! single clock with a counter. UUIDs older than `MAX_AGE_MILLIS` are dropped, so timestamps never
+//! lag far behind the time an id was generated. An empty pool falls back to generating in place.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use pgrx::bgworkers::{
+    BackgroundWorker, BackgroundWorkerBuilder, BgWorkerStartTime, SignalWakeFlags,
+};
+use pgrx::prelude::*;
+use pgrx::{pg_shmem_init, PGRXSharedMemory, PgLwLock, PgSharedMemoryInitialization};
+use uuid::Uuid;
+
+use crate::guc;
+use crate::monotonic::Clock;
+use crate::reload;
+use crate::time::POSTGRES_EPOCH_MICROS;
+
+/// Number of UUIDs the shared memory of the pool holds, the upper bound of `typeid.pool_size`
+pub const POOL_CAPACITY: usize = 16384;
+/// How old a UUID in the pool may get before it's dropped
+const MAX_AGE_MILLIS: u64 = 1000;
+/// Number of UUIDs a connection takes from the pool at once
+const CHUNK: usize = 64;
+
+/// Ring buffer of UUIDs, oldest first
+#[derive(Copy, Clone)]
+pub struct Pool {
+    uuids: [u128; POOL_CAPACITY],
+    head: usize,
+    len: usize,
+    /// The latch of the worker, set by connections when the pool runs low
+    latch: usize,
+}
+
+impl Default for Pool {
+    fn default() -> Self {
+        Self {
+            uuids: [0; POOL_CAPACITY],
+            head: 0,
+            len: 0,
+            latch: 0,
+        }
+    }
+}
+
+unsafe impl PGRXSharedMemory for Pool {}
+
+fn millis_of(uuid: u128) -> u64 {
+    (uuid >> 80) as u64
+}
+
+impl Pool {
+    /// Takes up to `count` of the oldest UUIDs, emptying the pool instead when they're older than
+    /// `MAX_AGE_MILLIS`
+    fn take(&mut self, now: u64, count: usize) -> Vec<u128> {
+        self.drop_stale(now);
+        let count = count.min(self.len);
+        let uuids = (0..count)
+            .map(|i| self.uuids[(self.head + i) % POOL_CAPACITY])
+            .collect();
+        self.head = (self.head + count) % POOL_CAPACITY;
+        self.len -= count;
+        uuids
+    }
+
+    /// Drops the UUIDs when the oldest is older than `MAX_AGE_MILLIS`
+    fn drop_stale(&mut self, now: u64) {
+        if self.len > 0 && millis_of(self.uuids[self.head]) + MAX_AGE_MILLIS < now {
+            self.len = 0;
+        }
+    }
+
+    /// Appends as many of the UUIDs as there's room for
+    fn extend(&mut self, uuids: &[u128]) {
+        for &uuid in uuids.iter().take(POOL_CAPACITY - self.len) {
+            self.uuids[(self.head + self.len) % POOL_CAPACITY] = uuid;
+            self.len += 1;
+        }
+    }
+}
+
+static POOL: PgLwLock<Pool> = PgLwLock::new();
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// UUIDs the connection took from the pool and hasn't used yet, oldest first
+    static TAKEN: RefCell<VecDeque<u128>> = const { RefCell::new(VecDeque::new()) };
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// The Unix time in milliseconds at the start of the current statement, which Postgres keeps
+/// without reading the clock
+fn statement_start_millis() -> u64 {
+    let micros = unsafe { pg_sys::GetCurrentStatementStartTimestamp() } + POSTGRES_EPOCH_MICROS;
+    (micros / 1000) as u64
+}
+
+/// A UUID from the pool, or None when the pool is disabled or empty. The clock is only read to
+/// take the next chunk, the UUIDs held by the connection are checked against the start of the
+/// statement instead.
+pub fn pop() -> Option<Uuid> {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return None;
+    }
+
+    TAKEN.with(|taken| {
+        let mut taken = taken.borrow_mut();
+        // UUIDs taken by the connection go stale like the ones left in the pool
+        if taken
+            .front()
+            .is_some_and(|&uuid| millis_of(uuid) + MAX_AGE_MILLIS < statement_start_millis())
+        {
+            taken.clear();
+        }
+        if taken.is_empty() {
+            let (uuids, low, latch) = {
+                let mut pool = POOL.exclusive();
+                let uuids = pool.take(unix_millis(), CHUNK);
+                let low = pool.len < guc::POOL_REFILL_THRESHOLD.get() as usize;
+                (uuids, low, pool.latch)
+            };
+            if low && latch != 0 {
+                unsafe { pg_sys::SetLatch(latch as *mut pg_sys::Latch) };
+            }
+            taken.extend(uuids);
+        }
+        taken.pop_front().map(Uuid::from_u128)
+    })
+}
+
+/// Reserves the pool and registers its worker when the library is preloaded and the worker is
+/// enabled
+pub fn init() {
+    if !guc::preloading() || !guc::POOL_WORKER.get() {
+        return;
+    }
+
+    pg_shmem_init!(POOL);
+    ENABLED.store(true, Ordering::Relaxed);
+    BackgroundWorkerBuilder::new("typeid pool worker")
+        .set_type("typeid pool worker")
+        .set_library("typeid")
+        .set_function("typeid_pool_worker_main")
+        .enable_shmem_access(None)
+        .set_start_time(BgWorkerStartTime::PostmasterStart)
+        .set_restart_time(Some(Duration::from_secs(1)))
+        .load();
+}
+
+#[pg_guard]
+#[no_mangle]
+pub extern "C" fn typeid_pool_worker_main(_arg: pg_sys::Datum) {
+    BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGHUP | SignalWakeFlags::SIGTERM);
+    POOL.exclusive().latch = unsafe { pg_sys::MyLatch } as usize;
+
+    let mut clock = Clock::default();
+    loop {
+        if BackgroundWorker::sighup_received() {
+            unsafe { pg_sys::ProcessConfigFile(pg_sys::GucContext_PGC_SIGHUP) };
+            reload::record();
+        }
+
+        let size = (guc::POOL_SIZE.get() as usize).min(POOL_CAPACITY);
+        let now = unix_millis();
+        let missing = {
+            let mut pool = POOL.exclusive();
+            pool.drop_stale(now);
+            size.saturating_sub(pool.len)
+        };
+        // Generate without holding the lock, connections only take UUIDs out meanwhile
+        let uuids: Vec<_> = (0..missing)
+            .map(|_| clock.next(now, rand::random()).as_u128())
+            .collect();
+        POOL.exclusive().extend(&uuids);
+
+        // Wake up before the pool goes stale even when no connection asks for a refill
+        if !BackgroundWorker::wait_latch(Some(Duration::from_millis(MAX_AGE_MILLIS / 2))) {
+            break;
+        }
+    }
+    POOL.exclusive().latch = 0;
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use super::{Pool, MAX_AGE_MILLIS, POOL_CAPACITY};
+
+    #[test]
+    fn test_pool() {
+        let now = 1_000_000;
+        let uuid = |n: u128| (now as u128) << 80 | n;
+        let mut pool = Box::<Pool>::default();
+        pool.extend(&[uuid(1), uuid(2), uuid(3)]);
+        assert_eq!(pool.take(now, 1), [uuid(1)]);
+        assert_eq!(pool.take(now, 5), [uuid(2), uuid(3)]);
+
+        // UUIDs are appended after the remaining ones, and wrap around the end of the buffer
+        pool.head = POOL_CAPACITY - 1;
+        pool.len = 0;
+        pool.extend(&[uuid(4), uuid(5), uuid(6)]);
+        assert_eq!(pool.take(now, 2), [uuid(4), uuid(5)]);
+        assert_eq!(pool.head, 1);
+        assert_eq!(pool.take(now, 2), [uuid(6)]);
+        assert_eq!(pool.take(now, 2), []);
+
+        // A full pool takes no more
+        pool.extend(&vec![uuid(7); POOL_CAPACITY + 1]);
+        assert_eq!(pool.len, POOL_CAPACITY);
+
+        // Stale UUIDs are dropped
+        pool.drop_stale(now + MAX_AGE_MILLIS);
+        assert_eq!(pool.len, POOL_CAPACITY);
+        assert_eq!(pool.take(now + MAX_AGE_MILLIS + 1, 1), []);
+        assert_eq!(pool.len, 0);
+        pool.extend(&[uuid(8)]);
+        pool.drop_stale(now + MAX_AGE_MILLIS + 1);
+        assert_eq!(pool.len, 0);
+    }
+}
@@ -0,0 +1,527 @@
+use pgrx::prelude::*;
+use pgrx::PgRelation;
+use uuid::Uuid;
+
+use crate::base32::encode_base32_uuid;
+use crate::catalog::{check_typeid_column, ColumnName};
+use crate::datum::TypeIDRef;
+use crate::pattern::PrefixPattern;
+use crate::typeid::{checked_prefix, TypeID};
+
+/// The prefix of the id, empty for ids without one
+#[pg_extern(immutable, parallel_safe, requires = [TypeID])]
+fn typeid_prefix(typeid: TypeIDRef<'_>) -> &str {
+    typeid.type_prefix()
+}
+
+/// The 26 base32 characters of the UUID following the prefix, which are also a valid ULID
+#[pg_extern(immutable, parallel_safe, requires = [TypeID])]
+fn typeid_suffix(typeid: TypeIDRef<'_>) -> String {
+    encode_base32_uuid(&Uuid::from_bytes(*typeid.uuid_bytes()))
+}
+
+/// The smallest id with the prefix, whose UUID is all zeros. Ids with the prefix sort between it
+/// and `typeid_max_for_prefix`, ids with other prefixes outside of them.
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_min_for_prefix(prefix: &str) -> TypeID {
+    TypeID::new(checked_prefix(prefix), Uuid::nil())
+}
+
+/// The largest id with the prefix, whose UUID is all ones
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_max_for_prefix(prefix: &str) -> TypeID {
+    TypeID::new(checked_prefix(prefix), Uuid::max())
+}
+
+/// Statements adding a stored generated column with the prefix of the typeid `column` and an
+/// index on it, for tools that can't use expression indexes. The column is named after `column`
+/// with a `_prefix` suffix unless `prefix_column` is given. The statements are executed as well
+/// when `execute` is true. Generated columns require Postgres 12.
+#[pg_extern(cost = 100)]
+fn typeid_add_prefix_column(
+    relation: PgRelation,
+    column: default!(ColumnName, "'id'"),
+    prefix_column: default!(Option<ColumnName>, "NULL"),
+    execute: default!(bool, false),
+) -> SetOfIterator<'static, String> {
+    check_typeid_column(&relation, column.as_str());
+    let prefix_column = match prefix_column {
+        Some(prefix_column) => prefix_column.as_str().to_string(),
+        None => format!("{}_prefix", column.as_str()),
+    };
+
+    let statements = Spi::connect(|mut client| {
+        let statements = client
+            .select(
+                "SELECT ARRAY[
+                    format(
+                        'ALTER TABLE %s ADD COLUMN %I text GENERATED ALWAYS AS (%s.typeid_prefix(%I)) STORED',
+                        $1::regclass, $3, e.extnamespace::regnamespace, $2
+                    ),
+                    format('CREATE INDEX ON %s (%I)', $1::regclass, $3)
+                 ]
+                 FROM pg_catalog.pg_extension e
+                 WHERE e.extname = 'typeid'",
+                None,
+                Some(vec![
+                    (PgBuiltInOids::OIDOID.oid(), relation.oid().into_datum()),
+                    (PgBuiltInOids::TEXTOID.oid(), column.as_str().into_datum()),
+                    (PgBuiltInOids::TEXTOID.oid(), prefix_column.into_datum()),
+                ]),
+            )?
+            .first()
+            .get_one::<Vec<String>>()?
+            .unwrap_or_default();
+
+        if execute {
+            for statement in &statements {
+                client.update(statement, None, None)?;
+            }
+        }
+        Ok::<_, pgrx::spi::Error>(statements)
+    })
+    .unwrap();
+
+    SetOfIterator::new(statements)
+}
+
+/// The smallest, i.e. earliest, id of every prefix in the typeid `column`, or the largest one when
+/// `latest` is true. With an index on the column, every prefix costs one index descent skipping
+/// over the ids of the previous one, rather than a `DISTINCT ON` over the whole table.
+#[pg_extern(stable, cost = 1000)]
+fn typeid_first_per_prefix(
+    relation: PgRelation,
+    column: default!(ColumnName, "'id'"),
+    latest: default!(bool, false),
+) -> TableIterator<'static, (name!(prefix, String), name!(id, TypeID))> {
+    check_typeid_column(&relation, column.as_str());
+    let (direction, operator) = if latest { ("DESC", "<") } else { ("ASC", ">") };
+
+    // The scalar subqueries return a NULL id after the last prefix
+    let rows = Spi::connect(|client| {
+        let (first, following) = client
+            .select(
+                &format!(
+                    "SELECT
+                        format('SELECT (SELECT %2$I FROM %1$s WHERE %2$I IS NOT NULL ORDER BY %2$I {direction} LIMIT 1)', $1::regclass, $2),
+                        format('SELECT (SELECT %2$I FROM %1$s WHERE %2$I {operator} $1 ORDER BY %2$I {direction} LIMIT 1)', $1::regclass, $2)"
+                ),
+                None,
+                Some(vec![
+                    (PgBuiltInOids::OIDOID.oid(), relation.oid().into_datum()),
+                    (PgBuiltInOids::TEXTOID.oid(), column.as_str().into_datum()),
+                ]),
+            )?
+            .first()
+            .get_two::<String, String>()?;
+        let (Some(first), Some(following)) = (first, following) else {
+            return Ok(Vec::new());
+        };
+
+        let mut rows = Vec::new();
+        let mut next = client.select(&first, Some(1), None)?.first().get_one::<TypeID>()?;
+        while let Some(id) = next {
+            // Skip past every id of the prefix
+            let bound = TypeID::new(
+                id.prefix().clone(),
+                if latest { Uuid::nil() } else { Uuid::max() },
+            );
+            rows.push((id.type_prefix().to_string(), id));
+            next = client
+                .select(
+                    &following,
+                    Some(1),
+                    Some(vec![(TypeID::type_oid().into(), bound.into_datum())]),
+                )?
+                .first()
+                .get_one::<TypeID>()?;
+        }
+        Ok::<_, pgrx::spi::Error>(rows)
+    })
+    .unwrap();
+
+    TableIterator::new(rows)
+}
+
+/// Checks the prefix of a TypeID against a LIKE pattern, e.g. `typeid_prefix_like(id, 'inv\_%')`.
+/// When the pattern is a constant with a literal head, the planner turns this into a btree range scan.
+#[pg_extern(immutable, parallel_safe, cost = 5, requires = [TypeID])]
+fn typeid_prefix_like(typeid: TypeIDRef<'_>, pattern: &str) -> bool {
+    match PrefixPattern::parse(pattern) {
+        Ok(pattern) => pattern.matches(typeid.type_prefix()),
+        Err(err) => error!("invalid pattern {pattern:?}: {err}"),
+    }
+}
+
+/// Whether the id has exactly `prefix`, backing the `id @> prefix` operator. Unlike the inlined
+/// `typeid_has_prefix`, the operator stays a single clause, which the planner turns into a range
+/// scan over a btree index on the id when the prefix is a constant (Postgres 12+).
+#[pg_extern(immutable, parallel_safe, requires = [TypeID])]
+fn typeid_contains_prefix(typeid: TypeIDRef<'_>, prefix: &str) -> bool {
+    typeid.type_prefix() == prefix
+}
+
+/// Guards against relating ids of different entities, e.g. joining on the wrong foreign key:
+/// raises an error naming both prefixes when they differ and returns true otherwise, so it can be
+/// used in CHECK constraints and WHERE clauses
+#[pg_extern(immutable, parallel_safe, requires = [TypeID])]
+fn typeid_assert_same_prefix(a: TypeIDRef<'_>, b: TypeIDRef<'_>) -> bool {
+    if a.type_prefix() != b.type_prefix() {
+        error!(
+            "cannot relate ids with different prefixes {:?} and {:?}",
+            a.type_prefix(),
+            b.type_prefix()
+        );
+    }
+    true
+}
+
+/// Equality which raises an error instead of returning false when the prefixes differ, backing
+/// the `==` operator for join conditions
+#[pg_extern(immutable, parallel_safe, requires = [TypeID])]
+fn typeid_eq_same_prefix(a: TypeIDRef<'_>, b: TypeIDRef<'_>) -> bool {
+    typeid_assert_same_prefix(a, b) && a == b
+}
+
+// Neither hashable nor mergeable, a hash or merge join would only compare ids with equal hashes or
+// neighbouring sort positions and miss the mismatch
+extension_sql!(
+    r#"
+    CREATE OPERATOR == (
+        LEFTARG = typeid,
+        RIGHTARG = typeid,
+        PROCEDURE = typeid_eq_same_prefix,
+        COMMUTATOR = '==',
+        RESTRICT = eqsel,
+        JOIN = eqjoinsel
+    );
+    "#,
+    name = "create_typeid_same_prefix_operator",
+    requires = [typeid_eq_same_prefix],
+);
+
+extension_sql!(
+    r#"
+    CREATE OPERATOR @> (
+        LEFTARG = typeid,
+        RIGHTARG = text,
+        PROCEDURE = typeid_contains_prefix,
+        RESTRICT = typeid_contains_prefix_sel,
+        JOIN = typeid_contains_prefix_joinsel
+    );
+    "#,
+    name = "create_typeid_contains_prefix_operator",
+    requires = [
+        typeid_contains_prefix,
+        estimate::typeid_contains_prefix_sel,
+        estimate::typeid_contains_prefix_joinsel
+    ],
+);
+
+// `typeid_has_prefix(id, prefix)` is written in SQL, so the planner inlines it into a range over
+// the ids of the prefix. That keeps the btree index usable inside views and RLS policies, where a
+// call to a C function would hide the comparison. The CASE folds away for a constant prefix, and
+// keeps an invalid one from reaching `uuid_to_typeid`, so the predicate is false rather than an
+// error.
+extension_sql!(
+    r#"
+    CREATE FUNCTION typeid_has_prefix(id typeid, prefix text) RETURNS boolean
+    IMMUTABLE PARALLEL SAFE
+    LANGUAGE sql
+    AS $$
+        SELECT CASE WHEN @extschema@.typeid_is_valid_prefix(prefix) THEN
+            id OPERATOR(@extschema@.>=)
+                @extschema@.uuid_to_typeid(prefix, '00000000-0000-0000-0000-000000000000')
+            AND id OPERATOR(@extschema@.<=)
+                @extschema@.uuid_to_typeid(prefix, 'ffffffff-ffff-ffff-ffff-ffffffffffff')
+        ELSE false END
+    $$;
+    "#,
+    name = "create_typeid_has_prefix",
+    requires = [
+        "create_typeid_operator_class",
+        uuid_to_typeid,
+        crate::typeid_is_valid_prefix
+    ],
+);
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    #[pg_test]
+    fn test_typeid_prefix() {
+        let prefixes = Spi::get_one::<Vec<String>>(
+            "SELECT ARRAY[typeid_prefix(typeid_generate('user_account')), typeid_prefix(typeid_generate(''))]",
+        );
+        assert_eq!(
+            prefixes,
+            Ok(Some(vec!["user_account".to_string(), String::new()]))
+        );
+    }
+
+    #[pg_test]
+    fn test_typeid_min_max_for_prefix() {
+        let bounds = Spi::get_one::<Vec<String>>(
+            "SELECT ARRAY[typeid_min_for_prefix('user')::text, typeid_max_for_prefix('user')::text]",
+        );
+        assert_eq!(
+            bounds,
+            Ok(Some(vec![
+                "user_00000000000000000000000000".to_string(),
+                "user_7zzzzzzzzzzzzzzzzzzzzzzzzz".to_string()
+            ]))
+        );
+
+        Spi::run("CREATE TABLE ids (id typeid PRIMARY KEY)").unwrap();
+        Spi::run(
+            "INSERT INTO ids SELECT typeid_generate(p) FROM unnest(ARRAY['user', 'user', 'use', 'user_account', 'usera', '']) p",
+        )
+        .unwrap();
+        let users = Spi::get_one::<i64>(
+            "SELECT count(*) FROM ids
+             WHERE id BETWEEN typeid_min_for_prefix('user') AND typeid_max_for_prefix('user')",
+        );
+        assert_eq!(users, Ok(Some(2)));
+    }
+
+    #[pg_test(error = "invalid typeid prefix \"User\"")]
+    fn test_typeid_min_for_prefix_invalid() {
+        Spi::run("SELECT typeid_min_for_prefix('User')").unwrap();
+    }
+
+    #[pg_test]
+    fn test_typeid_suffix() {
+        let suffixes = Spi::get_one::<Vec<String>>(
+            "SELECT ARRAY[
+                typeid_suffix('user_account_01h455vb4pex5vsknk084sn02q'),
+                typeid_suffix('01h455vb4pex5vsknk084sn02q')
+             ]",
+        );
+        assert_eq!(
+            suffixes,
+            Ok(Some(vec!["01h455vb4pex5vsknk084sn02q".to_string(); 2]))
+        );
+    }
+
+    #[pg_test]
+    fn test_typeid_first_per_prefix() {
+        Spi::run("CREATE TABLE items (item_id typeid PRIMARY KEY)").unwrap();
+        Spi::run(
+            "INSERT INTO items
+             SELECT uuid_to_typeid(p, ('018f0000-' || lpad(to_hex(n), 4, '0') || '-7000-8000-000000000000')::uuid)
+             FROM unnest(ARRAY['user', 'org', 'invoice', '']) p, generate_series(1, 100) n",
+        )
+        .unwrap();
+
+        for (latest, expected) in [(false, "0001"), (true, "0064")] {
+            let firsts = Spi::get_one_with_args::<Vec<String>>(
+                "SELECT array_agg(prefix || ' ' || typeid_to_uuid(id)::text ORDER BY prefix)
+                 FROM typeid_first_per_prefix('items', 'item_id', $1)",
+                vec![(PgBuiltInOids::BOOLOID.oid(), latest.into_datum())],
+            )
+            .unwrap()
+            .unwrap();
+            assert_eq!(
+                firsts,
+                ["", "invoice", "org", "user"]
+                    .map(|prefix| format!("{prefix} 018f0000-{expected}-7000-8000-000000000000"))
+                    .to_vec()
+            );
+        }
+
+        Spi::run("TRUNCATE items").unwrap();
+        let count = Spi::get_one::<i64>(
+            "SELECT count(*) FROM typeid_first_per_prefix('items', 'item_id', latest => true)",
+        );
+        assert_eq!(count, Ok(Some(0)));
+    }
+
+    #[pg_test]
+    fn test_typeid_add_prefix_column() {
+        Spi::run("CREATE TABLE events (event_id typeid PRIMARY KEY)").unwrap();
+
+        let statements = Spi::get_one::<Vec<String>>(
+            "SELECT array_agg(s) FROM typeid_add_prefix_column('events', 'event_id', execute => true) s",
+        )
+        .unwrap()
+        .unwrap();
+        assert!(
+            statements[0].starts_with(
+                "ALTER TABLE events ADD COLUMN event_id_prefix text GENERATED ALWAYS AS ("
+            ),
+            "{}",
+            statements[0]
+        );
+        assert_eq!(statements[1], "CREATE INDEX ON events (event_id_prefix)");
+
+        Spi::run("INSERT INTO events SELECT typeid_generate('click') FROM generate_series(1, 3)")
+            .unwrap();
+        let clicks =
+            Spi::get_one::<i64>("SELECT count(*) FROM events WHERE event_id_prefix = 'click'");
+        assert_eq!(clicks, Ok(Some(3)));
+    }
+
+    #[pg_test]
+    fn test_typeid_add_prefix_column_quotes_identifiers() {
+        Spi::run(r#"CREATE TABLE "Events" ("Event Id" typeid PRIMARY KEY)"#).unwrap();
+
+        let index = Spi::get_one::<String>(
+            r#"SELECT s FROM typeid_add_prefix_column('"Events"', 'Event Id', 'Kind; DROP TABLE x') s OFFSET 1"#,
+        );
+        assert_eq!(
+            index,
+            Ok(Some(
+                r#"CREATE INDEX ON "Events" ("Kind; DROP TABLE x")"#.to_string()
+            ))
+        );
+    }
+
+    #[pg_test]
+    fn test_typeid_prefix_like() {
+        Spi::run("CREATE TABLE items (id typeid primary key)").unwrap();
+        Spi::run(
+            "INSERT INTO items SELECT typeid_generate(p) FROM unnest(ARRAY['inv_item', 'inv_location', 'invoice', 'user']) p, generate_series(1, 50)",
+        )
+        .unwrap();
+        Spi::run("ANALYZE items").unwrap();
+
+        let count = Spi::get_one::<i64>(
+            "SELECT count(*) FROM items WHERE typeid_prefix_like(id, 'inv\\_%')",
+        )
+        .unwrap();
+        assert_eq!(count, Some(100));
+
+        let count =
+            Spi::get_one::<i64>("SELECT count(*) FROM items WHERE typeid_prefix_like(id, 'inv%')")
+                .unwrap();
+        assert_eq!(count, Some(150));
+
+        let count = Spi::get_one::<i64>(
+            "SELECT count(*) FROM items WHERE typeid_prefix_like(id, 'inv_location')",
+        )
+        .unwrap();
+        assert_eq!(count, Some(50));
+
+        Spi::run("SET enable_seqscan = off").unwrap();
+        let plan = Spi::explain("SELECT * FROM items WHERE typeid_prefix_like(id, 'inv\\_%')")
+            .unwrap()
+            .0
+            .to_string();
+        assert!(plan.contains("Index"), "expected an index scan: {plan}");
+    }
+
+    #[pg_test]
+    fn test_typeid_has_prefix() {
+        Spi::run("CREATE TABLE items (id typeid primary key)").unwrap();
+        Spi::run(
+            "INSERT INTO items SELECT typeid_generate(p) FROM unnest(ARRAY['inv', 'invoice', 'user', '']) p, generate_series(1, 50)",
+        )
+        .unwrap();
+        Spi::run("ANALYZE items").unwrap();
+
+        for (prefix, expected) in [("inv", 50), ("invoice", 50), ("", 50), ("org", 0)] {
+            let count = Spi::get_one_with_args::<i64>(
+                "SELECT count(*) FROM items WHERE typeid_has_prefix(id, $1)",
+                vec![(PgBuiltInOids::TEXTOID.oid(), prefix.into_datum())],
+            )
+            .unwrap();
+            assert_eq!(count, Some(expected), "prefix {prefix:?}");
+        }
+
+        // An invalid prefix matches nothing, like any other prefix no id has
+        let invalid = Spi::get_one::<bool>(
+            "SELECT typeid_has_prefix(typeid_generate('user'), 'User')
+                OR typeid_has_prefix(typeid_generate('user'), (SELECT 'user-'))",
+        );
+        assert_eq!(invalid, Ok(Some(false)));
+
+        // Inlined into a range scan, also behind a security barrier view
+        Spi::run(
+            "CREATE VIEW invoices WITH (security_barrier) AS
+             SELECT * FROM items WHERE typeid_has_prefix(id, 'invoice')",
+        )
+        .unwrap();
+        Spi::run("SET enable_seqscan = off").unwrap();
+        let plan = Spi::explain("SELECT * FROM invoices")
+            .unwrap()
+            .0
+            .to_string();
+        assert!(plan.contains("Index"), "expected an index scan: {plan}");
+        assert!(
+            !plan.contains("typeid_has_prefix"),
+            "expected inlining: {plan}"
+        );
+    }
+
+    #[pg_test]
+    fn test_typeid_contains_prefix() {
+        Spi::run("CREATE TABLE items (id typeid primary key)").unwrap();
+        Spi::run(
+            "INSERT INTO items SELECT typeid_generate(p) FROM unnest(ARRAY['inv', 'invoice', 'user', '']) p, generate_series(1, 50)",
+        )
+        .unwrap();
+        Spi::run("ANALYZE items").unwrap();
+
+        for (prefix, expected) in [("inv", 50), ("invoice", 50), ("", 50), ("Inv", 0)] {
+            let count = Spi::get_one_with_args::<i64>(
+                "SELECT count(*) FROM items WHERE id @> $1",
+                vec![(PgBuiltInOids::TEXTOID.oid(), prefix.into_datum())],
+            )
+            .unwrap();
+            assert_eq!(count, Some(expected), "prefix {prefix:?}");
+        }
+
+        // The operator becomes an exact range over the index, without a recheck
+        #[cfg(not(feature = "pg11"))]
+        {
+            Spi::run("SET enable_seqscan = off").unwrap();
+            let plan = Spi::explain("SELECT * FROM items WHERE id @> 'invoice'")
+                .unwrap()
+                .0
+                .to_string();
+            assert!(plan.contains("Index"), "expected an index scan: {plan}");
+            assert!(!plan.contains("Filter"), "expected no recheck: {plan}");
+        }
+    }
+
+    #[pg_test]
+    fn test_typeid_assert_same_prefix() {
+        let same = Spi::get_one::<Vec<bool>>(
+            "SELECT ARRAY[
+                typeid_assert_same_prefix(typeid_generate('user'), typeid_generate('user')),
+                typeid_generate('user') == typeid_generate('user'),
+                'user_01h455vb4pex5vsknk084sn02q'::typeid == 'user_01h455vb4pex5vsknk084sn02q'
+            ]",
+        );
+        assert_eq!(same, Ok(Some(vec![true, false, true])));
+    }
+
+    #[pg_test(error = "cannot relate ids with different prefixes \"order\" and \"user\"")]
+    fn test_typeid_same_prefix_join() {
+        Spi::run("CREATE TABLE users (id typeid PRIMARY KEY)").unwrap();
+        Spi::run("CREATE TABLE orders (id typeid PRIMARY KEY, user_id typeid NOT NULL)").unwrap();
+        Spi::run("INSERT INTO users SELECT typeid_generate('user')").unwrap();
+        Spi::run("INSERT INTO orders SELECT typeid_generate('order'), id FROM users").unwrap();
+
+        // Joined on the wrong column
+        Spi::run("SELECT * FROM orders o JOIN users u ON o.id == u.id").unwrap();
+    }
+
+    #[pg_test]
+    fn test_typeid_same_prefix_join_estimate() {
+        Spi::run("CREATE TABLE users (id typeid PRIMARY KEY)").unwrap();
+        Spi::run("CREATE TABLE orders (id typeid PRIMARY KEY, user_id typeid NOT NULL)").unwrap();
+        Spi::run("INSERT INTO users SELECT typeid_generate_batch('user', 1000)").unwrap();
+        Spi::run("INSERT INTO orders SELECT typeid_generate('order'), id FROM users").unwrap();
+        Spi::run("ANALYZE users, orders").unwrap();
+
+        // Estimated like `=`, one user per order rather than a fixed share of all pairs
+        let plan = Spi::explain("SELECT * FROM orders o JOIN users u ON o.user_id == u.id")
+            .unwrap()
+            .0;
+        let rows = plan[0]["Plan"]["Plan Rows"].as_f64().unwrap();
+        assert!((500.0..=2000.0).contains(&rows), "{rows} rows");
+    }
+}
@@ -0,0 +1,210 @@
+//! Mapping between typeid and an ENUM mirroring the prefix registry, for tools that handle
+//! enums better than text, e.g. to slice by entity type.
+
+use std::ffi::{CStr, CString};
+
+use pgrx::prelude::*;
+use pgrx::{direct_function_call, AnyElement};
+use uuid::Uuid;
+
+use crate::typeid::{TypeID, TypeIDPrefix};
+
+/// Creates the enum type if it doesn't exist and adds every registered prefix which isn't a label
+/// yet, returning the number of labels added. Labels are never removed, so retired and renamed
+/// prefixes keep mapping to their old values. Adding labels to an enum created by an earlier
+/// transaction requires Postgres 12+.
+#[pg_extern(cost = 100)]
+fn typeid_sync_prefix_enum(enum_name: default!(&str, "'typeid_prefix'")) -> i32 {
+    Spi::connect(|mut client| {
+        let quoted = client
+            .select(
+                "SELECT string_agg(quote_ident(part), '.') FROM unnest(parse_ident($1)) part",
+                None,
+                Some(vec![(PgBuiltInOids::TEXTOID.oid(), enum_name.into_datum())]),
+            )?
+            .first()
+            .get_one::<String>()?
+            .unwrap_or_else(|| error!("invalid enum name {enum_name:?}"));
+
+        let existing = client
+            .select(
+                "SELECT to_regtype($1)",
+                None,
+                Some(vec![(PgBuiltInOids::TEXTOID.oid(), quoted.as_str().into_datum())]),
+            )?
+            .first()
+            .get_one::<pg_sys::Oid>()?;
+        match existing {
+            Some(oid) if !unsafe { pg_sys::type_is_enum(oid) } => {
+                error!("type {enum_name:?} exists but is not an enum")
+            }
+            Some(_) => {}
+            None => {
+                client.update(&format!("CREATE TYPE {quoted} AS ENUM ()"), None, None)?;
+            }
+        }
+
+        // Enum labels can't be empty, so TypeIDs without a prefix have no label
+        let missing = client
+            .select(
+                "SELECT prefix FROM typeid_prefix_registry
+                 WHERE prefix <> ''
+                   AND prefix NOT IN (SELECT enumlabel FROM pg_enum WHERE enumtypid = to_regtype($1))
+                 ORDER BY registered_at, prefix",
+                None,
+                Some(vec![(PgBuiltInOids::TEXTOID.oid(), quoted.as_str().into_datum())]),
+            )?
+            .map(|row| row.get::<String>(1))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // The registry checks its prefixes, but rows from before the check or with the check
+        // dropped don't become labels
+        let mut added = 0;
+        for prefix in missing.iter().flatten() {
+            if TypeIDPrefix::new(prefix).is_err() {
+                warning!("skipping invalid prefix {prefix:?} in typeid_prefix_registry");
+                continue;
+            }
+            client.update(
+                &format!("ALTER TYPE {quoted} ADD VALUE {}", quote_literal(prefix)),
+                None,
+                None,
+            )?;
+            added += 1;
+        }
+
+        Ok::<_, pgrx::spi::Error>(added)
+    })
+    .unwrap()
+}
+
+fn quote_literal(value: &str) -> String {
+    let value = CString::new(value).unwrap_or_else(|_| error!("value contains a null byte"));
+    unsafe { CStr::from_ptr(pg_sys::quote_literal_cstr(value.as_ptr())) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn enum_type(typoid: pg_sys::Oid) -> pg_sys::Oid {
+    if typoid == pg_sys::InvalidOid || !unsafe { pg_sys::type_is_enum(typoid) } {
+        error!("expected an enum type");
+    }
+    typoid
+}
+
+/// The prefix of the id as a value of the enum type of the second argument, which is only used
+/// for its type: `typeid_prefix_enum(id, NULL::typeid_prefix)`
+#[pg_extern(stable, parallel_safe)]
+fn typeid_prefix_enum(
+    typeid: Option<TypeID>,
+    enum_type_of: Option<AnyElement>,
+    fcinfo: pg_sys::FunctionCallInfo,
+) -> Option<AnyElement> {
+    let _ = enum_type_of;
+    let typoid = enum_type(unsafe { pg_sys::get_fn_expr_argtype((*fcinfo).flinfo, 1) });
+    let label = CString::new(typeid?.type_prefix()).unwrap();
+
+    let value = unsafe {
+        direct_function_call::<pg_sys::Oid>(
+            pg_sys::enum_in,
+            &[label.as_c_str().into_datum(), typoid.into_datum()],
+        )
+    }?;
+    unsafe { AnyElement::from_polymorphic_datum(value.into_datum()?, false, typoid) }
+}
+
+/// Builds a TypeID from a prefix enum value and a UUID
+#[pg_extern(stable, parallel_safe)]
+fn typeid_from_enum(prefix: AnyElement, uuid: pgrx::Uuid) -> TypeID {
+    enum_type(prefix.oid());
+    let label = unsafe { direct_function_call::<&CStr>(pg_sys::enum_out, &[Some(prefix.datum())]) }
+        .unwrap_or_else(|| error!("enum value has no label"));
+
+    let prefix = label
+        .to_str()
+        .ok()
+        .and_then(|label| TypeIDPrefix::new(label).ok())
+        .unwrap_or_else(|| error!("enum label {label:?} is not a valid prefix"));
+    TypeID::new(prefix, Uuid::from_bytes(*uuid.as_bytes()))
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    #[pg_test]
+    fn test_typeid_prefix_enum() {
+        Spi::run("SELECT typeid_register_prefix('user')").unwrap();
+        Spi::run("SELECT typeid_register_prefix('post')").unwrap();
+
+        assert_eq!(
+            Spi::get_one::<i32>("SELECT typeid_sync_prefix_enum()").unwrap(),
+            Some(2)
+        );
+        assert_eq!(
+            Spi::get_one::<i32>("SELECT typeid_sync_prefix_enum()").unwrap(),
+            Some(0)
+        );
+
+        Spi::run("SELECT typeid_register_prefix('comment')").unwrap();
+        assert_eq!(
+            Spi::get_one::<i32>("SELECT typeid_sync_prefix_enum('typeid_prefix')").unwrap(),
+            Some(1)
+        );
+
+        let labels =
+            Spi::get_one::<String>("SELECT array_to_string(enum_range(NULL::typeid_prefix), ',')")
+                .unwrap();
+        assert_eq!(labels.as_deref(), Some("user,post,comment"));
+
+        let prefix = Spi::get_one::<String>(
+            "SELECT typeid_prefix_enum('post_01h455vb4pex5vsknk084sn02q', NULL::typeid_prefix)::text",
+        )
+        .unwrap();
+        assert_eq!(prefix.as_deref(), Some("post"));
+
+        let typeid = Spi::get_one::<String>(
+            "SELECT typeid_from_enum('post'::typeid_prefix, typeid_to_uuid('post_01h455vb4pex5vsknk084sn02q'))::text",
+        )
+        .unwrap();
+        assert_eq!(typeid.as_deref(), Some("post_01h455vb4pex5vsknk084sn02q"));
+    }
+
+    #[pg_test]
+    fn test_typeid_sync_prefix_enum_skips_invalid_prefixes() {
+        Spi::run("CREATE TABLE victims ()").unwrap();
+        Spi::run("SELECT typeid_register_prefix('user')").unwrap();
+        Spi::run("ALTER TABLE typeid_prefix_registry DROP CONSTRAINT typeid_prefix_registry_prefix_check")
+            .unwrap();
+        Spi::run(
+            "INSERT INTO typeid_prefix_registry (prefix) VALUES ('x''; DROP TABLE victims; --')",
+        )
+        .unwrap();
+
+        assert_eq!(
+            Spi::get_one::<i32>("SELECT typeid_sync_prefix_enum()"),
+            Ok(Some(1))
+        );
+        let labels =
+            Spi::get_one::<String>("SELECT array_to_string(enum_range(NULL::typeid_prefix), ',')");
+        assert_eq!(labels, Ok(Some("user".to_string())));
+        assert_eq!(
+            Spi::get_one::<bool>("SELECT to_regclass('victims') IS NOT NULL"),
+            Ok(Some(true))
+        );
+    }
+
+    #[pg_test(
+        error = "new row for relation \"typeid_prefix_registry\" violates check constraint \"typeid_prefix_registry_prefix_check\""
+    )]
+    fn test_typeid_prefix_registry_checks_prefixes() {
+        Spi::run("INSERT INTO typeid_prefix_registry (prefix) VALUES ('User')").unwrap();
+    }
+
+    #[pg_test(error = "expected an enum type")]
+    fn test_typeid_prefix_enum_requires_enum() {
+        Spi::run("SELECT typeid_prefix_enum('post_01h455vb4pex5vsknk084sn02q', NULL::text)")
+            .unwrap();
+    }
+}
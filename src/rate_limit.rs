@@ -0,0 +1,172 @@
+//! Per-prefix limit on the number of TypeIDs generated per second.
+//!
+//! Counters live in shared memory when the library is in `shared_preload_libraries`, otherwise
+//! every connection counts on its own.
+
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use pgrx::prelude::*;
+use pgrx::{pg_shmem_init, PGRXSharedMemory, PgLwLock, PgSharedMemoryInitialization};
+
+use crate::guc::{self, RateLimitAction};
+
+const SLOTS: usize = 1024;
+/// Number of slots a prefix may occupy, starting at the one its hash points to
+const PROBES: usize = 8;
+
+#[derive(Copy, Clone, Default)]
+struct Slot {
+    key: u64,
+    second: u64,
+    count: u32,
+}
+
+/// Open addressing table of per-second counters keyed by prefix hash
+#[derive(Copy, Clone)]
+pub struct Counters([Slot; SLOTS]);
+
+impl Default for Counters {
+    fn default() -> Self {
+        Self([Slot::default(); SLOTS])
+    }
+}
+
+unsafe impl PGRXSharedMemory for Counters {}
+
+impl Counters {
+    /// Adds `n` to the counter of `key` in `second` and returns the new count. Counters of
+    /// previous seconds are reused, so when all slots of the key are taken by other prefixes in
+    /// the same second the key isn't counted and `None` is returned.
+    fn add(&mut self, key: u64, second: u64, n: u32) -> Option<u32> {
+        let start = key as usize % SLOTS;
+        let slots = (0..PROBES).map(|probe| (start + probe) % SLOTS);
+
+        let index = slots
+            .clone()
+            .find(|&i| self.0[i].key == key && self.0[i].second == second)
+            .or_else(|| slots.clone().find(|&i| self.0[i].second != second))?;
+
+        let slot = &mut self.0[index];
+        if slot.key != key || slot.second != second {
+            *slot = Slot {
+                key,
+                second,
+                count: 0,
+            };
+        }
+        slot.count = slot.count.saturating_add(n);
+        Some(slot.count)
+    }
+}
+
+static COUNTERS: PgLwLock<Counters> = PgLwLock::new();
+static SHARED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static LOCAL_COUNTERS: RefCell<Option<Box<Counters>>> = const { RefCell::new(None) };
+}
+
+/// Reserves the shared counters when the library is preloaded
+pub fn init() {
+    if guc::preloading() {
+        pg_shmem_init!(COUNTERS);
+        SHARED.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Accounts for `n` TypeIDs about to be generated with `prefix`, raising an error or a warning
+/// (depending on `typeid.rate_limit_action`) once `typeid.rate_limit` per second is exceeded
+pub fn check(prefix: &str, n: u32) {
+    let limit = guc::RATE_LIMIT.get();
+    if limit <= 0 {
+        return;
+    }
+    let limit = limit as u32;
+
+    let mut hasher = DefaultHasher::new();
+    prefix.hash(&mut hasher);
+    // Zero marks an unused slot
+    let key = hasher.finish().max(1);
+    let second = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    let count = if SHARED.load(Ordering::Relaxed) {
+        COUNTERS.exclusive().add(key, second, n)
+    } else {
+        LOCAL_COUNTERS.with(|counters| {
+            counters
+                .borrow_mut()
+                .get_or_insert_with(Default::default)
+                .add(key, second, n)
+        })
+    };
+
+    let Some(count) = count else {
+        return;
+    };
+    if count <= limit {
+        return;
+    }
+
+    match guc::RATE_LIMIT_ACTION.get() {
+        RateLimitAction::Error => error!(
+            "generating TypeIDs with prefix {prefix:?} exceeded typeid.rate_limit of {limit} per second"
+        ),
+        // Warn once per second instead of for every id
+        RateLimitAction::Warning if count - n <= limit => warning!(
+            "generating TypeIDs with prefix {prefix:?} exceeded typeid.rate_limit of {limit} per second"
+        ),
+        RateLimitAction::Warning => {}
+    }
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    use super::{Counters, SLOTS};
+
+    #[test]
+    fn test_counters() {
+        let mut counters = Box::<Counters>::default();
+        assert_eq!(counters.add(1, 100, 1), Some(1));
+        assert_eq!(counters.add(1, 100, 2), Some(3));
+        assert_eq!(counters.add(1, 101, 1), Some(1));
+
+        // Keys pointing at the same slot take the next ones
+        let colliding: Vec<u64> = (0..8).map(|i| 2 + i * SLOTS as u64).collect();
+        for key in &colliding {
+            assert_eq!(counters.add(*key, 101, 1), Some(1));
+        }
+        assert_eq!(counters.add(2 + 8 * SLOTS as u64, 101, 1), None);
+        // ...until their second is over
+        assert_eq!(counters.add(2 + 8 * SLOTS as u64, 102, 1), Some(1));
+    }
+
+    #[pg_test(
+        error = "generating TypeIDs with prefix \"user\" exceeded typeid.rate_limit of 5 per second"
+    )]
+    fn test_rate_limit_error() {
+        Spi::run("SET typeid.rate_limit = 5").unwrap();
+        Spi::run("SELECT count(typeid_generate('user')) FROM generate_series(1, 100)").unwrap();
+    }
+
+    #[pg_test]
+    fn test_rate_limit_warning() {
+        Spi::run("SET typeid.rate_limit = 5").unwrap();
+        Spi::run("SET typeid.rate_limit_action = 'warning'").unwrap();
+
+        let count = Spi::get_one::<i64>(
+            "SELECT count(typeid_generate('user')) FROM generate_series(1, 100)",
+        )
+        .unwrap();
+        assert_eq!(count, Some(100));
+    }
+}
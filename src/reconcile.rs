@@ -0,0 +1,196 @@
+//! Reconciling typeid columns of different tables, e.g. replicas and downstream caches.
+
+use pgrx::prelude::*;
+use pgrx::PgRelation;
+use uuid::Uuid;
+
+use crate::catalog::{check_typeid_column, ColumnName};
+use crate::stream::QueryStream;
+use crate::time::timestamptz_to_millis;
+use crate::typeid::{TypeID, TypeIDPrefix};
+
+/// Inclusive bounds of the ids with the prefix and timestamps between `start` and `end`
+fn prefix_bounds(
+    prefix: &str,
+    start: Option<TimestampWithTimeZone>,
+    end: Option<TimestampWithTimeZone>,
+) -> Option<(TypeID, TypeID)> {
+    let prefix = match TypeIDPrefix::new(prefix) {
+        Ok(prefix) => prefix,
+        Err(err) => error!("invalid prefix: {err}"),
+    };
+    let millis = |timestamp: TimestampWithTimeZone| {
+        timestamptz_to_millis(timestamp)
+            .unwrap_or_else(|| error!("time range must be finite and after 1970"))
+    };
+
+    let lower = TypeID::lower_bound_at(prefix.clone(), start.map(millis).unwrap_or(0));
+    let upper = match end.map(millis) {
+        Some(end) => TypeID::lower_bound_at(prefix.clone(), end)
+            .uuid()
+            .as_u128()
+            .checked_sub(1)?,
+        None => u128::MAX,
+    };
+
+    Some((lower, TypeID::new(prefix, Uuid::from_u128(upper))))
+}
+
+/// Ids present in only one of two typeid columns, along with the table they are in. Limiting the
+/// comparison to a prefix, and optionally a time range of it, turns it into index range scans.
+#[pg_extern(cost = 10000)]
+#[allow(clippy::type_complexity)]
+fn typeid_diff(
+    a: PgRelation,
+    a_column: ColumnName,
+    b: PgRelation,
+    b_column: ColumnName,
+    prefix: default!(Option<&str>, "NULL"),
+    start: default!(Option<TimestampWithTimeZone>, "NULL"),
+    end: default!(Option<TimestampWithTimeZone>, "NULL"),
+) -> TableIterator<'static, (name!(id, TypeID), name!(only_in, String))> {
+    check_typeid_column(&a, a_column.as_str());
+    check_typeid_column(&b, b_column.as_str());
+
+    let bounds = match prefix {
+        Some(prefix) => match prefix_bounds(prefix, start, end) {
+            Some(bounds) => Some(bounds),
+            None => return TableIterator::new(Vec::new()),
+        },
+        None if start.is_some() || end.is_some() => {
+            error!("a time range can only be used together with a prefix")
+        }
+        None => None,
+    };
+    let (lower, upper) = bounds.unzip();
+
+    let query = Spi::connect(|client| {
+        client
+            .select(
+                "SELECT format(
+                    'SELECT a.%2$I, %5$L FROM %1$s a
+                     WHERE ($1 IS NULL OR a.%2$I BETWEEN $1 AND $2)
+                       AND NOT EXISTS (SELECT FROM %3$s b WHERE b.%4$I = a.%2$I)
+                     UNION ALL
+                     SELECT b.%4$I, %6$L FROM %3$s b
+                     WHERE ($1 IS NULL OR b.%4$I BETWEEN $1 AND $2)
+                       AND NOT EXISTS (SELECT FROM %1$s a WHERE a.%2$I = b.%4$I)',
+                    $1::regclass, $2, $3::regclass, $4, $1::regclass::text, $3::regclass::text
+                 )",
+                None,
+                Some(vec![
+                    (PgBuiltInOids::OIDOID.oid(), a.oid().into_datum()),
+                    (PgBuiltInOids::TEXTOID.oid(), a_column.as_str().into_datum()),
+                    (PgBuiltInOids::OIDOID.oid(), b.oid().into_datum()),
+                    (PgBuiltInOids::TEXTOID.oid(), b_column.as_str().into_datum()),
+                ]),
+            )?
+            .first()
+            .get_one::<String>()
+    })
+    .unwrap()
+    .unwrap_or_default();
+
+    // The differences of huge tables are streamed rather than collected first
+    TableIterator::new(QueryStream::open(
+        &query,
+        vec![
+            (TypeID::type_oid().into(), lower.into_datum()),
+            (TypeID::type_oid().into(), upper.into_datum()),
+        ],
+        |row| {
+            Ok((
+                row.get::<TypeID>(1)?
+                    .unwrap_or_else(|| error!("unexpected NULL id")),
+                row.get::<String>(2)?.unwrap_or_default(),
+            ))
+        },
+    ))
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    #[pg_test]
+    fn test_typeid_diff() {
+        Spi::run("CREATE TABLE primary_users (id typeid PRIMARY KEY)").unwrap();
+        Spi::run("CREATE TABLE cached_users (user_id typeid PRIMARY KEY)").unwrap();
+        Spi::run(
+            "INSERT INTO primary_users VALUES
+                (uuid_to_typeid('user', '018f0000-0000-7000-8000-000000000000')),
+                (uuid_to_typeid('user', '018f0000-ea60-7000-8000-000000000000')),
+                (uuid_to_typeid('org', '018f0000-ea60-7000-8000-000000000000'))",
+        )
+        .unwrap();
+        Spi::run(
+            "INSERT INTO cached_users VALUES
+                (uuid_to_typeid('user', '018f0000-0000-7000-8000-000000000000')),
+                (uuid_to_typeid('user', '018f006d-dd00-7000-8000-000000000000'))",
+        )
+        .unwrap();
+
+        let diff = Spi::get_one::<Vec<String>>(
+            "SELECT array_agg(id::text || ' ' || only_in ORDER BY id)
+             FROM typeid_diff('primary_users', 'id', 'cached_users', 'user_id')",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            diff,
+            vec![
+                "org_01hw001tk0e008000000000000 primary_users",
+                "user_01hw001tk0e008000000000000 primary_users",
+                "user_01hw06vq80e008000000000000 cached_users",
+            ]
+        );
+
+        let count = Spi::get_one::<i64>(
+            "SELECT count(*) FROM typeid_diff(
+                'primary_users', 'id', 'cached_users', 'user_id',
+                prefix => 'user', \"end\" => '2024-04-21 10:00:00 UTC'
+             )",
+        )
+        .unwrap();
+        assert_eq!(count, Some(1));
+    }
+
+    #[pg_test]
+    fn test_typeid_diff_streams_batches() {
+        Spi::run("CREATE TABLE events (id typeid PRIMARY KEY)").unwrap();
+        Spi::run("CREATE TABLE archived_events (id typeid PRIMARY KEY)").unwrap();
+        Spi::run("INSERT INTO events SELECT typeid_generate_batch('event', 2500)").unwrap();
+
+        let (count, distinct) = Spi::get_two::<i64, i64>(
+            "SELECT count(*), count(DISTINCT id) FROM typeid_diff('events', 'id', 'archived_events', 'id')",
+        )
+        .unwrap();
+        assert_eq!(count, Some(2500));
+        assert_eq!(distinct, Some(2500));
+
+        // Stopping early leaves the cursor to the end of the transaction
+        let count = Spi::get_one::<i64>(
+            "SELECT count(*) FROM (
+                SELECT * FROM typeid_diff('events', 'id', 'archived_events', 'id') LIMIT 10
+             ) d",
+        )
+        .unwrap();
+        assert_eq!(count, Some(10));
+    }
+
+    #[pg_test(error = "column \"missing\" of \"users\" does not exist")]
+    fn test_typeid_diff_missing_column() {
+        Spi::run("CREATE TABLE users (id typeid)").unwrap();
+        Spi::run("SELECT typeid_diff('users', 'missing', 'users', 'id')").unwrap();
+    }
+
+    #[pg_test(error = "column \"id FROM users; DROP TABLE users; --\" of \"users\" does not exist")]
+    fn test_typeid_diff_column_injection() {
+        Spi::run("CREATE TABLE users (id typeid)").unwrap();
+        Spi::run(
+            "SELECT typeid_diff('users', 'id FROM users; DROP TABLE users; --', 'users', 'id')",
+        )
+        .unwrap();
+    }
+}
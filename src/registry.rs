@@ -0,0 +1,368 @@
+//! Registry of the prefixes in use, with an append-only history of every change to it.
+
+use pgrx::prelude::*;
+use pgrx::spi::{self, SpiClient};
+
+use crate::typeid::TypeIDPrefix;
+
+fn valid_prefix(prefix: &str) -> &str {
+    match TypeIDPrefix::new(prefix) {
+        Ok(_) => prefix,
+        Err(err) => error!("invalid prefix: {err}"),
+    }
+}
+
+/// Serializes changes to the registry entries of the prefixes until the end of the transaction, so
+/// concurrent registrations and renames see each other's results. Locks are taken in a fixed
+/// order, which keeps two renames in opposite directions from deadlocking.
+fn lock_prefixes(client: &mut SpiClient<'_>, prefixes: &[&str]) -> spi::Result<()> {
+    let mut prefixes = prefixes.to_vec();
+    prefixes.sort_unstable();
+    prefixes.dedup();
+
+    for prefix in prefixes {
+        client.select(
+            "SELECT pg_catalog.pg_advisory_xact_lock(
+                pg_catalog.hashtext('typeid_prefix_registry'), pg_catalog.hashtext($1)
+             )",
+            None,
+            Some(vec![(PgBuiltInOids::TEXTOID.oid(), prefix.into_datum())]),
+        )?;
+    }
+    Ok(())
+}
+
+/// Whether the prefix is retired, `None` when it isn't registered
+fn retired(client: &SpiClient<'_>, prefix: &str) -> spi::Result<Option<bool>> {
+    client
+        .select(
+            "SELECT retired_at IS NOT NULL FROM typeid_prefix_registry WHERE prefix = $1",
+            None,
+            Some(vec![(PgBuiltInOids::TEXTOID.oid(), prefix.into_datum())]),
+        )?
+        .first()
+        .get_one::<bool>()
+}
+
+/// Adds a prefix to the registry, returning false when it already is registered. Registering is
+/// idempotent, a registered prefix keeps its description. Retired prefixes can't be registered
+/// again, ids generated with them may still be around.
+#[pg_extern(cost = 100)]
+fn typeid_register_prefix(prefix: &str, description: default!(Option<&str>, "NULL")) -> bool {
+    let prefix = valid_prefix(prefix);
+
+    Spi::connect(|mut client| {
+        lock_prefixes(&mut client, &[prefix])?;
+        match retired(&client, prefix)? {
+            Some(true) => error!("prefix {prefix:?} is retired and can't be registered again"),
+            Some(false) => return Ok(false),
+            None => {}
+        }
+
+        // ON CONFLICT also covers REPEATABLE READ transactions, whose snapshot may predate the
+        // registration that held the lock
+        let inserted = client.update(
+            "INSERT INTO typeid_prefix_registry (prefix, description) VALUES ($1, $2)
+             ON CONFLICT (prefix) DO NOTHING
+             RETURNING prefix",
+            None,
+            Some(vec![
+                (PgBuiltInOids::TEXTOID.oid(), prefix.into_datum()),
+                (PgBuiltInOids::TEXTOID.oid(), description.into_datum()),
+            ]),
+        )?;
+        Ok::<_, spi::Error>(!inserted.is_empty())
+    })
+    .unwrap()
+}
+
+/// Renames a registered prefix. Existing TypeIDs keep their prefix, this only changes the registry.
+#[pg_extern(cost = 100)]
+fn typeid_rename_prefix(prefix: &str, new_prefix: &str) {
+    let new_prefix = valid_prefix(new_prefix);
+
+    Spi::connect(|mut client| {
+        lock_prefixes(&mut client, &[prefix, new_prefix])?;
+        if retired(&client, prefix)?.is_none() {
+            error!("prefix {prefix:?} is not registered");
+        }
+        if prefix != new_prefix && retired(&client, new_prefix)?.is_some() {
+            error!("prefix {new_prefix:?} is already registered");
+        }
+
+        client.update(
+            "UPDATE typeid_prefix_registry SET prefix = $2 WHERE prefix = $1",
+            None,
+            Some(vec![
+                (PgBuiltInOids::TEXTOID.oid(), prefix.into_datum()),
+                (PgBuiltInOids::TEXTOID.oid(), new_prefix.into_datum()),
+            ]),
+        )?;
+        Ok::<_, spi::Error>(())
+    })
+    .unwrap()
+}
+
+/// Marks a registered prefix as no longer in use. This only changes the registry: sessions can keep
+/// generating ids with the prefix, and existing ids stay valid.
+#[pg_extern(cost = 100)]
+fn typeid_retire_prefix(prefix: &str) {
+    let retired = Spi::connect(|mut client| {
+        lock_prefixes(&mut client, &[prefix])?;
+        client
+            .update(
+                "UPDATE typeid_prefix_registry SET retired_at = now()
+                 WHERE prefix = $1 AND retired_at IS NULL
+                 RETURNING prefix",
+                None,
+                Some(vec![(PgBuiltInOids::TEXTOID.oid(), prefix.into_datum())]),
+            )
+            .map(|rows| !rows.is_empty())
+    })
+    .unwrap();
+
+    if !retired {
+        error!("prefix {prefix:?} is not registered or already retired");
+    }
+}
+
+/// Every change made to the registry, oldest first, optionally limited to the changes of a single
+/// prefix including renames from or to it
+#[pg_extern(cost = 100)]
+#[allow(clippy::type_complexity)]
+fn typeid_registry_history(
+    prefix: default!(Option<&str>, "NULL"),
+) -> TableIterator<
+    'static,
+    (
+        name!(id, i64),
+        name!(prefix, String),
+        name!(action, String),
+        name!(previous_prefix, Option<String>),
+        name!(changed_at, TimestampWithTimeZone),
+        name!(changed_by, String),
+        name!(acting_role, String),
+    ),
+> {
+    let rows = Spi::connect(|client| {
+        client
+            .select(
+                "SELECT id, prefix, action, previous_prefix, changed_at, changed_by, acting_role
+                 FROM typeid_registry_history
+                 WHERE $1 IS NULL OR prefix = $1 OR previous_prefix = $1
+                 ORDER BY id",
+                None,
+                Some(vec![(PgBuiltInOids::TEXTOID.oid(), prefix.into_datum())]),
+            )?
+            .map(|row| {
+                Ok((
+                    row.get::<i64>(1)?.unwrap_or_default(),
+                    row.get::<String>(2)?.unwrap_or_default(),
+                    row.get::<String>(3)?.unwrap_or_default(),
+                    row.get::<String>(4)?,
+                    row.get::<TimestampWithTimeZone>(5)?
+                        .unwrap_or_else(TimestampWithTimeZone::negative_infinity),
+                    row.get::<String>(6)?.unwrap_or_default(),
+                    row.get::<String>(7)?.unwrap_or_default(),
+                ))
+            })
+            .collect::<Result<Vec<_>, pgrx::spi::Error>>()
+    })
+    .unwrap();
+
+    TableIterator::new(rows)
+}
+
+extension_sql!(
+    r#"
+    CREATE TABLE typeid_prefix_registry (
+        prefix text PRIMARY KEY CHECK (typeid_is_valid_prefix(prefix)),
+        description text,
+        registered_at timestamptz NOT NULL DEFAULT now(),
+        retired_at timestamptz
+    );
+
+    CREATE TABLE typeid_registry_history (
+        id bigserial PRIMARY KEY,
+        prefix text NOT NULL,
+        action text NOT NULL,
+        previous_prefix text,
+        changed_at timestamptz NOT NULL DEFAULT clock_timestamp(),
+        changed_by text NOT NULL DEFAULT session_user,
+        acting_role text NOT NULL DEFAULT current_user
+    );
+
+    CREATE FUNCTION typeid_registry_audit() RETURNS trigger
+    LANGUAGE plpgsql
+    SET search_path FROM CURRENT
+    AS $$
+    BEGIN
+        -- Restores load the registry with COPY and the history after it, which already holds
+        -- these changes
+        IF pg_catalog.current_query() ~* '^\s*COPY\s' THEN
+            RETURN NULL;
+        END IF;
+        IF TG_OP = 'INSERT' THEN
+            INSERT INTO typeid_registry_history (prefix, action) VALUES (NEW.prefix, 'register');
+        ELSIF TG_OP = 'DELETE' THEN
+            INSERT INTO typeid_registry_history (prefix, action) VALUES (OLD.prefix, 'delete');
+        ELSIF NEW.prefix <> OLD.prefix THEN
+            INSERT INTO typeid_registry_history (prefix, action, previous_prefix)
+            VALUES (NEW.prefix, 'rename', OLD.prefix);
+        ELSIF NEW.retired_at IS NOT NULL AND OLD.retired_at IS NULL THEN
+            INSERT INTO typeid_registry_history (prefix, action) VALUES (NEW.prefix, 'retire');
+        ELSE
+            INSERT INTO typeid_registry_history (prefix, action) VALUES (NEW.prefix, 'update');
+        END IF;
+        RETURN NULL;
+    END
+    $$;
+
+    CREATE TRIGGER typeid_registry_audit
+    AFTER INSERT OR UPDATE OR DELETE ON typeid_prefix_registry
+    FOR EACH ROW EXECUTE FUNCTION typeid_registry_audit();
+
+    CREATE FUNCTION typeid_registry_history_append_only() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+    BEGIN
+        RAISE EXCEPTION 'typeid_registry_history is append-only';
+    END
+    $$;
+
+    CREATE TRIGGER typeid_registry_history_append_only
+    BEFORE UPDATE OR DELETE ON typeid_registry_history
+    FOR EACH ROW EXECUTE FUNCTION typeid_registry_history_append_only();
+
+    CREATE TRIGGER typeid_registry_history_no_truncate
+    BEFORE TRUNCATE ON typeid_registry_history
+    FOR EACH STATEMENT EXECUTE FUNCTION typeid_registry_history_append_only();
+
+    SELECT pg_catalog.pg_extension_config_dump('typeid_prefix_registry', '');
+    SELECT pg_catalog.pg_extension_config_dump('typeid_registry_history', '');
+    SELECT pg_catalog.pg_extension_config_dump('typeid_registry_history_id_seq', '');
+    "#,
+    name = "create_prefix_registry",
+    requires = [typeid_is_valid_prefix],
+);
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    #[pg_test]
+    fn test_typeid_registry_history() {
+        Spi::run("SELECT typeid_register_prefix('user', 'Users')").unwrap();
+        Spi::run("SELECT typeid_register_prefix('post')").unwrap();
+        Spi::run("SELECT typeid_rename_prefix('user', 'account')").unwrap();
+        Spi::run("SELECT typeid_retire_prefix('account')").unwrap();
+
+        let actions = Spi::get_one::<Vec<String>>(
+            "SELECT array_agg(action ORDER BY id) FROM typeid_registry_history('user')",
+        )
+        .unwrap();
+        assert_eq!(actions, Some(vec!["register".into(), "rename".into()]));
+
+        let (prefix, previous) = Spi::get_two::<String, String>(
+            "SELECT prefix, previous_prefix FROM typeid_registry_history() WHERE action = 'rename'",
+        )
+        .unwrap();
+        assert_eq!(prefix.as_deref(), Some("account"));
+        assert_eq!(previous.as_deref(), Some("user"));
+
+        let changed_by = Spi::get_one::<bool>(
+            "SELECT bool_and(changed_by = session_user AND acting_role = current_user) FROM typeid_registry_history()",
+        )
+        .unwrap();
+        assert_eq!(changed_by, Some(true));
+    }
+
+    #[pg_test]
+    fn test_typeid_register_prefix_twice() {
+        let registered = Spi::get_one::<Vec<bool>>(
+            "SELECT ARRAY[typeid_register_prefix('user', 'Users'), typeid_register_prefix('user', 'Accounts')]",
+        );
+        assert_eq!(registered, Ok(Some(vec![true, false])));
+
+        let description = Spi::get_one::<String>(
+            "SELECT description FROM typeid_prefix_registry WHERE prefix = 'user'",
+        );
+        assert_eq!(description, Ok(Some("Users".to_string())));
+
+        let registrations = Spi::get_one::<i64>(
+            "SELECT count(*) FROM typeid_registry_history('user') WHERE action = 'register'",
+        );
+        assert_eq!(registrations, Ok(Some(1)));
+    }
+
+    #[pg_test(error = "prefix \"user\" is retired and can't be registered again")]
+    fn test_typeid_register_retired_prefix() {
+        Spi::run("SELECT typeid_register_prefix('user')").unwrap();
+        Spi::run("SELECT typeid_retire_prefix('user')").unwrap();
+        Spi::run("SELECT typeid_register_prefix('user')").unwrap();
+    }
+
+    #[pg_test(error = "prefix \"post\" is already registered")]
+    fn test_typeid_rename_prefix_to_registered() {
+        Spi::run("SELECT typeid_register_prefix('user')").unwrap();
+        Spi::run("SELECT typeid_register_prefix('post')").unwrap();
+        Spi::run("SELECT typeid_rename_prefix('user', 'post')").unwrap();
+    }
+
+    #[pg_test]
+    fn test_typeid_generate_retired_prefix() {
+        Spi::run("SELECT typeid_register_prefix('user')").unwrap();
+        Spi::run("SELECT typeid_retire_prefix('user')").unwrap();
+        let prefix = Spi::get_one::<String>("SELECT typeid_generate('user')::text");
+        assert!(prefix.unwrap().unwrap().starts_with("user_"));
+    }
+
+    #[pg_test]
+    fn test_typeid_registry_restore() {
+        // Dumps and restores a database through separate connections, since the audit trigger
+        // looks at the statement of the client
+        Spi::run(
+            r#"CREATE TEMP TABLE restored (line text);
+            DO $do$
+            BEGIN
+                EXECUTE format(
+                    'COPY restored FROM PROGRAM %L',
+                    format(
+                        $sh$
+                        export PATH=%s:$PATH PGHOST=%s PGPORT=%s
+                        dropdb --if-exists typeid_dump && createdb typeid_dump
+                        dropdb --if-exists typeid_restore && createdb typeid_restore
+                        psql -q -v ON_ERROR_STOP=1 -d typeid_dump \
+                            -c "CREATE EXTENSION typeid" \
+                            -c "SELECT typeid_register_prefix('user')" \
+                            -c "SELECT typeid_rename_prefix('user', 'account')" >/dev/null
+                        pg_dump typeid_dump | psql -q -v ON_ERROR_STOP=1 -d typeid_restore \
+                            >/dev/null 2>&1 \
+                            && psql -At -d typeid_restore -c "SELECT string_agg(action, ',' ORDER BY id) FROM typeid_registry_history" \
+                            || echo failed
+                        dropdb typeid_dump && dropdb typeid_restore
+                        $sh$,
+                        (SELECT setting FROM pg_config WHERE name = 'BINDIR'),
+                        coalesce(
+                            nullif(split_part(current_setting('unix_socket_directories'), ',', 1), ''),
+                            'localhost'
+                        ),
+                        current_setting('port')
+                    )
+                );
+            END
+            $do$"#,
+        )
+        .unwrap();
+
+        let history = Spi::get_one::<String>("SELECT line FROM restored");
+        assert_eq!(history, Ok(Some("register,rename".into())));
+    }
+
+    #[pg_test(error = "typeid_registry_history is append-only")]
+    fn test_typeid_registry_history_append_only() {
+        Spi::run("SELECT typeid_register_prefix('user')").unwrap();
+        Spi::run("DELETE FROM typeid_registry_history").unwrap();
+    }
+}
@@ -0,0 +1,216 @@
+//! Reloading the configuration of running backends. `typeid_reload_config()` signals them like
+//! `pg_reload_conf()`, and `typeid_reload_status()` reports which of them have reloaded since.
+//!
+//! Backends reload the configuration files before their next command, and note the time they did
+//! in shared memory when they commit a transaction afterwards. Without the library in
+//! `shared_preload_libraries` there's no shared memory, and only the current backend is reported.
+
+use std::cell::Cell;
+use std::os::raw::c_void;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use pgrx::prelude::*;
+use pgrx::{pg_shmem_init, PGRXSharedMemory, PgLwLock, PgSharedMemoryInitialization};
+
+use crate::guc;
+
+/// Number of processes whose reloads are tracked, processes numbered beyond aren't reported
+const MAX_PROCS: usize = 1024;
+
+#[derive(Copy, Clone, Default)]
+struct Proc {
+    pid: i32,
+    reloaded_at: pg_sys::TimestampTz,
+}
+
+/// The last reload requested through `typeid_reload_config()`, and the last one of every process,
+/// indexed by its `pgprocno`
+#[derive(Copy, Clone)]
+pub struct Reloads {
+    requested_at: Option<pg_sys::TimestampTz>,
+    procs: [Proc; MAX_PROCS],
+}
+
+impl Default for Reloads {
+    fn default() -> Self {
+        Self {
+            requested_at: None,
+            procs: [Proc::default(); MAX_PROCS],
+        }
+    }
+}
+
+unsafe impl PGRXSharedMemory for Reloads {}
+
+static RELOADS: PgLwLock<Reloads> = PgLwLock::new();
+static SHARED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static REQUESTED_AT: Cell<Option<pg_sys::TimestampTz>> = const { Cell::new(None) };
+    static RECORDED_AT: Cell<Option<pg_sys::TimestampTz>> = const { Cell::new(None) };
+}
+
+/// Reserves the shared reload times and tracks them in every backend when the library is
+/// preloaded
+pub fn init() {
+    if guc::preloading() {
+        pg_shmem_init!(RELOADS);
+        SHARED.store(true, Ordering::Relaxed);
+        unsafe { pg_sys::RegisterXactCallback(Some(xact_callback), std::ptr::null_mut()) };
+    }
+}
+
+#[pg_guard]
+unsafe extern "C" fn xact_callback(event: pg_sys::XactEvent, _arg: *mut c_void) {
+    if event == pg_sys::XactEvent_XACT_EVENT_PRE_COMMIT {
+        record();
+    }
+}
+
+/// Notes the time the current process last loaded the configuration files in shared memory, if it
+/// changed since the last call
+pub fn record() {
+    if !SHARED.load(Ordering::Relaxed) {
+        return;
+    }
+    let (reloaded_at, pid, procno) = unsafe {
+        if pg_sys::MyProc.is_null() {
+            return;
+        }
+        (
+            pg_sys::PgReloadTime,
+            pg_sys::MyProcPid,
+            (*pg_sys::MyProc).pgprocno,
+        )
+    };
+    if RECORDED_AT.get() == Some(reloaded_at) {
+        return;
+    }
+    let Some(index) = usize::try_from(procno)
+        .ok()
+        .filter(|&index| index < MAX_PROCS)
+    else {
+        return;
+    };
+
+    RELOADS.exclusive().procs[index] = Proc { pid, reloaded_at };
+    RECORDED_AT.set(Some(reloaded_at));
+}
+
+/// Signals the postmaster and through it every backend and worker to reload the configuration
+/// files, like `pg_reload_conf()` and with its privileges. Returns the time of the request, which
+/// `typeid_reload_status()` compares the reloads of the backends with.
+#[pg_extern(cost = 100)]
+fn typeid_reload_config() -> TimestampWithTimeZone {
+    // Taken before signalling, a backend may reload before this function returns
+    let requested_at = unsafe { pg_sys::GetCurrentTimestamp() };
+    if Spi::get_one::<bool>("SELECT pg_catalog.pg_reload_conf()") != Ok(Some(true)) {
+        error!("failed to signal the postmaster to reload the configuration");
+    }
+
+    if SHARED.load(Ordering::Relaxed) {
+        RELOADS.exclusive().requested_at = Some(requested_at);
+    } else {
+        REQUESTED_AT.set(Some(requested_at));
+    }
+    TimestampWithTimeZone::try_from(requested_at).unwrap()
+}
+
+/// The backends and typeid workers running now, when they last loaded the configuration files as
+/// far as known, and whether that was after the last `typeid_reload_config()`
+#[pg_extern(cost = 100)]
+fn typeid_reload_status() -> TableIterator<
+    'static,
+    (
+        name!(pid, i32),
+        name!(backend_type, String),
+        name!(reloaded_at, Option<TimestampWithTimeZone>),
+        name!(reloaded, Option<bool>),
+    ),
+> {
+    record();
+    let (requested_at, procs) = if SHARED.load(Ordering::Relaxed) {
+        let reloads = RELOADS.share();
+        let procs = reloads
+            .procs
+            .iter()
+            .filter(|proc| proc.pid != 0)
+            .copied()
+            .collect();
+        (reloads.requested_at, procs)
+    } else {
+        let own = unsafe {
+            Proc {
+                pid: pg_sys::MyProcPid,
+                reloaded_at: pg_sys::PgReloadTime,
+            }
+        };
+        (REQUESTED_AT.get(), vec![own])
+    };
+
+    let timestamp = |time: pg_sys::TimestampTz| TimestampWithTimeZone::try_from(time).unwrap();
+    let pids = procs.iter().map(|proc| proc.pid).collect::<Vec<_>>();
+    let reloaded_at = procs
+        .iter()
+        .map(|proc| timestamp(proc.reloaded_at))
+        .collect::<Vec<_>>();
+
+    let status = Spi::connect(|client| {
+        client
+            .select(
+                "SELECT a.pid, a.backend_type, p.reloaded_at, p.reloaded_at >= $3
+                 FROM pg_catalog.pg_stat_activity a
+                 LEFT JOIN unnest($1::int4[], $2::timestamptz[]) p (pid, reloaded_at) USING (pid)
+                 WHERE a.backend_type = 'client backend' OR a.backend_type LIKE 'typeid%'
+                 ORDER BY a.pid",
+                None,
+                Some(vec![
+                    (PgBuiltInOids::INT4ARRAYOID.oid(), pids.into_datum()),
+                    (
+                        PgBuiltInOids::TIMESTAMPTZARRAYOID.oid(),
+                        reloaded_at.into_datum(),
+                    ),
+                    (
+                        PgBuiltInOids::TIMESTAMPTZOID.oid(),
+                        requested_at.map(timestamp).into_datum(),
+                    ),
+                ]),
+            )?
+            .map(|row| {
+                Ok((
+                    row.get::<i32>(1)?.unwrap_or_default(),
+                    row.get::<String>(2)?.unwrap_or_default(),
+                    row.get::<TimestampWithTimeZone>(3)?,
+                    row.get::<bool>(4)?,
+                ))
+            })
+            .collect::<Result<Vec<_>, pgrx::spi::Error>>()
+    })
+    .unwrap();
+
+    TableIterator::new(status)
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    #[pg_test]
+    fn test_typeid_reload_config() {
+        let before = Spi::get_one::<bool>(
+            "SELECT reloaded FROM typeid_reload_status() WHERE pid = pg_backend_pid()",
+        );
+        assert_eq!(before, Ok(None));
+
+        Spi::run("SELECT typeid_reload_config()").unwrap();
+
+        // The backend reloads before its next command, not within this transaction
+        let (reloaded_at, reloaded) = Spi::get_two::<bool, bool>(
+            "SELECT reloaded_at = pg_conf_load_time(), reloaded
+             FROM typeid_reload_status() WHERE pid = pg_backend_pid()",
+        )
+        .unwrap();
+        assert_eq!((reloaded_at, reloaded), (Some(true), Some(false)));
+    }
+}
@@ -0,0 +1,193 @@
+//! Predefined roles: `typeid_usage` for generating and working with TypeIDs, `typeid_admin` for
+//! the registry, validation and other maintenance helpers.
+//!
+//! Functions for everyday use stay executable by PUBLIC, so revoking that is up to the
+//! administrator. Administrative functions and tables are only available to `typeid_admin`.
+
+use pgrx::prelude::*;
+
+extension_sql!(
+    r#"
+    DO $$
+    BEGIN
+        IF NOT EXISTS (SELECT FROM pg_catalog.pg_roles WHERE rolname = 'typeid_usage') THEN
+            CREATE ROLE typeid_usage NOLOGIN;
+        END IF;
+        IF NOT EXISTS (SELECT FROM pg_catalog.pg_roles WHERE rolname = 'typeid_admin') THEN
+            CREATE ROLE typeid_admin NOLOGIN;
+        END IF;
+    END
+    $$;
+
+    GRANT typeid_usage TO typeid_admin;
+    GRANT USAGE ON SCHEMA @extschema@ TO typeid_usage;
+
+    GRANT EXECUTE ON FUNCTION
+        typeid_generate(text),
+        typeid_generate_map(jsonb),
+        typeid_coalesce_generate(typeid, text),
+        typeid_generate_history(text, timestamptz, timestamptz, bigint, text),
+        typeid_generate_batch(text, bigint),
+        typeid_generate_mixed(jsonb, bigint),
+        typeid_generate_for_tenant(text, bigint),
+        typeid_tenant_of(typeid),
+        typeid_time(internal),
+        typeid_to_uuid(typeid),
+        uuid_to_typeid(text, uuid),
+        uuid_to_typeid_strict(text, uuid, integer),
+        typeid_uuid_generate_v7(),
+        typeid_clamp(typeid, typeid, typeid),
+        typeid_is_valid(text),
+        typeid_is_valid_prefix(text),
+        typeid_prefix_like(typeid, text),
+        typeid_has_prefix(typeid, text),
+        typeid_assert_same_prefix(typeid, typeid),
+        typeid_eq_same_prefix(typeid, typeid),
+        typeid_eq_any(typeid, typeid[]),
+        typeid_series_between(typeid, typeid, interval),
+        typeid_session_gap(typeid, interval),
+        typeid_strictly_increasing(typeid),
+        typeid_prefix_stats(typeid),
+        typeid_prefix_enum(typeid, anyelement),
+        typeid_from_enum(anyelement, uuid),
+        typeid_timestamp_at(typeid, text),
+        typeid_older(typeid, typeid),
+        typeid_newer(typeid, typeid),
+        typeid_shuffle_order(typeid, bigint),
+        typeid_within(typeid, typeid, interval),
+        typeid_array_unique(typeid[]),
+        typeid_array_diff(typeid[], typeid[]),
+        typeid_array_intersect(typeid[], typeid[]),
+        typeid_contains_prefix(typeid, text),
+        typeid_column_prefixes(regclass, name),
+        typeid_hash_partition(typeid, integer),
+        typeid_try_parse(text),
+        typeid_parse(text),
+        typeid_print(text, uuid),
+        typeid_from_composite(anyelement),
+        typeid_timestamp(typeid),
+        typeid_timestamp_millis(typeid),
+        typeid_age(typeid),
+        typeid_suffix(typeid),
+        typeid_unpack(typeid),
+        typeid_uuid_version(typeid),
+        typeid_bucket(typeid, interval),
+        typeid_generate_v4(text),
+        typeid_generate_v8(text, bytea),
+        typeid_generate_series(text, bigint),
+        typeid_generate_sharded(text, integer),
+        typeid_shard(typeid),
+        gen_random_typeid(),
+        typeid_min_for_prefix(text),
+        typeid_max_for_prefix(text),
+        typeid_to_jsonb_parts(typeid),
+        typeid_from_jsonb(jsonb),
+        typeid_from_cdc(text),
+        to_char(typeid, text),
+        typeid_prefix(typeid),
+        typeid_to_struct(typeid),
+        typeid_to_structs(typeid[]),
+        typeid_type_oid(),
+        typeid_type_info(),
+        typeid_dump_format(),
+        typeid_version()
+    TO typeid_usage;
+    GRANT SELECT ON typeid_columns TO typeid_usage;
+
+    REVOKE EXECUTE ON FUNCTION
+        typeid_register_prefix(text, text),
+        typeid_rename_prefix(text, text),
+        typeid_retire_prefix(text),
+        typeid_registry_history(text),
+        typeid_validate_batch(),
+        typeid_sync_prefix_enum(text),
+        typeid_create_monthly_partitions(regclass, text, date, date, boolean),
+        typeid_diff(regclass, name, regclass, name, text, timestamptz, timestamptz),
+        typeid_estimate_rows(regclass, name, tstzrange),
+        typeid_tokenize(typeid, text),
+        typeid_detokenize(typeid, text),
+        typeid_create_tenant_policy(regclass, name, text, boolean),
+        typeid_create_fdw_view(regclass, text, boolean),
+        typeid_bench_setup(integer),
+        typeid_bench_teardown(),
+        typeid_add_prefix_column(regclass, name, name, boolean),
+        typeid_suggest_indexes(regclass, boolean),
+        typeid_first_per_prefix(regclass, name, boolean),
+        typeid_create_composite_cast(regtype),
+        typeid_reload_config(),
+        typeid_reload_status()
+    FROM PUBLIC;
+
+    GRANT EXECUTE ON FUNCTION
+        typeid_register_prefix(text, text),
+        typeid_rename_prefix(text, text),
+        typeid_retire_prefix(text),
+        typeid_registry_history(text),
+        typeid_validate_batch(),
+        typeid_sync_prefix_enum(text),
+        typeid_create_monthly_partitions(regclass, text, date, date, boolean),
+        typeid_diff(regclass, name, regclass, name, text, timestamptz, timestamptz),
+        typeid_estimate_rows(regclass, name, tstzrange),
+        typeid_tokenize(typeid, text),
+        typeid_detokenize(typeid, text),
+        typeid_create_tenant_policy(regclass, name, text, boolean),
+        typeid_create_fdw_view(regclass, text, boolean),
+        typeid_bench_setup(integer),
+        typeid_bench_teardown(),
+        typeid_add_prefix_column(regclass, name, name, boolean),
+        typeid_suggest_indexes(regclass, boolean),
+        typeid_first_per_prefix(regclass, name, boolean),
+        typeid_create_composite_cast(regtype),
+        typeid_reload_config(),
+        typeid_reload_status(),
+        typeid_settings(),
+        typeid_extension_health(),
+        typeid_verify_spec_vectors()
+    TO typeid_admin;
+
+    GRANT SELECT, INSERT, UPDATE, DELETE ON typeid_prefix_registry TO typeid_admin;
+    -- INSERT is needed by the audit and dictionary triggers, which run as the user changing the
+    -- registry
+    GRANT SELECT, INSERT ON typeid_registry_history, typeid_prefix_dictionary TO typeid_admin;
+    GRANT SELECT, INSERT, UPDATE, DELETE
+        ON typeid_validation_results, typeid_validation_progress
+        TO typeid_admin;
+    -- UPDATE of the token is needed by the no-op update resolving concurrent tokenization
+    GRANT SELECT, INSERT, UPDATE (token) ON typeid_tokens TO typeid_admin;
+    GRANT SELECT ON typeid_generation_audit TO typeid_admin;
+    GRANT USAGE ON SEQUENCE typeid_registry_history_id_seq, typeid_validation_results_id_seq
+        TO typeid_admin;
+    "#,
+    name = "create_roles",
+    finalize,
+);
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    #[pg_test]
+    fn test_roles() {
+        let privileges = Spi::get_one::<Vec<bool>>(
+            "SELECT ARRAY[
+                has_function_privilege('typeid_usage', 'typeid_generate(text)', 'EXECUTE'),
+                has_function_privilege('typeid_usage', 'typeid_register_prefix(text, text)', 'EXECUTE'),
+                has_function_privilege('typeid_admin', 'typeid_register_prefix(text, text)', 'EXECUTE'),
+                has_function_privilege('typeid_admin', 'typeid_generate(text)', 'EXECUTE'),
+                has_table_privilege('typeid_usage', 'typeid_prefix_registry', 'SELECT'),
+                has_table_privilege('typeid_admin', 'typeid_registry_history', 'DELETE'),
+                has_function_privilege('typeid_usage', 'typeid_detokenize(typeid, text)', 'EXECUTE'),
+                has_function_privilege('typeid_admin', 'typeid_detokenize(typeid, text)', 'EXECUTE'),
+                has_table_privilege('typeid_usage', 'typeid_tokens', 'SELECT')
+            ]",
+        )
+        .unwrap();
+        assert_eq!(
+            privileges,
+            Some(vec![
+                true, false, true, true, false, false, false, true, false
+            ])
+        );
+    }
+}
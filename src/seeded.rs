@@ -0,0 +1,127 @@
+//! Deterministic generation for test suites and documentation. With `typeid.test_seed` set, the
+//! random bits of new ids come from a PRNG seeded with it, and their timestamps from a clock
+//! starting at 2024-01-01 00:00:00 UTC that steps by a millisecond on every read.
+
+use std::cell::RefCell;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::distributions::{Distribution, Standard};
+use rand::rngs::StdRng;
+use rand::{Rng as _, RngCore, SeedableRng};
+
+use crate::guc;
+
+/// Start of the stepping clock, 2024-01-01 00:00:00 UTC
+const TEST_EPOCH_MILLIS: u64 = 1_704_067_200_000;
+
+struct State {
+    seed: i32,
+    rng: StdRng,
+    millis: u64,
+}
+
+thread_local! {
+    static STATE: RefCell<Option<State>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` on the seeded state, which starts over whenever the seed changes. Returns None without
+/// a seed.
+fn with_state<T>(f: impl FnOnce(&mut State) -> T) -> Option<T> {
+    let seed = guc::TEST_SEED.get();
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        if seed == 0 {
+            *state = None;
+            return None;
+        }
+        if state.as_ref().is_none_or(|state| state.seed != seed) {
+            *state = Some(State {
+                seed,
+                rng: StdRng::seed_from_u64(seed as u64),
+                millis: TEST_EPOCH_MILLIS,
+            });
+        }
+        state.as_mut().map(f)
+    })
+}
+
+/// Whether `typeid.test_seed` is set
+pub fn enabled() -> bool {
+    guc::TEST_SEED.get() != 0
+}
+
+/// The current Unix time in milliseconds, or the next step of the clock with a seed
+pub fn unix_millis() -> u64 {
+    with_state(|state| {
+        state.millis += 1;
+        state.millis - 1
+    })
+    .unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64
+    })
+}
+
+/// Random numbers from the seeded PRNG, or from the thread's generator without a seed
+pub struct SeededRng;
+
+impl RngCore for SeededRng {
+    fn next_u32(&mut self) -> u32 {
+        with_state(|state| state.rng.next_u32()).unwrap_or_else(|| rand::thread_rng().next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        with_state(|state| state.rng.next_u64()).unwrap_or_else(|| rand::thread_rng().next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        if with_state(|state| state.rng.fill_bytes(dest)).is_none() {
+            rand::thread_rng().fill_bytes(dest);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// A random value like `rand::random`, drawn from the seeded PRNG with a seed
+pub fn random<T>() -> T
+where
+    Standard: Distribution<T>,
+{
+    SeededRng.gen()
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    const GENERATE: &str = "SELECT array_agg(id::text ORDER BY n) FROM (
+        SELECT n, typeid_generate('user') id FROM generate_series(1, 3) n
+        UNION ALL SELECT 3 + n, id FROM typeid_generate_batch('user', 3) WITH ORDINALITY ids (id, n)
+        UNION ALL SELECT 7, typeid_generate_v4('user')
+    ) ids";
+
+    #[pg_test]
+    fn test_test_seed() {
+        Spi::run("SET typeid.test_seed = 42").unwrap();
+        let first = Spi::get_one::<Vec<String>>(GENERATE).unwrap().unwrap();
+        Spi::run("SET typeid.test_seed = 7").unwrap();
+        let other = Spi::get_one::<Vec<String>>(GENERATE).unwrap().unwrap();
+        // Changing the seed starts over
+        Spi::run("SET typeid.test_seed = 42").unwrap();
+        let again = Spi::get_one::<Vec<String>>(GENERATE).unwrap().unwrap();
+        assert_eq!(first, again);
+        assert_ne!(first, other);
+
+        let start = Spi::get_one::<bool>(
+            "SELECT typeid_timestamp(typeid_generate('user')) = '2024-01-01 00:00:00.004+00'",
+        );
+        assert_eq!(start, Ok(Some(true)));
+    }
+}
@@ -0,0 +1,194 @@
+//! The official TypeID spec test vectors, embedded so an installation can check itself.
+
+use std::ffi::{CStr, CString};
+use std::panic::AssertUnwindSafe;
+
+use pgrx::pg_sys::panic::CaughtError;
+use pgrx::prelude::*;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::typeid::{TypeID, TypeIDPrefix};
+
+#[derive(Deserialize)]
+struct Valid {
+    name: String,
+    typeid: String,
+    prefix: String,
+    uuid: Uuid,
+}
+
+#[derive(Deserialize)]
+struct Invalid {
+    name: String,
+    typeid: String,
+    description: String,
+}
+
+/// Runs `f` in a subtransaction, turning an error raised by it into its message
+fn catch_error<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    let (memory_context, resource_owner) =
+        unsafe { (pg_sys::CurrentMemoryContext, pg_sys::CurrentResourceOwner) };
+    unsafe { pg_sys::BeginInternalSubTransaction(std::ptr::null()) };
+
+    // Nothing `f` leaves behind is used after an error, the subtransaction is rolled back
+    let mut f = AssertUnwindSafe(Some(f));
+    PgTryBuilder::new(move || {
+        let value = f.take().unwrap()();
+        unsafe {
+            pg_sys::ReleaseCurrentSubTransaction();
+            pg_sys::MemoryContextSwitchTo(memory_context);
+            pg_sys::CurrentResourceOwner = resource_owner;
+        }
+        Ok(value)
+    })
+    .catch_others(|err| {
+        unsafe {
+            pg_sys::MemoryContextSwitchTo(memory_context);
+            pg_sys::RollbackAndReleaseCurrentSubTransaction();
+            pg_sys::MemoryContextSwitchTo(memory_context);
+            pg_sys::CurrentResourceOwner = resource_owner;
+        }
+        let (CaughtError::PostgresError(report)
+        | CaughtError::ErrorReport(report)
+        | CaughtError::RustPanic {
+            ereport: report, ..
+        }) = err;
+        Err(report.message().to_string())
+    })
+    .execute()
+}
+
+/// The input and output functions of the typeid type as installed in the database
+struct TypeIO {
+    input: pg_sys::Oid,
+    ioparam: pg_sys::Oid,
+    output: pg_sys::Oid,
+}
+
+impl TypeIO {
+    fn lookup() -> Self {
+        let mut io = Self {
+            input: pg_sys::InvalidOid,
+            ioparam: pg_sys::InvalidOid,
+            output: pg_sys::InvalidOid,
+        };
+        let mut is_varlena = false;
+        unsafe {
+            pg_sys::getTypeInputInfo(TypeID::type_oid(), &mut io.input, &mut io.ioparam);
+            pg_sys::getTypeOutputInfo(TypeID::type_oid(), &mut io.output, &mut is_varlena);
+        }
+        io
+    }
+
+    fn input(&self, text: &str) -> Result<pg_sys::Datum, String> {
+        let text = CString::new(text).map_err(|err| err.to_string())?;
+        catch_error(|| unsafe {
+            pg_sys::OidInputFunctionCall(self.input, text.as_ptr().cast_mut(), self.ioparam, -1)
+        })
+    }
+
+    fn output(&self, datum: pg_sys::Datum) -> Result<String, String> {
+        catch_error(|| unsafe {
+            CStr::from_ptr(pg_sys::OidOutputFunctionCall(self.output, datum))
+                .to_string_lossy()
+                .into_owned()
+        })
+    }
+}
+
+/// Why the valid vector doesn't round-trip through the input and output functions, if it doesn't
+fn check_valid(io: &TypeIO, test: &Valid) -> Result<(), String> {
+    let datum = io
+        .input(&test.typeid)
+        .map_err(|err| format!("rejected: {err}"))?;
+    let typeid = unsafe { TypeID::from_datum(datum, false) }.expect("input returned NULL");
+    if typeid.type_prefix() != test.prefix {
+        return Err(format!(
+            "expected prefix {:?}, got {:?}",
+            test.prefix,
+            typeid.type_prefix()
+        ));
+    }
+    if *typeid.uuid() != test.uuid {
+        return Err(format!(
+            "expected uuid {}, got {}",
+            test.uuid,
+            typeid.uuid()
+        ));
+    }
+
+    let output = io.output(datum)?;
+    if output != test.typeid {
+        return Err(format!("output {output:?} differs from the input"));
+    }
+
+    let prefix = TypeIDPrefix::new(&test.prefix).map_err(|err| err.to_string())?;
+    let encoded = TypeID::new(prefix, test.uuid)
+        .into_datum()
+        .expect("typeid is never NULL");
+    let output = io.output(encoded)?;
+    if output != test.typeid {
+        return Err(format!("encoding the prefix and uuid gave {output:?}"));
+    }
+    Ok(())
+}
+
+/// Runs the spec test vectors against the typeid input and output functions of this database and
+/// returns the ones that fail, so no rows means the installation conforms to the spec
+#[pg_extern(cost = 1000)]
+fn typeid_verify_spec_vectors() -> TableIterator<
+    'static,
+    (
+        name!(vector, String),
+        name!(input, String),
+        name!(failure, String),
+    ),
+> {
+    let valid: Vec<Valid> = serde_yaml::from_str(include_str!("../tests/spec/valid.yml")).unwrap();
+    let invalid: Vec<Invalid> =
+        serde_yaml::from_str(include_str!("../tests/spec/invalid.yml")).unwrap();
+
+    let io = TypeIO::lookup();
+    let mut failures = Vec::new();
+    for test in valid {
+        if let Err(failure) = check_valid(&io, &test) {
+            failures.push((format!("valid/{}", test.name), test.typeid, failure));
+        }
+    }
+    for test in invalid {
+        if io.input(&test.typeid).is_ok() {
+            failures.push((
+                format!("invalid/{}", test.name),
+                test.typeid,
+                format!("accepted: {}", test.description),
+            ));
+        }
+    }
+
+    TableIterator::new(failures)
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    #[pg_test]
+    fn test_typeid_verify_spec_vectors() {
+        let failures = Spi::get_one::<i64>("SELECT count(*) FROM typeid_verify_spec_vectors()");
+        assert_eq!(failures, Ok(Some(0)));
+
+        // The transaction is still usable after the invalid vectors raised errors
+        assert_eq!(Spi::get_one::<i32>("SELECT 1"), Ok(Some(1)));
+    }
+
+    #[pg_test]
+    fn test_catch_error() {
+        assert_eq!(super::catch_error(|| 42), Ok(42));
+        assert_eq!(
+            super::catch_error(|| -> i32 { error!("boom") }),
+            Err("boom".to_string())
+        );
+    }
+}
@@ -0,0 +1,309 @@
+//! SP-GiST operator class indexing typeids as a radix tree over their text form, e.g.
+//! `CREATE INDEX ON items USING spgist (id)`.
+//!
+//! Ids of a prefix share the first levels of the tree, so `id @> 'user'` only descends into the
+//! branch of `user_`. The tree is the one of the built-in SP-GiST operator class of text: typeids
+//! are compressed to their text form, which the built-in functions choose nodes and split pages
+//! by, and the conditions on typeids are passed on to them as conditions on the text. Leaves of
+//! another type than the indexed one need Postgres 14.
+
+use std::ffi::c_void;
+use std::os::raw::c_int;
+
+use pgrx::prelude::*;
+use pgrx::{direct_function_call_as_datum, FromDatum, Internal, IntoDatum};
+
+use crate::datum::TypeIDRef;
+
+/// Strategy of `=`, as in the btree operator class
+const STRATEGY_EQUAL: u16 = pg_sys::BTEqualStrategyNumber as u16;
+/// Strategy of `@>`, the one of `^@` in the operator class of text
+const STRATEGY_PREFIX: u16 = pg_sys::RTPrefixStrategyNumber as u16;
+/// Length of the text form of the UUID of an id
+const SUFFIX_LEN: usize = 26;
+
+// The arguments of the SP-GiST support functions, as declared by `access/spgist.h`
+
+#[allow(dead_code)]
+#[repr(C)]
+struct SpgConfigOut {
+    prefix_type: pg_sys::Oid,
+    label_type: pg_sys::Oid,
+    leaf_type: pg_sys::Oid,
+    can_return_data: bool,
+    long_values_ok: bool,
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SpgInnerConsistentIn {
+    scankeys: pg_sys::ScanKey,
+    orderbys: pg_sys::ScanKey,
+    nkeys: c_int,
+    norderbys: c_int,
+    reconstructed_value: pg_sys::Datum,
+    traversal_value: *mut c_void,
+    traversal_memory_context: pg_sys::MemoryContext,
+    level: c_int,
+    return_data: bool,
+    all_the_same: bool,
+    has_prefix: bool,
+    prefix_datum: pg_sys::Datum,
+    n_nodes: c_int,
+    node_labels: *mut pg_sys::Datum,
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+struct SpgLeafConsistentIn {
+    scankeys: pg_sys::ScanKey,
+    orderbys: pg_sys::ScanKey,
+    nkeys: c_int,
+    norderbys: c_int,
+    reconstructed_value: pg_sys::Datum,
+    traversal_value: *mut c_void,
+    level: c_int,
+    return_data: bool,
+    leaf_datum: pg_sys::Datum,
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+struct SpgLeafConsistentOut {
+    leaf_value: pg_sys::Datum,
+    recheck: bool,
+    recheck_distances: bool,
+    distances: *mut f64,
+}
+
+/// Whether the text form of an id has exactly `prefix`. Prefixes may contain underscores, so it's
+/// told by the length of the text, the UUID taking the last 26 characters.
+fn text_has_prefix(text: &[u8], prefix: &str) -> bool {
+    if prefix.is_empty() {
+        return text.len() == SUFFIX_LEN;
+    }
+    text.len() == prefix.len() + 1 + SUFFIX_LEN
+        && text.starts_with(prefix.as_bytes())
+        && text[prefix.len()] == b'_'
+}
+
+unsafe fn scan_keys<'a>(keys: pg_sys::ScanKey, nkeys: c_int) -> &'a [pg_sys::ScanKeyData] {
+    if keys.is_null() || nkeys <= 0 {
+        return &[];
+    }
+    std::slice::from_raw_parts(keys, nkeys as usize)
+}
+
+/// The conditions on the text form the built-in functions can descend the tree by. Ids without a
+/// prefix share no leading text, so their condition is only checked on the leaves. The text form
+/// is compared bytewise, as in the C collation the built-in functions are called with.
+unsafe fn text_scan_keys(keys: &[pg_sys::ScanKeyData]) -> Vec<pg_sys::ScanKeyData> {
+    keys.iter()
+        .filter_map(|key| {
+            let text = match key.sk_strategy {
+                STRATEGY_EQUAL => TypeIDRef::from_datum(key.sk_argument, false)?
+                    .to_typeid()
+                    .to_string(),
+                STRATEGY_PREFIX => match String::from_datum(key.sk_argument, false)? {
+                    prefix if prefix.is_empty() => return None,
+                    prefix => format!("{prefix}_"),
+                },
+                _ => return None,
+            };
+            let mut key = std::ptr::read(key);
+            key.sk_argument = text.into_datum()?;
+            key.sk_collation = pg_sys::C_COLLATION_OID;
+            Some(key)
+        })
+        .collect()
+}
+
+#[pg_extern(immutable, parallel_safe, requires = [TypeID])]
+fn typeid_spg_config(config_in: Internal, config_out: Internal) {
+    unsafe {
+        let (config_in, config_out) = (config_in.unwrap(), config_out.unwrap());
+        direct_function_call_as_datum(pg_sys::spg_text_config, &[config_in, config_out]);
+        let Some(config) = config_out.map(|datum| &mut *datum.cast_mut_ptr::<SpgConfigOut>())
+        else {
+            return;
+        };
+        config.leaf_type = pg_sys::TEXTOID;
+        // Leaves hold the text form, which index-only scans would return in place of typeids
+        config.can_return_data = false;
+    }
+}
+
+#[pg_extern(immutable, parallel_safe, requires = [TypeID])]
+fn typeid_spg_compress(typeid: TypeIDRef<'_>) -> String {
+    typeid.to_typeid().to_string()
+}
+
+#[pg_extern(immutable, parallel_safe, requires = [TypeID])]
+fn typeid_spg_inner_consistent(
+    inner_in: Internal,
+    _inner_out: Internal,
+    fcinfo: pg_sys::FunctionCallInfo,
+) {
+    unsafe {
+        let Some(inner_in) = inner_in.get_mut::<SpgInnerConsistentIn>() else {
+            return;
+        };
+        // Swapped in for the call and back, the caller frees its own keys
+        let mut keys = text_scan_keys(scan_keys(inner_in.scankeys, inner_in.nkeys));
+        let typeid_keys = (inner_in.scankeys, inner_in.nkeys);
+        (inner_in.scankeys, inner_in.nkeys) = (keys.as_mut_ptr(), keys.len() as c_int);
+        (*fcinfo).fncollation = pg_sys::C_COLLATION_OID;
+        pg_sys::spg_text_inner_consistent(fcinfo);
+        (inner_in.scankeys, inner_in.nkeys) = typeid_keys;
+    }
+}
+
+#[pg_extern(immutable, parallel_safe, requires = [TypeID])]
+fn typeid_spg_leaf_consistent(leaf_in: Internal, leaf_out: Internal) -> bool {
+    unsafe {
+        let (Some(leaf_in), Some(leaf_out)) = (
+            leaf_in.get::<SpgLeafConsistentIn>(),
+            leaf_out.get_mut::<SpgLeafConsistentOut>(),
+        ) else {
+            return false;
+        };
+        leaf_out.recheck = false;
+
+        // Leaves hold the text following the one reconstructed by the inner tuples above them
+        let reconstructed = <&[u8]>::from_datum(
+            leaf_in.reconstructed_value,
+            leaf_in.reconstructed_value.is_null(),
+        )
+        .unwrap_or_default();
+        let leaf = <&[u8]>::from_datum(leaf_in.leaf_datum, false).unwrap_or_default();
+        let text = [reconstructed, leaf].concat();
+
+        scan_keys(leaf_in.scankeys, leaf_in.nkeys)
+            .iter()
+            .all(|key| match key.sk_strategy {
+                STRATEGY_EQUAL => TypeIDRef::from_datum(key.sk_argument, false)
+                    .is_some_and(|typeid| typeid.to_typeid().to_string().as_bytes() == text),
+                STRATEGY_PREFIX => String::from_datum(key.sk_argument, false)
+                    .is_some_and(|prefix| text_has_prefix(&text, &prefix)),
+                _ => false,
+            })
+    }
+}
+
+extension_sql!(
+    r#"
+    CREATE OPERATOR CLASS typeid_ops DEFAULT FOR TYPE typeid USING spgist AS
+        OPERATOR 3 = (typeid, typeid),
+        OPERATOR 28 @> (typeid, text),
+        FUNCTION 1 typeid_spg_config(internal, internal),
+        FUNCTION 2 pg_catalog.spg_text_choose(internal, internal),
+        FUNCTION 3 pg_catalog.spg_text_picksplit(internal, internal),
+        FUNCTION 4 typeid_spg_inner_consistent(internal, internal),
+        FUNCTION 5 typeid_spg_leaf_consistent(internal, internal),
+        FUNCTION 6 typeid_spg_compress(typeid),
+        STORAGE text;
+    "#,
+    name = "create_typeid_spgist_operator_class",
+    requires = [
+        "create_typeid_operator_class",
+        "create_typeid_contains_prefix_operator",
+        typeid_spg_config,
+        typeid_spg_compress,
+        typeid_spg_inner_consistent,
+        typeid_spg_leaf_consistent
+    ],
+);
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    #[test]
+    fn test_text_has_prefix() {
+        let suffix = "01h455vb4pex5vsknk084sn02q";
+        assert!(super::text_has_prefix(
+            format!("user_{suffix}").as_bytes(),
+            "user"
+        ));
+        assert!(super::text_has_prefix(suffix.as_bytes(), ""));
+        assert!(!super::text_has_prefix(
+            format!("user_account_{suffix}").as_bytes(),
+            "user"
+        ));
+        assert!(!super::text_has_prefix(
+            format!("user_{suffix}").as_bytes(),
+            ""
+        ));
+        assert!(!super::text_has_prefix(
+            format!("users_{suffix}").as_bytes(),
+            "user"
+        ));
+    }
+
+    #[pg_test]
+    fn test_typeid_spgist_index() {
+        Spi::run("CREATE TABLE items (id typeid)").unwrap();
+        Spi::run(
+            "INSERT INTO items
+             SELECT typeid_generate(p) FROM unnest(ARRAY['user', 'user_account', 'org', '']) p,
+                 generate_series(1, 500)",
+        )
+        .unwrap();
+        Spi::run("INSERT INTO items VALUES ('user_01h455vb4pex5vsknk084sn02q')").unwrap();
+        Spi::run("CREATE INDEX items_id_spgist ON items USING spgist (id)").unwrap();
+        Spi::run("SET enable_seqscan = off").unwrap();
+
+        for (condition, expected) in [
+            ("id @> 'user'", 501),
+            ("id @> 'user_account'", 500),
+            ("id @> ''", 500),
+            ("id @> 'team'", 0),
+            ("id = 'user_01h455vb4pex5vsknk084sn02q'", 1),
+        ] {
+            let plan = Spi::explain(&format!("SELECT * FROM items WHERE {condition}"))
+                .unwrap()
+                .0
+                .to_string();
+            assert!(plan.contains("items_id_spgist"), "{condition}: {plan}");
+
+            let count =
+                Spi::get_one::<i64>(&format!("SELECT count(*) FROM items WHERE {condition}"));
+            assert_eq!(count, Ok(Some(expected)), "{condition}");
+        }
+    }
+
+    #[pg_test]
+    fn test_typeid_spgist_pruning() {
+        Spi::run("CREATE TABLE items (id typeid)").unwrap();
+        Spi::run(
+            "INSERT INTO items
+             SELECT typeid_generate(p) FROM unnest(ARRAY['user', 'org', 'invoice', '']) p,
+                 generate_series(1, 5000)",
+        )
+        .unwrap();
+        Spi::run("CREATE INDEX items_id_spgist ON items USING spgist (id)").unwrap();
+        Spi::run("SET enable_seqscan = off").unwrap();
+        let pages = Spi::get_one::<i64>("SELECT pg_relation_size('items_id_spgist') / 8192")
+            .unwrap()
+            .unwrap();
+
+        // Only the branches leading to the prefix are read, not the whole index
+        for condition in ["id @> 'team'", "id @> 'user_account'"] {
+            let plan = Spi::get_one::<pgrx::Json>(&format!(
+                "EXPLAIN (ANALYZE, BUFFERS, FORMAT JSON) SELECT * FROM items WHERE {condition}"
+            ))
+            .unwrap()
+            .unwrap()
+            .0;
+            let plan = &plan[0]["Plan"];
+            let blocks = plan["Shared Hit Blocks"].as_i64().unwrap()
+                + plan["Shared Read Blocks"].as_i64().unwrap();
+            assert!(
+                blocks * 10 < pages,
+                "{condition}: {blocks} of {pages} pages"
+            );
+        }
+    }
+}
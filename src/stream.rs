@@ -0,0 +1,76 @@
+//! Streaming query results out of set-returning functions.
+//!
+//! Set-returning functions run in value-per-call mode and are asked for one row at a time, but an
+//! SPI connection can't stay open between those calls. `QueryStream` keeps the query open as a
+//! cursor for the rest of the transaction instead and fetches a batch of rows whenever it runs
+//! out, so only one batch is ever held in memory.
+
+use pgrx::prelude::*;
+use pgrx::spi::{self, SpiHeapTupleData};
+
+/// Number of rows fetched from the cursor at once
+const BATCH_SIZE: i64 = 1000;
+
+pub struct QueryStream<T> {
+    /// Name of the open cursor, `None` once all rows have been fetched
+    cursor: Option<String>,
+    batch: std::vec::IntoIter<T>,
+    read: for<'conn> fn(&SpiHeapTupleData<'conn>) -> spi::Result<T>,
+}
+
+impl<T> QueryStream<T> {
+    /// Opens a cursor for the query, `read` converts every row into an owned value
+    pub fn open(
+        query: &str,
+        args: Vec<(PgOid, Option<pg_sys::Datum>)>,
+        read: for<'conn> fn(&SpiHeapTupleData<'conn>) -> spi::Result<T>,
+    ) -> Self {
+        let cursor =
+            Spi::connect(|client| client.open_cursor(query, Some(args)).detach_into_name());
+
+        Self {
+            cursor: Some(cursor),
+            batch: Vec::new().into_iter(),
+            read,
+        }
+    }
+
+    fn fetch(&mut self) -> Option<()> {
+        let name = self.cursor.as_deref()?;
+        let read = self.read;
+
+        let rows = Spi::connect(|client| {
+            let mut cursor = client.find_cursor(name)?;
+            let rows = cursor
+                .fetch(BATCH_SIZE)?
+                .map(|row| read(&row))
+                .collect::<spi::Result<Vec<_>>>()?;
+
+            // Dropping the cursor closes it, keep it open while there may be more rows
+            if !rows.is_empty() {
+                cursor.detach_into_name();
+            }
+            Ok::<_, spi::Error>(rows)
+        })
+        .unwrap();
+
+        if rows.is_empty() {
+            self.cursor = None;
+        }
+        self.batch = rows.into_iter();
+        Some(())
+    }
+}
+
+impl<T> Iterator for QueryStream<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        loop {
+            if let Some(row) = self.batch.next() {
+                return Some(row);
+            }
+            self.fetch()?;
+        }
+    }
+}
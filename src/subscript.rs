@@ -0,0 +1,267 @@
+//! Subscripts reading the parts of a typeid, `id['prefix']` as text and `id['uuid']` as uuid.
+//!
+//! The type of a subscripted value is decided when the query is parsed, so subscripts have to be
+//! constants. They're read-only: ids are values, and assigning to a part would make a new one.
+//! Type subscripting handlers need Postgres 14.
+
+use pgrx::prelude::*;
+use pgrx::{is_a, FromDatum, Internal, IntoDatum, PgList};
+
+use crate::datum::TypeIDRef;
+
+extern "C" {
+    // Not among the bindings of pgrx, from `parser/parse_expr.h`. Raises errors, e.g. for unknown
+    // columns, so it's only called through `pg_guard_ffi_boundary`: `#[pg_guard]` on an extern
+    // block only works within pgrx itself.
+    fn transformExpr(
+        pstate: *mut pg_sys::ParseState,
+        expr: *mut pg_sys::Node,
+        expr_kind: pg_sys::ParseExprKind,
+    ) -> *mut pg_sys::Node;
+}
+
+// The structs of the subscripting API, as declared by `nodes/subscripting.h` and
+// `executor/execExpr.h`
+
+type ExecEvalSubroutine = unsafe extern "C" fn(
+    state: *mut pg_sys::ExprState,
+    op: *mut ExprEvalStep,
+    econtext: *mut pg_sys::ExprContext,
+);
+
+#[repr(C)]
+struct SubscriptRoutines {
+    transform: unsafe extern "C" fn(
+        sbsref: *mut pg_sys::SubscriptingRef,
+        indirection: *mut pg_sys::List,
+        pstate: *mut pg_sys::ParseState,
+        is_slice: bool,
+        is_assignment: bool,
+    ),
+    exec_setup: unsafe extern "C" fn(
+        sbsref: *const pg_sys::SubscriptingRef,
+        sbsrefstate: *mut std::ffi::c_void,
+        methods: *mut SubscriptExecSteps,
+    ),
+    fetch_strict: bool,
+    fetch_leakproof: bool,
+    store_leakproof: bool,
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+struct SubscriptExecSteps {
+    sbs_check_subscripts: Option<unsafe extern "C" fn()>,
+    sbs_fetch: Option<ExecEvalSubroutine>,
+    sbs_assign: Option<ExecEvalSubroutine>,
+    sbs_fetch_old: Option<ExecEvalSubroutine>,
+}
+
+/// The leading fields of a step of an expression, enough for the fetch step
+#[allow(dead_code)]
+#[repr(C)]
+struct ExprEvalStep {
+    opcode: isize,
+    resvalue: *mut pg_sys::Datum,
+    resnull: *mut bool,
+}
+
+static ROUTINES: SubscriptRoutines = SubscriptRoutines {
+    transform,
+    exec_setup,
+    // NULL ids have NULL parts, and reading them never fails
+    fetch_strict: true,
+    fetch_leakproof: true,
+    store_leakproof: false,
+};
+
+/// The type of the part named by a subscript
+fn part_type(part: &str) -> Option<pg_sys::Oid> {
+    match part {
+        "prefix" => Some(pg_sys::TEXTOID),
+        "uuid" => Some(pg_sys::UUIDOID),
+        _ => None,
+    }
+}
+
+fn invalid_subscript() -> ! {
+    ereport!(
+        ERROR,
+        PgSqlErrorCode::ERRCODE_DATATYPE_MISMATCH,
+        "typeid subscript must be the constant 'prefix' or 'uuid'"
+    );
+}
+
+#[pg_guard]
+unsafe extern "C" fn transform(
+    sbsref: *mut pg_sys::SubscriptingRef,
+    indirection: *mut pg_sys::List,
+    pstate: *mut pg_sys::ParseState,
+    is_slice: bool,
+    is_assignment: bool,
+) {
+    if is_assignment {
+        ereport!(
+            ERROR,
+            PgSqlErrorCode::ERRCODE_FEATURE_NOT_SUPPORTED,
+            "typeid subscripts are read-only"
+        );
+    }
+    let indices = PgList::<pg_sys::A_Indices>::from_pg(indirection);
+    let Some(index) = indices
+        .get_ptr(0)
+        .filter(|index| !is_slice && indices.len() == 1 && !(**index).uidx.is_null())
+    else {
+        ereport!(
+            ERROR,
+            PgSqlErrorCode::ERRCODE_DATATYPE_MISMATCH,
+            "typeid takes a single subscript, e.g. id['prefix']"
+        );
+    };
+
+    let expr = pg_sys::ffi::pg_guard_ffi_boundary(|| {
+        transformExpr(pstate, (*index).uidx, (*pstate).p_expr_kind)
+    });
+    let expr = pg_sys::coerce_to_target_type(
+        pstate,
+        expr,
+        pg_sys::exprType(expr),
+        pg_sys::TEXTOID,
+        -1,
+        pg_sys::CoercionContext_COERCION_ASSIGNMENT,
+        pg_sys::CoercionForm_COERCE_IMPLICIT_CAST,
+        -1,
+    );
+    if expr.is_null() || !is_a(expr, pg_sys::NodeTag::T_Const) {
+        invalid_subscript();
+    }
+    let part = expr.cast::<pg_sys::Const>();
+    let Some(restype) = String::from_datum((*part).constvalue, (*part).constisnull)
+        .as_deref()
+        .and_then(part_type)
+    else {
+        invalid_subscript();
+    };
+
+    (*sbsref).refupperindexpr = pg_sys::lappend(std::ptr::null_mut(), expr.cast());
+    (*sbsref).reflowerindexpr = std::ptr::null_mut();
+    (*sbsref).refrestype = restype;
+    (*sbsref).reftypmod = -1;
+}
+
+#[pg_guard]
+unsafe extern "C" fn exec_setup(
+    sbsref: *const pg_sys::SubscriptingRef,
+    _sbsrefstate: *mut std::ffi::c_void,
+    methods: *mut SubscriptExecSteps,
+) {
+    // The part was chosen by the type of the result, the subscript itself is a constant
+    (*methods).sbs_check_subscripts = None;
+    (*methods).sbs_fetch = Some(match (*sbsref).refrestype {
+        pg_sys::TEXTOID => fetch_prefix,
+        _ => fetch_uuid,
+    });
+    (*methods).sbs_assign = None;
+    (*methods).sbs_fetch_old = None;
+}
+
+/// Replaces the id in the result of the step, which fetching starts from, with one of its parts
+unsafe fn fetch(op: *mut ExprEvalStep, part: impl FnOnce(TypeIDRef<'_>) -> Option<pg_sys::Datum>) {
+    let datum = TypeIDRef::from_datum(*(*op).resvalue, false).and_then(part);
+    *(*op).resnull = datum.is_none();
+    *(*op).resvalue = datum.unwrap_or(pg_sys::Datum::from(0));
+}
+
+#[pg_guard]
+unsafe extern "C" fn fetch_prefix(
+    _state: *mut pg_sys::ExprState,
+    op: *mut ExprEvalStep,
+    _econtext: *mut pg_sys::ExprContext,
+) {
+    fetch(op, |typeid| typeid.type_prefix().into_datum());
+}
+
+#[pg_guard]
+unsafe extern "C" fn fetch_uuid(
+    _state: *mut pg_sys::ExprState,
+    op: *mut ExprEvalStep,
+    _econtext: *mut pg_sys::ExprContext,
+) {
+    fetch(op, |typeid| {
+        pgrx::Uuid::from_bytes(*typeid.uuid_bytes()).into_datum()
+    });
+}
+
+#[pg_extern(immutable, parallel_safe, requires = [TypeID])]
+fn typeid_subscript_handler(_internal: Internal) -> Internal {
+    Internal::from(Some(pg_sys::Datum::from(
+        &ROUTINES as *const SubscriptRoutines,
+    )))
+}
+
+extension_sql!(
+    r#"
+    ALTER TYPE typeid SET (SUBSCRIPT = typeid_subscript_handler);
+    "#,
+    name = "set_typeid_subscript_handler",
+    requires = [typeid_subscript_handler],
+);
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    #[pg_test]
+    fn test_typeid_subscripts() {
+        Spi::run("CREATE TABLE users (id typeid)").unwrap();
+        Spi::run("INSERT INTO users VALUES ('user_01h455vb4pex5vsknk084sn02q'), (NULL)").unwrap();
+
+        let (prefix, uuid) = Spi::get_two::<String, String>(
+            "SELECT id['prefix'], id['uuid']::text FROM users WHERE id IS NOT NULL",
+        )
+        .unwrap();
+        assert_eq!(prefix.as_deref(), Some("user"));
+        assert_eq!(
+            uuid.as_deref(),
+            Some("01890a5d-ac96-774b-bcce-b302099a8057")
+        );
+
+        let types = Spi::get_one::<Vec<String>>(
+            "SELECT ARRAY[pg_typeof(id['prefix'])::text, pg_typeof(id['uuid'])::text]
+             FROM users LIMIT 1",
+        );
+        assert_eq!(
+            types,
+            Ok(Some(vec!["text".to_string(), "uuid".to_string()]))
+        );
+
+        let nulls = Spi::get_one::<bool>(
+            "SELECT id['prefix'] IS NULL AND id['uuid'] IS NULL FROM users WHERE id IS NULL",
+        );
+        assert_eq!(nulls, Ok(Some(true)));
+    }
+
+    #[pg_test(error = "typeid subscript must be the constant 'prefix' or 'uuid'")]
+    fn test_typeid_subscript_unknown() {
+        Spi::run("SELECT (typeid_generate('user'))['timestamp']").unwrap();
+    }
+
+    #[pg_test(error = "column \"no_such_column\" does not exist")]
+    fn test_typeid_subscript_unknown_column() {
+        Spi::run("CREATE TABLE users (id typeid)").unwrap();
+        Spi::run("SELECT id[no_such_column] FROM users").unwrap();
+    }
+
+    #[pg_test(error = "typeid subscript must be the constant 'prefix' or 'uuid'")]
+    fn test_typeid_subscript_not_constant() {
+        Spi::run("CREATE TABLE users (id typeid, part text)").unwrap();
+        Spi::run("SELECT id[part] FROM users").unwrap();
+    }
+
+    #[pg_test(error = "typeid subscripts are read-only")]
+    fn test_typeid_subscript_assignment() {
+        Spi::run("CREATE TABLE users (id typeid)").unwrap();
+        Spi::run("UPDATE users SET id['prefix'] = 'org'").unwrap();
+    }
+}
@@ -0,0 +1,338 @@
+//! Planner support functions which turn typeid predicates into btree index conditions.
+
+use pgrx::prelude::*;
+use pgrx::{is_a, FromDatum, Internal, PgBox, PgList};
+use uuid::Uuid;
+
+use crate::pattern::PrefixPattern;
+use crate::time::{tolerance_millis, within_bounds};
+use crate::typeid::{TypeID, TypeIDPrefix};
+
+/// A bound of a range over the default btree opclass
+struct Bound {
+    typeid: TypeID,
+    inclusive: bool,
+}
+
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_prefix_like_support(arg: Internal) -> Internal {
+    unsafe {
+        let conditions = index_condition_request(request(arg)).and_then(|(req, args)| {
+            if (*req).indexarg != 0 {
+                return None;
+            }
+            let (lower, upper, lossy) = prefix_like_range(&args)?;
+            range_condition(req, &args, lower, upper, lossy)
+        });
+        reply(conditions.map(|list| list.cast()))
+    }
+}
+
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_eq_any_support(arg: Internal) -> Internal {
+    unsafe {
+        let node = request(arg);
+
+        if is_a(node, pg_sys::NodeTag::T_SupportRequestSelectivity) {
+            let req = node.cast::<pg_sys::SupportRequestSelectivity>();
+            let typcache =
+                pg_sys::lookup_type_cache(TypeID::type_oid(), pg_sys::TYPECACHE_EQ_OPR as i32);
+            if (*typcache).eq_opr == pg_sys::InvalidOid {
+                return reply(None);
+            }
+
+            // Estimate it exactly like the `id = ANY(ids)` it stands for
+            let clause = eq_any_clause((*typcache).eq_opr, (*req).args);
+            (*req).selectivity = pg_sys::scalararraysel(
+                (*req).root,
+                clause,
+                (*req).is_join,
+                (*req).varRelid,
+                (*req).jointype,
+                (*req).sjinfo,
+            );
+            return reply(Some(node.cast()));
+        }
+
+        let condition = index_condition_request(node).and_then(|(req, args)| {
+            if (*req).indexarg != 0 {
+                return None;
+            }
+            let typoid = TypeID::type_oid();
+            let opno = pg_sys::get_opfamily_member(
+                (*req).opfamily,
+                typoid,
+                typoid,
+                pg_sys::BTEqualStrategyNumber as i16,
+            );
+            if opno == pg_sys::InvalidOid {
+                return None;
+            }
+
+            let mut conditions = PgList::<pg_sys::ScalarArrayOpExpr>::new();
+            conditions.push(eq_any_clause(opno, args.as_ptr()));
+            (*req).lossy = false;
+            Some(conditions.into_pg())
+        });
+        reply(condition.map(|list| list.cast()))
+    }
+}
+
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_within_support(arg: Internal) -> Internal {
+    unsafe {
+        let conditions = index_condition_request(request(arg)).and_then(|(req, args)| {
+            // Either id can be the indexed one, the other has to be a constant
+            let other = match (*req).indexarg {
+                0 => 1,
+                1 => 0,
+                _ => return None,
+            };
+            let typeid = const_arg::<TypeID>(&args, other)?;
+            let tolerance = tolerance_millis(const_arg::<pgrx::Interval>(&args, 2)?)?;
+
+            let (lower, upper) = within_bounds(&typeid, tolerance);
+            range_condition(
+                req,
+                &args,
+                Bound {
+                    typeid: lower,
+                    inclusive: true,
+                },
+                Bound {
+                    typeid: upper,
+                    inclusive: true,
+                },
+                false,
+            )
+        });
+        reply(conditions.map(|list| list.cast()))
+    }
+}
+
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_contains_prefix_support(arg: Internal) -> Internal {
+    unsafe {
+        let conditions = index_condition_request(request(arg)).and_then(|(req, args)| {
+            if (*req).indexarg != 0 {
+                return None;
+            }
+            let prefix = const_arg::<&str>(&args, 1)?;
+            if !TypeIDPrefix::is_valid(prefix) {
+                return None;
+            }
+
+            let prefix = TypeIDPrefix::try_unsafe(prefix);
+            range_condition(
+                req,
+                &args,
+                Bound {
+                    typeid: TypeID::new(prefix.clone(), Uuid::nil()),
+                    inclusive: true,
+                },
+                Bound {
+                    typeid: TypeID::new(prefix, Uuid::max()),
+                    inclusive: true,
+                },
+                false,
+            )
+        });
+        reply(conditions.map(|list| list.cast()))
+    }
+}
+
+/// Bounds of the prefixes matched by the constant pattern of `typeid_prefix_like(id, pattern)`
+unsafe fn prefix_like_range(args: &PgList<pg_sys::Node>) -> Option<(Bound, Bound, bool)> {
+    let pattern = const_arg::<&str>(args, 1)?;
+    let pattern = PrefixPattern::parse(pattern).ok()?;
+    let literal = pattern.literal_prefix();
+
+    if literal.is_empty()
+        || literal.len() > 63
+        || !literal.iter().all(|b| matches!(b, b'a'..=b'z' | b'_'))
+    {
+        return None;
+    }
+    let literal = String::from_utf8(literal).ok()?;
+
+    if pattern.is_exact() {
+        return Some((
+            Bound {
+                typeid: TypeID::new(TypeIDPrefix::try_unsafe(&literal), Uuid::nil()),
+                inclusive: true,
+            },
+            Bound {
+                typeid: TypeID::new(TypeIDPrefix::try_unsafe(&literal), Uuid::max()),
+                inclusive: true,
+            },
+            false,
+        ));
+    }
+
+    // Every prefix starting with the literal sorts below the literal with its last byte bumped
+    let mut upper = literal.clone().into_bytes();
+    *upper.last_mut()? += 1;
+    let upper = String::from_utf8(upper).ok()?;
+
+    Some((
+        Bound {
+            typeid: TypeID::new(TypeIDPrefix::try_unsafe(&literal), Uuid::nil()),
+            inclusive: true,
+        },
+        Bound {
+            typeid: TypeID::new(TypeIDPrefix::try_unsafe(&upper), Uuid::nil()),
+            inclusive: false,
+        },
+        true,
+    ))
+}
+
+/// The node passed to a support function
+unsafe fn request(arg: Internal) -> *mut pg_sys::Node {
+    arg.unwrap()
+        .map(|datum| datum.cast_mut_ptr::<pg_sys::Node>())
+        .unwrap_or(std::ptr::null_mut())
+}
+
+/// A support function returns a null pointer when it can't handle the request
+fn reply(result: Option<*mut pg_sys::Node>) -> Internal {
+    let pointer = result.unwrap_or(std::ptr::null_mut());
+    Internal::from(Some(pg_sys::Datum::from(pointer)))
+}
+
+/// Matches a `SupportRequestIndexCondition` for a btree index on an argument of the function, or
+/// of the operator it backs, returning the request together with the arguments
+unsafe fn index_condition_request(
+    node: *mut pg_sys::Node,
+) -> Option<(
+    *mut pg_sys::SupportRequestIndexCondition,
+    PgList<pg_sys::Node>,
+)> {
+    if !is_a(node, pg_sys::NodeTag::T_SupportRequestIndexCondition) {
+        return None;
+    }
+
+    let req = node.cast::<pg_sys::SupportRequestIndexCondition>();
+    if (*(*req).index).relam != pg_sys::BTREE_AM_OID {
+        return None;
+    }
+
+    let args = if is_a((*req).node, pg_sys::NodeTag::T_FuncExpr) {
+        (*(*req).node.cast::<pg_sys::FuncExpr>()).args
+    } else if is_a((*req).node, pg_sys::NodeTag::T_OpExpr) {
+        (*(*req).node.cast::<pg_sys::OpExpr>()).args
+    } else {
+        return None;
+    };
+    Some((req, PgList::<pg_sys::Node>::from_pg(args)))
+}
+
+/// Builds `indexed >(=) lower AND indexed <(=) upper` for the indexed argument using the operators
+/// of the index opfamily
+unsafe fn range_condition(
+    req: *mut pg_sys::SupportRequestIndexCondition,
+    args: &PgList<pg_sys::Node>,
+    lower: Bound,
+    upper: Bound,
+    lossy: bool,
+) -> Option<*mut pg_sys::List> {
+    let indexed = args.get_ptr((*req).indexarg as usize)?;
+    let typoid = TypeID::type_oid();
+
+    let mut conditions = PgList::<pg_sys::Expr>::new();
+    for (bound, strategy) in [
+        (
+            lower,
+            [
+                pg_sys::BTGreaterStrategyNumber,
+                pg_sys::BTGreaterEqualStrategyNumber,
+            ],
+        ),
+        (
+            upper,
+            [
+                pg_sys::BTLessStrategyNumber,
+                pg_sys::BTLessEqualStrategyNumber,
+            ],
+        ),
+    ] {
+        let strategy = strategy[bound.inclusive as usize];
+        let opno = pg_sys::get_opfamily_member((*req).opfamily, typoid, typoid, strategy as i16);
+        if opno == pg_sys::InvalidOid {
+            return None;
+        }
+
+        let constant = pg_sys::makeConst(
+            typoid,
+            -1,
+            pg_sys::InvalidOid,
+            -1,
+            bound.typeid.into_datum()?,
+            false,
+            false,
+        );
+        conditions.push(pg_sys::make_opclause(
+            opno,
+            pg_sys::BOOLOID,
+            false,
+            indexed.cast(),
+            constant.cast(),
+            pg_sys::InvalidOid,
+            pg_sys::InvalidOid,
+        ));
+    }
+
+    (*req).lossy = lossy;
+    Some(conditions.into_pg())
+}
+
+/// Builds `args[0] = ANY(args[1])`
+unsafe fn eq_any_clause(
+    opno: pg_sys::Oid,
+    args: *mut pg_sys::List,
+) -> *mut pg_sys::ScalarArrayOpExpr {
+    let mut clause =
+        PgBox::<pg_sys::ScalarArrayOpExpr>::alloc_node(pg_sys::NodeTag::T_ScalarArrayOpExpr);
+    clause.opno = opno;
+    clause.opfuncid = pg_sys::get_opcode(opno);
+    clause.useOr = true;
+    clause.inputcollid = pg_sys::InvalidOid;
+    clause.args = pg_sys::list_copy(args);
+    clause.location = -1;
+    clause.into_pg()
+}
+
+/// Reads a non-null constant argument of the function being planned
+unsafe fn const_arg<T: FromDatum>(args: &PgList<pg_sys::Node>, index: usize) -> Option<T> {
+    let arg = args.get_ptr(index)?;
+    if !is_a(arg, pg_sys::NodeTag::T_Const) {
+        return None;
+    }
+
+    let constant = arg.cast::<pg_sys::Const>();
+    T::from_polymorphic_datum(
+        (*constant).constvalue,
+        (*constant).constisnull,
+        (*constant).consttype,
+    )
+}
+
+extension_sql!(
+    r#"
+    ALTER FUNCTION typeid_prefix_like(typeid, text) SUPPORT typeid_prefix_like_support;
+    ALTER FUNCTION typeid_eq_any(typeid, typeid[]) SUPPORT typeid_eq_any_support;
+    ALTER FUNCTION typeid_within(typeid, typeid, interval) SUPPORT typeid_within_support;
+    ALTER FUNCTION typeid_contains_prefix(typeid, text) SUPPORT typeid_contains_prefix_support;
+    "#,
+    name = "typeid_set_support_functions",
+    requires = [
+        prefix::typeid_prefix_like,
+        typeid_prefix_like_support,
+        array::typeid_eq_any,
+        typeid_eq_any_support,
+        time::typeid_within,
+        typeid_within_support,
+        prefix::typeid_contains_prefix,
+        typeid_contains_prefix_support,
+    ],
+);
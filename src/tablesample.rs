@@ -0,0 +1,352 @@
+//! `TABLESAMPLE typeid_time(fraction, start, end)`, sampling a fraction of the blocks of a table
+//! and returning the rows whose primary key was generated between `start` and `end`.
+//!
+//! Blocks are picked like the built-in `SYSTEM` method does, so only the sampled blocks are read.
+//! The time window is checked against the typeid primary key of every tuple in them before
+//! visibility, without evaluating any per-row predicate.
+
+use std::os::raw::c_int;
+
+use pgrx::prelude::*;
+use pgrx::{is_a, FromDatum, Internal, PgBox, PgList};
+
+use crate::time::timestamptz_to_millis;
+use crate::typeid::TypeID;
+
+/// Mirrors `TsmRoutine` from `access/tsmapi.h`, which isn't part of the generated bindings
+#[repr(C)]
+struct TsmRoutine {
+    type_: pg_sys::NodeTag,
+    parameter_types: *mut pg_sys::List,
+    repeatable_across_queries: bool,
+    repeatable_across_scans: bool,
+    sample_scan_get_sample_size: Option<
+        unsafe extern "C" fn(
+            *mut pg_sys::PlannerInfo,
+            *mut pg_sys::RelOptInfo,
+            *mut pg_sys::List,
+            *mut pg_sys::BlockNumber,
+            *mut f64,
+        ),
+    >,
+    init_sample_scan: Option<unsafe extern "C" fn(*mut pg_sys::SampleScanState, c_int)>,
+    begin_sample_scan:
+        Option<unsafe extern "C" fn(*mut pg_sys::SampleScanState, *mut pg_sys::Datum, c_int, u32)>,
+    #[cfg(feature = "pg11")]
+    next_sample_block:
+        Option<unsafe extern "C" fn(*mut pg_sys::SampleScanState) -> pg_sys::BlockNumber>,
+    #[cfg(not(feature = "pg11"))]
+    next_sample_block: Option<
+        unsafe extern "C" fn(
+            *mut pg_sys::SampleScanState,
+            pg_sys::BlockNumber,
+        ) -> pg_sys::BlockNumber,
+    >,
+    next_sample_tuple: Option<
+        unsafe extern "C" fn(
+            *mut pg_sys::SampleScanState,
+            pg_sys::BlockNumber,
+            pg_sys::OffsetNumber,
+        ) -> pg_sys::OffsetNumber,
+    >,
+    end_sample_scan: Option<unsafe extern "C" fn(*mut pg_sys::SampleScanState)>,
+}
+
+/// Used when the fraction isn't known at plan time
+const DEFAULT_FRACTION: f64 = 0.1;
+
+/// Scan state, allocated in the executor's memory context
+#[repr(C)]
+struct SampleState {
+    /// Attribute number of the typeid primary key
+    attnum: i16,
+    seed: u32,
+    fraction: f64,
+    start_millis: u64,
+    end_millis: u64,
+    next_block: pg_sys::BlockNumber,
+    last_offset: pg_sys::OffsetNumber,
+}
+
+impl SampleState {
+    /// Whether a block is part of the sample, deterministic for a seed
+    fn selects(&self, block: pg_sys::BlockNumber) -> bool {
+        let hash = crate::splitmix64(u64::from(self.seed) << 32 | u64::from(block));
+
+        self.fraction >= 1.0 || (hash as f64) < self.fraction * u64::MAX as f64
+    }
+}
+
+#[pg_extern(sql = r#"
+CREATE FUNCTION typeid_time(internal) RETURNS tsm_handler
+    STRICT
+    LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_time_wrapper';
+"#)]
+fn typeid_time(_arg: Internal) -> Internal {
+    unsafe {
+        let mut routine = PgBox::<TsmRoutine>::alloc0();
+        routine.type_ = pg_sys::NodeTag::T_TsmRoutine;
+
+        for typoid in [
+            pg_sys::FLOAT4OID,
+            pg_sys::TIMESTAMPTZOID,
+            pg_sys::TIMESTAMPTZOID,
+        ] {
+            routine.parameter_types = pg_sys::lappend_oid(routine.parameter_types, typoid);
+        }
+
+        routine.repeatable_across_queries = true;
+        routine.repeatable_across_scans = true;
+        routine.sample_scan_get_sample_size = Some(sample_size);
+        routine.init_sample_scan = Some(init_sample_scan);
+        routine.begin_sample_scan = Some(begin_sample_scan);
+        routine.next_sample_block = Some(next_sample_block);
+        routine.next_sample_tuple = Some(next_sample_tuple);
+        routine.end_sample_scan = None;
+
+        Internal::from(Some(pg_sys::Datum::from(routine.into_pg())))
+    }
+}
+
+#[pg_guard]
+unsafe extern "C" fn sample_size(
+    root: *mut pg_sys::PlannerInfo,
+    baserel: *mut pg_sys::RelOptInfo,
+    paramexprs: *mut pg_sys::List,
+    pages: *mut pg_sys::BlockNumber,
+    tuples: *mut f64,
+) {
+    let params = PgList::<pg_sys::Node>::from_pg(paramexprs);
+    let fraction = params
+        .get_ptr(0)
+        .map(|param| pg_sys::estimate_expression_value(root, param))
+        .filter(|param| is_a(*param, pg_sys::NodeTag::T_Const))
+        .map(|param| param.cast::<pg_sys::Const>())
+        .filter(|param| !(**param).constisnull)
+        .and_then(|param| f32::from_datum((*param).constvalue, false))
+        .map(f64::from)
+        .filter(|fraction| (0.0..=1.0).contains(fraction))
+        .unwrap_or(DEFAULT_FRACTION);
+
+    *pages = ((*baserel).pages as f64 * fraction).ceil() as pg_sys::BlockNumber;
+    *tuples = pg_sys::clamp_row_est((*baserel).tuples * fraction);
+}
+
+/// Attribute number of the typeid primary key of a relation, from the relcache
+unsafe fn primary_key_attnum(relation: pg_sys::Relation) -> Option<i16> {
+    // Fills in rd_pkindex as a side effect
+    pg_sys::list_free(pg_sys::RelationGetIndexList(relation));
+    let pkindex = (*relation).rd_pkindex;
+    if pkindex == pg_sys::InvalidOid {
+        return None;
+    }
+
+    let index = pg_sys::index_open(pkindex, pg_sys::AccessShareLock as pg_sys::LOCKMODE);
+    let attnum = (*(*index).rd_index).indkey.values.as_slice(1)[0];
+    pg_sys::index_close(index, pg_sys::AccessShareLock as pg_sys::LOCKMODE);
+
+    let tupdesc = (*relation).rd_att;
+    let attrs = (*tupdesc).attrs.as_slice((*tupdesc).natts as usize);
+    let attribute = attrs.get(usize::try_from(attnum).ok()?.checked_sub(1)?)?;
+    (attribute.atttypid == TypeID::type_oid()).then_some(attnum)
+}
+
+#[pg_guard]
+unsafe extern "C" fn init_sample_scan(node: *mut pg_sys::SampleScanState, _eflags: c_int) {
+    let relation = (*node).ss.ss_currentRelation;
+    let Some(attnum) = primary_key_attnum(relation) else {
+        let name = std::ffi::CStr::from_ptr((*(*relation).rd_rel).relname.data.as_ptr());
+        error!(
+            "TABLESAMPLE typeid_time requires a typeid primary key, {:?} doesn't have one",
+            name.to_string_lossy()
+        );
+    };
+
+    let mut state = PgBox::<SampleState>::alloc0();
+    state.attnum = attnum;
+    (*node).tsm_state = state.into_pg().cast();
+}
+
+#[pg_guard]
+unsafe extern "C" fn begin_sample_scan(
+    node: *mut pg_sys::SampleScanState,
+    params: *mut pg_sys::Datum,
+    _nparams: c_int,
+    seed: u32,
+) {
+    // Tuples are read straight from heap pages, other table access methods lay them out otherwise
+    #[cfg(not(feature = "pg11"))]
+    {
+        let relation = (*node).ss.ss_currentRelation;
+        if (*relation).rd_tableam != pg_sys::GetHeapamTableAmRoutine() {
+            let name = std::ffi::CStr::from_ptr((*(*relation).rd_rel).relname.data.as_ptr());
+            error!(
+                "TABLESAMPLE typeid_time only supports heap tables, {:?} isn't one",
+                name.to_string_lossy()
+            );
+        }
+    }
+
+    let state = &mut *(*node).tsm_state.cast::<SampleState>();
+    let params = std::slice::from_raw_parts(params, 3);
+
+    let fraction = f32::from_datum(params[0], false).map(f64::from);
+    state.fraction = match fraction {
+        Some(fraction) if (0.0..=1.0).contains(&fraction) => fraction,
+        _ => error!("sample fraction must be between 0 and 1"),
+    };
+
+    // Times before 1970 are as good as -infinity, no typeid has them
+    let millis = |datum: pg_sys::Datum| match TimestampWithTimeZone::from_datum(datum, false) {
+        Some(timestamp) if timestamp.is_finite() => timestamptz_to_millis(timestamp).unwrap_or(0),
+        Some(timestamp) if timestamp == TimestampWithTimeZone::positive_infinity() => u64::MAX,
+        _ => 0,
+    };
+    state.start_millis = millis(params[1]);
+    state.end_millis = millis(params[2]);
+
+    state.seed = seed;
+    state.next_block = 0;
+    state.last_offset = pg_sys::InvalidOffsetNumber as pg_sys::OffsetNumber;
+}
+
+#[cfg(feature = "pg11")]
+#[pg_guard]
+unsafe extern "C" fn next_sample_block(node: *mut pg_sys::SampleScanState) -> pg_sys::BlockNumber {
+    let nblocks = (*(*node).ss.ss_currentScanDesc).rs_nblocks;
+    next_block(node, nblocks)
+}
+
+#[cfg(not(feature = "pg11"))]
+#[pg_guard]
+unsafe extern "C" fn next_sample_block(
+    node: *mut pg_sys::SampleScanState,
+    nblocks: pg_sys::BlockNumber,
+) -> pg_sys::BlockNumber {
+    next_block(node, nblocks)
+}
+
+unsafe fn next_block(
+    node: *mut pg_sys::SampleScanState,
+    nblocks: pg_sys::BlockNumber,
+) -> pg_sys::BlockNumber {
+    let state = &mut *(*node).tsm_state.cast::<SampleState>();
+
+    while state.next_block < nblocks {
+        let block = state.next_block;
+        state.next_block += 1;
+        if state.selects(block) {
+            return block;
+        }
+    }
+
+    state.next_block = 0;
+    pg_sys::InvalidBlockNumber
+}
+
+#[pg_guard]
+unsafe extern "C" fn next_sample_tuple(
+    node: *mut pg_sys::SampleScanState,
+    _block: pg_sys::BlockNumber,
+    max_offset: pg_sys::OffsetNumber,
+) -> pg_sys::OffsetNumber {
+    let state = &mut *(*node).tsm_state.cast::<SampleState>();
+    let relation = (*node).ss.ss_currentRelation;
+
+    // The scan keeps the current block pinned, which is enough to read tuples from it
+    let scan = (*node)
+        .ss
+        .ss_currentScanDesc
+        .cast::<pg_sys::HeapScanDescData>();
+    let page = pg_sys::BufferGetPage((*scan).rs_cbuf);
+    let items = (*page.cast::<pg_sys::PageHeaderData>()).pd_linp.as_ptr();
+
+    while state.last_offset < max_offset {
+        state.last_offset += 1;
+        let item = &*items.add(state.last_offset as usize - 1);
+        if item.lp_flags() != pg_sys::LP_NORMAL {
+            continue;
+        }
+
+        let mut tuple = pg_sys::HeapTupleData {
+            t_len: item.lp_len(),
+            t_tableOid: (*relation).rd_id,
+            t_data: page.cast::<u8>().add(item.lp_off() as usize).cast(),
+            ..Default::default()
+        };
+        let mut isnull = false;
+        let datum = pg_sys::heap_getattr(
+            &mut tuple,
+            state.attnum as i32,
+            (*relation).rd_att,
+            &mut isnull,
+        );
+
+        let in_window = TypeID::from_datum(datum, isnull)
+            .map(|typeid| typeid.timestamp_millis())
+            .is_some_and(|millis| state.start_millis <= millis && millis < state.end_millis);
+        if in_window {
+            return state.last_offset;
+        }
+    }
+
+    state.last_offset = pg_sys::InvalidOffsetNumber as pg_sys::OffsetNumber;
+    pg_sys::InvalidOffsetNumber as pg_sys::OffsetNumber
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    #[pg_test]
+    fn test_typeid_time_tablesample() {
+        Spi::run("CREATE TABLE events (id typeid PRIMARY KEY, payload text)").unwrap();
+        Spi::run(
+            "INSERT INTO events
+             SELECT typeid_generate_history('event', '2024-01-01 UTC', '2024-01-02 UTC', 1000), 'old'
+             UNION ALL
+             SELECT typeid_generate_history('event', '2024-03-01 UTC', '2024-03-02 UTC', 500), 'new'",
+        )
+        .unwrap();
+
+        let (total, new) = Spi::get_two::<i64, i64>(
+            "SELECT count(*), count(*) FILTER (WHERE payload = 'new')
+             FROM events TABLESAMPLE typeid_time(1, '2024-02-01 UTC', 'infinity')",
+        )
+        .unwrap();
+        assert_eq!(total, Some(500));
+        assert_eq!(new, Some(500));
+
+        let count = Spi::get_one::<i64>(
+            "SELECT count(*) FROM events TABLESAMPLE typeid_time(0, '-infinity', 'infinity')",
+        )
+        .unwrap();
+        assert_eq!(count, Some(0));
+
+        let repeated = Spi::get_one::<bool>(
+            "SELECT (SELECT count(*) FROM events TABLESAMPLE typeid_time(0.5, '-infinity', 'infinity') REPEATABLE (7))
+                  = (SELECT count(*) FROM events TABLESAMPLE typeid_time(0.5, '-infinity', 'infinity') REPEATABLE (7))",
+        )
+        .unwrap();
+        assert_eq!(repeated, Some(true));
+    }
+
+    #[pg_test(
+        error = "TABLESAMPLE typeid_time requires a typeid primary key, \"logs\" doesn't have one"
+    )]
+    fn test_typeid_time_tablesample_requires_primary_key() {
+        Spi::run("CREATE TABLE logs (id typeid)").unwrap();
+        Spi::run("SELECT count(*) FROM logs TABLESAMPLE typeid_time(1, '-infinity', 'infinity')")
+            .unwrap();
+    }
+
+    #[pg_test(
+        error = "TABLESAMPLE typeid_time requires a typeid primary key, \"tags\" doesn't have one"
+    )]
+    fn test_typeid_time_tablesample_requires_typeid_primary_key() {
+        Spi::run("CREATE TABLE tags (name text PRIMARY KEY, id typeid UNIQUE)").unwrap();
+        Spi::run("SELECT count(*) FROM tags TABLESAMPLE typeid_time(1, '-infinity', 'infinity')")
+            .unwrap();
+    }
+}
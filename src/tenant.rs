@@ -0,0 +1,238 @@
+//! Multi-tenant tables keyed by TypeIDs carrying their tenant.
+//!
+//! `typeid_generate_for_tenant` stores the tenant in the lowest 32 bits of the UUIDv7, which are
+//! random otherwise. `typeid_generate_sharded` stores a shard in the 16 bits following the
+//! variant, so an id can carry both. The timestamp and the counter keeping ids of a connection
+//! ordered stay as they are, so tenant and shard ids sort and index like any other.
+
+use pgrx::prelude::*;
+use pgrx::PgRelation;
+use uuid::Uuid;
+
+use crate::catalog::{check_typeid_column, ColumnName};
+use crate::datum::TypeIDRef;
+use crate::typeid::{checked_prefix, TypeID};
+use crate::{monotonic, rate_limit};
+
+const TENANT_MASK: u128 = 0xffff_ffff;
+const SHARD_SHIFT: u32 = 46;
+const SHARD_MASK: u128 = 0xffff << SHARD_SHIFT;
+
+/// Generates an id for a tenant between 0 and 4294967295
+#[pg_extern(cost = 10)]
+fn typeid_generate_for_tenant(prefix: &str, tenant: i64) -> TypeID {
+    let Ok(tenant) = u32::try_from(tenant) else {
+        ereport!(
+            ERROR,
+            PgSqlErrorCode::ERRCODE_INVALID_PARAMETER_VALUE,
+            format!(
+                "tenant {tenant} is out of range, expected 0 to {}",
+                u32::MAX
+            )
+        );
+    };
+    let type_prefix = checked_prefix(prefix);
+    rate_limit::check(prefix, 1);
+
+    let uuid = monotonic::now_v7().as_u128() & !TENANT_MASK | tenant as u128;
+    TypeID::new(type_prefix, Uuid::from_u128(uuid))
+}
+
+/// The tenant of an id generated by `typeid_generate_for_tenant`. Ids generated otherwise give an
+/// arbitrary number.
+#[pg_extern(immutable, parallel_safe, requires = [TypeID])]
+fn typeid_tenant_of(id: TypeIDRef<'_>) -> i64 {
+    (u128::from_be_bytes(*id.uuid_bytes()) & TENANT_MASK) as i64
+}
+
+/// Generates an id for a shard between 0 and 65535
+#[pg_extern(cost = 10)]
+fn typeid_generate_sharded(prefix: &str, shard: i32) -> TypeID {
+    let Ok(shard) = u16::try_from(shard) else {
+        ereport!(
+            ERROR,
+            PgSqlErrorCode::ERRCODE_INVALID_PARAMETER_VALUE,
+            format!("shard {shard} is out of range, expected 0 to {}", u16::MAX)
+        );
+    };
+    let type_prefix = checked_prefix(prefix);
+    rate_limit::check(prefix, 1);
+
+    let uuid = monotonic::now_v7().as_u128() & !SHARD_MASK | (shard as u128) << SHARD_SHIFT;
+    TypeID::new(type_prefix, Uuid::from_u128(uuid))
+}
+
+/// The shard of an id generated by `typeid_generate_sharded`. Ids generated otherwise give an
+/// arbitrary number.
+#[pg_extern(immutable, parallel_safe, requires = [TypeID])]
+fn typeid_shard(id: TypeIDRef<'_>) -> i32 {
+    ((u128::from_be_bytes(*id.uuid_bytes()) & SHARD_MASK) >> SHARD_SHIFT) as i32
+}
+
+/// Statements enabling row level security on the relation with a policy only letting through rows
+/// whose `column` belongs to the tenant in the `setting` configuration parameter, e.g. after
+/// `SET typeid.tenant = 42`. Without the setting no rows are visible. The statements are executed
+/// as well when `execute` is true.
+#[pg_extern(cost = 100)]
+fn typeid_create_tenant_policy(
+    relation: PgRelation,
+    column: default!(ColumnName, "'id'"),
+    setting: default!(&str, "'typeid.tenant'"),
+    execute: default!(bool, false),
+) -> SetOfIterator<'static, String> {
+    check_typeid_column(&relation, column.as_str());
+
+    let statements = Spi::connect(|mut client| {
+        let statements = client
+            .select(
+                "SELECT ARRAY[
+                    format('ALTER TABLE %s ENABLE ROW LEVEL SECURITY', $1::regclass),
+                    format(
+                        'CREATE POLICY %I ON %s USING (%s) WITH CHECK (%s)',
+                        'typeid_tenant_isolation', $1::regclass, c.condition, c.condition
+                    )
+                 ]
+                 FROM (
+                    SELECT format(
+                        '%s.typeid_tenant_of(%I) = NULLIF(current_setting(%L, true), %L)::bigint',
+                        e.extnamespace::regnamespace, $2, $3, ''
+                    ) AS condition
+                    FROM pg_catalog.pg_extension e
+                    WHERE e.extname = 'typeid'
+                 ) c",
+                None,
+                Some(vec![
+                    (PgBuiltInOids::OIDOID.oid(), relation.oid().into_datum()),
+                    (PgBuiltInOids::TEXTOID.oid(), column.as_str().into_datum()),
+                    (PgBuiltInOids::TEXTOID.oid(), setting.into_datum()),
+                ]),
+            )?
+            .first()
+            .get_one::<Vec<String>>()?
+            .unwrap_or_default();
+
+        if execute {
+            for statement in &statements {
+                client.update(statement, None, None)?;
+            }
+        }
+        Ok::<_, pgrx::spi::Error>(statements)
+    })
+    .unwrap();
+
+    SetOfIterator::new(statements)
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    #[pg_test]
+    fn test_typeid_tenant_of() {
+        let tenants = Spi::get_one::<Vec<i64>>(
+            "SELECT array_agg(typeid_tenant_of(typeid_generate_for_tenant('doc', t)) ORDER BY t)
+             FROM unnest(ARRAY[0, 42, 4294967295]) t",
+        );
+        assert_eq!(tenants, Ok(Some(vec![0, 42, 4_294_967_295])));
+
+        let leakproof = Spi::get_one::<bool>(
+            "SELECT proleakproof FROM pg_proc WHERE proname = 'typeid_tenant_of'",
+        );
+        assert_eq!(leakproof, Ok(Some(true)));
+    }
+
+    #[pg_test(error = "tenant -1 is out of range, expected 0 to 4294967295")]
+    fn test_typeid_generate_for_tenant_out_of_range() {
+        Spi::run("SELECT typeid_generate_for_tenant('doc', -1)").unwrap();
+    }
+
+    #[pg_test(error = "invalid typeid prefix \"Doc\"")]
+    fn test_typeid_generate_for_tenant_invalid_prefix() {
+        Spi::run("SELECT typeid_generate_for_tenant('Doc', 1)").unwrap();
+    }
+
+    #[pg_test]
+    fn test_typeid_shard() {
+        let shards = Spi::get_one::<Vec<i32>>(
+            "SELECT array_agg(typeid_shard(typeid_generate_sharded('doc', s)) ORDER BY s)
+             FROM unnest(ARRAY[0, 7, 65535]) s",
+        );
+        assert_eq!(shards, Ok(Some(vec![0, 7, 65535])));
+
+        // The shard leaves the version and variant alone
+        let valid = Spi::get_one::<bool>(
+            "SELECT typeid_uuid_version(id) = 7 AND substr(typeid_to_uuid(id)::text, 20, 1) = 'b'
+             FROM typeid_generate_sharded('doc', 65535) id",
+        );
+        assert_eq!(valid, Ok(Some(true)));
+
+        let leakproof =
+            Spi::get_one::<bool>("SELECT proleakproof FROM pg_proc WHERE proname = 'typeid_shard'");
+        assert_eq!(leakproof, Ok(Some(true)));
+    }
+
+    #[pg_test(error = "shard 65536 is out of range, expected 0 to 65535")]
+    fn test_typeid_generate_sharded_out_of_range() {
+        Spi::run("SELECT typeid_generate_sharded('doc', 65536)").unwrap();
+    }
+
+    #[pg_test]
+    fn test_typeid_create_tenant_policy() {
+        Spi::run("CREATE TABLE docs (id typeid PRIMARY KEY, body text)").unwrap();
+        Spi::run(
+            "INSERT INTO docs
+             SELECT typeid_generate_for_tenant('doc', t), 'hello' FROM generate_series(1, 3) t, generate_series(1, 10)",
+        )
+        .unwrap();
+
+        let statements = Spi::get_one::<Vec<String>>(
+            "SELECT array_agg(s) FROM typeid_create_tenant_policy('docs', execute => true) s",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(statements[0], "ALTER TABLE docs ENABLE ROW LEVEL SECURITY");
+        assert!(statements[1].starts_with("CREATE POLICY typeid_tenant_isolation ON docs"));
+
+        // Superusers and table owners bypass the policy
+        Spi::run("CREATE ROLE typeid_tenant_test").unwrap();
+        Spi::run(
+            "DO $$ BEGIN
+                EXECUTE format('GRANT USAGE ON SCHEMA %I TO typeid_tenant_test', current_schema());
+             END $$",
+        )
+        .unwrap();
+        Spi::run("GRANT SELECT ON docs TO typeid_tenant_test").unwrap();
+        Spi::run("SET ROLE typeid_tenant_test").unwrap();
+        Spi::run("SET typeid.tenant = 2").unwrap();
+        assert_eq!(
+            Spi::get_one::<i64>("SELECT count(*) FROM docs"),
+            Ok(Some(10))
+        );
+        Spi::run("RESET typeid.tenant").unwrap();
+        assert_eq!(
+            Spi::get_one::<i64>("SELECT count(*) FROM docs"),
+            Ok(Some(0))
+        );
+        Spi::run("RESET ROLE").unwrap();
+    }
+
+    #[pg_test]
+    fn test_typeid_create_tenant_policy_quotes_identifiers() {
+        Spi::run(r#"CREATE TABLE "Docs; DROP TABLE x" ("Doc Id" typeid PRIMARY KEY)"#).unwrap();
+
+        let policy = Spi::get_one::<String>(
+            r#"SELECT s FROM typeid_create_tenant_policy('"Docs; DROP TABLE x"', 'Doc Id', 'app.tenant') s OFFSET 1"#,
+        )
+        .unwrap()
+        .unwrap();
+        assert!(
+            policy.contains(r#"ON "Docs; DROP TABLE x" USING"#),
+            "{policy}"
+        );
+        assert!(
+            policy.contains(r#".typeid_tenant_of("Doc Id") = NULLIF(current_setting('app.tenant', true), '')::bigint"#),
+            "{policy}"
+        );
+    }
+}
@@ -0,0 +1,516 @@
+use pgrx::prelude::*;
+
+use crate::typeid::TypeID;
+
+/// Microseconds between the Unix epoch and the Postgres epoch (2000-01-01)
+pub(crate) const POSTGRES_EPOCH_MICROS: i64 = 946_684_800_000_000;
+
+pub fn millis_to_timestamptz(millis: u64) -> TimestampWithTimeZone {
+    let micros = millis as i64 * 1000 - POSTGRES_EPOCH_MICROS;
+    TimestampWithTimeZone::try_from(micros)
+        .unwrap_or_else(|_| TimestampWithTimeZone::positive_infinity())
+}
+
+pub fn timestamptz_to_millis(timestamp: TimestampWithTimeZone) -> Option<u64> {
+    if !timestamp.is_finite() {
+        return None;
+    }
+
+    let micros = timestamp.into_inner() + POSTGRES_EPOCH_MICROS;
+    u64::try_from(micros.div_euclid(1000)).ok()
+}
+
+/// The year, month and day in UTC of milliseconds since the Unix epoch
+pub fn civil_date_utc(millis: u64) -> (i64, i64, i64) {
+    let days = (millis / 86_400_000) as i64;
+
+    // Civil date from days since the epoch, with years starting in March
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+/// Formats milliseconds since the Unix epoch as an RFC 3339 timestamp in UTC, independent of the
+/// session's `DateStyle` and `TimeZone`
+pub fn format_millis_utc(millis: u64) -> String {
+    let (year, month, day) = civil_date_utc(millis);
+    let millis_of_day = millis % 86_400_000;
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        millis_of_day / 3_600_000,
+        millis_of_day / 60_000 % 60,
+        millis_of_day / 1000 % 60,
+        millis_of_day % 1000
+    )
+}
+
+/// Emits the smallest TypeID of every `step` starting at the timestamp of `a` up to the timestamp
+/// of `b`, e.g. to generate partition bounds or keyset pagination boundaries over a time range.
+#[pg_extern(stable, parallel_safe, cost = 10)]
+fn typeid_series_between(a: TypeID, b: TypeID, step: Interval) -> SetOfIterator<'static, TypeID> {
+    if a.type_prefix() != b.type_prefix() {
+        error!(
+            "typeid_series_between requires both ids to have the same prefix, got {:?} and {:?}",
+            a.type_prefix(),
+            b.type_prefix()
+        );
+    }
+    if step.is_negative() || step.as_micros() == 0 {
+        error!("step size must be a positive interval");
+    }
+
+    let prefix = a.prefix().clone();
+    let end = b.timestamp_millis();
+    let mut next = Some(millis_to_timestamptz(a.timestamp_millis()));
+
+    SetOfIterator::new(std::iter::from_fn(move || {
+        let current = next?;
+        let millis = timestamptz_to_millis(current).filter(|millis| *millis <= end)?;
+
+        // Stop when the step doesn't move us forward anymore, e.g. after reaching infinity
+        let following = current + step;
+        next = (following > current).then_some(following);
+
+        Some(TypeID::lower_bound_at(prefix.clone(), millis))
+    }))
+}
+
+/// The smallest id of the prefix of `typeid` in the bucket of `width` its timestamp falls into, e.g.
+/// to group by day with `typeid_bucket(id, '1 day')`. Buckets are aligned to the Unix epoch, like
+/// `date_bin` with an origin of 1970-01-01 UTC, so days start at midnight UTC. Months vary in
+/// length and can't be used.
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_bucket(typeid: TypeID, width: Interval) -> TypeID {
+    if width.months() != 0 {
+        ereport!(
+            ERROR,
+            PgSqlErrorCode::ERRCODE_FEATURE_NOT_SUPPORTED,
+            "typeid_bucket doesn't support intervals with months or years"
+        );
+    }
+    let Some(width) = tolerance_millis(width).filter(|width| *width > 0) else {
+        ereport!(
+            ERROR,
+            PgSqlErrorCode::ERRCODE_INVALID_PARAMETER_VALUE,
+            "bucket width must be at least 1 millisecond"
+        );
+    };
+    let millis = typeid.timestamp_millis();
+    TypeID::lower_bound_at(typeid.prefix().clone(), millis - millis % width)
+}
+
+/// When the id was generated, as embedded in its UUIDv7
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_timestamp(typeid: TypeID) -> TimestampWithTimeZone {
+    millis_to_timestamptz(typeid.timestamp_millis())
+}
+
+/// The embedded timestamp as milliseconds since the Unix epoch
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_timestamp_millis(typeid: TypeID) -> i64 {
+    typeid.timestamp_millis() as i64
+}
+
+/// Time since the id was generated, `now() - typeid_timestamp(id)`. Like `now()`, it's measured
+/// from the start of the transaction, so ids generated by the transaction itself have negative
+/// ages.
+#[pg_extern(stable, parallel_safe)]
+fn typeid_age(typeid: TypeID) -> Interval {
+    pgrx::now() - millis_to_timestamptz(typeid.timestamp_millis())
+}
+
+/// The timestamp embedded in the id as local time in the given time zone, e.g. to bucket rows by
+/// local business days: `typeid_timestamp_at(id, 'America/New_York')::date`
+#[pg_extern(stable, parallel_safe)]
+fn typeid_timestamp_at(typeid: TypeID, timezone: &str) -> Timestamp {
+    match millis_to_timestamptz(typeid.timestamp_millis()).at_timezone(timezone) {
+        Ok(timestamp) => timestamp,
+        Err(err) => error!("{err}"),
+    }
+}
+
+/// Orders ids by their embedded timestamps, breaking ties by the ids themselves so the result
+/// doesn't depend on the order of the arguments
+fn chronological(a: &TypeID, b: &TypeID) -> std::cmp::Ordering {
+    a.timestamp_millis()
+        .cmp(&b.timestamp_millis())
+        .then_with(|| a.cmp(b))
+}
+
+/// The id generated first, by embedded timestamp, or the other one when either is NULL, e.g. to
+/// pick the surviving entity when merging duplicates
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_older(a: Option<TypeID>, b: Option<TypeID>) -> Option<TypeID> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(std::cmp::min_by(a, b, chronological)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// The id generated last, by embedded timestamp, or the other one when either is NULL
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_newer(a: Option<TypeID>, b: Option<TypeID>) -> Option<TypeID> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(std::cmp::max_by(a, b, chronological)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Whole milliseconds of a tolerance, None for negative intervals
+pub(crate) fn tolerance_millis(tolerance: Interval) -> Option<u64> {
+    u64::try_from(tolerance.as_micros().div_euclid(1000)).ok()
+}
+
+/// The smallest and largest ids of the prefix of `typeid` generated within `tolerance_millis` of it
+pub(crate) fn within_bounds(typeid: &TypeID, tolerance_millis: u64) -> (TypeID, TypeID) {
+    let millis = typeid.timestamp_millis();
+    (
+        TypeID::lower_bound_at(
+            typeid.prefix().clone(),
+            millis.saturating_sub(tolerance_millis),
+        ),
+        TypeID::upper_bound_at(
+            typeid.prefix().clone(),
+            millis
+                .saturating_add(tolerance_millis)
+                .min(0xffff_ffff_ffff),
+        ),
+    )
+}
+
+/// Whether the ids have the same prefix and embedded timestamps at most `tolerance` apart, e.g. to
+/// correlate events that happened around the same time. When one of the ids is a constant, the
+/// planner turns it into a range scan over an index on the other (Postgres 12+).
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_within(a: TypeID, b: TypeID, tolerance: Interval) -> bool {
+    let Some(tolerance) = tolerance_millis(tolerance) else {
+        error!("tolerance must not be a negative interval");
+    };
+    // The same bounds as the index range the planner scans, so both always agree
+    let (lower, upper) = within_bounds(&b, tolerance);
+    lower <= a && a <= upper
+}
+
+/// The btree in_range support function behind `RANGE` window frames over typeids, with offsets
+/// applied to the embedded timestamp. Bounds keep the prefix of the current row and span whole
+/// milliseconds, like `typeid_within`.
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_in_range(val: TypeID, base: TypeID, offset: Interval, sub: bool, less: bool) -> bool {
+    let Some(offset) = tolerance_millis(offset) else {
+        ereport!(
+            ERROR,
+            PgSqlErrorCode::ERRCODE_INVALID_PRECEDING_OR_FOLLOWING_SIZE,
+            "invalid preceding or following size in window function"
+        );
+    };
+    // The largest timestamp in the 48 bits of a UUIDv7
+    const MAX_MILLIS: i128 = 0xffff_ffff_ffff;
+
+    let millis = base.timestamp_millis() as i128;
+    let bound = if sub {
+        millis - offset as i128
+    } else {
+        millis + offset as i128
+    };
+
+    // Bounds past either end of the timestamps are past all the ids of the prefix
+    let prefix = base.prefix().clone();
+    match less {
+        true if bound < 0 => val < TypeID::lower_bound_at(prefix, 0),
+        true => val <= TypeID::upper_bound_at(prefix, bound.min(MAX_MILLIS) as u64),
+        false if bound > MAX_MILLIS => val > TypeID::upper_bound_at(prefix, MAX_MILLIS as u64),
+        false => val >= TypeID::lower_bound_at(prefix, bound.max(0) as u64),
+    }
+}
+
+extension_sql!(
+    r#"
+    ALTER OPERATOR FAMILY typeid_ops USING btree ADD
+        FUNCTION 3 (typeid, interval) typeid_in_range(typeid, typeid, interval, boolean, boolean);
+    "#,
+    name = "typeid_in_range_support",
+    requires = ["create_typeid_operator_class", typeid_in_range],
+);
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    #[test]
+    fn test_format_millis_utc() {
+        assert_eq!(super::format_millis_utc(0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            super::format_millis_utc(1_709_164_800_123),
+            "2024-02-29T00:00:00.123Z"
+        );
+        assert_eq!(
+            super::format_millis_utc(253_402_300_799_999),
+            "9999-12-31T23:59:59.999Z"
+        );
+    }
+
+    #[pg_test]
+    fn test_typeid_series_between() {
+        let bounds = Spi::get_one::<Vec<String>>(
+            "SELECT array_agg(b::text) FROM typeid_series_between(
+                uuid_to_typeid('event', '018f0000-0000-7000-8000-000000000000'),
+                uuid_to_typeid('event', '018f0000-ea60-7000-8000-000000000000'),
+                '20 seconds'
+            ) b",
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(bounds.len(), 4);
+        assert_eq!(bounds[0], "event_01hw0000000000000000000000");
+
+        let count = Spi::get_one::<i64>(
+            "SELECT count(*) FROM typeid_series_between(
+                uuid_to_typeid('event', '018f0000-ea60-7000-8000-000000000000'),
+                uuid_to_typeid('event', '018f0000-0000-7000-8000-000000000000'),
+                '1 second'
+            )",
+        )
+        .unwrap();
+        assert_eq!(count, Some(0));
+    }
+
+    #[pg_test]
+    fn test_typeid_timestamp() {
+        let (timestamp, millis) = Spi::get_two::<bool, i64>(
+            "SELECT typeid_timestamp(id) = '2024-12-10 00:00:00+00', typeid_timestamp_millis(id)
+             FROM uuid_to_typeid('event', '0193addd-9400-7000-8000-000000000000') id",
+        )
+        .unwrap();
+        assert_eq!(timestamp, Some(true));
+        assert_eq!(millis, Some(1_733_788_800_000));
+
+        let recent = Spi::get_one::<bool>(
+            "SELECT typeid_timestamp(typeid_generate('user')) BETWEEN now() - interval '1 minute' AND clock_timestamp()",
+        );
+        assert_eq!(recent, Ok(Some(true)));
+    }
+
+    #[pg_test]
+    fn test_typeid_bucket() {
+        let buckets = Spi::get_one::<Vec<String>>(
+            "SELECT ARRAY[
+                typeid_bucket(id, '1 day'),
+                typeid_bucket(id, '1 hour'),
+                typeid_bucket(id, '15 minutes'),
+                typeid_bucket(id, '1 millisecond')
+             ]::text[]
+             FROM uuid_to_typeid('event', '0193af12-4c99-7000-8000-000000000000') id",
+        );
+        assert_eq!(
+            buckets,
+            Ok(Some(vec![
+                "event_01jepxv5000000000000000000".to_string(),
+                "event_01jeqf0f400000000000000000".to_string(),
+                "event_01jeqgqcy00000000000000000".to_string(),
+                "event_01jeqh4k4s0000000000000000".to_string(),
+            ]))
+        );
+
+        let grouped = Spi::get_one::<bool>(
+            "SELECT typeid_timestamp(typeid_bucket(id, '1 day')) = '2024-12-10 00:00:00+00'
+             FROM uuid_to_typeid('event', '0193af12-4c99-7000-8000-000000000000') id",
+        );
+        assert_eq!(grouped, Ok(Some(true)));
+    }
+
+    #[pg_test(error = "typeid_bucket doesn't support intervals with months or years")]
+    fn test_typeid_bucket_months() {
+        Spi::run("SELECT typeid_bucket(typeid_generate('event'), '1 month')").unwrap();
+    }
+
+    #[pg_test(error = "bucket width must be at least 1 millisecond")]
+    fn test_typeid_bucket_width() {
+        Spi::run("SELECT typeid_bucket(typeid_generate('event'), '-1 day')").unwrap();
+    }
+
+    #[pg_test]
+    fn test_typeid_age() {
+        let age = Spi::get_one::<bool>(
+            "SELECT typeid_age(id) = now() - '2024-12-10 00:00:00+00'
+             FROM uuid_to_typeid('event', '0193addd-9400-7000-8000-000000000000') id",
+        );
+        assert_eq!(age, Ok(Some(true)));
+
+        let recent = Spi::get_one::<bool>(
+            "SELECT typeid_age(typeid_generate('order')) BETWEEN '-1 minute' AND '1 minute'",
+        );
+        assert_eq!(recent, Ok(Some(true)));
+    }
+
+    #[pg_test]
+    fn test_typeid_timestamp_at() {
+        let (new_york, utc) = Spi::get_two::<String, String>(
+            "SELECT typeid_timestamp_at(id, 'America/New_York')::text, typeid_timestamp_at(id, 'UTC')::text
+             FROM uuid_to_typeid('event', '0193addd-9400-7000-8000-000000000000') id",
+        )
+        .unwrap();
+        assert_eq!(new_york.as_deref(), Some("2024-12-09 19:00:00"));
+        assert_eq!(utc.as_deref(), Some("2024-12-10 00:00:00"));
+    }
+
+    #[pg_test]
+    fn test_typeid_older_newer() {
+        let resolved = Spi::get_one::<Vec<String>>(
+            "WITH ids(old, new, tie) AS (SELECT
+                uuid_to_typeid('user', '018f0000-0000-7000-8000-000000000000'),
+                uuid_to_typeid('account', '018f0000-0001-7000-8000-000000000000'),
+                uuid_to_typeid('account', '018f0000-0000-7000-8000-000000000000')
+             )
+             SELECT ARRAY[
+                typeid_older(new, old), typeid_newer(new, old),
+                typeid_older(old, tie), typeid_older(tie, old),
+                typeid_newer(NULL, old), typeid_older(new, NULL)
+             ]::text[] FROM ids",
+        );
+        assert_eq!(
+            resolved,
+            Ok(Some(
+                [
+                    "user_01hw000000e008000000000000",
+                    "account_01hw000001e008000000000000",
+                    "account_01hw000000e008000000000000",
+                    "account_01hw000000e008000000000000",
+                    "user_01hw000000e008000000000000",
+                    "account_01hw000001e008000000000000",
+                ]
+                .map(String::from)
+                .to_vec()
+            ))
+        );
+
+        let null = Spi::get_one::<String>("SELECT typeid_newer(NULL, NULL)::text");
+        assert_eq!(null, Ok(None));
+    }
+
+    #[test]
+    fn test_within_bounds() {
+        use crate::typeid::{TypeID, TypeIDPrefix};
+
+        let typeid = TypeID::lower_bound_at(TypeIDPrefix::new("event").unwrap(), 5_000);
+        let (lower, upper) = super::within_bounds(&typeid, 2_000);
+        assert_eq!(lower.timestamp_millis(), 3_000);
+        assert_eq!(upper.timestamp_millis(), 7_000);
+        assert_eq!(upper.uuid().as_u128() & ((1 << 80) - 1), (1 << 80) - 1);
+
+        // Bounds saturate at the smallest and largest timestamps
+        let (lower, upper) = super::within_bounds(&typeid, u64::MAX);
+        assert_eq!(lower.timestamp_millis(), 0);
+        assert_eq!(upper.timestamp_millis(), 0xffff_ffff_ffff);
+    }
+
+    #[pg_test]
+    fn test_typeid_within() {
+        let within = Spi::get_one::<Vec<bool>>(
+            "WITH ids(a, b, c) AS (SELECT
+                uuid_to_typeid('event', '018f0000-0000-7000-8000-000000000000'),
+                uuid_to_typeid('event', '018f0000-03e8-7fff-bfff-ffffffffffff'),
+                uuid_to_typeid('order', '018f0000-0000-7000-8000-000000000000')
+             )
+             SELECT ARRAY[
+                typeid_within(a, b, '1 second'), typeid_within(b, a, '1 second'),
+                typeid_within(a, b, '999 milliseconds'), typeid_within(a, c, '1 day')
+             ] FROM ids",
+        );
+        assert_eq!(within, Ok(Some(vec![true, true, false, false])));
+
+        Spi::run("CREATE TABLE events (id typeid PRIMARY KEY)").unwrap();
+        Spi::run(
+            "INSERT INTO events
+             SELECT uuid_to_typeid(p, ('018f0000-' || lpad(to_hex(n), 4, '0') || '-7000-8000-000000000000')::uuid)
+             FROM unnest(ARRAY['event', 'order']) p, generate_series(0, 999) n",
+        )
+        .unwrap();
+        Spi::run("ANALYZE events").unwrap();
+
+        let count = Spi::get_one::<i64>(
+            "SELECT count(*) FROM events
+             WHERE typeid_within(id, uuid_to_typeid('event', '018f0000-0064-7000-8000-000000000000'), '10 milliseconds')",
+        );
+        assert_eq!(count, Ok(Some(21)));
+
+        #[cfg(not(feature = "pg11"))]
+        {
+            Spi::run("SET enable_seqscan = off").unwrap();
+            let plan = Spi::explain(
+                "SELECT * FROM events
+                 WHERE typeid_within(uuid_to_typeid('event', '018f0000-0064-7000-8000-000000000000'), id, '10 milliseconds')",
+            )
+            .unwrap()
+            .0
+            .to_string();
+            assert!(plan.contains("Index"), "expected an index scan: {plan}");
+        }
+    }
+
+    #[pg_test(error = "tolerance must not be a negative interval")]
+    fn test_typeid_within_negative_tolerance() {
+        Spi::run(
+            "SELECT typeid_within(typeid_generate('event'), typeid_generate('event'), '-1 second')",
+        )
+        .unwrap();
+    }
+
+    #[pg_test]
+    fn test_typeid_in_range() {
+        // Ids 0, 400, 1000 and 2500 milliseconds in, and an id of another prefix
+        let counts = Spi::get_one::<Vec<i64>>(
+            "SELECT array_agg(n ORDER BY id) FROM (
+                SELECT id, count(*) OVER (ORDER BY id RANGE BETWEEN '1 second' PRECEDING AND CURRENT ROW) n
+                FROM unnest(ARRAY[
+                    uuid_to_typeid('event', '018f0000-0000-7000-8000-000000000000'),
+                    uuid_to_typeid('event', '018f0000-0190-7000-8000-000000000000'),
+                    uuid_to_typeid('event', '018f0000-03e8-7000-8000-000000000000'),
+                    uuid_to_typeid('event', '018f0000-09c4-7000-8000-000000000000'),
+                    uuid_to_typeid('order', '018f0000-03e8-7000-8000-000000000000')
+                ]) id
+             ) t",
+        );
+        assert_eq!(counts, Ok(Some(vec![1, 2, 3, 1, 1])));
+
+        let following = Spi::get_one::<Vec<i64>>(
+            "SELECT array_agg(n ORDER BY id) FROM (
+                SELECT id, count(*) OVER (ORDER BY id RANGE BETWEEN CURRENT ROW AND '500 milliseconds' FOLLOWING) n
+                FROM unnest(ARRAY[
+                    uuid_to_typeid('event', '018f0000-0000-7000-8000-000000000000'),
+                    uuid_to_typeid('event', '018f0000-0190-7000-8000-000000000000'),
+                    uuid_to_typeid('event', '018f0000-03e8-7000-8000-000000000000')
+                ]) id
+             ) t",
+        );
+        assert_eq!(following, Ok(Some(vec![2, 1, 1])));
+    }
+
+    #[pg_test(error = "invalid preceding or following size in window function")]
+    fn test_typeid_in_range_negative_offset() {
+        Spi::run(
+            "SELECT count(*) OVER (ORDER BY id RANGE BETWEEN '-1 second' PRECEDING AND CURRENT ROW)
+             FROM (SELECT typeid_generate('event')) t(id)",
+        )
+        .unwrap();
+    }
+
+    #[pg_test(error = "step size must be a positive interval")]
+    fn test_typeid_series_between_negative_step() {
+        Spi::run(
+            "SELECT typeid_series_between(typeid_generate('event'), typeid_generate('event'), '-1 day')",
+        )
+        .unwrap();
+    }
+}
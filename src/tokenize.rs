@@ -0,0 +1,118 @@
+//! Reversible tokenization of TypeIDs, e.g. for datasets shared with vendors. Tokens look like ids
+//! of the same prefix but are random, so they reveal neither the original id nor its timestamp.
+//! Only the mapping table kept by the extension leads back to the original, and only
+//! `typeid_admin` can read it.
+
+use pgrx::prelude::*;
+use uuid::Builder;
+
+use crate::typeid::TypeID;
+
+/// The token standing in for the id within `scope`. The same id always gets the same token in a
+/// scope, so tokenized datasets can still be joined, while tokens of different scopes are
+/// unrelated.
+#[pg_extern(cost = 100)]
+fn typeid_tokenize(id: TypeID, scope: &str) -> TypeID {
+    // Never seeded by `typeid.test_seed`, which would make tokens predictable
+    let token = TypeID::new(
+        id.prefix().clone(),
+        Builder::from_random_bytes(rand::random()).into_uuid(),
+    );
+
+    Spi::connect(|mut client| {
+        let existing = client
+            .select(
+                "SELECT token FROM typeid_tokens WHERE scope = $1 AND id = $2",
+                None,
+                Some(vec![
+                    (PgBuiltInOids::TEXTOID.oid(), scope.into_datum()),
+                    (TypeID::type_oid().into(), id.clone().into_datum()),
+                ]),
+            )?
+            .first()
+            .get_one::<TypeID>()?;
+        if existing.is_some() {
+            return Ok(existing);
+        }
+
+        // A concurrent transaction may tokenize the id first. The no-op update returns its token
+        // once it commits, and fails to serialize under REPEATABLE READ, where it isn't visible.
+        client
+            .update(
+                "INSERT INTO typeid_tokens (scope, id, token) VALUES ($1, $2, $3)
+                 ON CONFLICT (scope, id) DO UPDATE SET token = typeid_tokens.token
+                 RETURNING token",
+                None,
+                Some(vec![
+                    (PgBuiltInOids::TEXTOID.oid(), scope.into_datum()),
+                    (TypeID::type_oid().into(), id.into_datum()),
+                    (TypeID::type_oid().into(), token.into_datum()),
+                ]),
+            )?
+            .first()
+            .get_one::<TypeID>()
+    })
+    .unwrap()
+    .unwrap_or_else(|| error!("failed to tokenize id in scope {scope:?}"))
+}
+
+/// The id a token of the scope stands for, NULL for unknown tokens
+#[pg_extern(stable, cost = 100)]
+fn typeid_detokenize(token: TypeID, scope: &str) -> Option<TypeID> {
+    Spi::get_one_with_args::<TypeID>(
+        "SELECT id FROM typeid_tokens WHERE scope = $1 AND token = $2",
+        vec![
+            (PgBuiltInOids::TEXTOID.oid(), scope.into_datum()),
+            (TypeID::type_oid().into(), token.into_datum()),
+        ],
+    )
+    .unwrap_or(None)
+}
+
+extension_sql!(
+    r#"
+    CREATE TABLE typeid_tokens (
+        scope text NOT NULL,
+        id typeid NOT NULL,
+        token typeid NOT NULL,
+        created_at timestamptz NOT NULL DEFAULT now(),
+        PRIMARY KEY (scope, id),
+        UNIQUE (scope, token)
+    );
+
+    SELECT pg_catalog.pg_extension_config_dump('typeid_tokens', '');
+    "#,
+    name = "create_typeid_tokens",
+    requires = ["create_typeid_operator_class"],
+);
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    #[pg_test]
+    fn test_typeid_tokenize() {
+        Spi::run("CREATE TABLE users (id typeid PRIMARY KEY)").unwrap();
+        Spi::run("INSERT INTO users SELECT typeid_generate_batch('user', 100)").unwrap();
+
+        Spi::run(
+            "CREATE TABLE shared AS
+             SELECT id, typeid_tokenize(id, 'vendor') AS token, typeid_tokenize(id, 'auditor') AS other
+             FROM users",
+        )
+        .unwrap();
+
+        let checks = Spi::get_one::<Vec<bool>>(
+            "SELECT ARRAY[
+                bool_and(typeid_tokenize(id, 'vendor') = token),
+                bool_and(typeid_detokenize(token, 'vendor') = id),
+                bool_and(typeid_detokenize(token, 'auditor') IS NULL),
+                bool_and(token <> other),
+                bool_and(token::text LIKE 'user\\_%')
+             ]
+             FROM shared",
+        );
+        assert_eq!(checks, Ok(Some(vec![true, true, true, true, true])));
+    }
+}
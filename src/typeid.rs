@@ -1,12 +1,15 @@
+// The `PostgresType` derive iterates over `NULL_ERROR_MESSAGE`, which is an `Option`
+#![allow(for_loops_over_fallibles)]
+
 use core::fmt;
 use std::{borrow::Cow, cmp::Ordering};
 
+use pgrx::pg_sys::panic::ErrorReport;
 use pgrx::prelude::*;
-use serde::{Deserialize, Serialize};
 use std::hash::{Hash, Hasher};
 use uuid::Uuid;
 
-use crate::base32::{decode_base32_uuid, encode_base32_uuid};
+use crate::base32::{decode_base32_uuid, encode_base32_uuid, is_valid_base32_uuid};
 
 #[non_exhaustive]
 #[derive(Debug, thiserror::Error)]
@@ -25,7 +28,66 @@ pub enum Error {
     InvalidData,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, PartialOrd)]
+/// What makes a prefix valid, the HINT of errors about invalid prefixes
+const PREFIX_HINT: &str =
+    "Prefixes are up to 63 lowercase letters a-z and underscores, not starting or ending with an underscore.";
+
+impl Error {
+    /// Explains the error for the DETAIL of the error reported to clients
+    pub fn detail(&self) -> String {
+        match self {
+            Error::InvalidType => "The prefix before the underscore is empty.".to_string(),
+            Error::IncorrectType { actual, .. } => format!("Prefix \"{actual}\" is invalid."),
+            Error::InvalidData => {
+                "The suffix must be 26 base32 characters, the first one 0 to 7.".to_string()
+            }
+        }
+    }
+}
+
+/// Raises the error of text that isn't a typeid, with the standard code of malformed input
+pub fn report_invalid_input(input: &str, err: &Error) -> ! {
+    let hint = match err {
+        Error::IncorrectType { .. } => PREFIX_HINT,
+        _ => "Typeids look like user_01h455vb4pex5vsknk084sn02q, or just the suffix without a prefix.",
+    };
+    ErrorReport::new(
+        PgSqlErrorCode::ERRCODE_INVALID_TEXT_REPRESENTATION,
+        format!("invalid input syntax for type typeid: \"{input}\""),
+        function_name!(),
+    )
+    .set_detail(err.detail())
+    .set_hint(hint)
+    .report(PgLogLevel::ERROR);
+    unreachable!()
+}
+
+/// The prefix, raising an error for prefixes passed to functions that aren't valid
+pub fn checked_prefix(prefix: &str) -> TypeIDPrefix {
+    match TypeIDPrefix::new(prefix) {
+        Ok(prefix) => prefix,
+        Err(err) => {
+            ErrorReport::new(
+                PgSqlErrorCode::ERRCODE_INVALID_PARAMETER_VALUE,
+                format!("invalid typeid prefix \"{prefix}\""),
+                function_name!(),
+            )
+            .set_detail(err.detail())
+            .set_hint(PREFIX_HINT)
+            .report(PgLogLevel::ERROR);
+            unreachable!()
+        }
+    }
+}
+
+/// Version of the on-disk representation of a TypeID, bumped whenever the layout changes
+pub const STORAGE_FORMAT_VERSION: i32 = 2;
+
+/// Version of the text representation, which plain dumps and `COPY` are made of. It only changes
+/// with the TypeID spec, and newer versions keep accepting the text older ones wrote.
+pub const TEXT_FORMAT_VERSION: i32 = 1;
+
+#[derive(Debug, PartialEq, Eq, Clone, PartialOrd)]
 pub struct TypeIDPrefix(String);
 
 impl TypeIDPrefix {
@@ -40,6 +102,17 @@ impl TypeIDPrefix {
         Self(tag.to_string())
     }
 
+    /// Whether `tag` is a valid prefix, without allocating
+    pub fn is_valid(tag: &str) -> bool {
+        let bytes = tag.as_bytes();
+        match (bytes.first(), bytes.last()) {
+            _ if bytes.len() > 63 => false,
+            (None, _) => true,
+            (Some(b'_'), _) | (_, Some(b'_')) => false,
+            _ => bytes.iter().all(|&b| matches!(b, b'a'..=b'z' | b'_')),
+        }
+    }
+
     fn try_from_type_prefix(tag: &str) -> Result<Self, Cow<'static, str>> {
         // Check length
         if tag.len() > 63 {
@@ -73,7 +146,7 @@ impl TypeIDPrefix {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PostgresType, PartialOrd, PartialEq, Eq)]
+#[derive(Debug, Clone, PostgresType, PartialEq, Eq)]
 #[inoutfuncs]
 pub struct TypeID(TypeIDPrefix, Uuid);
 
@@ -99,13 +172,53 @@ impl TypeID {
         Ok(TypeID(prefix, uuid))
     }
 
+    /// Whether `from_string` would accept `id`, checked without allocating or decoding it
+    pub fn is_valid(id: &str) -> bool {
+        match id.rsplit_once('_') {
+            Some(("", _)) => false,
+            Some((tag, id)) => TypeIDPrefix::is_valid(tag) && is_valid_base32_uuid(id),
+            None => is_valid_base32_uuid(id),
+        }
+    }
+
+    /// The smallest TypeID with the given prefix whose embedded timestamp is `millis`
+    pub fn lower_bound_at(prefix: TypeIDPrefix, millis: u64) -> Self {
+        TypeID(
+            prefix,
+            Uuid::from_u128(((millis & 0xffff_ffff_ffff) as u128) << 80),
+        )
+    }
+
+    /// The largest TypeID with the given prefix whose embedded timestamp is `millis`
+    pub fn upper_bound_at(prefix: TypeIDPrefix, millis: u64) -> Self {
+        TypeID(
+            prefix,
+            Uuid::from_u128(((millis & 0xffff_ffff_ffff) as u128) << 80 | ((1 << 80) - 1)),
+        )
+    }
+
     pub fn type_prefix(&self) -> &str {
         self.0.to_type_prefix()
     }
 
+    pub fn prefix(&self) -> &TypeIDPrefix {
+        &self.0
+    }
+
     pub fn uuid(&self) -> &Uuid {
         &self.1
     }
+
+    /// Milliseconds since the Unix epoch, stored in the first 48 bits of a UUIDv7
+    pub fn timestamp_millis(&self) -> u64 {
+        (self.1.as_u128() >> 80) as u64
+    }
+}
+
+impl PartialOrd for TypeID {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
 impl Ord for TypeID {
@@ -141,12 +254,12 @@ impl fmt::Display for TypeID {
 
 impl InOutFuncs for TypeID {
     fn input(input: &core::ffi::CStr) -> TypeID {
-        // Convert the input to a str and handle potential UTF-8 errors
-        let str_input = input.to_str().expect("text input is not valid UTF8");
+        // Text that isn't UTF-8 can't be a valid id either
+        let str_input = input.to_str().unwrap_or_default();
 
         match TypeID::from_string(str_input) {
             Ok(typeid) => typeid,
-            Err(err) => panic!("Failed to construct TypeId<{str_input}>: {err}"),
+            Err(err) => report_invalid_input(&input.to_string_lossy(), &err),
         }
     }
 
@@ -0,0 +1,528 @@
+//! Type modifiers declaring the prefix of a typeid column, e.g. `id typeid('user')`.
+//!
+//! A type modifier is a single integer, too small to hold a prefix, so modifiers are 31-bit hashes
+//! of their prefixes. Declaring a modifier notes its prefix in the `typeid_typmods` table, which
+//! `\d` and dumps show the modifier with, unless the transaction is read-only, as on a standby.
+//! Prefixes are noted by a security definer function, users can't write the table themselves.
+//! The table is dumped, and restoring the tables declaring modifiers before it notes their
+//! prefixes again. Values are checked against the hash of their prefix alone, so the input
+//! function neither reads nor writes tables and stays immutable. The noted prefix, cached by every
+//! backend, only names the expected prefix in errors.
+//!
+//! Values are checked by the input and receive functions, which COPY calls with the modifier of
+//! the column, and by the length coercion cast `typeid(typeid, integer, boolean)` Postgres applies
+//! on assignments and casts to a modified type. From Postgres 16 on, the input function reports
+//! invalid ids and prefixes as soft errors to callers asking for them, like `pg_input_is_valid`, so
+//! they can skip rows rather than failing.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::CStr;
+
+use pgrx::prelude::*;
+use pgrx::{InOutFuncs, StringInfo};
+
+use crate::catalog::extension_schema;
+use crate::datum::{TypeIDDatum, TypeIDRef};
+use crate::typeid::{TypeID, TypeIDPrefix};
+
+thread_local! {
+    /// Prefixes of the modifiers committed by other transactions. Rows are never updated, so
+    /// they're kept for the lifetime of the backend.
+    static PREFIXES: RefCell<HashMap<i32, String>> = RefCell::new(HashMap::new());
+}
+
+/// The modifier declaring a prefix, its 32-bit FNV-1a hash without the sign bit
+pub(crate) fn typmod_of(prefix: &str) -> i32 {
+    let hash = prefix.bytes().fold(0x811c_9dc5_u32, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    });
+    (hash & 0x7fff_ffff) as i32
+}
+
+/// The prefix noted for a modifier, if any
+fn prefix_of(typmod: i32) -> Option<String> {
+    if let Some(prefix) = PREFIXES.with(|prefixes| prefixes.borrow().get(&typmod).cloned()) {
+        return Some(prefix);
+    }
+
+    let (prefix, xmin) = Spi::connect(|client| {
+        let schema = extension_schema(&client)?;
+        client
+            .select(
+                &format!(
+                    "SELECT t.prefix, t.xmin::text::int8
+                     FROM (SELECT) one LEFT JOIN {schema}.typeid_typmods t ON t.typmod = $1"
+                ),
+                None,
+                Some(vec![(PgBuiltInOids::INT4OID.oid(), typmod.into_datum())]),
+            )?
+            .first()
+            .get_two::<String, i64>()
+    })
+    .unwrap();
+    let prefix = prefix?;
+
+    // Rows added by this transaction may still roll back
+    let xmin = xmin.unwrap_or_default() as pg_sys::TransactionId;
+    if !unsafe { pg_sys::TransactionIdIsCurrentTransactionId(xmin) } {
+        PREFIXES.with(|prefixes| prefixes.borrow_mut().insert(typmod, prefix.clone()));
+    }
+    Some(prefix)
+}
+
+/// Notes the prefix of a modifier in `typeid_typmods`. Read-only and parallel transactions can't,
+/// and show the modifier as a number until another one declares it. Errors when another prefix is
+/// noted with the same modifier.
+fn note(prefix: &str, typmod: i32) {
+    let noted = prefix_of(typmod).or_else(|| {
+        if unsafe { pg_sys::XactReadOnly || pg_sys::IsInParallelMode() } {
+            return None;
+        }
+        Spi::connect(|mut client| {
+            let schema = extension_schema(&client)?;
+            client.update(
+                &format!("SELECT {schema}.typeid_note_typmod($1)"),
+                None,
+                Some(vec![(PgBuiltInOids::TEXTOID.oid(), prefix.into_datum())]),
+            )?;
+            Ok::<_, pgrx::spi::Error>(())
+        })
+        .unwrap();
+        prefix_of(typmod)
+    });
+
+    if let Some(other) = noted.filter(|noted| noted != prefix) {
+        ereport!(
+            ERROR,
+            PgSqlErrorCode::ERRCODE_INVALID_PARAMETER_VALUE,
+            format!("prefix \"{prefix}\" has the same type modifier as prefix \"{other}\""),
+            "Declare the prefix with a CHECK constraint calling typeid_has_prefix instead."
+        );
+    }
+}
+
+/// Why the prefix isn't the one declared by the modifier, `-1` declaring none
+fn mismatch(prefix: &str, typmod: i32) -> Option<String> {
+    if typmod < 0 || typmod_of(prefix) == typmod {
+        return None;
+    }
+    // Only looked up for the error, the check itself doesn't depend on the table
+    Some(match prefix_of(typmod) {
+        Some(expected) => {
+            format!("typeid('{expected}') requires prefix \"{expected}\", got \"{prefix}\"")
+        }
+        None => format!("typeid({typmod}) requires another prefix, got \"{prefix}\""),
+    })
+}
+
+/// Errors unless the prefix is the one declared by the modifier
+pub(crate) fn check(prefix: &str, typmod: i32) {
+    // Reported like the CHECK constraints modifiers stand in for
+    if let Some(message) = mismatch(prefix, typmod) {
+        ereport!(ERROR, PgSqlErrorCode::ERRCODE_CHECK_VIOLATION, message);
+    }
+}
+
+/// Saves an error in the `ErrorSaveContext` the function was called with, if any, returning
+/// whether it was saved rather than having to be raised
+#[cfg(feature = "pg16")]
+fn save_error(
+    fcinfo: pg_sys::FunctionCallInfo,
+    code: PgSqlErrorCode,
+    message: &str,
+    detail: Option<&str>,
+) -> bool {
+    extern "C" {
+        // Not among the bindings of pgrx, from `utils/elog.h`
+        fn errcode(sqlerrcode: std::os::raw::c_int) -> std::os::raw::c_int;
+    }
+
+    unsafe {
+        let context = (*fcinfo).context;
+        if context.is_null() || (*context).type_ != pg_sys::NodeTag::T_ErrorSaveContext {
+            return false;
+        }
+        // Only collects the details when the caller wants them, e.g. `pg_input_error_info`
+        if pg_sys::errsave_start(context, std::ptr::null()) {
+            let c_string = |text: &str| std::ffi::CString::new(text.replace('\0', "")).unwrap();
+            errcode(code as i32);
+            pg_sys::errmsg_internal(c"%s".as_ptr(), c_string(message).as_ptr());
+            if let Some(detail) = detail {
+                pg_sys::errdetail_internal(c"%s".as_ptr(), c_string(detail).as_ptr());
+            }
+            pg_sys::errsave_finish(
+                context,
+                c"typmod.rs".as_ptr(),
+                line!() as i32,
+                c"typeid_input".as_ptr(),
+            );
+        }
+        true
+    }
+}
+
+/// Soft errors only exist from Postgres 16 on
+#[cfg(not(feature = "pg16"))]
+fn save_error(
+    _fcinfo: pg_sys::FunctionCallInfo,
+    _code: PgSqlErrorCode,
+    _message: &str,
+    _detail: Option<&str>,
+) -> bool {
+    false
+}
+
+#[pg_extern(requires = [TypeID])]
+fn typeid_typmod_in(modifiers: Array<&CStr>) -> i32 {
+    let [Some(prefix)] = modifiers.iter().collect::<Vec<_>>()[..] else {
+        ereport!(
+            ERROR,
+            PgSqlErrorCode::ERRCODE_INVALID_PARAMETER_VALUE,
+            "typeid takes a single prefix as type modifier, e.g. typeid('user')"
+        );
+    };
+    let prefix = prefix.to_str().unwrap_or_default();
+    if let Err(err) = TypeIDPrefix::new(prefix) {
+        ereport!(
+            ERROR,
+            PgSqlErrorCode::ERRCODE_INVALID_PARAMETER_VALUE,
+            format!("invalid prefix: {err}")
+        );
+    }
+
+    let typmod = typmod_of(prefix);
+    note(prefix, typmod);
+    typmod
+}
+
+#[pg_extern(stable, parallel_safe, requires = [TypeID])]
+fn typeid_typmod_out(typmod: i32) -> &'static CStr {
+    let mut buffer = StringInfo::new();
+    match prefix_of(typmod) {
+        Some(prefix) => buffer.push_str(&format!("('{prefix}')")),
+        None => buffer.push_str(&format!("({typmod})")),
+    }
+    buffer.into()
+}
+
+/// The type modifier declaring a prefix, as found in `pg_attribute.atttypmod`
+#[pg_extern(immutable, parallel_safe)]
+fn typeid_prefix_typmod(prefix: &str) -> i32 {
+    typmod_of(prefix)
+}
+
+/// Input function of typeid, replacing the generated `typeid_in`. Postgres passes the modifier of
+/// the column being read into as a third argument, which direct calls with one don't have. Returns
+/// NULL when the error was saved for the caller.
+#[pg_extern(sql = false)]
+fn typeid_input(input: Option<&CStr>, fcinfo: pg_sys::FunctionCallInfo) -> Option<TypeID> {
+    let input = input?;
+    let typmod = unsafe {
+        if (*fcinfo).nargs >= 3 {
+            pgrx::pg_getarg::<i32>(fcinfo, 2).unwrap_or(-1)
+        } else {
+            -1
+        }
+    };
+
+    let typeid = match TypeID::from_string(input.to_str().unwrap_or_default()) {
+        Ok(typeid) => typeid,
+        Err(err) => {
+            let message = format!(
+                "invalid input syntax for type typeid: \"{}\"",
+                input.to_string_lossy()
+            );
+            if save_error(
+                fcinfo,
+                PgSqlErrorCode::ERRCODE_INVALID_TEXT_REPRESENTATION,
+                &message,
+                Some(&err.detail()),
+            ) {
+                return None;
+            }
+            // Raises the error of the type's own input function
+            return Some(TypeID::input(input));
+        }
+    };
+
+    if let Some(message) = mismatch(typeid.type_prefix(), typmod) {
+        if save_error(
+            fcinfo,
+            PgSqlErrorCode::ERRCODE_CHECK_VIOLATION,
+            &message,
+            None,
+        ) {
+            return None;
+        }
+        check(typeid.type_prefix(), typmod);
+    }
+    Some(typeid)
+}
+
+/// Applies the modifier of a column or cast, backing the length coercion cast of typeid
+#[pg_extern(name = "typeid", immutable, parallel_safe, requires = [TypeID])]
+fn typeid_apply_typmod(typeid: TypeIDRef<'_>, typmod: i32, _explicit: bool) -> TypeIDDatum {
+    check(typeid.type_prefix(), typmod);
+    TypeIDDatum::new(typeid.type_prefix(), typeid.uuid_bytes())
+}
+
+extension_sql!(
+    r#"
+    CREATE TABLE typeid_typmods (
+        typmod integer PRIMARY KEY CHECK (typmod = typeid_prefix_typmod(prefix)),
+        prefix text NOT NULL UNIQUE CHECK (typeid_is_valid_prefix(prefix))
+    );
+    GRANT SELECT ON typeid_typmods TO PUBLIC;
+    SELECT pg_catalog.pg_extension_config_dump('typeid_typmods', '');
+
+    -- Declaring a modifier notes its prefix through this function, as the owner of the extension.
+    -- Modifiers are the hashes of their prefixes and rows can't be updated or deleted, so noting
+    -- prefixes can't change what existing modifiers show as.
+    CREATE FUNCTION typeid_note_typmod(prefix text) RETURNS void
+    LANGUAGE sql
+    SECURITY DEFINER
+    SET search_path FROM CURRENT
+    AS $$
+        INSERT INTO typeid_typmods (typmod, prefix)
+        VALUES (typeid_prefix_typmod(prefix), prefix)
+        ON CONFLICT DO NOTHING
+    $$;
+
+    -- Restores create the tables declaring modifiers, noting their prefixes, before they load the
+    -- table from the dump
+    CREATE FUNCTION typeid_typmods_skip_noted() RETURNS trigger
+    LANGUAGE plpgsql
+    SET search_path FROM CURRENT
+    AS $$
+    BEGIN
+        IF EXISTS (SELECT FROM typeid_typmods WHERE typmod = NEW.typmod AND prefix = NEW.prefix) THEN
+            RETURN NULL;
+        END IF;
+        RETURN NEW;
+    END
+    $$;
+
+    CREATE TRIGGER typeid_typmods_skip_noted
+    BEFORE INSERT ON typeid_typmods
+    FOR EACH ROW EXECUTE FUNCTION typeid_typmods_skip_noted();
+
+    CREATE FUNCTION typeid_typmods_append_only() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+    BEGIN
+        RAISE EXCEPTION 'typeid_typmods is append-only, column definitions are shown with its prefixes';
+    END
+    $$;
+
+    CREATE TRIGGER typeid_typmods_append_only
+    BEFORE UPDATE OR DELETE ON typeid_typmods
+    FOR EACH ROW EXECUTE FUNCTION typeid_typmods_append_only();
+
+    CREATE TRIGGER typeid_typmods_no_truncate
+    BEFORE TRUNCATE ON typeid_typmods
+    FOR EACH STATEMENT EXECUTE FUNCTION typeid_typmods_append_only();
+
+    CREATE CAST (typeid AS typeid) WITH FUNCTION typeid(typeid, integer, boolean) AS IMPLICIT;
+
+    -- `ALTER TYPE ... SET` only accepts modifier functions from Postgres 13 on, and the input
+    -- function can't be changed with it at all. Postgres passes the modifier to the input function
+    -- whatever its declared arguments, so the one the type was created with is replaced instead.
+    DO $$
+    BEGIN
+        IF current_setting('server_version_num')::int >= 130000 THEN
+            EXECUTE 'ALTER TYPE typeid SET (TYPMOD_IN = typeid_typmod_in, TYPMOD_OUT = typeid_typmod_out)';
+        ELSE
+            UPDATE pg_catalog.pg_type
+            SET typmodin = 'typeid_typmod_in(cstring[])'::regprocedure,
+                typmodout = 'typeid_typmod_out(integer)'::regprocedure
+            WHERE oid IN ('typeid'::regtype, 'typeid[]'::regtype);
+        END IF;
+    END
+    $$;
+    CREATE OR REPLACE FUNCTION typeid_in(input cstring) RETURNS typeid
+        IMMUTABLE PARALLEL SAFE
+        LANGUAGE c AS 'MODULE_PATHNAME', 'typeid_input_wrapper';
+    "#,
+    name = "set_typeid_typmod",
+    requires = [
+        typeid_typmod_in,
+        typeid_typmod_out,
+        typeid_prefix_typmod,
+        typeid_apply_typmod,
+        crate::typeid_is_valid_prefix
+    ],
+);
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    #[pg_test]
+    fn test_typeid_typmod() {
+        Spi::run(
+            "CREATE TABLE users (id typeid('user') PRIMARY KEY, manager_ids typeid('user')[])",
+        )
+        .unwrap();
+
+        let types = Spi::get_one::<Vec<String>>(
+            "SELECT array_agg(format_type(atttypid, atttypmod) ORDER BY attnum)
+             FROM pg_attribute WHERE attrelid = 'users'::regclass AND attnum > 0",
+        );
+        assert_eq!(
+            types,
+            Ok(Some(vec![
+                "typeid('user')".to_string(),
+                "typeid('user')[]".to_string()
+            ]))
+        );
+
+        Spi::run(
+            "INSERT INTO users VALUES
+                (typeid_generate('user'), ARRAY[typeid_generate('user')]),
+                ('user_01h455vb4pex5vsknk084sn02q', '{user_01h455vb4pex5vsknk084sn02r}')",
+        )
+        .unwrap();
+        let count = Spi::get_one::<i64>("SELECT count(*) FROM users");
+        assert_eq!(count, Ok(Some(2)));
+
+        // Casts check the prefix, unmodified typeids take any
+        let cast = Spi::get_one::<String>(
+            "SELECT 'user_01h455vb4pex5vsknk084sn02q'::typeid('user')::typeid::text",
+        );
+        assert_eq!(
+            cast,
+            Ok(Some("user_01h455vb4pex5vsknk084sn02q".to_string()))
+        );
+        let unmodified = Spi::get_one::<String>("SELECT typeid_generate('org')::typeid::text");
+        assert!(unmodified.unwrap().unwrap().starts_with("org_"));
+    }
+
+    #[test]
+    fn test_typmod_of() {
+        // Modifiers are stored in catalogs, so hashes must never change
+        assert_eq!(super::typmod_of(""), 18652613);
+        assert_eq!(super::typmod_of("user"), 1618501362);
+        assert_eq!(super::typmod_of("team"), 587037964);
+    }
+
+    #[pg_test]
+    fn test_typeid_typmod_read_only() {
+        Spi::run("SET LOCAL transaction_read_only = on").unwrap();
+
+        let (id, typ) = Spi::get_two::<String, String>(
+            "SELECT id::text, pg_typeof(id)::text
+             FROM (SELECT 'team_01h455vb4pex5vsknk084sn02q'::typeid('team') AS id) ids",
+        )
+        .unwrap();
+        assert_eq!(id.as_deref(), Some("team_01h455vb4pex5vsknk084sn02q"));
+        assert_eq!(typ.as_deref(), Some("typeid"));
+        let noted =
+            Spi::get_one::<i64>("SELECT count(*) FROM typeid_typmods WHERE prefix = 'team'");
+        assert_eq!(noted, Ok(Some(0)));
+
+        // Checked against the hash, no prefix is noted
+        if cfg!(feature = "pg16") {
+            let valid = Spi::get_one::<bool>(
+                "SELECT pg_input_is_valid('user_01h455vb4pex5vsknk084sn02q', 'typeid(''team'')')",
+            );
+            assert_eq!(valid, Ok(Some(false)));
+        }
+    }
+
+    #[pg_test]
+    fn test_typeid_input_immutable() {
+        let volatility = Spi::get_one::<String>(
+            "SELECT p.provolatile::text FROM pg_type t JOIN pg_proc p ON p.oid = t.typinput
+             WHERE t.oid = 'typeid'::regtype",
+        );
+        assert_eq!(volatility, Ok(Some("i".to_string())));
+
+        Spi::run("CREATE TABLE raw_events (id text)").unwrap();
+        Spi::run("CREATE INDEX ON raw_events ((id::typeid))").unwrap();
+        Spi::run("INSERT INTO raw_events VALUES ('event_01h455vb4pex5vsknk084sn02q')").unwrap();
+
+        let direct =
+            Spi::get_one::<String>("SELECT typeid_in('event_01h455vb4pex5vsknk084sn02q')::text");
+        assert_eq!(
+            direct,
+            Ok(Some("event_01h455vb4pex5vsknk084sn02q".to_string()))
+        );
+    }
+
+    #[pg_test(error = "typeid('user') requires prefix \"user\", got \"org\"")]
+    fn test_typeid_typmod_insert() {
+        Spi::run("CREATE TABLE users (id typeid('user'))").unwrap();
+        Spi::run("INSERT INTO users VALUES (typeid_generate('org'))").unwrap();
+    }
+
+    #[pg_test(error = "typeid('user') requires prefix \"user\", got \"org\"")]
+    fn test_typeid_typmod_input() {
+        Spi::run("SELECT 'org_01h455vb4pex5vsknk084sn02q'::typeid('user')").unwrap();
+    }
+
+    #[cfg(feature = "pg16")]
+    #[pg_test]
+    fn test_typeid_input_soft_errors() {
+        Spi::run("CREATE TABLE users (id typeid('user'))").unwrap();
+
+        for (input, typ, valid) in [
+            ("user_01h455vb4pex5vsknk084sn02q", "typeid", true),
+            ("user_01h455vb4pex5vsknk084sn02q", "typeid('user')", true),
+            ("org_01h455vb4pex5vsknk084sn02q", "typeid('user')", false),
+            ("user_not-a-typeid", "typeid", false),
+            ("_01h455vb4pex5vsknk084sn02q", "typeid", false),
+        ] {
+            let result =
+                Spi::get_one::<bool>(&format!("SELECT pg_input_is_valid('{input}', '{typ}')"));
+            assert_eq!(result, Ok(Some(valid)), "{input}::{typ}");
+        }
+
+        let (message, code) = Spi::get_two::<String, String>(
+            "SELECT message, sql_error_code FROM pg_input_error_info('user_nope', 'typeid')",
+        )
+        .unwrap();
+        assert_eq!(
+            message.as_deref(),
+            Some("invalid input syntax for type typeid: \"user_nope\"")
+        );
+        assert_eq!(code.as_deref(), Some("22P02"));
+
+        let code = Spi::get_one::<String>(
+            "SELECT sql_error_code
+             FROM pg_input_error_info('org_01h455vb4pex5vsknk084sn02q', 'typeid(''user'')')",
+        );
+        assert_eq!(code, Ok(Some("23514".to_string())));
+    }
+
+    #[pg_test(error = "permission denied for table typeid_typmods")]
+    fn test_typeid_typmods_not_writable() {
+        Spi::run("CREATE ROLE typeid_typmods_test").unwrap();
+        Spi::run("SET ROLE typeid_typmods_test").unwrap();
+
+        // Declaring a modifier notes its prefix on behalf of the user
+        Spi::run("CREATE TEMP TABLE teams (id typeid('team'))").unwrap();
+        let noted =
+            Spi::get_one::<i64>("SELECT count(*) FROM typeid_typmods WHERE prefix = 'team'");
+        assert_eq!(noted, Ok(Some(1)));
+
+        Spi::run("INSERT INTO typeid_typmods VALUES (typeid_prefix_typmod('org'), 'org')").unwrap();
+    }
+
+    #[pg_test(
+        error = "new row for relation \"typeid_typmods\" violates check constraint \"typeid_typmods_prefix_check\""
+    )]
+    fn test_typeid_typmods_valid_prefix() {
+        // Noted prefixes are written into type names unquoted
+        Spi::run(
+            "INSERT INTO typeid_typmods SELECT typeid_prefix_typmod(p), p FROM (SELECT 'a'') x') t (p)",
+        )
+        .unwrap();
+    }
+
+    #[pg_test(error = "typeid takes a single prefix as type modifier, e.g. typeid('user')")]
+    fn test_typeid_typmod_in_arity() {
+        Spi::run("CREATE TABLE users (id typeid('user', 'org'))").unwrap();
+    }
+}
@@ -0,0 +1,417 @@
+//! Incremental validation of typeid columns, run by the background worker or by hand.
+
+use core::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use pgrx::bgworkers::{
+    BackgroundWorker, BackgroundWorkerBuilder, BgWorkerStartTime, SignalWakeFlags,
+};
+use pgrx::prelude::*;
+use pgrx::PgOid;
+
+use crate::guc;
+use crate::typeid::{TypeID, TypeIDPrefix};
+
+#[non_exhaustive]
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// A `typeid.validation_targets` entry couldn't be parsed
+    #[error("validation target {0:?} must look like [schema.]table.column[=prefix]")]
+    InvalidTarget(String),
+}
+
+/// A column checked by the validation worker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    relation: String,
+    column: String,
+    prefix: Option<String>,
+}
+
+impl Target {
+    /// Parses a comma separated list of `[schema.]table.column[=prefix]` entries
+    pub fn parse_list(list: &str) -> Result<Vec<Self>, Error> {
+        list.split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (path, prefix) = match entry.split_once('=') {
+                    Some((path, prefix)) => (path.trim(), Some(prefix.trim().to_string())),
+                    None => (entry, None),
+                };
+
+                match path.rsplit_once('.') {
+                    Some((relation, column)) if !relation.is_empty() && !column.is_empty() => {
+                        Ok(Self {
+                            relation: relation.to_string(),
+                            column: column.to_string(),
+                            prefix,
+                        })
+                    }
+                    _ => Err(Error::InvalidTarget(entry.to_string())),
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.relation, self.column)
+    }
+}
+
+/// A problem with a stored TypeID
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Finding {
+    /// The prefix doesn't follow the TypeID spec
+    InvalidPrefix(String),
+    /// The embedded timestamp lies further in the future than allowed
+    ClockSkew { ahead_millis: u64 },
+    /// The prefix differs from the one configured for the column
+    PrefixDrift { expected: String },
+}
+
+impl Finding {
+    pub fn check(
+        typeid: &TypeID,
+        expected_prefix: Option<&str>,
+        now_millis: u64,
+        max_skew_millis: u64,
+    ) -> Vec<Self> {
+        let mut findings = Vec::new();
+
+        if let Err(err) = TypeIDPrefix::new(typeid.type_prefix()) {
+            findings.push(Self::InvalidPrefix(err.to_string()));
+        }
+
+        if typeid.uuid().get_version_num() == 7 {
+            let ahead_millis = typeid.timestamp_millis().saturating_sub(now_millis);
+            if ahead_millis > max_skew_millis {
+                findings.push(Self::ClockSkew { ahead_millis });
+            }
+        }
+
+        if let Some(expected) = expected_prefix {
+            if typeid.type_prefix() != expected {
+                findings.push(Self::PrefixDrift {
+                    expected: expected.to_string(),
+                });
+            }
+        }
+
+        findings
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::InvalidPrefix(_) => "invalid_prefix",
+            Self::ClockSkew { .. } => "clock_skew",
+            Self::PrefixDrift { .. } => "prefix_drift",
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidPrefix(err) => write!(f, "{err}"),
+            Self::ClockSkew { ahead_millis } => {
+                write!(f, "timestamp is {ahead_millis}ms in the future")
+            }
+            Self::PrefixDrift { expected } => write!(f, "expected prefix {expected:?}"),
+        }
+    }
+}
+
+/// Checks the next `typeid.validation_batch_size` values of every column listed in
+/// `typeid.validation_targets`, continuing where the previous batch stopped and starting over
+/// once the end of a column is reached. Findings go to `typeid_validation_results`, the number
+/// of new ones is returned.
+#[pg_extern(cost = 10000)]
+fn typeid_validate_batch() -> i64 {
+    // Also true on hot standbys, where the worker only starts once promoted
+    if unsafe { pg_sys::XactReadOnly } {
+        error!(
+            "typeid_validate_batch stores its findings and can't run in a read-only transaction"
+        );
+    }
+    let Some(targets) = guc::string(&guc::VALIDATION_TARGETS) else {
+        return 0;
+    };
+    let targets = Target::parse_list(&targets).unwrap_or_else(|err| error!("{err}"));
+
+    targets.iter().map(validate_target).sum()
+}
+
+fn validate_target(target: &Target) -> i64 {
+    let name = target.to_string();
+    let batch_size = guc::VALIDATION_BATCH_SIZE.get() as i64;
+    let max_skew = guc::VALIDATION_MAX_CLOCK_SKEW.get() as u64;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+
+    Spi::connect(|mut client| {
+        let query = client
+            .select(
+                // `$1 IS NULL OR` is folded away when planning with the actual parameter value
+                "SELECT format('SELECT %2$I FROM %1$s WHERE $1 IS NULL OR %2$I > $1 ORDER BY %2$I LIMIT $2', attrelid::regclass, attname)
+                 FROM pg_attribute
+                 WHERE attrelid = to_regclass($1) AND attname = $2 AND atttypid = $3 AND NOT attisdropped",
+                None,
+                Some(vec![
+                    (PgBuiltInOids::TEXTOID.oid(), target.relation.as_str().into_datum()),
+                    (PgBuiltInOids::TEXTOID.oid(), target.column.as_str().into_datum()),
+                    (PgBuiltInOids::OIDOID.oid(), TypeID::type_oid().into_datum()),
+                ]),
+            )?
+            .first()
+            .get_one::<String>()
+            .ok()
+            .flatten();
+        let Some(query) = query else {
+            warning!("validation target {name} is not a typeid column");
+            return Ok(0);
+        };
+
+        let last = client
+            .select(
+                "SELECT last_value FROM typeid_validation_progress WHERE target = $1",
+                None,
+                Some(vec![(PgBuiltInOids::TEXTOID.oid(), name.as_str().into_datum())]),
+            )?
+            .first()
+            .get_one::<TypeID>()
+            .ok()
+            .flatten();
+
+        let ids = client
+            .select(
+                &query,
+                None,
+                Some(vec![
+                    (PgOid::from(TypeID::type_oid()), last.into_datum()),
+                    (PgBuiltInOids::INT8OID.oid(), batch_size.into_datum()),
+                ]),
+            )?
+            .map(|row| row.get::<TypeID>(1))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut found = 0;
+        for typeid in ids.iter().flatten() {
+            for finding in Finding::check(typeid, target.prefix.as_deref(), now, max_skew) {
+                let inserted = client
+                    .update(
+                        "INSERT INTO typeid_validation_results (target, value, kind, detail)
+                         VALUES ($1, $2, $3, $4)
+                         ON CONFLICT (target, value, kind) DO NOTHING
+                         RETURNING id",
+                        None,
+                        Some(vec![
+                            (PgBuiltInOids::TEXTOID.oid(), name.as_str().into_datum()),
+                            (PgOid::from(TypeID::type_oid()), typeid.clone().into_datum()),
+                            (PgBuiltInOids::TEXTOID.oid(), finding.kind().into_datum()),
+                            (PgBuiltInOids::TEXTOID.oid(), finding.to_string().into_datum()),
+                        ]),
+                    )?
+                    .len();
+
+                if inserted > 0 {
+                    warning!("typeid validation: {name} contains {typeid}: {finding}");
+                    found += 1;
+                }
+            }
+        }
+
+        // A short batch means we reached the end of the column, start over next time
+        let next = if (ids.len() as i64) < batch_size {
+            None
+        } else {
+            ids.last().cloned().flatten()
+        };
+        client.update(
+            "INSERT INTO typeid_validation_progress (target, last_value) VALUES ($1, $2)
+             ON CONFLICT (target) DO UPDATE SET last_value = excluded.last_value, updated_at = now()",
+            None,
+            Some(vec![
+                (PgBuiltInOids::TEXTOID.oid(), name.as_str().into_datum()),
+                (PgOid::from(TypeID::type_oid()), next.into_datum()),
+            ]),
+        )?;
+
+        Ok::<_, pgrx::spi::Error>(found)
+    })
+    .unwrap()
+}
+
+/// Registers the validation worker when the library is preloaded and the worker is enabled
+pub fn init() {
+    if !guc::preloading() || !guc::VALIDATION_WORKER.get() {
+        return;
+    }
+
+    BackgroundWorkerBuilder::new("typeid validation worker")
+        .set_type("typeid validation worker")
+        .set_library("typeid")
+        .set_function("typeid_validation_worker_main")
+        .enable_spi_access()
+        .set_start_time(BgWorkerStartTime::RecoveryFinished)
+        .set_restart_time(Some(Duration::from_secs(60)))
+        .load();
+}
+
+#[pg_guard]
+#[no_mangle]
+pub extern "C" fn typeid_validation_worker_main(_arg: pg_sys::Datum) {
+    BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGHUP | SignalWakeFlags::SIGTERM);
+    BackgroundWorker::connect_worker_to_spi(
+        guc::string(&guc::VALIDATION_DATABASE).as_deref(),
+        None,
+    );
+
+    while BackgroundWorker::wait_latch(Some(Duration::from_secs(
+        guc::VALIDATION_INTERVAL.get() as u64
+    ))) {
+        if BackgroundWorker::sighup_received() {
+            unsafe { pg_sys::ProcessConfigFile(pg_sys::GucContext_PGC_SIGHUP) };
+        }
+        BackgroundWorker::transaction(|| {
+            // Resolve our tables in the schema the extension is installed into
+            let schema = Spi::get_one::<String>(
+                "SELECT (
+                    SELECT set_config('search_path', quote_ident(n.nspname) || ', ' || current_setting('search_path'), true)
+                    FROM pg_extension e
+                    JOIN pg_namespace n ON n.oid = e.extnamespace
+                    WHERE e.extname = 'typeid'
+                )",
+            )
+            .unwrap();
+
+            if schema.is_some() {
+                typeid_validate_batch();
+            }
+        });
+    }
+}
+
+extension_sql!(
+    r#"
+    CREATE TABLE typeid_validation_results (
+        id bigserial PRIMARY KEY,
+        found_at timestamptz NOT NULL DEFAULT now(),
+        target text NOT NULL,
+        value typeid NOT NULL,
+        kind text NOT NULL,
+        detail text,
+        UNIQUE (target, value, kind)
+    );
+
+    CREATE TABLE typeid_validation_progress (
+        target text PRIMARY KEY,
+        last_value typeid,
+        updated_at timestamptz NOT NULL DEFAULT now()
+    );
+
+    SELECT pg_catalog.pg_extension_config_dump('typeid_validation_results', '');
+    SELECT pg_catalog.pg_extension_config_dump('typeid_validation_progress', '');
+    SELECT pg_catalog.pg_extension_config_dump('typeid_validation_results_id_seq', '');
+    "#,
+    name = "create_validation_tables",
+    requires = ["create_typeid_operator_class"],
+);
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+    use uuid::Uuid;
+
+    use super::{Finding, Target};
+    use crate::typeid::{TypeID, TypeIDPrefix};
+
+    #[test]
+    fn test_parse_targets() {
+        let targets = Target::parse_list("public.users.id=user, orders.id ,").unwrap();
+        assert_eq!(
+            targets,
+            vec![
+                Target {
+                    relation: "public.users".into(),
+                    column: "id".into(),
+                    prefix: Some("user".into()),
+                },
+                Target {
+                    relation: "orders".into(),
+                    column: "id".into(),
+                    prefix: None,
+                },
+            ]
+        );
+        assert_eq!(targets[0].to_string(), "public.users.id");
+
+        assert!(Target::parse_list("users").is_err());
+        assert!(Target::parse_list(".id").is_err());
+    }
+
+    #[test]
+    fn test_check_findings() {
+        let now = 1_700_000_000_000;
+        let typeid = TypeID::lower_bound_at(TypeIDPrefix::new("user").unwrap(), now);
+        assert_eq!(Finding::check(&typeid, Some("user"), now, 0), vec![]);
+
+        let typeid = TypeID::new(typeid.prefix().clone(), Uuid::now_v7());
+        let findings = Finding::check(&typeid, Some("post"), now, 1000);
+        let kinds: Vec<_> = findings.iter().map(Finding::kind).collect();
+        assert_eq!(kinds, vec!["clock_skew", "prefix_drift"]);
+
+        let typeid = TypeID::new(TypeIDPrefix::try_unsafe("User"), Uuid::nil());
+        let findings = Finding::check(&typeid, None, now, 0);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].kind(), "invalid_prefix");
+    }
+
+    #[pg_test(
+        error = "typeid_validate_batch stores its findings and can't run in a read-only transaction"
+    )]
+    fn test_typeid_validate_batch_read_only() {
+        Spi::run("SET transaction_read_only = on").unwrap();
+        Spi::run("SELECT typeid_validate_batch()").unwrap();
+    }
+
+    #[pg_test]
+    fn test_typeid_validate_batch() {
+        Spi::run("CREATE TABLE accounts (id typeid primary key)").unwrap();
+        Spi::run(
+            "INSERT INTO accounts SELECT typeid_generate('account') FROM generate_series(1, 10)",
+        )
+        .unwrap();
+        Spi::run("INSERT INTO accounts VALUES (typeid_generate('user'))").unwrap();
+        Spi::run(
+            "INSERT INTO accounts VALUES (uuid_to_typeid('account', 'ffff0000-0000-7000-8000-000000000000'))",
+        )
+        .unwrap();
+
+        Spi::run("SET typeid.validation_targets = 'accounts.id=account'").unwrap();
+        Spi::run("SET typeid.validation_batch_size = 5").unwrap();
+
+        let found = (0..3)
+            .map(|_| Spi::get_one::<i64>("SELECT typeid_validate_batch()").unwrap())
+            .sum::<Option<i64>>();
+        assert_eq!(found, Some(2));
+
+        let kinds = Spi::get_one::<Vec<String>>(
+            "SELECT array_agg(kind ORDER BY kind) FROM typeid_validation_results",
+        )
+        .unwrap();
+        assert_eq!(
+            kinds,
+            Some(vec!["clock_skew".to_string(), "prefix_drift".to_string()])
+        );
+
+        // The next pass starts over and doesn't report the same values again
+        let found = Spi::get_one::<i64>("SELECT typeid_validate_batch()").unwrap();
+        assert_eq!(found, Some(0));
+    }
+}
@@ -0,0 +1,19 @@
+use std::fs;
+
+/// `fallback/typeid_pgtle.sql` is checked in for users without a Rust toolchain, so it must match
+/// what `src/fallback.rs` generates
+#[test]
+fn pgtle_script_is_up_to_date() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/fallback/typeid_pgtle.sql");
+    let script = typeid::fallback::pgtle_script();
+
+    if std::env::var_os("TYPEID_UPDATE_FALLBACK").is_some() {
+        fs::write(path, script).unwrap();
+        return;
+    }
+    let checked_in = fs::read_to_string(path).unwrap_or_default();
+    assert!(
+        checked_in == script,
+        "{path} is out of date, run TYPEID_UPDATE_FALLBACK=1 cargo test --test fallback"
+    );
+}
@@ -26,6 +26,10 @@ fn main() {
 
     for test in valid {
         tests.push(Trial::test(format!("valid::{}", test.name), move || {
+            if !TypeID::is_valid(&test.typeid) {
+                return Err("is_valid rejected a valid typeid".into());
+            }
+
             let id = match TypeID::from_string(&test.typeid) {
                 Ok(id) => id,
                 Err(e) => return Err(e.to_string().into()),
@@ -53,6 +57,9 @@ fn main() {
             format!("invalid::{}", test.name),
             move || match TypeID::from_string(&test.typeid) {
                 Ok(_) => Err(test.description.into()),
+                Err(_) if TypeID::is_valid(&test.typeid) => {
+                    Err(format!("is_valid accepted it: {}", test.description).into())
+                }
                 Err(_) => Ok(()),
             },
         ))